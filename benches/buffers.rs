@@ -0,0 +1,87 @@
+// Large-body throughput under different HTTP1_MAX_BUF_SIZE / HTTP1_WRITEV
+// settings: POST /echo round trips of one body over a keep-alive loopback
+// connection, so both the read and the write side are exercised.
+// `cargo bench --bench buffers`.
+
+use std::time::{Duration, Instant};
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::Request;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use rust_http_server::config::DEFAULT_HTTP1_MAX_BUF_SIZE;
+use rust_http_server::listener::bind;
+use rust_http_server::{serve, AppState, Config};
+
+// Time spent measuring each setting, after an equal warm-up
+const MEASURE_FOR: Duration = Duration::from_secs(2);
+const BODY_BYTES: usize = 512 * 1024;
+
+const CASES: &[(&str, usize, Option<bool>)] = &[
+    ("8 KiB", 8192, None),
+    ("64 KiB", 64 * 1024, None),
+    ("default (408 KiB)", DEFAULT_HTTP1_MAX_BUF_SIZE, None),
+    ("1 MiB", 1024 * 1024, None),
+    ("default, writev on", DEFAULT_HTTP1_MAX_BUF_SIZE, Some(true)),
+    (
+        "default, writev off",
+        DEFAULT_HTTP1_MAX_BUF_SIZE,
+        Some(false),
+    ),
+];
+
+// Echoes `body` repeatedly for `duration`; returns (round trips, elapsed)
+async fn run(uri: &str, body: &Bytes, duration: Duration) -> (u64, Duration) {
+    let client = Client::builder(TokioExecutor::new()).build_http();
+    let start = Instant::now();
+    let mut round_trips = 0;
+    while start.elapsed() < duration {
+        let request = Request::post(uri)
+            .header("Accept", "text/plain")
+            .body(Full::new(body.clone()))
+            .unwrap();
+        let response = client.request(request).await.unwrap();
+        assert!(response.status().is_success(), "{}", response.status());
+        let echoed = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(echoed.len(), body.len());
+        round_trips += 1;
+    }
+    (round_trips, start.elapsed())
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let body = Bytes::from(vec![b'x'; BODY_BYTES]);
+
+    println!("{:<22} {:>12} {:>10}", "buffer", "round trips/s", "MiB/s");
+    runtime.block_on(async {
+        for &(name, max_buf_size, writev) in CASES {
+            let listener = bind(([127, 0, 0, 1], 0).into()).unwrap();
+            let addr = listener.local_addr().unwrap();
+            let state = AppState::new(Config {
+                quiet: true,
+                log_sample_rate: 0.0,
+                compression: false,
+                http1_max_buf_size: max_buf_size,
+                http1_writev: writev,
+                ..Config::default()
+            });
+            let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+            let server = tokio::spawn(serve(listener, state, async {
+                let _ = stopped.await;
+            }));
+
+            let uri = format!("http://{}/echo", addr);
+            run(&uri, &body, MEASURE_FOR).await;
+            let (round_trips, elapsed) = run(&uri, &body, MEASURE_FOR).await;
+            let per_sec = round_trips as f64 / elapsed.as_secs_f64();
+            // Each round trip carries the body both ways
+            let mib_per_sec = per_sec * 2.0 * BODY_BYTES as f64 / (1024.0 * 1024.0);
+            println!("{:<22} {:>12.0} {:>10.0}", name, per_sec, mib_per_sec);
+
+            let _ = stop.send(());
+            let _ = server.await;
+        }
+    });
+}
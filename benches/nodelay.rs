@@ -0,0 +1,76 @@
+// Request latency with TCP_NODELAY on and off: sequential GET /health
+// requests over one keep-alive loopback connection, so each waits on the
+// last response's small write. `cargo bench --bench nodelay`.
+
+use std::time::{Duration, Instant};
+
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::Request;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use rust_http_server::listener::bind;
+use rust_http_server::{serve, AppState, Config};
+
+// Requests timed per run, after as many again to warm up
+const REQUESTS: u32 = 5_000;
+const RUNS: usize = 3;
+
+// Sends `REQUESTS` requests one after another; returns the average latency
+async fn run(uri: &str) -> Duration {
+    // The client never delays its own writes, so only the server's setting
+    // differs between cases
+    let mut connector = HttpConnector::new();
+    connector.set_nodelay(true);
+    let client = Client::builder(TokioExecutor::new()).build::<_, Empty<Bytes>>(connector);
+    let start = Instant::now();
+    for _ in 0..REQUESTS {
+        let request = Request::get(uri).body(Empty::new()).unwrap();
+        let response = client.request(request).await.unwrap();
+        assert!(response.status().is_success(), "{}", response.status());
+        response.into_body().collect().await.unwrap();
+    }
+    start.elapsed() / REQUESTS
+}
+
+fn main() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    println!("{:<12} {:>16}", "TCP_NODELAY", "average latency");
+    runtime.block_on(async {
+        for tcp_nodelay in [true, false] {
+            let listener = bind(([127, 0, 0, 1], 0).into()).unwrap();
+            let addr = listener.local_addr().unwrap();
+            let state = AppState::new(Config {
+                quiet: true,
+                log_sample_rate: 0.0,
+                compression: false,
+                tcp_nodelay,
+                ..Config::default()
+            });
+            let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+            let server = tokio::spawn(serve(listener, state, async {
+                let _ = stopped.await;
+            }));
+
+            let uri = format!("http://{}/health", addr);
+            run(&uri).await;
+            let mut latencies = Vec::with_capacity(RUNS);
+            for _ in 0..RUNS {
+                latencies.push(run(&uri).await);
+            }
+            let fastest = latencies.iter().min().unwrap();
+            let slowest = latencies.iter().max().unwrap();
+            println!(
+                "{:<12} {:>7.1}–{:.1} µs",
+                tcp_nodelay,
+                fastest.as_secs_f64() * 1e6,
+                slowest.as_secs_f64() * 1e6
+            );
+
+            let _ = stop.send(());
+            let _ = server.await;
+        }
+    });
+}
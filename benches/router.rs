@@ -0,0 +1,110 @@
+// Dispatch cost per route, measured through `handle_request` without a
+// network connection and including reading the response body. criterion
+// isn't a dependency, so this is a plain `harness = false` timing loop:
+// `cargo bench --bench router`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Method, Request};
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::{handle_request, AppState, Config};
+
+// Time spent measuring each route, after an equal warm-up
+const MEASURE_FOR: Duration = Duration::from_millis(500);
+
+struct Case {
+    name: &'static str,
+    method: Method,
+    uri: &'static str,
+    body: &'static str,
+}
+
+const fn case(name: &'static str, method: Method, uri: &'static str, body: &'static str) -> Case {
+    Case {
+        name,
+        method,
+        uri,
+        body,
+    }
+}
+
+// Successful responses only: 4xx and 5xx are always logged, and printing
+// would swamp the dispatch being measured
+const CASES: &[Case] = &[
+    case("root", Method::GET, "/", ""),
+    case("health", Method::GET, "/health", ""),
+    case("stats", Method::GET, "/stats", ""),
+    case("stats.csv", Method::GET, "/stats.csv", ""),
+    case("echo", Method::GET, "/echo/hello", ""),
+    case(
+        "echo (percent-encoded)",
+        Method::GET,
+        "/echo/hello%20world%21",
+        "",
+    ),
+    case("echo ?repeat=8", Method::GET, "/echo/hello?repeat=8", ""),
+    case("echo stream", Method::GET, "/echo/stream/hi?count=3", ""),
+    case("echo body", Method::POST, "/echo", "hello"),
+    case("kv put", Method::PUT, "/kv/bench", "{\"n\":1}"),
+    case("kv get", Method::GET, "/kv/bench", ""),
+    case("debug echo", Method::PATCH, "/debug/echo?x=1", "hello"),
+    case("options", Method::OPTIONS, "/kv/bench", ""),
+];
+
+fn request(case: &Case) -> Request<Full<Bytes>> {
+    Request::builder()
+        .method(case.method.clone())
+        .uri(case.uri)
+        .header("Host", "localhost")
+        .header("Content-Length", case.body.len())
+        .body(Full::new(Bytes::from_static(case.body.as_bytes())))
+        .unwrap()
+}
+
+// Dispatches `case` repeatedly for `duration`; returns (iterations, elapsed)
+async fn run(case: &Case, state: &AppState, duration: Duration) -> (u64, Duration) {
+    let start = Instant::now();
+    let mut iterations = 0;
+    while start.elapsed() < duration {
+        // Batches keep the clock reads out of the measurement
+        for _ in 0..64 {
+            let response = handle_request(request(case), state.clone(), None).await;
+            // Reading the body finishes streaming responses, which would
+            // otherwise count as cancelled by the client
+            let body = response.unwrap().into_body().collect().await;
+            black_box(body.unwrap());
+        }
+        iterations += 64;
+    }
+    (iterations, start.elapsed())
+}
+
+fn main() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let state = AppState::new(Config {
+        quiet: true,
+        log_sample_rate: 0.0,
+        // Off by default in release builds, which is what benches run
+        route_toggles: RouteToggles::new().with_route("/debug/echo", true),
+        ..Config::default()
+    });
+
+    println!(
+        "{:<24} {:>12} {:>14}",
+        "route", "ns/dispatch", "dispatches/s"
+    );
+    runtime.block_on(async {
+        for case in CASES {
+            run(case, &state, MEASURE_FOR).await;
+            let (iterations, elapsed) = run(case, &state, MEASURE_FOR).await;
+            let nanos = elapsed.as_nanos() as f64 / iterations as f64;
+            println!("{:<24} {:>12.0} {:>14.0}", case.name, nanos, 1e9 / nanos);
+        }
+    });
+}
@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+// Length of the sliding window behind the recent accept rate
+pub const WINDOW_SECS: u64 = 10;
+
+// Connections accepted per second over the last `WINDOW_SECS`, kept as one
+// bucket per second so memory stays constant however busy the listener is.
+// Lock-free, so counting an accept never waits on a `/stats` reader.
+#[derive(Clone)]
+pub struct AcceptRate {
+    window: Arc<Window>,
+    start: Instant,
+}
+
+struct Window {
+    // Each bucket packs (second since start, connections accepted in it) as
+    // `second << 32 | count`, so rolling it over to a new second and
+    // counting happen in one atomic update
+    buckets: [AtomicU64; WINDOW_SECS as usize],
+    // One more than the second the last rate warning was logged in; 0 if
+    // none has been
+    last_warning: AtomicU64,
+}
+
+impl AcceptRate {
+    pub fn new(start: Instant) -> Self {
+        Self {
+            window: Arc::new(Window {
+                buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+                last_warning: AtomicU64::new(0),
+            }),
+            start,
+        }
+    }
+
+    // Counts one accepted connection and returns the recent rate
+    pub fn record(&self, now: Instant) -> f64 {
+        let second = self.second(now);
+        let bucket = &self.window.buckets[(second % WINDOW_SECS) as usize];
+        let _ = bucket.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |packed| {
+            Some(if packed >> 32 == second {
+                packed + 1
+            } else {
+                second << 32 | 1
+            })
+        });
+        self.rate(second)
+    }
+
+    pub fn recent_per_sec(&self, now: Instant) -> f64 {
+        self.rate(self.second(now))
+    }
+
+    // Whether a rate warning may be logged now; at most one per window, so a
+    // connection storm doesn't also become a log storm
+    pub fn warning_due(&self, now: Instant) -> bool {
+        let second = self.second(now);
+        self.window
+            .last_warning
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+                (last == 0 || second + 1 >= last + WINDOW_SECS).then_some(second + 1)
+            })
+            .is_ok()
+    }
+
+    // Average over the window ending at `second`, or over the uptime so far
+    // when that is shorter
+    fn rate(&self, second: u64) -> f64 {
+        let oldest = (second + 1).saturating_sub(WINDOW_SECS);
+        let accepted: u64 = self
+            .window
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .filter(|packed| (oldest..=second).contains(&(packed >> 32)))
+            .map(|packed| packed & u32::MAX as u64)
+            .sum();
+        accepted as f64 / (second + 1).min(WINDOW_SECS) as f64
+    }
+
+    fn second(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.start).as_secs()
+    }
+}
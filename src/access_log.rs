@@ -0,0 +1,103 @@
+// Access log lines in Apache's Common and Combined Log Formats, for log
+// tooling that already parses them:
+//
+//   127.0.0.1 - - [14/Oct/2026:09:30:00 +0000] "GET /health HTTP/1.1" 200 97
+//
+// Combined adds the quoted Referer and User-Agent. Fields the server doesn't
+// know, like the identd user, are a single `-`.
+
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use chrono::{DateTime, FixedOffset};
+use hyper::{Method, StatusCode, Version};
+
+// Which layout request log lines use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    // The server's own `[time] METHOD path version status - Request #n`
+    Default,
+    // Common Log Format
+    Clf,
+    // Common Log Format plus referer and user agent
+    Combined,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "default" => Ok(LogFormat::Default),
+            "clf" | "common" => Ok(LogFormat::Clf),
+            "combined" => Ok(LogFormat::Combined),
+            other => Err(format!(
+                "unknown log format {:?} (expected default, clf or combined)",
+                other
+            )),
+        }
+    }
+}
+
+// What one access log line reports, captured as the request arrives and
+// completed once the response is ready
+#[derive(Debug, Clone)]
+pub struct AccessLogEntry {
+    // None for clients without an IP, like those on a Unix socket
+    pub remote_ip: Option<IpAddr>,
+    pub received: DateTime<FixedOffset>,
+    pub method: Method,
+    // Path and query as the client sent them
+    pub target: String,
+    pub version: Version,
+    pub status: StatusCode,
+    // Body bytes sent; None when unknown up front, as for streamed bodies
+    pub bytes: Option<u64>,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+// One log line: Common Log Format, or Combined when `combined` is set
+pub fn format_clf(entry: &AccessLogEntry, combined: bool) -> String {
+    let host = entry
+        .remote_ip
+        .map_or_else(|| "-".to_string(), |ip| ip.to_string());
+    // CLF writes an empty body as `-`, not 0
+    let bytes = match entry.bytes {
+        Some(bytes) if bytes > 0 => bytes.to_string(),
+        _ => "-".to_string(),
+    };
+    let mut line = format!(
+        "{} - - [{}] \"{} {} {:?}\" {} {}",
+        host,
+        entry.received.format("%d/%b/%Y:%H:%M:%S %z"),
+        entry.method,
+        escape(&entry.target),
+        entry.version,
+        entry.status.as_u16(),
+        bytes
+    );
+    if combined {
+        for value in [&entry.referer, &entry.user_agent] {
+            line.push_str(" \"");
+            line.push_str(&value.as_deref().map_or_else(|| "-".to_string(), escape));
+            line.push('"');
+        }
+    }
+    line
+}
+
+// Escapes quotes, backslashes and anything unprintable the way Apache does,
+// so a header value can't break out of its quotes or forge a second line
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    escaped
+}
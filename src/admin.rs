@@ -0,0 +1,29 @@
+// Bearer-token check for the `/admin/` routes
+
+use hyper::header::AUTHORIZATION;
+use hyper::HeaderMap;
+
+// Whether `headers` carry `Authorization: Bearer <token>`. The comparison
+// takes the same time wherever the first mismatch is, so the token can't be
+// guessed byte by byte from response timings.
+pub fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    let Some(presented) = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            let (scheme, credentials) = value.trim().split_once(' ')?;
+            scheme
+                .eq_ignore_ascii_case("bearer")
+                .then(|| credentials.trim())
+        })
+    else {
+        return false;
+    };
+
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
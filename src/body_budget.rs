@@ -0,0 +1,148 @@
+// A global cap on memory held by buffered request bodies.
+//
+// `MAX_BODY_BYTES` bounds one body, but a thousand concurrent uploads just
+// under it would still hold a thousand times that. Each body reserves its
+// size from a shared pool before it is read and gives it back once the
+// handler is done with it; a request that can't get its share in time is
+// turned away instead of queueing indefinitely, told how many are queued
+// and roughly when the queue will have cleared.
+
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use hyper::body::Bytes;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::accept_rate::AcceptRate;
+
+// Bytes available for buffered bodies, shared by every connection
+#[derive(Clone)]
+pub struct BodyBudget {
+    semaphore: Arc<Semaphore>,
+    total: u32,
+    timeout: Duration,
+    // Requests currently waiting for their share
+    waiting: Arc<AtomicUsize>,
+    // Reservations given back per second, the rate the queue drains at
+    released: AcceptRate,
+}
+
+// Why a body was turned away: the queue it gave up on, and how long until
+// that queue should have drained
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backlog {
+    // Requests waiting for the budget, the rejected one included
+    pub queue_depth: usize,
+    pub retry_after_secs: u64,
+}
+
+impl BodyBudget {
+    pub fn new(total: u32, timeout: Duration) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(total as usize)),
+            total,
+            timeout,
+            waiting: Arc::new(AtomicUsize::new(0)),
+            released: AcceptRate::new(Instant::now()),
+        }
+    }
+
+    // Waits up to the timeout for `bytes` of the budget, or describes the
+    // backlog if they didn't free up in time. Asking for more than the
+    // whole budget gets all of it.
+    pub async fn reserve(&self, bytes: u64) -> Result<Reservation, Backlog> {
+        let bytes = bytes.min(self.total as u64) as u32;
+        let _waiting = Waiting::new(&self.waiting);
+        match tokio::time::timeout(
+            self.timeout,
+            self.semaphore.clone().acquire_many_owned(bytes),
+        )
+        .await
+        {
+            Ok(Ok(permit)) => Ok(Reservation {
+                _permit: permit,
+                released: self.released.clone(),
+            }),
+            _ => Err(self.backlog()),
+        }
+    }
+
+    // The queue as it stands, with the time it would take to drain at the
+    // recent release rate; with nothing released lately, one more timeout
+    fn backlog(&self) -> Backlog {
+        let queue_depth = self.waiting.load(Ordering::Relaxed);
+        let per_sec = self.released.recent_per_sec(Instant::now());
+        let estimate = if per_sec > 0.0 {
+            queue_depth as f64 / per_sec
+        } else {
+            self.timeout.as_secs_f64()
+        };
+        Backlog {
+            queue_depth,
+            retry_after_secs: (estimate.ceil() as u64).max(1),
+        }
+    }
+
+    // Bytes not currently reserved
+    pub fn available(&self) -> u64 {
+        self.semaphore.available_permits() as u64
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total as u64
+    }
+}
+
+// A share of the budget, returned when dropped
+pub struct Reservation {
+    _permit: OwnedSemaphorePermit,
+    released: AcceptRate,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.released.record(Instant::now());
+    }
+}
+
+// One request counted as waiting until dropped
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl<'a> Waiting<'a> {
+    fn new(waiting: &'a AtomicUsize) -> Self {
+        waiting.fetch_add(1, Ordering::Relaxed);
+        Self(waiting)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// A request body read into memory, holding its share of the budget (if
+// one is configured) for as long as it is alive
+pub struct BufferedBody {
+    bytes: Bytes,
+    _reservation: Option<Reservation>,
+}
+
+impl BufferedBody {
+    pub fn new(bytes: Bytes, reservation: Option<Reservation>) -> Self {
+        Self {
+            bytes,
+            _reservation: reservation,
+        }
+    }
+}
+
+impl Deref for BufferedBody {
+    type Target = Bytes;
+
+    fn deref(&self) -> &Bytes {
+        &self.bytes
+    }
+}
@@ -0,0 +1,83 @@
+// Operator-chosen `Cache-Control` headers, per route.
+//
+// Routes are named by their pattern in `ROUTES`, as with route toggles;
+// `*` covers every route without an entry of its own. Only successful and
+// `304` responses get a policy, so no error is ever cached because of one.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use hyper::header::{HeaderValue, CACHE_CONTROL};
+use hyper::Response;
+
+// Route pattern -> Cache-Control value, parsed from
+// `pattern=value;pattern=value`. Entries are separated by `;` because the
+// values themselves contain commas.
+#[derive(Debug, Clone, Default)]
+pub struct CachePolicy {
+    routes: HashMap<String, HeaderValue>,
+    fallback: Option<HeaderValue>,
+}
+
+impl CachePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Sends `value` from the route registered as `pattern`, or from every
+    // other route for `*`
+    pub fn with_route(mut self, pattern: &str, value: HeaderValue) -> Self {
+        if pattern == "*" {
+            self.fallback = Some(value);
+        } else {
+            self.routes.insert(pattern.to_string(), value);
+        }
+        self
+    }
+
+    // The route patterns given a policy, not counting `*`
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.routes.keys().map(String::as_str)
+    }
+
+    // Applies the policy for `route` (`None` for paths no route matches).
+    // A route's own entry replaces whatever the handler sent; `*` only
+    // fills in for handlers that sent nothing, so built-in defaults like
+    // the favicon's day-long caching survive it.
+    pub fn apply<B>(&self, route: Option<&str>, response: &mut Response<B>) {
+        let status = response.status();
+        if !status.is_success() && status != hyper::StatusCode::NOT_MODIFIED {
+            return;
+        }
+        let headers = response.headers_mut();
+        if let Some(value) = route.and_then(|route| self.routes.get(route)) {
+            headers.insert(CACHE_CONTROL, value.clone());
+        } else if let Some(value) = &self.fallback {
+            headers
+                .entry(CACHE_CONTROL)
+                .or_insert_with(|| value.clone());
+        }
+    }
+}
+
+impl FromStr for CachePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .try_fold(CachePolicy::new(), |policy, entry| {
+                let (pattern, value) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected route=directives, got {:?}", entry))?;
+                let value = value.trim();
+                if value.is_empty() {
+                    return Err(format!("missing directives for {:?}", pattern.trim()));
+                }
+                let value = HeaderValue::from_str(value)
+                    .map_err(|_| format!("invalid Cache-Control value {:?}", value))?;
+                Ok(policy.with_route(pattern.trim(), value))
+            })
+    }
+}
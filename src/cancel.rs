@@ -0,0 +1,47 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::timestamp;
+
+// Notices work being dropped before it finished. hyper drops the handler
+// future (and any streaming body) when the client disconnects mid-request,
+// which cancels whatever it was awaiting; this only records that it
+// happened, in `cancelled_requests` and the request log.
+pub struct CancelGuard {
+    cancelled: Arc<AtomicU64>,
+    what: String,
+    started: Instant,
+    finished: bool,
+}
+
+impl CancelGuard {
+    // `what` names the work in the log line, e.g. "GET /delay/5000"
+    pub fn new(cancelled: &Arc<AtomicU64>, what: String) -> Self {
+        Self {
+            cancelled: cancelled.clone(),
+            what,
+            started: Instant::now(),
+            finished: false,
+        }
+    }
+
+    pub fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.cancelled.fetch_add(1, Ordering::Relaxed);
+        println!(
+            "[{}] {} - Cancelled by client after {}ms",
+            timestamp::log_time(),
+            self.what,
+            self.started.elapsed().as_millis()
+        );
+    }
+}
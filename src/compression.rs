@@ -0,0 +1,376 @@
+// gzip response compression (RFC 1951 DEFLATE inside an RFC 1952 wrapper).
+//
+// The encoder emits a single fixed-Huffman block with LZ77 back-references
+// found through 3-byte hash chains. It doesn't compete with zlib's ratios,
+// but it's small, allocation-light and plenty for JSON bodies.
+
+use http_body_util::BodyExt;
+use hyper::body::Body as _;
+use hyper::header::{HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, VARY};
+use hyper::{HeaderMap, Response, StatusCode};
+
+use crate::{full, Body};
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+// Base values and extra-bit counts for length codes 257..=285
+pub(crate) const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+pub(crate) const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+// Base values and extra-bit counts for distance codes 0..=29
+pub(crate) const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+pub(crate) const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+// The content codings a response may be sent in, from Accept-Encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Negotiated {
+    // gzip when it's worth it, identity otherwise
+    Gzip,
+    // gzip even when it isn't worth it: the client refused identity
+    GzipOnly,
+    Identity,
+    // The client refused everything the server can send
+    NotAcceptable,
+}
+
+// Negotiates per RFC 9110 section 12.5.3, offering gzip only when
+// `gzip_available`. An explicit entry for a coding beats `*`, and identity
+// stays acceptable unless `identity;q=0` (or `*;q=0` without an identity
+// entry) rules it out. No header at all means identity.
+pub fn negotiate(headers: &HeaderMap, gzip_available: bool) -> Negotiated {
+    let mut values = headers
+        .get_all(hyper::header::ACCEPT_ENCODING)
+        .iter()
+        .peekable();
+    if values.peek().is_none() {
+        return Negotiated::Identity;
+    }
+
+    let (mut gzip, mut identity, mut any) = (None, None, None);
+    for coding in values
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+    {
+        let mut params = coding.split(';');
+        let name = params.next().unwrap_or("").trim();
+        let q = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case("gzip") || name.eq_ignore_ascii_case("x-gzip") {
+            gzip = Some(q);
+        } else if name.eq_ignore_ascii_case("identity") {
+            identity = Some(q);
+        } else if name == "*" {
+            any = Some(q);
+        }
+    }
+
+    let gzip = gzip_available && gzip.or(any).unwrap_or(0.0) > 0.0;
+    let identity = identity.or(any).unwrap_or(1.0) > 0.0;
+    match (gzip, identity) {
+        (true, true) => Negotiated::Gzip,
+        (true, false) => Negotiated::GzipOnly,
+        (false, true) => Negotiated::Identity,
+        (false, false) => Negotiated::NotAcceptable,
+    }
+}
+
+// gzip-compresses `response` in place when it is large enough to be worth it
+// and not already encoded; with `required`, whenever it can be. Streaming
+// bodies (no exact size) and statuses that never have a body (1xx, 204,
+// 304) are left alone either way. Returns
+// `(uncompressed, compressed)` byte counts when compression was applied.
+pub async fn compress_response(
+    response: Response<Body>,
+    min_bytes: usize,
+    required: bool,
+) -> (Response<Body>, Option<(u64, u64)>) {
+    let status = response.status();
+    if status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+        || response.headers().contains_key(CONTENT_ENCODING)
+        || !(required || is_compressible(response.headers().get(CONTENT_TYPE)))
+        || response.body().size_hint().exact().is_none()
+    {
+        return (response, None);
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(never) => match never {},
+    };
+    parts
+        .headers
+        .append(VARY, HeaderValue::from_static("Accept-Encoding"));
+
+    if body.len() < min_bytes && !required {
+        return (Response::from_parts(parts, full(body)), None);
+    }
+
+    let compressed = gzip(&body);
+    if compressed.len() >= body.len() && !required {
+        return (Response::from_parts(parts, full(body)), None);
+    }
+
+    let sizes = (body.len() as u64, compressed.len() as u64);
+    parts
+        .headers
+        .insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+    parts.headers.remove(CONTENT_LENGTH);
+    (Response::from_parts(parts, full(compressed)), Some(sizes))
+}
+
+fn is_compressible(content_type: Option<&HeaderValue>) -> bool {
+    let Some(content_type) = content_type.and_then(|value| value.to_str().ok()) else {
+        return false;
+    };
+    let content_type = content_type.to_ascii_lowercase();
+    content_type.starts_with("text/")
+        || content_type.starts_with("application/json")
+        || content_type.starts_with("application/javascript")
+        || content_type.starts_with("application/xml")
+}
+
+// Wraps a DEFLATE stream of `data` in a gzip header and trailer
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // Magic, CM=deflate, no flags, no mtime, no extra flags, OS=unknown
+    let mut out = vec![0x1f, 0x8b, 0x08, 0x00, 0, 0, 0, 0, 0x00, 0xff];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+// Raw DEFLATE: one final block using the fixed Huffman tables
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::new(data.len() / 2 + 16);
+    writer.write_bits(1, 1); // BFINAL
+    writer.write_bits(1, 2); // BTYPE = 01, fixed Huffman
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let (length, distance) = longest_match(data, pos, &head, &prev);
+        if length >= MIN_MATCH {
+            write_length(&mut writer, length);
+            write_distance(&mut writer, distance);
+            for i in pos..pos + length {
+                insert_hash(data, i, &mut head, &mut prev);
+            }
+            pos += length;
+        } else {
+            write_literal(&mut writer, data[pos] as u16);
+            insert_hash(data, pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+
+    write_literal(&mut writer, 256); // end of block
+    writer.finish()
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let value = (data[pos] as u32) << 16 | (data[pos + 1] as u32) << 8 | data[pos + 2] as u32;
+    (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+fn insert_hash(data: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize]) {
+    if pos + MIN_MATCH > data.len() {
+        return;
+    }
+    let h = hash(data, pos);
+    prev[pos % WINDOW_SIZE] = head[h];
+    head[h] = pos;
+}
+
+// Walks the hash chain for `pos`, returning the best (length, distance)
+fn longest_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> (usize, usize) {
+    if pos + MIN_MATCH > data.len() {
+        return (0, 0);
+    }
+
+    let max_len = MAX_MATCH.min(data.len() - pos);
+    let mut best = (0, 0);
+    let mut candidate = head[hash(data, pos)];
+    let mut chain = 0;
+
+    while candidate != usize::MAX && chain < MAX_CHAIN {
+        if candidate >= pos || pos - candidate > WINDOW_SIZE {
+            break;
+        }
+        let len = data[candidate..]
+            .iter()
+            .zip(&data[pos..pos + max_len])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if len > best.0 {
+            best = (len, pos - candidate);
+            if len == max_len {
+                break;
+            }
+        }
+        let next = prev[candidate % WINDOW_SIZE];
+        if next != usize::MAX && next >= candidate {
+            break;
+        }
+        candidate = next;
+        chain += 1;
+    }
+
+    best
+}
+
+// Fixed literal/length code for `symbol` (0..=287) as (code, bit length)
+fn fixed_code(symbol: u16) -> (u16, u8) {
+    match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + (symbol - 144), 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xC0 + (symbol - 280), 8),
+    }
+}
+
+fn write_literal(writer: &mut BitWriter, symbol: u16) {
+    let (code, len) = fixed_code(symbol);
+    writer.write_huffman(code, len);
+}
+
+fn write_length(writer: &mut BitWriter, length: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|&base| base as usize <= length)
+        .unwrap();
+    write_literal(writer, 257 + index as u16);
+    let extra = LENGTH_EXTRA[index];
+    if extra > 0 {
+        writer.write_bits((length - LENGTH_BASE[index] as usize) as u32, extra);
+    }
+}
+
+fn write_distance(writer: &mut BitWriter, distance: usize) {
+    let index = DIST_BASE
+        .iter()
+        .rposition(|&base| base as usize <= distance)
+        .unwrap();
+    writer.write_huffman(index as u16, 5);
+    let extra = DIST_EXTRA[index];
+    if extra > 0 {
+        writer.write_bits((distance - DIST_BASE[index] as usize) as u32, extra);
+    }
+}
+
+// LSB-first bit packer, as DEFLATE requires
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u64,
+    count: u8,
+}
+
+impl BitWriter {
+    fn new(capacity: usize) -> Self {
+        Self {
+            out: Vec::with_capacity(capacity),
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn write_bits(&mut self, value: u32, bits: u8) {
+        self.buffer |= (value as u64) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit
+    fn write_huffman(&mut self, code: u16, len: u8) {
+        let reversed = code.reverse_bits() >> (16 - len);
+        self.write_bits(reversed as u32, len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+// CRC-32 (IEEE 802.3), as used by the gzip trailer
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finish()
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+// Incremental CRC-32 for data that arrives in pieces
+#[derive(Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { state: 0xFFFF_FFFF }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.state =
+                CRC32_TABLE[((self.state ^ byte as u32) & 0xFF) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    pub fn finish(self) -> u32 {
+        !self.state
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
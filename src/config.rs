@@ -0,0 +1,904 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use hyper::body::Bytes;
+use hyper::{Method, Uri};
+
+use crate::access_log::LogFormat;
+use crate::cache_policy::CachePolicy;
+use crate::json_case::FieldCase;
+use crate::rate_limit::{RateLimitAlgorithm, MIN_RATE_PER_SEC};
+use crate::redact::RedactList;
+use crate::response_cache::CacheRoutes;
+use crate::route_headers::{RouteHeaders, RouteSelector};
+use crate::route_timeout::RouteTimeouts;
+use crate::route_toggle::RouteToggles;
+use crate::vhost::HostTable;
+
+// hyper's own default buffer limit, and the smallest it accepts
+pub const DEFAULT_HTTP1_MAX_BUF_SIZE: usize = 8192 + 4096 * 100;
+pub const MIN_HTTP1_BUF_SIZE: usize = 8192;
+
+// Most requests `/debug/recent` may keep; its buffer is allocated up front
+pub const MAX_RECENT_REQUESTS: usize = 100_000;
+
+// Smallest nonzero `MAX_RESPONSE_SIZE`; the 500 sent in place of an
+// oversized response has to fit under it
+pub const MIN_MAX_RESPONSE_SIZE: usize = 1024;
+
+// Runtime configuration, read once at startup
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Replace the startup banner with a single structured log line.
+    pub quiet: bool,
+    /// Least severe diagnostics written to stderr; `debug` adds client disconnects.
+    pub log_level: LogLevel,
+    /// Shut down gracefully after serving this many requests; 0 means unlimited.
+    pub max_requests: u64,
+    /// `/health` answers 503 after this many 5xx responses in a row, until a 2xx; 0 disables.
+    pub unhealthy_after_errors: u64,
+    /// Longest a request may take before the server answers 504; 0 disables.
+    pub request_timeout_ms: u64,
+    /// `request_timeout_ms` overridden by route pattern, in ms or `off`; unlisted routes use it as is.
+    pub route_timeouts: RouteTimeouts,
+    /// Log `/favicon.ico` requests; browsers send one per page load.
+    pub log_favicon: bool,
+    /// Icon served at `/favicon.ico`; unset answers 204 No Content.
+    pub favicon: Option<Favicon>,
+    /// Fraction of successful requests logged (0.0 to 1.0); 4xx and 5xx are always logged.
+    pub log_sample_rate: f64,
+    /// Layout of request log lines: `default`, `clf` (Common Log Format) or `combined`.
+    pub log_format: LogFormat,
+    /// Write timestamps in UTC (`Z`) rather than local time, in responses and logs alike.
+    pub use_utc_timestamps: bool,
+    /// Requests kept for `/debug/recent`, newest replacing oldest; 0 keeps none.
+    pub recent_requests: usize,
+    /// Append each request's headers to its log line (default log format only).
+    pub log_headers: bool,
+    /// Headers whose values are logged and kept as `***`; defaults to credentials and cookies.
+    pub redacted_headers: RedactList,
+    /// Key naming of the server's JSON responses (snake_case or camelCase).
+    pub json_field_case: FieldCase,
+    /// Wrap the server's JSON responses as `{"data", "error", "meta"}` instead of sending them flat.
+    pub json_envelope: bool,
+    /// Format echo routes answer in when the client's Accept doesn't decide.
+    pub echo_format: EchoFormat,
+    /// Upper bound for `GET /delay/:ms`; larger values are rejected with 400.
+    pub max_delay_ms: u64,
+    /// Most chunks `GET /echo/stream/:msg` will send.
+    pub max_stream_chunks: usize,
+    /// Most lines `GET /stream/ndjson` will send.
+    pub max_ndjson_lines: usize,
+    /// Most query parameters a request may carry before it is answered with 400.
+    pub max_query_params: usize,
+    /// Longest query parameter key or value, in bytes before percent-decoding.
+    pub max_query_param_bytes: usize,
+    /// `GET /echo/:msg?repeat=` bodies larger than this are streamed instead of buffered.
+    pub echo_stream_threshold_bytes: usize,
+    /// Largest body `?repeat=` may generate; larger requests are rejected with 400.
+    pub max_echo_bytes: usize,
+    /// Bearer token for the `/admin/` routes; unset disables them.
+    pub admin_token: Option<String>,
+    /// Start in maintenance mode, answering non-admin routes with 503.
+    pub maintenance: bool,
+    /// `Retry-After` sent with maintenance-mode 503s.
+    pub maintenance_retry_after_secs: u64,
+    /// How long `POST /admin/shutdown?drain=true` reports not ready before connections start draining.
+    pub shutdown_drain_delay_ms: u64,
+    /// TCP addresses to listen on; every one is bound before any is served.
+    pub listen_addrs: Vec<SocketAddr>,
+    /// Set SO_REUSEPORT so another process can bind the same addresses (Unix only).
+    pub reuse_port: bool,
+    /// Pending-connection queue length passed to `listen` on each TCP socket; the OS may cap it.
+    pub listen_backlog: u32,
+    /// Probe each TCP listener with `GET /health` at startup, exiting if it doesn't answer.
+    pub startup_probe: bool,
+    /// How long the startup probe waits for each listener's answer.
+    pub startup_probe_timeout_ms: u64,
+    /// Hold `/readyz` at 503 this long after binding, to simulate a slow start; 0 for none.
+    pub startup_delay_ms: u64,
+    /// File the lifetime `/stats` counters are saved to on shutdown and restored from on startup.
+    pub stats_snapshot_path: Option<PathBuf>,
+    /// Record a span per request and export them to `otel_endpoint`.
+    pub otel_traces: bool,
+    /// OTLP/HTTP collector base URL; spans are POSTed to `/v1/traces` under it.
+    pub otel_endpoint: Uri,
+    /// `service.name` reported with every exported span.
+    pub otel_service_name: String,
+    /// Listen on this Unix domain socket instead of TCP when set.
+    pub unix_socket: Option<PathBuf>,
+    /// Bring non-ASCII path characters to Unicode NFC before routing, so differently composed names match.
+    pub normalize_path_unicode: bool,
+    /// Answer requests that didn't arrive over HTTPS with a 308 to their `https://` URL.
+    pub https_redirect: bool,
+    /// Path prefix stripped before routing, e.g. `/api` behind a reverse proxy; empty serves from `/`.
+    pub base_path: String,
+    /// Routes switched on or off by pattern; unlisted routes keep their default (`/debug/` is off in release builds).
+    pub route_toggles: RouteToggles,
+    /// GET responses kept by route pattern, in ms; identical requests meanwhile share one response.
+    pub response_cache: CacheRoutes,
+    /// `Cache-Control` sent by route pattern, `*` for the rest; unlisted routes send their built-in default.
+    pub cache_control: CachePolicy,
+    /// Static headers added by route pattern, `/prefix/*` or `*`, on every status.
+    pub route_headers: RouteHeaders,
+    /// Hostname -> site routing; empty serves every route on every host.
+    pub virtual_hosts: HostTable,
+    /// Configured host whose site unknown hosts get; unset answers them 404.
+    pub default_host: Option<String>,
+    /// Largest request body accepted by POST routes; larger bodies get 413.
+    pub max_body_bytes: usize,
+    /// Largest JSON body the server will generate; larger ones get 500 instead. 0 means no limit.
+    pub max_response_size: usize,
+    /// Total bytes all buffered request bodies may reserve at once; 0 means no limit.
+    pub body_budget_bytes: usize,
+    /// How long a body waits for its share of `body_budget_bytes` before getting 503.
+    pub body_budget_timeout_ms: u64,
+    /// Longest a request body may take to arrive before the server answers 408; 0 disables.
+    pub body_read_timeout_ms: u64,
+    /// Check bodies against a `Content-MD5` or `Digest` (MD5, SHA-256) header when one is sent, answering 400 on a mismatch.
+    pub verify_body_digest: bool,
+    /// How long a response stays replayable under its `Idempotency-Key`.
+    pub idempotency_ttl_secs: u64,
+    /// gzip responses for clients that accept it.
+    pub compression: bool,
+    /// Bodies smaller than this are sent uncompressed.
+    pub compression_min_bytes: usize,
+    /// Set TCP_NODELAY on accepted connections, disabling Nagle's algorithm.
+    pub tcp_nodelay: bool,
+    /// Largest buffer an HTTP/1 connection's reads and queued writes may grow to, in bytes (at least 8192).
+    pub http1_max_buf_size: usize,
+    /// Write HTTP/1 responses with vectored IO (`Some(true)`) or by flattening into one buffer; `None` lets hyper decide.
+    pub http1_writev: Option<bool>,
+    /// Keep HTTP/1.0 connections open for clients that send `Connection: keep-alive`; off closes them after every response.
+    pub http1_0_keep_alive: bool,
+    /// Prefix of the runtime's thread names, numbered from 0, e.g. `http-worker-3`.
+    pub worker_thread_name: String,
+    /// Log a warning when the recent accept rate exceeds this many connections per second; 0 disables.
+    pub accept_rate_warn_per_sec: f64,
+    /// Simultaneous open connections allowed per client IP; 0 means unlimited.
+    pub max_connections_per_ip: usize,
+    /// How long after startup the accept rate ramps up to `slow_start_accept_per_sec`, in ms; 0 disables slow start.
+    pub slow_start_ms: u64,
+    /// Connections per second accepted at the end of the slow-start ramp, which starts at a tenth of it.
+    pub slow_start_accept_per_sec: f64,
+    /// Sustained requests per second allowed per client prefix; 0 disables rate limiting.
+    pub rate_limit_per_sec: f64,
+    /// `token_bucket` lets a client spend its burst at once; `leaky_bucket` spaces requests out.
+    pub rate_limit_algorithm: RateLimitAlgorithm,
+    /// Requests a client may burst above the sustained rate.
+    pub rate_limit_burst: u32,
+    /// IPv4 clients sharing this many leading bits share a bucket.
+    pub rate_limit_ipv4_prefix: u8,
+    /// IPv6 clients sharing this many leading bits share a bucket.
+    pub rate_limit_ipv6_prefix: u8,
+    /// How often idle buckets are swept from the rate limiter; 0 never sweeps.
+    pub rate_limit_sweep_interval_ms: u64,
+    /// A bucket unused for this long, and refilled by now, is dropped by the sweep.
+    pub rate_limit_idle_ttl_ms: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            quiet: false,
+            log_level: LogLevel::Info,
+            max_requests: 0,
+            unhealthy_after_errors: 0,
+            request_timeout_ms: 30_000,
+            route_timeouts: RouteTimeouts::new(),
+            log_favicon: true,
+            favicon: None,
+            log_sample_rate: 1.0,
+            log_format: LogFormat::Default,
+            use_utc_timestamps: false,
+            recent_requests: 0,
+            log_headers: false,
+            redacted_headers: RedactList::default(),
+            json_field_case: FieldCase::Snake,
+            json_envelope: false,
+            echo_format: EchoFormat::Json,
+            max_delay_ms: 10_000,
+            max_stream_chunks: 10_000,
+            max_ndjson_lines: 10_000,
+            max_query_params: 64,
+            max_query_param_bytes: 1024,
+            echo_stream_threshold_bytes: 64 * 1024,
+            max_echo_bytes: 16 * 1024 * 1024,
+            admin_token: None,
+            maintenance: false,
+            maintenance_retry_after_secs: 300,
+            shutdown_drain_delay_ms: 5000,
+            listen_addrs: vec![SocketAddr::from(([127, 0, 0, 1], 8080))],
+            reuse_port: false,
+            listen_backlog: 1024,
+            startup_probe: false,
+            startup_probe_timeout_ms: 2000,
+            startup_delay_ms: 0,
+            stats_snapshot_path: None,
+            otel_traces: false,
+            otel_endpoint: Uri::from_static("http://localhost:4318"),
+            otel_service_name: "rust-http-server".to_string(),
+            unix_socket: None,
+            normalize_path_unicode: false,
+            https_redirect: false,
+            base_path: String::new(),
+            route_toggles: RouteToggles::new(),
+            response_cache: CacheRoutes::new(),
+            cache_control: CachePolicy::new(),
+            route_headers: RouteHeaders::new(),
+            virtual_hosts: HostTable::new(),
+            default_host: None,
+            max_body_bytes: 1024 * 1024,
+            max_response_size: 0,
+            body_budget_bytes: 0,
+            body_budget_timeout_ms: 1000,
+            body_read_timeout_ms: 10_000,
+            verify_body_digest: false,
+            idempotency_ttl_secs: 300,
+            compression: true,
+            compression_min_bytes: 1024,
+            tcp_nodelay: true,
+            http1_max_buf_size: DEFAULT_HTTP1_MAX_BUF_SIZE,
+            http1_writev: None,
+            http1_0_keep_alive: true,
+            worker_thread_name: "http-worker".to_string(),
+            accept_rate_warn_per_sec: 0.0,
+            max_connections_per_ip: 0,
+            slow_start_ms: 0,
+            slow_start_accept_per_sec: 100.0,
+            rate_limit_per_sec: 0.0,
+            rate_limit_algorithm: RateLimitAlgorithm::TokenBucket,
+            rate_limit_burst: 20,
+            rate_limit_ipv4_prefix: 32,
+            rate_limit_ipv6_prefix: 64,
+            rate_limit_sweep_interval_ms: 60_000,
+            rate_limit_idle_ttl_ms: 60_000,
+        }
+    }
+}
+
+impl Config {
+    // Reads every setting from the environment; unset variables keep their
+    // defaults, but a value that is set and wrong is an error, not a fallback
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|key| env::var(key).ok())
+    }
+
+    // `from_env` over any source of variables
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let vars = Vars(&lookup);
+        let defaults = Self::default();
+        let config = Self {
+            quiet: vars.flag("QUIET", defaults.quiet)?,
+            log_level: vars.parse_or("LOG_LEVEL", defaults.log_level)?,
+            max_requests: vars.parse_or("MAX_REQUESTS", defaults.max_requests)?,
+            unhealthy_after_errors: vars
+                .parse_or("UNHEALTHY_AFTER_ERRORS", defaults.unhealthy_after_errors)?,
+            request_timeout_ms: vars.parse_or("REQUEST_TIMEOUT_MS", defaults.request_timeout_ms)?,
+            route_timeouts: vars.parse_or("ROUTE_TIMEOUTS", defaults.route_timeouts)?,
+            log_favicon: vars.flag("LOG_FAVICON", defaults.log_favicon)?,
+            favicon: vars
+                .get("FAVICON_PATH")
+                .map(|path| {
+                    Favicon::load(&path).map_err(|err| ConfigError::Invalid {
+                        key: "FAVICON_PATH",
+                        value: path,
+                        reason: err.to_string(),
+                    })
+                })
+                .transpose()?,
+            log_sample_rate: vars.parse_or("LOG_SAMPLE_RATE", defaults.log_sample_rate)?,
+            log_format: vars.parse_or("LOG_FORMAT", defaults.log_format)?,
+            use_utc_timestamps: vars.flag("USE_UTC_TIMESTAMPS", defaults.use_utc_timestamps)?,
+            recent_requests: vars.parse_or("RECENT_REQUESTS", defaults.recent_requests)?,
+            log_headers: vars.flag("LOG_HEADERS", defaults.log_headers)?,
+            redacted_headers: vars.parse_or("REDACT_HEADERS", defaults.redacted_headers)?,
+            json_field_case: vars.parse_or("JSON_FIELD_CASE", defaults.json_field_case)?,
+            json_envelope: vars.flag("JSON_ENVELOPE", defaults.json_envelope)?,
+            echo_format: vars.parse_or("ECHO_FORMAT", defaults.echo_format)?,
+            max_delay_ms: vars.parse_or("MAX_DELAY_MS", defaults.max_delay_ms)?,
+            max_stream_chunks: vars.parse_or("MAX_STREAM_CHUNKS", defaults.max_stream_chunks)?,
+            max_ndjson_lines: vars.parse_or("MAX_NDJSON_LINES", defaults.max_ndjson_lines)?,
+            max_query_params: vars.parse_or("MAX_QUERY_PARAMS", defaults.max_query_params)?,
+            max_query_param_bytes: vars
+                .parse_or("MAX_QUERY_PARAM_BYTES", defaults.max_query_param_bytes)?,
+            echo_stream_threshold_bytes: vars.parse_or(
+                "ECHO_STREAM_THRESHOLD_BYTES",
+                defaults.echo_stream_threshold_bytes,
+            )?,
+            max_echo_bytes: vars.parse_or("MAX_ECHO_BYTES", defaults.max_echo_bytes)?,
+            admin_token: vars.get("ADMIN_TOKEN").filter(|token| !token.is_empty()),
+            maintenance: vars.flag("MAINTENANCE", defaults.maintenance)?,
+            maintenance_retry_after_secs: vars.parse_or(
+                "MAINTENANCE_RETRY_AFTER_SECS",
+                defaults.maintenance_retry_after_secs,
+            )?,
+            shutdown_drain_delay_ms: vars
+                .parse_or("SHUTDOWN_DRAIN_DELAY_MS", defaults.shutdown_drain_delay_ms)?,
+            listen_addrs: vars
+                .get("LISTEN_ADDRS")
+                .map(|value| parse_listen_addrs(&value))
+                .transpose()?
+                .unwrap_or(defaults.listen_addrs),
+            reuse_port: vars.flag("REUSE_PORT", defaults.reuse_port)?,
+            listen_backlog: vars.parse_or("LISTEN_BACKLOG", defaults.listen_backlog)?,
+            startup_probe: vars.flag("STARTUP_PROBE", defaults.startup_probe)?,
+            startup_probe_timeout_ms: vars.parse_or(
+                "STARTUP_PROBE_TIMEOUT_MS",
+                defaults.startup_probe_timeout_ms,
+            )?,
+            startup_delay_ms: vars.parse_or("STARTUP_DELAY_MS", defaults.startup_delay_ms)?,
+            stats_snapshot_path: vars.get("STATS_SNAPSHOT_PATH").map(PathBuf::from),
+            otel_traces: vars.flag("OTEL_TRACES", defaults.otel_traces)?,
+            otel_endpoint: vars.parse_or("OTEL_EXPORTER_OTLP_ENDPOINT", defaults.otel_endpoint)?,
+            otel_service_name: vars
+                .get("OTEL_SERVICE_NAME")
+                .filter(|name| !name.is_empty())
+                .unwrap_or(defaults.otel_service_name),
+            unix_socket: vars.get("UNIX_SOCKET").map(PathBuf::from),
+            normalize_path_unicode: vars
+                .flag("NORMALIZE_PATH_UNICODE", defaults.normalize_path_unicode)?,
+            https_redirect: vars.flag("HTTPS_REDIRECT", defaults.https_redirect)?,
+            // `/api/` means `/api`, and `/` no prefix at all
+            base_path: vars
+                .get("BASE_PATH")
+                .map(|path| {
+                    if path.starts_with('/') {
+                        path.trim_end_matches('/').to_string()
+                    } else {
+                        path
+                    }
+                })
+                .unwrap_or_default(),
+            route_toggles: vars.parse_or("ROUTE_TOGGLES", defaults.route_toggles)?,
+            response_cache: vars.parse_or("RESPONSE_CACHE", defaults.response_cache)?,
+            cache_control: vars.parse_or("CACHE_CONTROL", defaults.cache_control)?,
+            route_headers: vars.parse_or("ROUTE_HEADERS", defaults.route_headers)?,
+            virtual_hosts: vars.parse_or("VIRTUAL_HOSTS", defaults.virtual_hosts)?,
+            default_host: vars.get("DEFAULT_HOST"),
+            max_body_bytes: vars.parse_or("MAX_BODY_BYTES", defaults.max_body_bytes)?,
+            max_response_size: vars.parse_or("MAX_RESPONSE_SIZE", defaults.max_response_size)?,
+            body_budget_bytes: vars.parse_or("BODY_BUDGET_BYTES", defaults.body_budget_bytes)?,
+            body_budget_timeout_ms: vars
+                .parse_or("BODY_BUDGET_TIMEOUT_MS", defaults.body_budget_timeout_ms)?,
+            body_read_timeout_ms: vars
+                .parse_or("BODY_READ_TIMEOUT_MS", defaults.body_read_timeout_ms)?,
+            verify_body_digest: vars.flag("VERIFY_BODY_DIGEST", defaults.verify_body_digest)?,
+            idempotency_ttl_secs: vars
+                .parse_or("IDEMPOTENCY_TTL_SECS", defaults.idempotency_ttl_secs)?,
+            compression: vars.flag("COMPRESSION", defaults.compression)?,
+            compression_min_bytes: vars
+                .parse_or("COMPRESSION_MIN_BYTES", defaults.compression_min_bytes)?,
+            tcp_nodelay: vars.flag("TCP_NODELAY", defaults.tcp_nodelay)?,
+            http1_max_buf_size: vars.parse_or("HTTP1_MAX_BUF_SIZE", defaults.http1_max_buf_size)?,
+            http1_writev: match vars.get("HTTP1_WRITEV") {
+                Some(value) if value.trim().eq_ignore_ascii_case("auto") => None,
+                Some(_) => Some(vars.flag("HTTP1_WRITEV", false)?),
+                None => defaults.http1_writev,
+            },
+            http1_0_keep_alive: vars.flag("HTTP1_0_KEEP_ALIVE", defaults.http1_0_keep_alive)?,
+            worker_thread_name: vars
+                .get("WORKER_THREAD_NAME")
+                .filter(|name| !name.is_empty())
+                .unwrap_or(defaults.worker_thread_name),
+            accept_rate_warn_per_sec: vars.parse_or(
+                "ACCEPT_RATE_WARN_PER_SEC",
+                defaults.accept_rate_warn_per_sec,
+            )?,
+            max_connections_per_ip: vars
+                .parse_or("MAX_CONNECTIONS_PER_IP", defaults.max_connections_per_ip)?,
+            slow_start_ms: vars.parse_or("SLOW_START_MS", defaults.slow_start_ms)?,
+            slow_start_accept_per_sec: vars.parse_or(
+                "SLOW_START_ACCEPT_PER_SEC",
+                defaults.slow_start_accept_per_sec,
+            )?,
+            rate_limit_per_sec: vars.parse_or("RATE_LIMIT_PER_SEC", defaults.rate_limit_per_sec)?,
+            rate_limit_algorithm: vars
+                .parse_or("RATE_LIMIT_ALGORITHM", defaults.rate_limit_algorithm)?,
+            rate_limit_burst: vars.parse_or("RATE_LIMIT_BURST", defaults.rate_limit_burst)?,
+            rate_limit_ipv4_prefix: vars
+                .parse_or("RATE_LIMIT_IPV4_PREFIX", defaults.rate_limit_ipv4_prefix)?,
+            rate_limit_ipv6_prefix: vars
+                .parse_or("RATE_LIMIT_IPV6_PREFIX", defaults.rate_limit_ipv6_prefix)?,
+            rate_limit_sweep_interval_ms: vars.parse_or(
+                "RATE_LIMIT_SWEEP_INTERVAL_MS",
+                defaults.rate_limit_sweep_interval_ms,
+            )?,
+            rate_limit_idle_ttl_ms: vars
+                .parse_or("RATE_LIMIT_IDLE_TTL_MS", defaults.rate_limit_idle_ttl_ms)?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    // The `Host` the startup probe sends: with `VIRTUAL_HOSTS`, a configured
+    // name whose site serves `/health` (`DEFAULT_HOST` first), since the
+    // listening address matches none of them. `None` without virtual hosts.
+    pub fn probe_host(&self) -> Option<String> {
+        self.virtual_hosts
+            .host_serving("/health", self.default_host.as_deref())
+    }
+
+    // Checks ranges and combinations of settings that parse fine individually
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let in_range = |ok: bool, key: &'static str, value: String, expected: &'static str| {
+            if ok {
+                Ok(())
+            } else {
+                Err(ConfigError::OutOfRange {
+                    key,
+                    value,
+                    expected,
+                })
+            }
+        };
+        in_range(
+            (0.0..=1.0).contains(&self.log_sample_rate),
+            "LOG_SAMPLE_RATE",
+            self.log_sample_rate.to_string(),
+            "between 0 and 1",
+        )?;
+        in_range(
+            self.rate_limit_per_sec.is_finite()
+                && (self.rate_limit_per_sec == 0.0 || self.rate_limit_per_sec >= MIN_RATE_PER_SEC),
+            "RATE_LIMIT_PER_SEC",
+            self.rate_limit_per_sec.to_string(),
+            "0, or at least one request a day (0.0000116)",
+        )?;
+        in_range(
+            self.rate_limit_ipv4_prefix <= 32,
+            "RATE_LIMIT_IPV4_PREFIX",
+            self.rate_limit_ipv4_prefix.to_string(),
+            "at most 32",
+        )?;
+        in_range(
+            self.rate_limit_ipv6_prefix <= 128,
+            "RATE_LIMIT_IPV6_PREFIX",
+            self.rate_limit_ipv6_prefix.to_string(),
+            "at most 128",
+        )?;
+        in_range(
+            self.body_budget_bytes <= u32::MAX as usize,
+            "BODY_BUDGET_BYTES",
+            self.body_budget_bytes.to_string(),
+            "at most 4294967295",
+        )?;
+        in_range(
+            self.http1_max_buf_size >= MIN_HTTP1_BUF_SIZE,
+            "HTTP1_MAX_BUF_SIZE",
+            self.http1_max_buf_size.to_string(),
+            "at least 8192",
+        )?;
+        in_range(
+            self.recent_requests <= MAX_RECENT_REQUESTS,
+            "RECENT_REQUESTS",
+            self.recent_requests.to_string(),
+            "at most 100000",
+        )?;
+        in_range(
+            self.max_response_size == 0 || self.max_response_size >= MIN_MAX_RESPONSE_SIZE,
+            "MAX_RESPONSE_SIZE",
+            self.max_response_size.to_string(),
+            "0 or at least 1024",
+        )?;
+        in_range(
+            self.max_query_params >= 1,
+            "MAX_QUERY_PARAMS",
+            self.max_query_params.to_string(),
+            "at least 1",
+        )?;
+        in_range(
+            self.max_query_param_bytes >= 1,
+            "MAX_QUERY_PARAM_BYTES",
+            self.max_query_param_bytes.to_string(),
+            "at least 1",
+        )?;
+        in_range(
+            (1..=65535).contains(&self.listen_backlog),
+            "LISTEN_BACKLOG",
+            self.listen_backlog.to_string(),
+            "between 1 and 65535",
+        )?;
+        in_range(
+            self.accept_rate_warn_per_sec.is_finite() && self.accept_rate_warn_per_sec >= 0.0,
+            "ACCEPT_RATE_WARN_PER_SEC",
+            self.accept_rate_warn_per_sec.to_string(),
+            "a non-negative number",
+        )?;
+        in_range(
+            self.slow_start_accept_per_sec.is_finite() && self.slow_start_accept_per_sec > 0.0,
+            "SLOW_START_ACCEPT_PER_SEC",
+            self.slow_start_accept_per_sec.to_string(),
+            "a positive number",
+        )?;
+
+        if let Some(host) = &self.default_host {
+            if self.virtual_hosts.is_empty() {
+                return Err(ConfigError::Conflict {
+                    key: "DEFAULT_HOST",
+                    reason: "has no effect without VIRTUAL_HOSTS".to_string(),
+                });
+            }
+            if self.virtual_hosts.resolve(Some(host), None).is_none() {
+                return Err(ConfigError::Conflict {
+                    key: "DEFAULT_HOST",
+                    reason: format!("{:?} is not one of the VIRTUAL_HOSTS", host),
+                });
+            }
+        }
+        if !self.base_path.is_empty() && !self.base_path.starts_with('/') {
+            return Err(ConfigError::Invalid {
+                key: "BASE_PATH",
+                value: self.base_path.clone(),
+                reason: "must start with /".to_string(),
+            });
+        }
+        if let Some(pattern) = self
+            .route_toggles
+            .patterns()
+            .find(|pattern| !crate::is_route_pattern(pattern))
+        {
+            return Err(ConfigError::Invalid {
+                key: "ROUTE_TOGGLES",
+                value: pattern.to_string(),
+                reason: "no route is registered under that pattern".to_string(),
+            });
+        }
+        if let Some(pattern) = self
+            .cache_control
+            .patterns()
+            .find(|pattern| !crate::is_route_pattern(pattern))
+        {
+            return Err(ConfigError::Invalid {
+                key: "CACHE_CONTROL",
+                value: pattern.to_string(),
+                reason: "no route is registered under that pattern".to_string(),
+            });
+        }
+        if let Some(selector) = self.route_headers.selectors().find(|selector| {
+            **selector != RouteSelector::All
+                && !crate::ROUTES
+                    .iter()
+                    .any(|route| selector.matches(Some(route.pattern)))
+        }) {
+            let value = match selector {
+                RouteSelector::Prefix(prefix) => format!("{}*", prefix),
+                RouteSelector::Route(pattern) => pattern.clone(),
+                RouteSelector::All => unreachable!(),
+            };
+            return Err(ConfigError::Invalid {
+                key: "ROUTE_HEADERS",
+                value,
+                reason: "no route is registered under that pattern".to_string(),
+            });
+        }
+        if let Some(pattern) = self.response_cache.patterns().find(|pattern| {
+            !crate::is_route_pattern(pattern)
+                || !crate::route_methods(pattern).contains(&Method::GET)
+        }) {
+            return Err(ConfigError::Invalid {
+                key: "RESPONSE_CACHE",
+                value: pattern.to_string(),
+                reason: "no GET route is registered under that pattern".to_string(),
+            });
+        }
+        // A shared 101 would leave every other client without its upgrade
+        if self
+            .response_cache
+            .patterns()
+            .any(|pattern| pattern == "/ws")
+        {
+            return Err(ConfigError::Invalid {
+                key: "RESPONSE_CACHE",
+                value: "/ws".to_string(),
+                reason: "WebSocket upgrades can't be cached".to_string(),
+            });
+        }
+        // Answers that depend on who is asking: the bearer-gated admin API,
+        // and the reflector, which echoes credentials back
+        if let Some(pattern) = self
+            .response_cache
+            .patterns()
+            .find(|pattern| pattern.starts_with("/admin/") || *pattern == "/debug/echo")
+        {
+            return Err(ConfigError::Invalid {
+                key: "RESPONSE_CACHE",
+                value: pattern.to_string(),
+                reason: "its responses depend on the client's credentials".to_string(),
+            });
+        }
+        if let Some(pattern) = self
+            .route_timeouts
+            .patterns()
+            .find(|pattern| !crate::is_route_pattern(pattern))
+        {
+            return Err(ConfigError::Invalid {
+                key: "ROUTE_TIMEOUTS",
+                value: pattern.to_string(),
+                reason: "no route is registered under that pattern".to_string(),
+            });
+        }
+        // Exports are plain HTTP; there is no TLS client to send them with
+        if self.otel_traces
+            && (self.otel_endpoint.scheme_str() != Some("http")
+                || self.otel_endpoint.authority().is_none())
+        {
+            return Err(ConfigError::Invalid {
+                key: "OTEL_EXPORTER_OTLP_ENDPOINT",
+                value: self.otel_endpoint.to_string(),
+                reason: "expected an http:// URL".to_string(),
+            });
+        }
+        if self.body_budget_bytes > 0 && self.body_budget_bytes < self.max_body_bytes {
+            return Err(ConfigError::Conflict {
+                key: "BODY_BUDGET_BYTES",
+                reason: format!(
+                    "is smaller than one body of MAX_BODY_BYTES ({})",
+                    self.max_body_bytes
+                ),
+            });
+        }
+        if let Some(reason) = overlapping_listen_addr(&self.listen_addrs) {
+            return Err(ConfigError::Conflict {
+                key: "LISTEN_ADDRS",
+                reason,
+            });
+        }
+        if self.startup_probe && self.probe_host().is_none() && !self.virtual_hosts.is_empty() {
+            return Err(ConfigError::Conflict {
+                key: "STARTUP_PROBE",
+                reason: "no VIRTUAL_HOSTS site serves /health, so the probe could never pass"
+                    .to_string(),
+            });
+        }
+        if self.maintenance && self.admin_token.is_none() {
+            return Err(ConfigError::Conflict {
+                key: "MAINTENANCE",
+                reason: "could never be switched off without ADMIN_TOKEN".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+// Why the configuration couldn't be loaded. Each category has its own exit
+// status so orchestration can tell them apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    // The variable doesn't parse as its type
+    Invalid {
+        key: &'static str,
+        value: String,
+        reason: String,
+    },
+    // It parses, but is outside the values the setting allows
+    OutOfRange {
+        key: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+    // Fine on its own, but contradicts another setting
+    Conflict {
+        key: &'static str,
+        reason: String,
+    },
+}
+
+impl ConfigError {
+    // The variable at fault
+    pub fn key(&self) -> &'static str {
+        match self {
+            ConfigError::Invalid { key, .. }
+            | ConfigError::OutOfRange { key, .. }
+            | ConfigError::Conflict { key, .. } => key,
+        }
+    }
+
+    // Process exit status for this category
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ConfigError::Invalid { .. } => 2,
+            ConfigError::OutOfRange { .. } => 3,
+            ConfigError::Conflict { .. } => 4,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Invalid { key, value, reason } => {
+                write!(f, "invalid {}={:?}: {}", key, value, reason)
+            }
+            ConfigError::OutOfRange {
+                key,
+                value,
+                expected,
+            } => write!(
+                f,
+                "{}={} is out of range: expected {}",
+                key, value, expected
+            ),
+            ConfigError::Conflict { key, reason } => write!(f, "{} {}", key, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// An icon read into memory at startup
+#[derive(Debug, Clone)]
+pub struct Favicon {
+    pub bytes: Bytes,
+    pub content_type: &'static str,
+    // The file's modification time, sent as `Last-Modified`
+    pub modified: Option<SystemTime>,
+}
+
+impl Favicon {
+    // Reads `path`, typing it by extension and noting when it last changed
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let content_type = match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            Some("ico") => "image/x-icon",
+            Some("png") => "image/png",
+            Some("svg") => "image/svg+xml",
+            Some("gif") => "image/gif",
+            _ => "application/octet-stream",
+        };
+        Ok(Self {
+            bytes: fs::read(path)?.into(),
+            content_type,
+            modified: fs::metadata(path)?.modified().ok(),
+        })
+    }
+}
+
+// How echo routes present the echoed message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoFormat {
+    // Wrapped in the usual JSON envelope
+    Json,
+    // The bare message as text/plain
+    Text,
+}
+
+impl FromStr for EchoFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(EchoFormat::Json),
+            "text" => Ok(EchoFormat::Text),
+            other => Err(format!(
+                "unknown echo format {:?} (expected json or text)",
+                other
+            )),
+        }
+    }
+}
+
+// How chatty stderr diagnostics are. Request log lines aren't affected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    // Also routine events, like clients hanging up mid-response
+    Debug,
+    Info,
+}
+
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            other => Err(format!(
+                "unknown log level {:?} (expected debug or info)",
+                other
+            )),
+        }
+    }
+}
+
+// Parses `LISTEN_ADDRS`: comma-separated `ip:port` or `host:port` entries.
+// A hostname listens on the first address it resolves to.
+fn parse_listen_addrs(value: &str) -> Result<Vec<SocketAddr>, ConfigError> {
+    let invalid = |reason: String| ConfigError::Invalid {
+        key: "LISTEN_ADDRS",
+        value: value.to_string(),
+        reason,
+    };
+
+    let addrs = value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .to_socket_addrs()
+                .map_err(|err| invalid(format!("{:?}: {}", entry, err)))?
+                .next()
+                .ok_or_else(|| invalid(format!("{:?} resolves to no address", entry)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if addrs.is_empty() {
+        return Err(invalid("expected at least one address".to_string()));
+    }
+    Ok(addrs)
+}
+
+// Why two of `addrs` can't both be bound: the same address twice, or a
+// wildcard and a specific address of the same family on one port
+fn overlapping_listen_addr(addrs: &[SocketAddr]) -> Option<String> {
+    for (i, a) in addrs.iter().enumerate() {
+        for b in &addrs[i + 1..] {
+            // Port 0 picks a fresh port per bind, so it never collides
+            if a.port() != b.port() || a.port() == 0 {
+                continue;
+            }
+            if a == b {
+                return Some(format!("lists {} more than once", a));
+            }
+            let same_family = a.is_ipv4() == b.is_ipv4();
+            if same_family && (a.ip().is_unspecified() || b.ip().is_unspecified()) {
+                return Some(format!("{} overlaps {}", a, b));
+            }
+        }
+    }
+    None
+}
+
+// Source of configuration variables
+struct Vars<'a>(&'a dyn Fn(&str) -> Option<String>);
+
+impl Vars<'_> {
+    fn get(&self, key: &str) -> Option<String> {
+        (self.0)(key)
+    }
+
+    // Parses `key`, or returns `default` when it is unset
+    fn parse_or<T>(&self, key: &'static str, default: T) -> Result<T, ConfigError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        match self.get(key) {
+            Some(value) => value
+                .trim()
+                .parse()
+                .map_err(|err: T::Err| ConfigError::Invalid {
+                    key,
+                    reason: err.to_string(),
+                    value,
+                }),
+            None => Ok(default),
+        }
+    }
+
+    // Reads a boolean; accepts 1/0, true/false, yes/no, on/off
+    fn flag(&self, key: &'static str, default: bool) -> Result<bool, ConfigError> {
+        let Some(value) = self.get(key) else {
+            return Ok(default);
+        };
+        match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(true),
+            "0" | "false" | "no" | "off" => Ok(false),
+            _ => Err(ConfigError::Invalid {
+                key,
+                value,
+                reason: "expected true or false".to_string(),
+            }),
+        }
+    }
+}
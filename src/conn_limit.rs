@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::rate_limit::normalize_ip;
+
+// Caps simultaneous open connections per client IP. Unlike the rate limiter
+// this counts sockets, not requests: one keep-alive connection can send any
+// number of requests, but a client can't hold every connection slot.
+#[derive(Clone)]
+pub struct ConnectionLimiter {
+    active: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    max_per_ip: usize,
+}
+
+impl ConnectionLimiter {
+    pub fn new(max_per_ip: usize) -> Self {
+        Self {
+            active: Arc::new(Mutex::new(HashMap::new())),
+            max_per_ip,
+        }
+    }
+
+    // Claims a connection slot for `ip`; `None` when it already has the
+    // maximum open. The slot is released when the guard drops.
+    pub fn try_acquire(&self, ip: IpAddr) -> Option<ConnectionGuard> {
+        // Full addresses, but an IPv4-mapped IPv6 peer counts as its IPv4 self
+        let ip = normalize_ip(ip, 32, 128);
+
+        let mut active = self.active.lock().unwrap();
+        let count = active.entry(ip).or_insert(0);
+        if *count >= self.max_per_ip {
+            return None;
+        }
+        *count += 1;
+
+        Some(ConnectionGuard {
+            active: self.active.clone(),
+            ip,
+        })
+    }
+
+    // Connections currently open from `ip`
+    pub fn active(&self, ip: IpAddr) -> usize {
+        let ip = normalize_ip(ip, 32, 128);
+        self.active.lock().unwrap().get(&ip).copied().unwrap_or(0)
+    }
+}
+
+// One open connection, counted against its IP until dropped
+pub struct ConnectionGuard {
+    active: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut active = self.active.lock().unwrap();
+        if let Some(count) = active.get_mut(&self.ip) {
+            *count -= 1;
+            // Don't let the map grow with every client ever seen
+            if *count == 0 {
+                active.remove(&self.ip);
+            }
+        }
+    }
+}
+
+// At most one rejection line this often; the rest are only counted
+const REJECTION_LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+// Throttles the line logged for each connection turned away, so a client
+// hammering the limit can't also flood the log. Only the accept loop
+// touches it, so it needs no locking.
+#[derive(Default)]
+pub struct RejectionLog {
+    last_logged: Option<Instant>,
+    suppressed: u64,
+}
+
+impl RejectionLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Counts a rejection at `now`. When a line is due, returns how many
+    // rejections went unlogged since the previous one; `None` otherwise.
+    pub fn record(&mut self, now: Instant) -> Option<u64> {
+        match self.last_logged {
+            Some(last) if now.saturating_duration_since(last) < REJECTION_LOG_INTERVAL => {
+                self.suppressed += 1;
+                None
+            }
+            _ => {
+                self.last_logged = Some(now);
+                Some(std::mem::take(&mut self.suppressed))
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// CORS preflight answers. The allowed methods come from the route registry,
+// so a preflight for `/kv/foo` lists exactly what `/kv/:key` accepts.
+
+use hyper::header::{
+    HeaderValue, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+    ACCESS_CONTROL_REQUEST_METHOD, ALLOW, ORIGIN,
+};
+use hyper::{HeaderMap, Method, Response, StatusCode};
+
+use crate::{full, Body};
+
+// How long browsers may cache a preflight answer
+const MAX_AGE_SECS: u32 = 600;
+
+// The preflight response for an `OPTIONS` request to a route answering
+// `methods`, or `None` when the request isn't a CORS preflight
+pub fn preflight(headers: &HeaderMap, methods: &[Method]) -> Option<Response<Body>> {
+    if !headers.contains_key(ORIGIN) || !headers.contains_key(ACCESS_CONTROL_REQUEST_METHOD) {
+        return None;
+    }
+
+    let mut response = Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(ACCESS_CONTROL_ALLOW_METHODS, method_list(methods))
+        .header(ACCESS_CONTROL_MAX_AGE, MAX_AGE_SECS)
+        .header(ALLOW, method_list(methods))
+        .header("Server", "rust-http-server/1.0");
+    // Any request header is fine, so grant whatever was asked for
+    if let Some(requested) = headers.get(ACCESS_CONTROL_REQUEST_HEADERS) {
+        response = response.header(ACCESS_CONTROL_ALLOW_HEADERS, requested);
+    }
+    Some(response.body(full(Vec::new())).unwrap())
+}
+
+// `GET, PUT, PATCH`
+pub fn method_list(methods: &[Method]) -> HeaderValue {
+    let list = methods
+        .iter()
+        .map(Method::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    HeaderValue::from_str(&list).unwrap()
+}
@@ -0,0 +1,78 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hyper::HeaderMap;
+
+pub const DEADLINE_HEADER: &str = "x-request-deadline";
+pub const GRPC_TIMEOUT_HEADER: &str = "grpc-timeout";
+
+// Time a request may take before the server gives up on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    Unlimited,
+    Remaining(Duration),
+    Expired,
+}
+
+// Combines the configured request timeout with any deadline propagated by
+// the caller, via `X-Request-Deadline` (absolute, Unix milliseconds) or
+// `grpc-timeout` (relative, e.g. `250m`). The tightest limit wins.
+pub fn effective_budget(headers: &HeaderMap, configured: Option<Duration>) -> Budget {
+    let mut budget = configured;
+
+    if let Some(deadline_ms) = header_str(headers, DEADLINE_HEADER).and_then(|v| v.parse().ok()) {
+        match remaining_until(deadline_ms, SystemTime::now()) {
+            Some(remaining) => budget = Some(min_budget(budget, remaining)),
+            None => return Budget::Expired,
+        }
+    }
+
+    if let Some(timeout) = header_str(headers, GRPC_TIMEOUT_HEADER).and_then(parse_grpc_timeout) {
+        budget = Some(min_budget(budget, timeout));
+    }
+
+    match budget {
+        None => Budget::Unlimited,
+        Some(remaining) if remaining.is_zero() => Budget::Expired,
+        Some(remaining) => Budget::Remaining(remaining),
+    }
+}
+
+// Time left until `deadline_ms` (Unix epoch ms), or None once it has passed
+pub fn remaining_until(deadline_ms: u64, now: SystemTime) -> Option<Duration> {
+    let deadline = UNIX_EPOCH + Duration::from_millis(deadline_ms);
+    deadline
+        .duration_since(now)
+        .ok()
+        .filter(|remaining| !remaining.is_zero())
+}
+
+// Parses a gRPC-style timeout: up to 8 digits followed by a unit of
+// H (hours), M (minutes), S (seconds), m (millis), u (micros) or n (nanos)
+pub fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (digits, unit) = value.split_at(value.len() - 1);
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = digits.parse().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 3600)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+fn min_budget(current: Option<Duration>, other: Duration) -> Duration {
+    current.map_or(other, |current| current.min(other))
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
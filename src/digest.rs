@@ -0,0 +1,445 @@
+// Checking a request body against the checksum its client sent.
+//
+// `Content-MD5` (RFC 1864) carries the base64 MD5 of the body; `Digest`
+// (RFC 3230) carries one or more `algorithm=base64` pairs, of which MD5 and
+// SHA-256 are understood and the rest ignored. Both cover the body as sent,
+// so a gzipped upload is checked before it is inflated. The hashes are fed
+// each frame as it arrives rather than the collected body afterwards.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use hyper::HeaderMap;
+
+pub const CONTENT_MD5_HEADER: &str = "content-md5";
+pub const DIGEST_HEADER: &str = "digest";
+
+// Buffers input into the 64-byte blocks both hashes work on
+struct Blocks {
+    buf: [u8; 64],
+    filled: usize,
+    len: u64,
+}
+
+impl Blocks {
+    fn new() -> Self {
+        Self {
+            buf: [0; 64],
+            filled: 0,
+            len: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8], mut compress: impl FnMut(&[u8; 64])) {
+        self.len = self.len.wrapping_add(data.len() as u64);
+        while !data.is_empty() {
+            let take = (64 - self.filled).min(data.len());
+            self.buf[self.filled..self.filled + take].copy_from_slice(&data[..take]);
+            self.filled += take;
+            data = &data[take..];
+            if self.filled == 64 {
+                compress(&self.buf);
+                self.filled = 0;
+            }
+        }
+    }
+
+    // Pads with `0x80`, zeros and the bit length, little-endian for MD5 and
+    // big-endian for SHA-256
+    fn finish(mut self, big_endian: bool, mut compress: impl FnMut(&[u8; 64])) {
+        let bits = self.len.wrapping_mul(8);
+        let length = if big_endian {
+            bits.to_be_bytes()
+        } else {
+            bits.to_le_bytes()
+        };
+        let mut padding = vec![0x80];
+        padding.resize(1 + (119 - self.filled) % 64, 0);
+        padding.extend_from_slice(&length);
+        self.update(&padding, &mut compress);
+        debug_assert_eq!(self.filled, 0);
+    }
+}
+
+const MD5_SHIFTS: [u32; 64] = [
+    7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9,
+    14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15,
+    21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+const MD5_K: [u32; 64] = [
+    0xd76a_a478,
+    0xe8c7_b756,
+    0x2420_70db,
+    0xc1bd_ceee,
+    0xf57c_0faf,
+    0x4787_c62a,
+    0xa830_4613,
+    0xfd46_9501,
+    0x6980_98d8,
+    0x8b44_f7af,
+    0xffff_5bb1,
+    0x895c_d7be,
+    0x6b90_1122,
+    0xfd98_7193,
+    0xa679_438e,
+    0x49b4_0821,
+    0xf61e_2562,
+    0xc040_b340,
+    0x265e_5a51,
+    0xe9b6_c7aa,
+    0xd62f_105d,
+    0x0244_1453,
+    0xd8a1_e681,
+    0xe7d3_fbc8,
+    0x21e1_cde6,
+    0xc337_07d6,
+    0xf4d5_0d87,
+    0x455a_14ed,
+    0xa9e3_e905,
+    0xfcef_a3f8,
+    0x676f_02d9,
+    0x8d2a_4c8a,
+    0xfffa_3942,
+    0x8771_f681,
+    0x6d9d_6122,
+    0xfde5_380c,
+    0xa4be_ea44,
+    0x4bde_cfa9,
+    0xf6bb_4b60,
+    0xbebf_bc70,
+    0x289b_7ec6,
+    0xeaa1_27fa,
+    0xd4ef_3085,
+    0x0488_1d05,
+    0xd9d4_d039,
+    0xe6db_99e5,
+    0x1fa2_7cf8,
+    0xc4ac_5665,
+    0xf429_2244,
+    0x432a_ff97,
+    0xab94_23a7,
+    0xfc93_a039,
+    0x655b_59c3,
+    0x8f0c_cc92,
+    0xffef_f47d,
+    0x8584_5dd1,
+    0x6fa8_7e4f,
+    0xfe2c_e6e0,
+    0xa301_4314,
+    0x4e08_11a1,
+    0xf753_7e82,
+    0xbd3a_f235,
+    0x2ad7_d2bb,
+    0xeb86_d391,
+];
+
+// Incremental MD5 (RFC 1321). Long broken for signatures, but it is what
+// `Content-MD5` specifies, and it still catches a corrupted upload.
+pub struct Md5 {
+    state: [u32; 4],
+    blocks: Blocks,
+}
+
+impl Md5 {
+    pub fn new() -> Self {
+        Self {
+            state: [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476],
+            blocks: Blocks::new(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.blocks.update(data, |block| md5_compress(state, block));
+    }
+
+    pub fn finish(mut self) -> [u8; 16] {
+        let state = &mut self.state;
+        self.blocks
+            .finish(false, |block| md5_compress(state, block));
+        let mut digest = [0u8; 16];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+}
+
+impl Default for Md5 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn md5_compress(state: &mut [u32; 4], block: &[u8; 64]) {
+    let mut m = [0u32; 16];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        m[i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    let [mut a, mut b, mut c, mut d] = *state;
+    for i in 0..64 {
+        let (f, g) = match i {
+            0..=15 => ((b & c) | (!b & d), i),
+            16..=31 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            32..=47 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let f = f.wrapping_add(a).wrapping_add(MD5_K[i]).wrapping_add(m[g]);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(f.rotate_left(MD5_SHIFTS[i]));
+    }
+    for (word, value) in state.iter_mut().zip([a, b, c, d]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a_2f98,
+    0x7137_4491,
+    0xb5c0_fbcf,
+    0xe9b5_dba5,
+    0x3956_c25b,
+    0x59f1_11f1,
+    0x923f_82a4,
+    0xab1c_5ed5,
+    0xd807_aa98,
+    0x1283_5b01,
+    0x2431_85be,
+    0x550c_7dc3,
+    0x72be_5d74,
+    0x80de_b1fe,
+    0x9bdc_06a7,
+    0xc19b_f174,
+    0xe49b_69c1,
+    0xefbe_4786,
+    0x0fc1_9dc6,
+    0x240c_a1cc,
+    0x2de9_2c6f,
+    0x4a74_84aa,
+    0x5cb0_a9dc,
+    0x76f9_88da,
+    0x983e_5152,
+    0xa831_c66d,
+    0xb003_27c8,
+    0xbf59_7fc7,
+    0xc6e0_0bf3,
+    0xd5a7_9147,
+    0x06ca_6351,
+    0x1429_2967,
+    0x27b7_0a85,
+    0x2e1b_2138,
+    0x4d2c_6dfc,
+    0x5338_0d13,
+    0x650a_7354,
+    0x766a_0abb,
+    0x81c2_c92e,
+    0x9272_2c85,
+    0xa2bf_e8a1,
+    0xa81a_664b,
+    0xc24b_8b70,
+    0xc76c_51a3,
+    0xd192_e819,
+    0xd699_0624,
+    0xf40e_3585,
+    0x106a_a070,
+    0x19a4_c116,
+    0x1e37_6c08,
+    0x2748_774c,
+    0x34b0_bcb5,
+    0x391c_0cb3,
+    0x4ed8_aa4a,
+    0x5b9c_ca4f,
+    0x682e_6ff3,
+    0x748f_82ee,
+    0x78a5_636f,
+    0x84c8_7814,
+    0x8cc7_0208,
+    0x90be_fffa,
+    0xa450_6ceb,
+    0xbef9_a3f7,
+    0xc671_78f2,
+];
+
+// Incremental SHA-256 (FIPS 180-4)
+pub struct Sha256 {
+    state: [u32; 8],
+    blocks: Blocks,
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Self {
+            state: [
+                0x6a09_e667,
+                0xbb67_ae85,
+                0x3c6e_f372,
+                0xa54f_f53a,
+                0x510e_527f,
+                0x9b05_688c,
+                0x1f83_d9ab,
+                0x5be0_cd19,
+            ],
+            blocks: Blocks::new(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        let state = &mut self.state;
+        self.blocks
+            .update(data, |block| sha256_compress(state, block));
+    }
+
+    pub fn finish(mut self) -> [u8; 32] {
+        let state = &mut self.state;
+        self.blocks
+            .finish(true, |block| sha256_compress(state, block));
+        let mut digest = [0u8; 32];
+        for (chunk, word) in digest.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn sha256_compress(state: &mut [u32; 8], block: &[u8; 64]) {
+    let mut w = [0u32; 64];
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+    for (&k, &word) in SHA256_K.iter().zip(&w) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let temp1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(k)
+            .wrapping_add(word);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+    }
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+// The checksums a request's headers promise for its body. Several may be
+// given; the body must match every one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExpectedDigests {
+    pub md5: Option<[u8; 16]>,
+    pub sha256: Option<[u8; 32]>,
+}
+
+impl ExpectedDigests {
+    // The checksums in `Content-MD5` and `Digest`, `None` if neither names
+    // one this server can check, or a message for a 400 if one is malformed
+    // or two disagree
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, String> {
+        let mut expected = ExpectedDigests::default();
+        if let Some(value) = headers.get(CONTENT_MD5_HEADER) {
+            let value = value.to_str().unwrap_or_default().trim();
+            expected.md5 = Some(decode(value).ok_or("Content-MD5 is not a base64 MD5 digest")?);
+        }
+        for value in headers.get_all(DIGEST_HEADER) {
+            let value = value.to_str().map_err(|_| "Digest is not valid")?;
+            for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+                let (algorithm, digest) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("Digest entry {:?} is not algorithm=value", entry))?;
+                let algorithm = algorithm.trim();
+                let invalid = || {
+                    format!(
+                        "Digest {} value is not valid base64 of its length",
+                        algorithm
+                    )
+                };
+                if algorithm.eq_ignore_ascii_case("md5") {
+                    let digest = decode(digest.trim()).ok_or_else(invalid)?;
+                    if expected.md5.is_some_and(|md5| md5 != digest) {
+                        return Err("Content-MD5 and Digest disagree".to_string());
+                    }
+                    expected.md5 = Some(digest);
+                } else if algorithm.eq_ignore_ascii_case("sha-256") {
+                    let digest = decode(digest.trim()).ok_or_else(invalid)?;
+                    if expected.sha256.is_some_and(|sha256| sha256 != digest) {
+                        return Err("Digest lists two different SHA-256 values".to_string());
+                    }
+                    expected.sha256 = Some(digest);
+                }
+            }
+        }
+        Ok((expected != ExpectedDigests::default()).then_some(expected))
+    }
+}
+
+fn decode<const N: usize>(value: &str) -> Option<[u8; N]> {
+    STANDARD.decode(value).ok()?.try_into().ok()
+}
+
+// Hashes a body as it arrives with whichever algorithms `expected` uses
+pub struct BodyVerifier {
+    expected: ExpectedDigests,
+    md5: Option<Md5>,
+    sha256: Option<Sha256>,
+}
+
+impl BodyVerifier {
+    pub fn new(expected: ExpectedDigests) -> Self {
+        Self {
+            md5: expected.md5.map(|_| Md5::new()),
+            sha256: expected.sha256.map(|_| Sha256::new()),
+            expected,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        if let Some(md5) = &mut self.md5 {
+            md5.update(data);
+        }
+        if let Some(sha256) = &mut self.sha256 {
+            sha256.update(data);
+        }
+    }
+
+    // The first algorithm whose digest doesn't match the body, if any
+    pub fn mismatch(self) -> Option<&'static str> {
+        if let (Some(md5), Some(expected)) = (self.md5, self.expected.md5) {
+            if md5.finish() != expected {
+                return Some("MD5");
+            }
+        }
+        if let (Some(sha256), Some(expected)) = (self.sha256, self.expected.sha256) {
+            if sha256.finish() != expected {
+                return Some("SHA-256");
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,45 @@
+// An optional standard envelope around the server's JSON responses.
+//
+// With `JSON_ENVELOPE` on, a body `{...}` is sent as
+// `{"data": {...}, "error": null, "meta": {"status": 200}}`, or with the
+// body under `error` and `data` null for 4xx and 5xx. Like field casing,
+// it is a pass over responses `json_response` built, so handlers never see
+// it and bodies that aren't the server's own are left alone.
+
+use http_body_util::BodyExt;
+use hyper::header::CONTENT_LENGTH;
+use hyper::{Response, StatusCode};
+use serde_json::json;
+
+use crate::{full, Body};
+
+// Puts `body` under `data` or `error`, depending on `status`
+pub fn wrap(body: serde_json::Value, status: StatusCode) -> serde_json::Value {
+    let (data, error) = if status.is_client_error() || status.is_server_error() {
+        (serde_json::Value::Null, body)
+    } else {
+        (body, serde_json::Value::Null)
+    };
+    json!({
+        "data": data,
+        "error": error,
+        "meta": {"status": status.as_u16()},
+    })
+}
+
+// Re-serializes a buffered JSON response inside the envelope. Bodies that
+// don't parse are passed through untouched.
+pub async fn apply(response: Response<Body>) -> Response<Body> {
+    let (mut parts, body) = response.into_parts();
+    let body = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(never) => match never {},
+    };
+    let Ok(value) = serde_json::from_slice::<serde_json::Value>(&body) else {
+        return Response::from_parts(parts, full(body));
+    };
+
+    parts.headers.remove(CONTENT_LENGTH);
+    let wrapped = wrap(value, parts.status).to_string();
+    Response::from_parts(parts, full(wrapped))
+}
@@ -0,0 +1,92 @@
+// HTTP-dates (RFC 9110 section 5.6.7), as used by `Last-Modified` and
+// `If-Modified-Since`.
+//
+// Dates are always sent in the preferred IMF-fixdate form, but recipients
+// must also accept the obsolete RFC 850 and asctime forms old clients may
+// still send.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use hyper::header::{HeaderMap, IF_MODIFIED_SINCE, IF_NONE_MATCH};
+
+// `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn format_http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+// Any of the three forms; `None` for anything else, including dates before
+// 1970 and zones other than GMT.
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    let parsed = if let Some(rest) = value.strip_suffix(" GMT") {
+        // IMF-fixdate, then RFC 850 with its two-digit year
+        NaiveDateTime::parse_from_str(rest, "%a, %d %b %Y %H:%M:%S")
+            .ok()
+            .filter(|_| rest.len() == 25)
+            .or_else(|| parse_rfc850(rest))?
+    } else {
+        // asctime pads single-digit days with a space: `Sun Nov  6 ...`
+        NaiveDateTime::parse_from_str(value, "%a %b %e %H:%M:%S %Y").ok()?
+    };
+    let secs = u64::try_from(parsed.and_utc().timestamp()).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+// `Sunday, 06-Nov-94 08:49:37`. A two-digit year is taken as the most
+// recent past year ending in those digits, as RFC 9110 asks: one that
+// would fall more than 50 years in the future belongs to the last century.
+fn parse_rfc850(value: &str) -> Option<NaiveDateTime> {
+    let (weekday, rest) = value.split_once(", ")?;
+    let (date, time) = rest.split_once(' ')?;
+    let mut parts = date.split('-');
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let year = parts.next()?;
+    if parts.next().is_some() || year.len() != 2 {
+        return None;
+    }
+    let year: i32 = year.parse().ok()?;
+
+    let this_year = Utc::now().year();
+    let mut year = this_year - this_year % 100 + year;
+    if year > this_year + 50 {
+        year -= 100;
+    }
+    let month = NaiveDate::parse_from_str(&format!("1 {} 2000", month), "%d %b %Y")
+        .ok()?
+        .month();
+    let parsed = NaiveDate::from_ymd_opt(year, month, day)?
+        .and_time(chrono::NaiveTime::parse_from_str(time, "%H:%M:%S").ok()?);
+    // The weekday is redundant, but must agree with the date
+    let expected = parsed.format("%A").to_string();
+    expected.eq_ignore_ascii_case(weekday).then_some(parsed)
+}
+
+// Whether a request's `If-Modified-Since` shows it already has the version
+// last modified at `modified`. The header is ignored when unparseable,
+// when it lies in the future, and alongside `If-None-Match`, which takes
+// precedence.
+pub fn not_modified_since(headers: &HeaderMap, modified: SystemTime) -> bool {
+    if headers.contains_key(IF_NONE_MATCH) {
+        return false;
+    }
+    let Some(since) = headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_http_date)
+    else {
+        return false;
+    };
+    // HTTP-dates have whole seconds, so compare at that precision
+    since <= SystemTime::now() && truncate_to_secs(modified) <= since
+}
+
+fn truncate_to_secs(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
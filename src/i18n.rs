@@ -0,0 +1,83 @@
+// Localized human-readable messages, negotiated from Accept-Language.
+//
+// The table is deliberately tiny: a handful of messages in a few languages,
+// matched on the primary subtag only (`fr-CH` is served French).
+
+use hyper::header::ACCEPT_LANGUAGE;
+use hyper::HeaderMap;
+
+// Language served when nothing better matches
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+// Languages with a full set of translations
+pub const SUPPORTED_LANGUAGES: &[&str] = &["en", "es", "fr", "de"];
+
+// Messages that have translations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    Welcome,
+    Healthy,
+    NotFound,
+}
+
+// The text of `key` in `lang`, falling back to English for unknown languages
+pub fn localize(key: Message, lang: &str) -> &'static str {
+    match (key, lang) {
+        (Message::Welcome, "es") => "¡Bienvenido al servidor HTTP de Rust!",
+        (Message::Welcome, "fr") => "Bienvenue sur le serveur HTTP Rust !",
+        (Message::Welcome, "de") => "Willkommen beim Rust-HTTP-Server!",
+        (Message::Welcome, _) => "Welcome to Rust HTTP Server!",
+
+        (Message::Healthy, "es") => "El servidor está en buen estado",
+        (Message::Healthy, "fr") => "Le serveur est en bonne santé",
+        (Message::Healthy, "de") => "Der Server ist funktionsfähig",
+        (Message::Healthy, _) => "Server is healthy",
+
+        (Message::NotFound, "es") => "No encontrado",
+        (Message::NotFound, "fr") => "Introuvable",
+        (Message::NotFound, "de") => "Nicht gefunden",
+        (Message::NotFound, _) => "Not Found",
+    }
+}
+
+// Picks the supported language the client prefers most. Ties go to the one
+// listed first; `*` and a missing or unmatched header mean English.
+pub fn negotiate(headers: &HeaderMap) -> &'static str {
+    let mut best: Option<(&'static str, f32)> = None;
+
+    let ranges = headers
+        .get_all(ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+
+    for range in ranges {
+        let mut params = range.split(';');
+        let tag = params.next().unwrap_or("").trim();
+        let q = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if q <= 0.0 {
+            continue;
+        }
+
+        let primary = tag.split('-').next().unwrap_or("");
+        let language = if primary == "*" {
+            Some(DEFAULT_LANGUAGE)
+        } else {
+            SUPPORTED_LANGUAGES
+                .iter()
+                .copied()
+                .find(|supported| supported.eq_ignore_ascii_case(primary))
+        };
+
+        if let Some(language) = language {
+            if best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((language, q));
+            }
+        }
+    }
+
+    best.map_or(DEFAULT_LANGUAGE, |(language, _)| language)
+}
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use http_body_util::{BodyExt, Limited};
+use hyper::body::{Bytes, Frame, SizeHint};
+use hyper::header::{HeaderMap, HeaderValue};
+use hyper::{Method, Response, StatusCode};
+
+use crate::digest::Sha256;
+use crate::{full, Body, RequestBody};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+// A response captured so it can be replayed byte-for-byte
+#[derive(Clone)]
+pub struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl CachedResponse {
+    fn into_response(self) -> Response<Body> {
+        let mut response = Response::new(full(self.body));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        response
+            .headers_mut()
+            .insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+        response
+    }
+}
+
+// What a stored response is filed under: the client's Idempotency-Key on
+// the route it was sent to, so the same key sent elsewhere is a new request
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct IdempotencyKey {
+    method: Method,
+    path: String,
+    key: String,
+}
+
+impl IdempotencyKey {
+    pub fn new(method: Method, path: &str, key: &str) -> Self {
+        Self {
+            method,
+            path: path.to_string(),
+            key: key.to_string(),
+        }
+    }
+}
+
+struct Entry {
+    stored_at: Instant,
+    // SHA-256 of the request body the response was for
+    fingerprint: [u8; 32],
+    response: CachedResponse,
+}
+
+enum Slot {
+    // The first request with the key is still being answered
+    Pending,
+    Stored(Entry),
+}
+
+// What `IdempotencyCache::claim` found for a key
+pub enum Lookup {
+    // The fingerprint of the body the stored response was for, and the
+    // response
+    Stored([u8; 32], Response<Body>),
+    // Another request with the key hasn't been answered yet
+    InProgress,
+    // No request has the key, and now this one does
+    Claimed(Claim),
+}
+
+// Responses keyed by the client's Idempotency-Key, kept for `ttl`
+#[derive(Clone)]
+pub struct IdempotencyCache {
+    entries: Arc<Mutex<HashMap<IdempotencyKey, Slot>>>,
+    ttl: Duration,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            ttl,
+        }
+    }
+
+    // Looks `key` up, marking it in progress when nothing unexpired is
+    // stored under it, so a retry sent while the first request is still
+    // running isn't processed a second time
+    pub fn claim(&self, key: IdempotencyKey) -> Lookup {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(&key) {
+            Some(Slot::Stored(entry)) if entry.stored_at.elapsed() < self.ttl => {
+                return Lookup::Stored(entry.fingerprint, entry.response.clone().into_response());
+            }
+            Some(Slot::Pending) => return Lookup::InProgress,
+            Some(Slot::Stored(_)) | None => {}
+        }
+        entries.insert(key.clone(), Slot::Pending);
+        Lookup::Claimed(Claim {
+            cache: self.clone(),
+            key: Some(key),
+        })
+    }
+}
+
+// The in-progress mark on a key. Dropping it without `finish`, as when the
+// client disconnects, frees the key for a retry.
+pub struct Claim {
+    cache: IdempotencyCache,
+    key: Option<IdempotencyKey>,
+}
+
+impl Claim {
+    // Records `response` under the key along with the `fingerprint` of the
+    // request body, and hands back an equivalent response. Server errors
+    // aren't stored so the client's retry is processed again, and neither is
+    // a response to a body that wasn't read to the end (no fingerprint), as
+    // there'd be nothing to check a retry's body against.
+    pub async fn finish(
+        mut self,
+        fingerprint: Option<[u8; 32]>,
+        response: Response<Body>,
+    ) -> Response<Body> {
+        let Some(fingerprint) = fingerprint else {
+            return response;
+        };
+        if response.status().is_server_error() {
+            return response;
+        }
+
+        let (parts, body) = response.into_parts();
+        let body = match body.collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(never) => match never {},
+        };
+        let cached = CachedResponse {
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: body.clone(),
+        };
+
+        let key = self.key.take().expect("claim finished twice");
+        let mut entries = self.cache.entries.lock().unwrap();
+        let ttl = self.cache.ttl;
+        entries.retain(|_, slot| match slot {
+            Slot::Stored(entry) => entry.stored_at.elapsed() < ttl,
+            Slot::Pending => true,
+        });
+        entries.insert(
+            key,
+            Slot::Stored(Entry {
+                stored_at: Instant::now(),
+                fingerprint,
+                response: cached,
+            }),
+        );
+        drop(entries);
+
+        Response::from_parts(parts, full(body))
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            let mut entries = self.cache.entries.lock().unwrap();
+            if matches!(entries.get(&key), Some(Slot::Pending)) {
+                entries.remove(&key);
+            }
+        }
+    }
+}
+
+// SHA-256 of a retried request's body, read up to `limit` bytes; `None` if
+// it is longer or fails to arrive
+pub async fn fingerprint<B: RequestBody>(body: B, limit: usize) -> Option<[u8; 32]> {
+    let mut body = std::pin::pin!(Limited::new(body, limit));
+    let mut hasher = Sha256::new();
+    while let Some(frame) = body.frame().await {
+        if let Some(data) = frame.ok()?.data_ref() {
+            hasher.update(data);
+        }
+    }
+    Some(hasher.finish())
+}
+
+// A request body that takes its SHA-256 as the handler reads it, filling in
+// `fingerprint` once the end is reached. Without a slot it only passes the
+// body through.
+pub struct Fingerprinted<B> {
+    inner: Pin<Box<B>>,
+    hasher: Option<Sha256>,
+    fingerprint: Option<Arc<OnceLock<[u8; 32]>>>,
+}
+
+impl<B: RequestBody> Fingerprinted<B> {
+    pub fn new(inner: B, fingerprint: Option<Arc<OnceLock<[u8; 32]>>>) -> Self {
+        let mut body = Self {
+            inner: Box::pin(inner),
+            hasher: fingerprint.is_some().then(Sha256::new),
+            fingerprint,
+        };
+        // A handler needn't poll an empty body at all
+        if body.inner.is_end_stream() {
+            body.finish();
+        }
+        body
+    }
+
+    fn finish(&mut self) {
+        if let (Some(hasher), Some(slot)) = (self.hasher.take(), &self.fingerprint) {
+            let _ = slot.set(hasher.finish());
+        }
+    }
+}
+
+impl<B: RequestBody> hyper::body::Body for Fingerprinted<B> {
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, B::Error>>> {
+        let polled = self.inner.as_mut().poll_frame(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let (Some(hasher), Some(data)) = (self.hasher.as_mut(), frame.data_ref()) {
+                    hasher.update(data);
+                }
+                if self.inner.is_end_stream() {
+                    self.finish();
+                }
+            }
+            Poll::Ready(None) => self.finish(),
+            _ => {}
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,326 @@
+// gzip request body decompression (RFC 1952 around RFC 1951 DEFLATE).
+//
+// The counterpart to `compression`'s encoder, but it has to accept whatever
+// clients send: stored, fixed- and dynamic-Huffman blocks, and several
+// gzip members back to back. Output is capped while decoding, so a small
+// zip bomb is refused once it reaches the limit instead of being inflated
+// in full first.
+
+use crate::compression::{Crc32, DIST_BASE, DIST_EXTRA, LENGTH_BASE, LENGTH_EXTRA};
+
+const MAX_BITS: usize = 15;
+
+// Order in which a dynamic block lists its code length code lengths
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+// Why a gzip body couldn't be decompressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InflateError {
+    // The decompressed body would exceed the limit
+    TooLarge,
+    // Not valid gzip: a bad header, corrupt data or a failed checksum
+    Invalid,
+}
+
+// Decompresses every gzip member in `data`, failing with `TooLarge` as soon
+// as the output would pass `limit` bytes
+pub fn gunzip(data: &[u8], limit: usize) -> Result<Vec<u8>, InflateError> {
+    let mut out = Vec::new();
+    let mut rest = data;
+    loop {
+        let start = out.len();
+        let body = skip_header(rest)?;
+        let mut reader = BitReader::new(body);
+        inflate(&mut reader, &mut out, limit)?;
+        let trailer = reader.remaining();
+        if trailer.len() < 8 {
+            return Err(InflateError::Invalid);
+        }
+        let crc = u32::from_le_bytes(trailer[0..4].try_into().unwrap());
+        let size = u32::from_le_bytes(trailer[4..8].try_into().unwrap());
+        let mut actual = Crc32::new();
+        actual.update(&out[start..]);
+        if actual.finish() != crc || (out.len() - start) as u32 != size {
+            return Err(InflateError::Invalid);
+        }
+        rest = &trailer[8..];
+        if rest.is_empty() {
+            return Ok(out);
+        }
+    }
+}
+
+// Returns what follows the member header
+fn skip_header(data: &[u8]) -> Result<&[u8], InflateError> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if data.len() < 10 || data[0..3] != [0x1f, 0x8b, 8] || data[3] & 0xe0 != 0 {
+        return Err(InflateError::Invalid);
+    }
+    let flags = data[3];
+    let mut rest = &data[10..];
+    if flags & FEXTRA != 0 {
+        let length = match rest {
+            [low, high, ..] => u16::from_le_bytes([*low, *high]) as usize,
+            _ => return Err(InflateError::Invalid),
+        };
+        rest = rest.get(2 + length..).ok_or(InflateError::Invalid)?;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            let end = rest
+                .iter()
+                .position(|&byte| byte == 0)
+                .ok_or(InflateError::Invalid)?;
+            rest = &rest[end + 1..];
+        }
+    }
+    if flags & FHCRC != 0 {
+        rest = rest.get(2..).ok_or(InflateError::Invalid)?;
+    }
+    Ok(rest)
+}
+
+fn inflate(reader: &mut BitReader, out: &mut Vec<u8>, limit: usize) -> Result<(), InflateError> {
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored_block(reader, out, limit)?,
+            1 => {
+                let (literals, distances) = fixed_codes();
+                huffman_block(reader, out, limit, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(reader)?;
+                huffman_block(reader, out, limit, &literals, &distances)?;
+            }
+            _ => return Err(InflateError::Invalid),
+        }
+        if last {
+            return Ok(());
+        }
+    }
+}
+
+fn stored_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    limit: usize,
+) -> Result<(), InflateError> {
+    reader.align();
+    let length = reader.bits(16)? as u16;
+    let complement = reader.bits(16)? as u16;
+    if length != !complement {
+        return Err(InflateError::Invalid);
+    }
+    let bytes = reader.bytes(length as usize)?;
+    if out.len() + bytes.len() > limit {
+        return Err(InflateError::TooLarge);
+    }
+    out.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn huffman_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    limit: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), InflateError> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => {
+                if out.len() >= limit {
+                    return Err(InflateError::TooLarge);
+                }
+                out.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let length =
+                    LENGTH_BASE[index] as usize + reader.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = distances.decode(reader)? as usize;
+                if index >= DIST_BASE.len() {
+                    return Err(InflateError::Invalid);
+                }
+                let distance =
+                    DIST_BASE[index] as usize + reader.bits(DIST_EXTRA[index] as u32)? as usize;
+                if distance > out.len() {
+                    return Err(InflateError::Invalid);
+                }
+                if out.len() + length > limit {
+                    return Err(InflateError::TooLarge);
+                }
+                // Byte by byte: the copy may overlap what it is producing
+                let from = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[from + i]);
+                }
+            }
+            _ => return Err(InflateError::Invalid),
+        }
+    }
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[0..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..288].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_codes(reader: &mut BitReader) -> Result<(Huffman, Huffman), InflateError> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_length_count = reader.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(InflateError::Invalid);
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+        code_lengths[index] = reader.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths);
+
+    // Literal/length and distance lengths form one sequence, and a repeat
+    // may run from one into the other
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let (value, repeat) = match code_length_code.decode(reader)? {
+            length @ 0..=15 => (length as u8, 1),
+            16 => {
+                let previous = *lengths[..i].last().ok_or(InflateError::Invalid)?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            18 => (0, 11 + reader.bits(7)? as usize),
+            _ => return Err(InflateError::Invalid),
+        };
+        let run = lengths
+            .get_mut(i..i + repeat)
+            .ok_or(InflateError::Invalid)?;
+        run.fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        // No end-of-block code, so the block could never end
+        return Err(InflateError::Invalid);
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+// A canonical Huffman code, decoded a bit at a time
+struct Huffman {
+    // Codes of each bit length
+    counts: [u16; MAX_BITS + 1],
+    // Symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for bits in 1..=MAX_BITS {
+            offsets[bits + 1] = offsets[bits] + counts[bits];
+        }
+        let mut symbols = vec![0u16; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, InflateError> {
+        // `code` is the bits read so far; `first` the first code of this
+        // length; `index` where this length's symbols start
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for bits in 1..=MAX_BITS {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[bits] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(InflateError::Invalid)
+    }
+}
+
+// Reads DEFLATE's least-significant-bit-first bit stream
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u32) -> Result<u32, InflateError> {
+        while self.count < n {
+            let byte = *self.data.get(self.position).ok_or(InflateError::Invalid)?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    // Drops the rest of the current byte, as stored blocks require
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], InflateError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + n)
+            .ok_or(InflateError::Invalid)?;
+        self.position += n;
+        Ok(bytes)
+    }
+
+    // Whatever follows the last whole byte consumed
+    fn remaining(&self) -> &'a [u8] {
+        &self.data[self.position..]
+    }
+}
@@ -0,0 +1,91 @@
+// Runtime field naming for the server's own JSON responses.
+//
+// serde's `rename_all` is fixed at compile time, so camelCase is applied as
+// a post-serialization pass over responses built by `json_response`. Only
+// object keys change; values (including user data echoed back) don't.
+
+use std::str::FromStr;
+
+use http_body_util::BodyExt;
+use hyper::header::CONTENT_LENGTH;
+use hyper::Response;
+
+use crate::{full, Body};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldCase {
+    Snake,
+    Camel,
+}
+
+impl FromStr for FieldCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "snake" | "snake_case" => Ok(FieldCase::Snake),
+            "camel" | "camelcase" => Ok(FieldCase::Camel),
+            other => Err(format!(
+                "unknown field case {:?} (expected snake or camel)",
+                other
+            )),
+        }
+    }
+}
+
+// `requests_per_second` -> `requestsPerSecond`
+pub fn to_camel_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper_next = false;
+    for c in name.chars() {
+        if c == '_' && !out.is_empty() {
+            upper_next = true;
+        } else if upper_next {
+            out.extend(c.to_uppercase());
+            upper_next = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+// Renames every object key in `value`, recursively
+pub fn rename_keys(value: serde_json::Value, case: FieldCase) -> serde_json::Value {
+    match (value, case) {
+        (value, FieldCase::Snake) => value,
+        (serde_json::Value::Object(map), FieldCase::Camel) => serde_json::Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (to_camel_case(&key), rename_keys(value, case)))
+                .collect(),
+        ),
+        (serde_json::Value::Array(items), FieldCase::Camel) => serde_json::Value::Array(
+            items
+                .into_iter()
+                .map(|item| rename_keys(item, case))
+                .collect(),
+        ),
+        (value, _) => value,
+    }
+}
+
+// Re-serializes a buffered JSON response body with `case` keys. Bodies that
+// don't parse are passed through untouched.
+pub async fn apply(response: Response<Body>, case: FieldCase) -> Response<Body> {
+    if case == FieldCase::Snake {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match body.collect().await {
+        Ok(collected) => collected.to_bytes(),
+        Err(never) => match never {},
+    };
+    let Ok(value) = serde_json::from_slice::<serde_json::Value>(&body) else {
+        return Response::from_parts(parts, full(body));
+    };
+
+    parts.headers.remove(CONTENT_LENGTH);
+    let renamed = rename_keys(value, case).to_string();
+    Response::from_parts(parts, full(renamed))
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+// In-memory key-value store behind `/kv/:key`
+#[derive(Clone, Default)]
+pub struct KvStore {
+    entries: Arc<Mutex<HashMap<String, String>>>,
+}
+
+// Why a PATCH couldn't be applied
+#[derive(Debug, PartialEq, Eq)]
+pub enum PatchError {
+    NotFound,
+    // The stored value isn't JSON, so there is nothing to merge into
+    NotJson,
+    InvalidPatch,
+}
+
+impl KvStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    // Stores `value`; returns whether the key is new
+    pub fn put(&self, key: &str, value: String) -> bool {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), value)
+            .is_none()
+    }
+
+    // Returns whether the key existed
+    pub fn delete(&self, key: &str) -> bool {
+        self.entries.lock().unwrap().remove(key).is_some()
+    }
+
+    // Applies `patch` to the stored JSON value as an RFC 7386 merge patch
+    // and returns the updated value
+    pub fn patch(&self, key: &str, patch: &str) -> Result<String, PatchError> {
+        let patch: serde_json::Value =
+            serde_json::from_str(patch).map_err(|_| PatchError::InvalidPatch)?;
+
+        let mut entries = self.entries.lock().unwrap();
+        let value = entries.get_mut(key).ok_or(PatchError::NotFound)?;
+        let mut target: serde_json::Value =
+            serde_json::from_str(value).map_err(|_| PatchError::NotJson)?;
+
+        merge_patch(&mut target, patch);
+        *value = target.to_string();
+        Ok(value.clone())
+    }
+}
+
+// RFC 7386: objects merge member by member, `null` removes a member and
+// anything else replaces the target outright
+pub fn merge_patch(target: &mut serde_json::Value, patch: serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    let Some(target) = target.as_object_mut() else {
+        return;
+    };
+
+    for (name, value) in patch {
+        if value.is_null() {
+            target.shift_remove(&name);
+        } else {
+            merge_patch(target.entry(name).or_insert(serde_json::Value::Null), value);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// Request latency histogram behind `/metrics`.
+//
+// Bucket counts and the sum are plain atomics, so recording a request never
+// waits on another. With tracing on, each bucket also keeps its latest traced
+// request as an OpenMetrics exemplar; that slot is taken with `try_lock`, and
+// a request that finds it busy just doesn't leave one.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+// Upper bounds of the buckets in seconds, each counting requests that took
+// at most that long; `+Inf` follows implicitly
+pub const BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// A traced request that landed in a bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exemplar {
+    pub trace_id: [u8; 16],
+    pub seconds: f64,
+    pub at: SystemTime,
+}
+
+// One bucket as exported: cumulative, like Prometheus expects
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bucket {
+    // `f64::INFINITY` for the last
+    pub le: f64,
+    pub count: u64,
+    pub exemplar: Option<Exemplar>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    pub buckets: Vec<Bucket>,
+    pub sum_seconds: f64,
+    pub count: u64,
+}
+
+#[derive(Clone)]
+pub struct LatencyHistogram {
+    // Per bucket, not cumulative; the last is `+Inf`
+    counts: Arc<[AtomicU64; BUCKETS.len() + 1]>,
+    exemplars: Arc<[Mutex<Option<Exemplar>>; BUCKETS.len() + 1]>,
+    sum_nanos: Arc<AtomicU64>,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            counts: Arc::new(std::array::from_fn(|_| AtomicU64::new(0))),
+            exemplars: Arc::new(std::array::from_fn(|_| Mutex::new(None))),
+            sum_nanos: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Counts a request that took `elapsed`, keeping `trace_id` as its
+    // bucket's exemplar when the request was traced
+    pub fn record(&self, elapsed: Duration, trace_id: Option<[u8; 16]>) {
+        let seconds = elapsed.as_secs_f64();
+        let index = BUCKETS
+            .iter()
+            .position(|&le| seconds <= le)
+            .unwrap_or(BUCKETS.len());
+        self.counts[index].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos.fetch_add(
+            elapsed.as_nanos().min(u64::MAX as u128) as u64,
+            Ordering::Relaxed,
+        );
+
+        if let Some(trace_id) = trace_id {
+            if let Ok(mut exemplar) = self.exemplars[index].try_lock() {
+                *exemplar = Some(Exemplar {
+                    trace_id,
+                    seconds,
+                    at: SystemTime::now(),
+                });
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = BUCKETS
+            .iter()
+            .copied()
+            .chain([f64::INFINITY])
+            .enumerate()
+            .map(|(index, le)| {
+                cumulative += self.counts[index].load(Ordering::Relaxed);
+                Bucket {
+                    le,
+                    count: cumulative,
+                    exemplar: *self.exemplars[index].lock().unwrap(),
+                }
+            })
+            .collect();
+        HistogramSnapshot {
+            buckets,
+            sum_seconds: self.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9,
+            // The `+Inf` bucket, so the two always agree
+            count: cumulative,
+        }
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,3485 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode, Uri};
+use hyper_util::rt::TokioIo;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+use hyper::body::Body as _;
+use hyper::body::Bytes;
+use serde::Serialize;
+use base64::Engine as _;
+use tokio::sync::{oneshot, watch, Notify};
+use tracing::Instrument as _;
+
+pub mod accept_rate;
+pub mod access_log;
+pub mod admin;
+pub mod body_budget;
+pub mod cache_policy;
+pub mod cancel;
+pub mod compression;
+pub mod config;
+pub mod conn_limit;
+pub mod cors;
+pub mod deadline;
+pub mod digest;
+pub mod envelope;
+pub mod http_date;
+pub mod i18n;
+pub mod idempotency;
+pub mod inflate;
+pub mod json_case;
+pub mod kv;
+pub mod latency;
+pub mod listener;
+pub mod log_sample;
+pub mod normalize;
+pub mod openapi;
+pub mod otel;
+pub mod prebuilt;
+pub mod range;
+pub mod rate_limit;
+pub mod recent;
+pub mod redact;
+pub mod redirect;
+pub mod response;
+pub mod response_cache;
+pub mod route_headers;
+pub mod route_timeout;
+pub mod route_toggle;
+pub mod self_probe;
+pub mod slow_start;
+pub mod snapshot;
+pub mod stream;
+pub mod suggest;
+pub mod timestamp;
+pub mod vhost;
+pub mod websocket;
+
+use accept_rate::AcceptRate;
+use access_log::{format_clf, AccessLogEntry, LogFormat};
+use body_budget::{BodyBudget, BufferedBody};
+use cancel::CancelGuard;
+use compression::Negotiated;
+pub use config::{Config, ConfigError, EchoFormat, Favicon, LogLevel};
+use conn_limit::{ConnectionLimiter, RejectionLog};
+use deadline::Budget;
+use digest::{BodyVerifier, ExpectedDigests};
+use i18n::Message;
+use idempotency::{
+    Fingerprinted, IdempotencyCache, IdempotencyKey, Lookup, IDEMPOTENCY_KEY_HEADER,
+};
+use inflate::InflateError;
+use kv::{KvStore, PatchError};
+use latency::{HistogramSnapshot, LatencyHistogram};
+#[cfg(unix)]
+pub use listener::UnixSocketListener;
+pub use listener::{bind, bind_all, bind_socket, Listener, TcpListeners, DEFAULT_BACKLOG};
+use otel::{Span, SpanExporter, TraceContext};
+use prebuilt::TimestampedJson;
+use range::ByteRange;
+use rate_limit::RateLimiter;
+use recent::{RecentRequests, RequestSummary};
+use redact::redact_headers;
+use response::{backpressure, service_unavailable};
+use response_cache::ResponseCache;
+use slow_start::SlowStart;
+use snapshot::{HttpVersionCounts, StatsSnapshot};
+use stream::{EchoStream, NdjsonStream, RepeatStream, TextStream};
+use timestamp::timestamp_rfc3339;
+use vhost::Site;
+
+// Response body: buffered or streaming
+pub type Body = BoxBody<Bytes, Infallible>;
+
+// Wraps fully-buffered bytes as a response body
+pub fn full(bytes: impl Into<Bytes>) -> Body {
+    Full::new(bytes.into()).boxed()
+}
+
+// Server statistics
+#[derive(Clone)]
+pub struct ServerStats {
+    total_requests: Arc<AtomicU64>,
+    uncompressed_bytes: Arc<AtomicU64>,
+    compressed_bytes: Arc<AtomicU64>,
+    active_websockets: Arc<AtomicU64>,
+    cancelled_requests: Arc<AtomicU64>,
+    client_disconnects: Arc<AtomicU64>,
+    accepted_connections: Arc<AtomicU64>,
+    body_budget_rejections: Arc<AtomicU64>,
+    // 5xx responses since the last 2xx, for `unhealthy_after_errors`
+    consecutive_errors: Arc<AtomicU64>,
+    // Indexed like `HTTP_VERSIONS`
+    by_http_version: Arc<[AtomicU64; 5]>,
+    // Indexed like `ROUTES`, so there is one counter per route pattern
+    // however many distinct paths clients send
+    by_route: Arc<[AtomicU64]>,
+    // Time to the response head, for `/metrics`
+    latency: LatencyHistogram,
+    accept_rate: AcceptRate,
+    start_time: Instant,
+    // Counters carried over from a previous process; rates and the
+    // `max_requests` cap only count what this one did
+    restored: StatsSnapshot,
+}
+
+impl ServerStats {
+    pub fn new() -> Self {
+        Self::from_snapshot(StatsSnapshot::default())
+    }
+
+    // Counters continue from `snapshot`
+    pub fn from_snapshot(snapshot: StatsSnapshot) -> Self {
+        let start_time = Instant::now();
+        let counter = |value| Arc::new(AtomicU64::new(value));
+        Self {
+            total_requests: counter(snapshot.total_requests),
+            uncompressed_bytes: counter(snapshot.uncompressed_bytes),
+            compressed_bytes: counter(snapshot.compressed_bytes),
+            active_websockets: counter(0),
+            cancelled_requests: counter(snapshot.cancelled_requests),
+            client_disconnects: counter(snapshot.client_disconnects),
+            accepted_connections: counter(snapshot.accepted_connections),
+            body_budget_rejections: counter(snapshot.body_budget_rejections),
+            consecutive_errors: counter(0),
+            by_http_version: Arc::new(
+                http_version_counts(&snapshot.by_http_version).map(AtomicU64::new),
+            ),
+            by_route: ROUTES.iter().map(|_| AtomicU64::new(0)).collect(),
+            latency: LatencyHistogram::new(),
+            accept_rate: AcceptRate::new(start_time),
+            start_time,
+            restored: snapshot,
+        }
+    }
+
+    // The cumulative counters as they stand now
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        StatsSnapshot {
+            total_requests: load(&self.total_requests),
+            uncompressed_bytes: load(&self.uncompressed_bytes),
+            compressed_bytes: load(&self.compressed_bytes),
+            cancelled_requests: load(&self.cancelled_requests),
+            client_disconnects: load(&self.client_disconnects),
+            accepted_connections: load(&self.accepted_connections),
+            body_budget_rejections: load(&self.body_budget_rejections),
+            by_http_version: self.http_version_counts(),
+        }
+    }
+
+    fn record_version(&self, version: hyper::Version) {
+        if let Some(index) = HTTP_VERSIONS.iter().position(|&known| known == version) {
+            self.by_http_version[index].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // A 5xx extends the run of consecutive errors and a 2xx ends it; other
+    // statuses say nothing about the server's own health
+    fn record_outcome(&self, status: StatusCode) {
+        if status.is_server_error() {
+            self.consecutive_errors.fetch_add(1, Ordering::Relaxed);
+        } else if status.is_success() {
+            self.consecutive_errors.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn consecutive_errors(&self) -> u64 {
+        self.consecutive_errors.load(Ordering::Relaxed)
+    }
+
+    // Counts a request under the pattern of the route answering `path` (a
+    // route path, after `BASE_PATH`); paths no route matches aren't counted
+    fn record_route(&self, path: &str) {
+        if let Some(index) = ROUTES.iter().position(|route| route.matches(path)) {
+            self.by_route[index].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // Requests per route pattern, in `ROUTES` order. Every route is listed,
+    // so CSV columns don't come and go with traffic.
+    fn route_counts(&self) -> RouteCounts {
+        RouteCounts(
+            ROUTES
+                .iter()
+                .zip(self.by_route.iter())
+                .map(|(route, counter)| (route.pattern, counter.load(Ordering::Relaxed)))
+                .collect(),
+        )
+    }
+
+    fn http_version_counts(&self) -> HttpVersionCounts {
+        let [http_09, http_10, http_11, http_2, http_3] = self
+            .by_http_version
+            .each_ref()
+            .map(|counter| counter.load(Ordering::Relaxed));
+        HttpVersionCounts {
+            http_09,
+            http_10,
+            http_11,
+            http_2,
+            http_3,
+        }
+    }
+
+    // Returns the new total, and how many of those this process served
+    fn increment_requests(&self) -> (u64, u64) {
+        let total = self.total_requests.fetch_add(1, Ordering::Relaxed) + 1;
+        (total, total - self.restored.total_requests)
+    }
+
+    fn get_total_requests(&self) -> u64 {
+        self.total_requests.load(Ordering::Relaxed)
+    }
+
+    // Counts an accepted connection; returns the recent accept rate
+    fn record_accept(&self) -> f64 {
+        self.accepted_connections.fetch_add(1, Ordering::Relaxed);
+        self.accept_rate.record(Instant::now())
+    }
+
+    fn record_compression(&self, uncompressed: u64, compressed: u64) {
+        self.uncompressed_bytes
+            .fetch_add(uncompressed, Ordering::Relaxed);
+        self.compressed_bytes
+            .fetch_add(compressed, Ordering::Relaxed);
+    }
+
+    fn get_uptime_seconds(&self) -> u64 {
+        self.start_time.elapsed().as_secs()
+    }
+}
+
+// The versions `/stats` counts requests under, in `HttpVersionCounts` order
+const HTTP_VERSIONS: [hyper::Version; 5] = [
+    hyper::Version::HTTP_09,
+    hyper::Version::HTTP_10,
+    hyper::Version::HTTP_11,
+    hyper::Version::HTTP_2,
+    hyper::Version::HTTP_3,
+];
+
+fn http_version_counts(counts: &HttpVersionCounts) -> [u64; 5] {
+    [
+        counts.http_09,
+        counts.http_10,
+        counts.http_11,
+        counts.http_2,
+        counts.http_3,
+    ]
+}
+
+impl Default for ServerStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// State shared by every connection
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub stats: ServerStats,
+    pub idempotency: IdempotencyCache,
+    pub kv: KvStore,
+    pub rate_limiter: Option<Arc<dyn RateLimiter>>,
+    pub connection_limiter: Option<ConnectionLimiter>,
+    // Set while in maintenance mode; toggled by `POST /admin/maintenance`
+    pub maintenance: Arc<AtomicBool>,
+    // Stops `serve` from inside the server, e.g. once `max_requests` is hit
+    pub shutdown: Arc<Notify>,
+    // Set by the first `begin_shutdown`, so `shutdown` is only notified once
+    pub shutting_down: Arc<AtomicBool>,
+    // Set once `serve` stops accepting and starts closing connections;
+    // requests still arriving on them are turned away
+    pub closing: Arc<AtomicBool>,
+    // Cleared while warm-up tasks run; `/readyz` answers 503 until it is set
+    pub ready: Arc<AtomicBool>,
+    // Collects request spans when `otel_traces` is on
+    pub spans: Option<SpanExporter>,
+    // Shared by every buffered request body when `body_budget_bytes` is set
+    pub body_budget: Option<BodyBudget>,
+    // The last `recent_requests` requests, for `/debug/recent`
+    pub recent: Option<RecentRequests>,
+    // GET responses of the `response_cache` routes
+    pub response_cache: Option<ResponseCache>,
+}
+
+impl AppState {
+    pub fn new(config: Config) -> Self {
+        timestamp::set_utc(config.use_utc_timestamps);
+        let idempotency = IdempotencyCache::new(Duration::from_secs(config.idempotency_ttl_secs));
+        let rate_limiter = (config.rate_limit_per_sec > 0.0).then(|| {
+            rate_limit::new_limiter(
+                config.rate_limit_algorithm,
+                config.rate_limit_per_sec,
+                config.rate_limit_burst,
+                config.rate_limit_ipv4_prefix,
+                config.rate_limit_ipv6_prefix,
+            )
+        });
+        let connection_limiter = (config.max_connections_per_ip > 0)
+            .then(|| ConnectionLimiter::new(config.max_connections_per_ip));
+        let maintenance = Arc::new(AtomicBool::new(config.maintenance));
+        let stats = match &config.stats_snapshot_path {
+            Some(path) => ServerStats::from_snapshot(StatsSnapshot::load_or_default(path)),
+            None => ServerStats::new(),
+        };
+        let body_budget = (config.body_budget_bytes > 0).then(|| {
+            BodyBudget::new(
+                config.body_budget_bytes as u32,
+                Duration::from_millis(config.body_budget_timeout_ms),
+            )
+        });
+        let recent =
+            (config.recent_requests > 0).then(|| RecentRequests::new(config.recent_requests));
+        let response_cache = (!config.response_cache.is_empty()).then(ResponseCache::new);
+        let spans = config
+            .otel_traces
+            .then(|| SpanExporter::new(&config.otel_endpoint, &config.otel_service_name));
+        Self {
+            config: Arc::new(config),
+            stats,
+            idempotency,
+            kv: KvStore::new(),
+            rate_limiter,
+            connection_limiter,
+            maintenance,
+            shutdown: Arc::new(Notify::new()),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            closing: Arc::new(AtomicBool::new(false)),
+            ready: Arc::new(AtomicBool::new(true)),
+            spans,
+            body_budget,
+            recent,
+            response_cache,
+        }
+    }
+
+    // Asks `serve` to shut down gracefully. Returns whether this call did
+    // so; later calls, however close together, find it already under way.
+    pub fn begin_shutdown(&self) -> bool {
+        let first = self.claim_shutdown();
+        if first {
+            // Stores a permit if `serve` is busy accepting, so it can't be missed
+            self.shutdown.notify_one();
+        }
+        first
+    }
+
+    // `begin_shutdown`, but only after `delay`: `/readyz` answers 503 at
+    // once so load balancers stop sending traffic, while everything else is
+    // served as usual until connections start draining
+    pub fn begin_drain_then_shutdown(&self, delay: Duration) -> bool {
+        let first = self.claim_shutdown();
+        if first {
+            println!(
+                "Draining: /readyz now reports not ready; shutting down in {}ms",
+                delay.as_millis()
+            );
+            let shutdown = self.shutdown.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                println!("Drain delay over; closing connections");
+                shutdown.notify_one();
+            });
+        }
+        first
+    }
+
+    // Whether this call is the one that starts shutting down
+    fn claim_shutdown(&self) -> bool {
+        self.shutting_down
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    // Marks the server not ready until every task in `warmups` has
+    // finished. A task that panics leaves it not ready for good, since
+    // whatever it was preparing never happened.
+    pub fn ready_after<I>(&self, warmups: I) -> tokio::task::JoinHandle<()>
+    where
+        I: IntoIterator<Item = Warmup>,
+    {
+        self.ready.store(false, Ordering::Relaxed);
+        let mut tasks: tokio::task::JoinSet<()> = warmups.into_iter().collect();
+        let ready = self.ready.clone();
+        tokio::spawn(async move {
+            while let Some(result) = tasks.join_next().await {
+                if let Err(err) = result {
+                    eprintln!("Warm-up task failed: {}; staying not ready", err);
+                    return;
+                }
+            }
+            ready.store(true, Ordering::Relaxed);
+        })
+    }
+}
+
+// A task that must finish before the server reports ready
+pub type Warmup = std::pin::Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+// Response structures
+#[derive(Serialize)]
+struct JsonResponse {
+    message: String,
+    timestamp: String,
+    server: String,
+}
+
+#[derive(Serialize)]
+struct NotFoundResponse {
+    message: String,
+    timestamp: String,
+    server: String,
+    suggestions: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct MaintenanceResponse {
+    maintenance: bool,
+    timestamp: String,
+    server: String,
+}
+
+#[derive(Serialize)]
+struct StatsResponse {
+    total_requests: u64,
+    uptime_seconds: u64,
+    // `uptime_seconds` for people, e.g. "2d 3h 14m 5s"
+    uptime_human: String,
+    requests_per_second: f64,
+    uncompressed_bytes: u64,
+    compressed_bytes: u64,
+    compression_ratio: f64,
+    active_websockets: u64,
+    cancelled_requests: u64,
+    client_disconnects: u64,
+    accepted_connections: u64,
+    connections_per_second: f64,
+    recent_connections_per_second: f64,
+    // Both null without BODY_BUDGET_BYTES
+    body_budget_bytes: Option<u64>,
+    body_budget_available_bytes: Option<u64>,
+    body_budget_rejections: u64,
+    consecutive_errors: u64,
+    // Null without RATE_LIMIT_PER_SEC
+    rate_limiter_tracked_ips: Option<usize>,
+    by_http_version: HttpVersionCounts,
+    by_route: RouteCounts,
+}
+
+// Requests per route pattern, serialized as `{"/echo/:msg": 12, ...}`
+struct RouteCounts(Vec<(&'static str, u64)>);
+
+impl Serialize for RouteCounts {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(pattern, count)| (pattern, count)))
+    }
+}
+
+// What `/debug/echo` saw of a request
+#[derive(Serialize)]
+struct ReflectedRequest {
+    method: String,
+    uri: String,
+    version: String,
+    // Repeated headers become an array of their values, in order
+    headers: serde_json::Map<String, serde_json::Value>,
+    body: String,
+    // "utf-8", or "base64" for bodies that aren't valid UTF-8
+    body_encoding: &'static str,
+    timestamp: String,
+    server: String,
+}
+
+// What `/debug/recent` reports
+#[derive(Serialize)]
+struct RecentResponse {
+    // RECENT_REQUESTS; 0 when nothing is kept
+    capacity: usize,
+    requests: Vec<RequestSummary>,
+    timestamp: String,
+    server: String,
+}
+
+// What `/debug/runtime` reports of the Tokio runtime, from its stable
+// metrics
+#[derive(Serialize)]
+struct RuntimeMetrics {
+    flavor: String,
+    workers: usize,
+    alive_tasks: usize,
+    // Tasks spawned from outside the runtime, waiting for any worker
+    global_queue_depth: usize,
+    worker_stats: Vec<WorkerMetrics>,
+    timestamp: String,
+    server: String,
+}
+
+#[derive(Serialize)]
+struct WorkerMetrics {
+    index: usize,
+    // Time spent running tasks since the runtime started
+    busy_ms: u64,
+    // Times the worker ran out of work and went to sleep
+    park_count: u64,
+}
+
+// Request bodies the router can read: hyper's `Incoming` on a live
+// connection, or any in-memory body when dispatching directly (benches)
+pub trait RequestBody:
+    hyper::body::Body<Data = Bytes, Error: Into<Box<dyn std::error::Error + Send + Sync>>>
+    + Send
+    + 'static
+{
+}
+
+impl<B> RequestBody for B where
+    B: hyper::body::Body<Data = Bytes, Error: Into<Box<dyn std::error::Error + Send + Sync>>>
+        + Send
+        + 'static
+{
+}
+
+// Main request handler. Public so routing can be exercised without a
+// connection; `serve` is the normal way in.
+pub async fn handle_request<B: RequestBody>(
+    mut req: Request<B>,
+    state: AppState,
+    remote_addr: Option<SocketAddr>,
+) -> Result<Response<Body>, Infallible> {
+    let started = Instant::now();
+    let (served, served_here) = state.stats.increment_requests();
+    req.extensions_mut().insert(RequestNumber(served));
+    if state.config.max_requests > 0
+        && served_here == state.config.max_requests
+        && state.begin_shutdown()
+    {
+        println!("Served {} requests; shutting down", served_here);
+    }
+
+    let method = req.method().clone();
+    let version = req.version();
+    state.stats.record_version(version);
+    let path = req.uri().path().to_string();
+    let received = timestamp::now();
+    let route_path = strip_base_path(&path, &state.config.base_path);
+    if let Some(route_path) = route_path {
+        state.stats.record_route(route_path);
+    }
+    // A caller that didn't record its span doesn't want this one either
+    let span = state.spans.as_ref().and_then(|_| {
+        let parent = req
+            .headers()
+            .get(otel::TRACEPARENT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(TraceContext::parse);
+        parent
+            .is_none_or(|parent| parent.sampled)
+            .then(|| Span::start(parent, method.clone(), path.clone(), SystemTime::now()))
+    });
+    // Redacted once for everything below that logs or keeps them
+    let combined = state.config.log_format == LogFormat::Combined;
+    let headers = (state.config.log_headers || state.recent.is_some() || combined)
+        .then(|| redact_headers(req.headers(), &state.config.redacted_headers));
+    // Only the Apache formats need the rest of the request line and headers
+    let access = (state.config.log_format != LogFormat::Default).then(|| {
+        let header = |name: hyper::header::HeaderName| {
+            headers
+                .as_ref()?
+                .get(name)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        };
+        AccessLogEntry {
+            remote_ip: remote_addr.map(|addr| addr.ip()),
+            received,
+            method: method.clone(),
+            target: req
+                .uri()
+                .path_and_query()
+                .map_or_else(|| path.clone(), |target| target.to_string()),
+            version,
+            status: StatusCode::OK,
+            bytes: None,
+            referer: header(hyper::header::REFERER),
+            user_agent: header(hyper::header::USER_AGENT),
+        }
+    });
+    let keep_alive = version != hyper::Version::HTTP_10
+        || (state.config.http1_0_keep_alive && asks_keep_alive(req.headers()));
+    let mut response = MAX_RESPONSE_SIZE
+        .scope(
+            state.config.max_response_size,
+            respond(req, &state, remote_addr),
+        )
+        .await;
+    if method == Method::HEAD {
+        response = head_response(response);
+    }
+    if version == hyper::Version::HTTP_10 {
+        set_http1_0_connection(&mut response, keep_alive);
+    }
+
+    // Health checks are left out: their 503s would keep an unhealthy server
+    // unhealthy, and their 200s would hide the errors they are there to see
+    let route = route_path.and_then(route_pattern);
+    if route != Some("/health") {
+        state.stats.record_outcome(response.status());
+    }
+
+    state
+        .stats
+        .latency
+        .record(started.elapsed(), span.as_ref().map(|span| span.trace_id));
+
+    // Ends once the head is ready; a streamed body may still be sending
+    if let (Some(spans), Some(mut span)) = (&state.spans, span) {
+        span.route = route;
+        span.status = response.status();
+        span.end = SystemTime::now();
+        spans.record(span);
+    }
+    if let Some(recent) = &state.recent {
+        recent.record(RequestSummary {
+            method: method.to_string(),
+            path: path.clone(),
+            status: response.status().as_u16(),
+            timestamp: timestamp::rfc3339(&received),
+            latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+            headers: headers.as_ref().map(dump_headers).unwrap_or_default(),
+        });
+    }
+
+    let logged = state.config.log_favicon || path != FAVICON_PATH;
+    if logged && log_sample::should_log(response.status(), state.config.log_sample_rate) {
+        match access {
+            Some(mut entry) => {
+                entry.status = response.status();
+                entry.bytes = response.body().size_hint().exact();
+                println!("{}", format_clf(&entry, combined));
+            }
+            None => {
+                // Appended as one JSON object, so the line stays one line
+                let logged_headers = match &headers {
+                    Some(headers) if state.config.log_headers => format!(
+                        " headers={}",
+                        serde_json::Value::Object(dump_headers(headers))
+                    ),
+                    _ => String::new(),
+                };
+                println!(
+                    "[{}] {} {} {:?} {} - Request #{}{}",
+                    timestamp::log_time(),
+                    method,
+                    path,
+                    version,
+                    response.status().as_u16(),
+                    served,
+                    logged_headers
+                );
+            }
+        }
+    }
+    Ok(response)
+}
+
+// Everything between counting a request and logging it
+async fn respond<B: RequestBody>(
+    mut req: Request<B>,
+    state: &AppState,
+    remote_addr: Option<SocketAddr>,
+) -> Response<Body> {
+    // A keep-alive client caught by shutdown is told to go elsewhere rather
+    // than served by a server that is about to disappear
+    if state.closing.load(Ordering::Relaxed) {
+        let mut response = service_unavailable("Shutting down", 1);
+        response.headers_mut().insert(
+            hyper::header::CONNECTION,
+            hyper::header::HeaderValue::from_static("close"),
+        );
+        return shape_json(response, &state.config).await;
+    }
+
+    // Checked for every route, not only those that read a body: an
+    // ambiguous request can hide a second one whatever path it names
+    if let Some(response) = reject_framing(req.headers()) {
+        return shape_json(response, &state.config).await;
+    }
+    if let Some(response) = reject_method(req.method(), req.uri().path(), &state.config.base_path) {
+        return shape_json(response, &state.config).await;
+    }
+    if let Some(response) = reject_target(req.uri()) {
+        return shape_json(response, &state.config).await;
+    }
+    // Routed as the GET it mirrors; `handle_request` drops the body after
+    let head = req.method() == Method::HEAD;
+    if head {
+        *req.method_mut() = Method::GET;
+    }
+
+    // Probes come straight from the orchestrator, not through the proxy
+    if state.config.https_redirect
+        && !strip_base_path(req.uri().path(), &state.config.base_path)
+            .is_some_and(|path| PROBE_PATHS.contains(&path))
+    {
+        if let Some(location) = redirect::https_location(req.uri(), req.headers()) {
+            return shape_json(handle_permanent_redirect(&location), &state.config).await;
+        }
+    }
+
+    // Every check below sees the same path the router finally matches
+    let path = match normalize::normalize_path(req.uri().path()) {
+        Ok(path) if state.config.normalize_path_unicode => normalize::compose_path(path),
+        Ok(path) => path,
+        Err(normalize::PathError::ControlCharacter) => {
+            let response = handle_bad_request("Path must not contain control characters");
+            return shape_json(response, &state.config).await;
+        }
+    };
+    if let Cow::Owned(path) = path {
+        let target = match req.uri().query() {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        };
+        let mut parts = req.uri().clone().into_parts();
+        parts.path_and_query = target.parse().ok();
+        if let Ok(uri) = Uri::from_parts(parts) {
+            *req.uri_mut() = uri;
+        }
+    }
+
+    // Resolved now, since `req` is handed to the router
+    let pattern = strip_base_path(req.uri().path(), &state.config.base_path)
+        .or_else(|| (req.uri().path() == FAVICON_PATH).then_some(FAVICON_PATH))
+        .and_then(route_pattern);
+    let global_timeout = (state.config.request_timeout_ms > 0)
+        .then(|| Duration::from_millis(state.config.request_timeout_ms));
+    let configured_timeout = state.config.route_timeouts.timeout(pattern, global_timeout);
+    let mut budget = deadline::effective_budget(req.headers(), configured_timeout);
+
+    if let (Some(limiter), Some(addr)) = (&state.rate_limiter, remote_addr) {
+        match limiter.check(addr.ip()) {
+            // A leaky-bucket wait is spent out of the request's budget; one
+            // that would use all of it is refused now rather than timed out
+            // once it has been sat through
+            Ok(wait) if !wait.is_zero() => match budget {
+                Budget::Remaining(remaining) if wait >= remaining => {
+                    return shape_json(handle_too_many_requests(wait), &state.config).await
+                }
+                Budget::Remaining(remaining) => {
+                    tokio::time::sleep(wait).await;
+                    budget = Budget::Remaining(remaining - wait);
+                }
+                Budget::Unlimited => tokio::time::sleep(wait).await,
+                // Answered with 504 straight away below
+                Budget::Expired => {}
+            },
+            Ok(_) => {}
+            Err(retry_after) => {
+                return shape_json(handle_too_many_requests(retry_after), &state.config).await
+            }
+        }
+    }
+
+    // Health, stats and the admin API stay up so the mode can be switched off
+    if state.maintenance.load(Ordering::Relaxed)
+        && !strip_base_path(req.uri().path(), &state.config.base_path)
+            .is_some_and(maintenance_exempt)
+    {
+        let response = service_unavailable(
+            "Down for maintenance; please retry later",
+            state.config.maintenance_retry_after_secs,
+        );
+        return shape_json(response, &state.config).await;
+    }
+
+    // Retried POSTs carrying a known Idempotency-Key get the original response
+    let idempotency_key = match *req.method() {
+        hyper::Method::POST => req
+            .headers()
+            .get(IDEMPOTENCY_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|key| IdempotencyKey::new(Method::POST, req.uri().path(), key)),
+        _ => None,
+    };
+    let encoding = compression::negotiate(req.headers(), state.config.compression);
+    if encoding == Negotiated::NotAcceptable {
+        let response = handle_not_acceptable(if state.config.compression {
+            "No acceptable content coding; gzip and identity are available"
+        } else {
+            "No acceptable content coding; only identity is available"
+        });
+        return shape_json(response, &state.config).await;
+    }
+
+    // ...as long as they send the same body; a different one means the key
+    // was reused for another request. One arriving while the first is still
+    // being answered is told to come back.
+    let claim = match idempotency_key.map(|key| state.idempotency.claim(key)) {
+        Some(Lookup::Stored(fingerprint, cached)) => {
+            let sent = idempotency::fingerprint(req.into_body(), state.config.max_body_bytes).await;
+            if sent != Some(fingerprint) {
+                return shape_json(handle_idempotency_key_reused(), &state.config).await;
+            }
+            return cached;
+        }
+        Some(Lookup::InProgress) => {
+            return shape_json(handle_idempotency_key_in_progress(), &state.config).await;
+        }
+        Some(Lookup::Claimed(claim)) => Some(claim),
+        None => None,
+    };
+    let fingerprint = Arc::new(OnceLock::new());
+    let req =
+        req.map(|body| Fingerprinted::new(body, claim.is_some().then(|| fingerprint.clone())));
+
+    // Everything from here on awaits, so this is where a disconnect can land
+    let cancel_guard = CancelGuard::new(
+        &state.stats.cancelled_requests,
+        format!("{} {}", req.method(), req.uri().path()),
+    );
+
+    let cached = state
+        .response_cache
+        .as_ref()
+        .zip(state.config.response_cache.ttl(pattern))
+        .and_then(|(cache, ttl)| Some((cache, cache_key(&req)?, ttl)));
+
+    let compute = async {
+        let mut response = match budget {
+            Budget::Expired => handle_gateway_timeout(),
+            Budget::Unlimited => route(req, state).await,
+            Budget::Remaining(budget) => {
+                match tokio::time::timeout(budget, route(req, state)).await {
+                    Ok(response) => response,
+                    Err(_) => handle_gateway_timeout(),
+                }
+            }
+        };
+        state.config.cache_control.apply(pattern, &mut response);
+        state.config.route_headers.apply(pattern, &mut response);
+        shape_json(response, &state.config).await
+    };
+    let response = match cached {
+        Some((cache, key, ttl)) => cache.get_or_compute(key, ttl, compute).await,
+        None => compute.await,
+    };
+
+    let response = match claim {
+        Some(claim) => claim.finish(fingerprint.get().copied(), response).await,
+        None => response,
+    };
+
+    // A HEAD response's body is never sent, so there's nothing to compress
+    let response = if matches!(encoding, Negotiated::Gzip | Negotiated::GzipOnly) && !head {
+        let (response, sizes) = compression::compress_response(
+            response,
+            state.config.compression_min_bytes,
+            encoding == Negotiated::GzipOnly,
+        )
+        .await;
+        if let Some((uncompressed, compressed)) = sizes {
+            state.stats.record_compression(uncompressed, compressed);
+        }
+        response
+    } else {
+        response
+    };
+
+    cancel_guard.finish();
+    response
+}
+
+// Dispatches a request to its route handler
+async fn route<B: RequestBody>(req: Request<B>, state: &AppState) -> Response<Body> {
+    let method = req.method().clone();
+    let lang = i18n::negotiate(req.headers());
+    let base_path = state.config.base_path.as_str();
+    // Browsers ask for the icon at the host root, whatever the base path
+    let Some(path) = strip_base_path(req.uri().path(), base_path)
+        .or_else(|| (req.uri().path() == FAVICON_PATH).then_some(FAVICON_PATH))
+    else {
+        return handle_not_found(req.uri().path(), lang, Site::All, &state.config);
+    };
+    let path = path.to_string();
+
+    let site = if state.config.virtual_hosts.is_empty() {
+        Site::All
+    } else {
+        let host = vhost::request_host(&req);
+        match state
+            .config
+            .virtual_hosts
+            .resolve(host, state.config.default_host.as_deref())
+        {
+            Some(site) => site,
+            None => return handle_unknown_host(),
+        }
+    };
+    if !site.serves(&path) || !route_enabled(&path, &state.config) {
+        return handle_not_found(&path, lang, site, &state.config);
+    }
+
+    let methods = methods_for(&path);
+    if method == Method::OPTIONS && !methods.is_empty() {
+        if let Some(preflight) = cors::preflight(req.headers(), &methods) {
+            return preflight;
+        }
+        // Plain OPTIONS: routes that don't take it themselves list their methods
+        if !methods.contains(&Method::OPTIONS) {
+            return handle_options(&methods);
+        }
+    }
+    if !methods.is_empty() && !accepts(&path, &method) {
+        return handle_method_not_allowed(&methods);
+    }
+
+    // Parsed once, within the configured limits, for every handler below
+    let params = match parse_query(req.uri().query(), &state.config) {
+        Ok(params) => params,
+        Err(message) => return handle_bad_request(&message),
+    };
+
+    match (&method, path.as_str()) {
+        (&hyper::Method::GET, "/") => handle_root(lang),
+        (&hyper::Method::GET, FAVICON_PATH) => handle_favicon(&state.config, req.headers()),
+        (&hyper::Method::GET, "/health") => handle_health(lang, state),
+        (&hyper::Method::GET, "/livez") => handle_livez(),
+        (&hyper::Method::GET, "/readyz") => handle_readyz(state),
+        (&hyper::Method::GET, "/stats") => match stats_format(req.headers(), &params) {
+            Some(format) => handle_stats(&req, state, format),
+            None => handle_bad_request("format must be json, prometheus or csv"),
+        },
+        (&hyper::Method::GET, "/stats.csv") => handle_stats(&req, state, StatsFormat::Csv),
+        (&hyper::Method::GET, "/metrics") => match metrics_format(req.headers(), &params) {
+            Some(format) => handle_metrics(&req, state, format),
+            None => handle_bad_request("format must be prometheus or openmetrics"),
+        },
+        (&hyper::Method::GET, "/openapi.json") => handle_openapi(site, &state.config),
+        (&hyper::Method::GET, "/docs") => handle_docs(),
+        (&hyper::Method::GET, "/ws") => handle_websocket(req, state),
+        (&hyper::Method::GET, path) if path.starts_with("/delay/") => {
+            handle_delay(&path[7..], &state.config).await
+        }
+        (&hyper::Method::GET, path) if path.starts_with("/error/") => handle_error(&path[7..]),
+        (&hyper::Method::GET, path) if path.starts_with("/echo/stream/") => {
+            let range = req
+                .headers()
+                .get(range::RANGE_HEADER)
+                .and_then(|value| value.to_str().ok());
+            handle_echo_stream(&path[13..], &params, range, state)
+        }
+        (&hyper::Method::GET, path) if path.starts_with("/echo/") => {
+            let message = &path[6..];
+            let format = echo_format(req.headers(), &state.config);
+            handle_echo_repeat(message, &params, format, state)
+        }
+        (&hyper::Method::POST, "/echo") => handle_echo_body(req, state).await,
+        (&hyper::Method::GET, path) if path.starts_with("/echo-header/") => {
+            handle_echo_header(&path[13..])
+        }
+        (&hyper::Method::GET, "/stream/ndjson") => handle_ndjson(&params, state),
+        (_, "/admin/maintenance") => handle_maintenance(req, state).await,
+        (&hyper::Method::POST, "/admin/shutdown") => handle_admin_shutdown(req, &params, state),
+        (_, "/debug/echo") => handle_debug_echo(req, state).await,
+        (&hyper::Method::GET, "/debug/runtime") => handle_debug_runtime(),
+        (&hyper::Method::GET, "/debug/recent") => handle_debug_recent(state),
+        (&hyper::Method::GET, path) if path.starts_with("/debug/block/") => {
+            handle_debug_block(&path[13..], &state.config).await
+        }
+        (_, path) if path.starts_with("/kv/") => handle_kv(req, &path[4..], state).await,
+        _ => handle_not_found(&path, lang, site, &state.config),
+    }
+}
+
+// The route path under `base_path`, or `None` for paths outside it. With a
+// base of `/api`, `/api` and `/api/` are the root and `/apix` is outside.
+fn strip_base_path<'a>(path: &'a str, base_path: &str) -> Option<&'a str> {
+    let base_path = base_path.trim_end_matches('/');
+    if base_path.is_empty() {
+        return Some(path);
+    }
+    match path.strip_prefix(base_path)? {
+        "" => Some("/"),
+        rest if rest.starts_with('/') => Some(rest),
+        _ => None,
+    }
+}
+
+// A route pattern and the methods it answers. `:param` segments match the
+// rest of the path, as the `starts_with` arms in `route` do.
+struct Route {
+    pattern: &'static str,
+    methods: &'static [Method],
+}
+
+impl Route {
+    fn matches(&self, path: &str) -> bool {
+        match self.pattern.split_once(':') {
+            Some((prefix, _)) => path.len() > prefix.len() && path.starts_with(prefix),
+            None => path == self.pattern,
+        }
+    }
+}
+
+const GET: &[Method] = &[Method::GET];
+// Routes taking any method at all; these are the ones they advertise
+const ANY: &[Method] = &[
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+    Method::OPTIONS,
+];
+
+// Every route: the source for suggestions, `Allow` and CORS preflights
+const ROUTES: &[Route] = &[
+    Route {
+        pattern: "/",
+        methods: GET,
+    },
+    Route {
+        pattern: FAVICON_PATH,
+        methods: GET,
+    },
+    Route {
+        pattern: "/health",
+        methods: GET,
+    },
+    Route {
+        pattern: "/livez",
+        methods: GET,
+    },
+    Route {
+        pattern: "/readyz",
+        methods: GET,
+    },
+    Route {
+        pattern: "/stats",
+        methods: GET,
+    },
+    Route {
+        pattern: "/stats.csv",
+        methods: GET,
+    },
+    Route {
+        pattern: "/metrics",
+        methods: GET,
+    },
+    Route {
+        pattern: "/openapi.json",
+        methods: GET,
+    },
+    Route {
+        pattern: "/docs",
+        methods: GET,
+    },
+    Route {
+        pattern: "/delay/:ms",
+        methods: GET,
+    },
+    Route {
+        pattern: "/error/:code",
+        methods: GET,
+    },
+    Route {
+        pattern: "/echo/stream/:msg",
+        methods: GET,
+    },
+    Route {
+        pattern: "/echo/:msg",
+        methods: GET,
+    },
+    Route {
+        pattern: "/echo",
+        methods: &[Method::POST],
+    },
+    Route {
+        pattern: "/echo-header/:msg",
+        methods: GET,
+    },
+    Route {
+        pattern: "/stream/ndjson",
+        methods: GET,
+    },
+    Route {
+        pattern: "/ws",
+        methods: GET,
+    },
+    Route {
+        pattern: "/kv/:key",
+        methods: &[Method::GET, Method::PUT, Method::PATCH, Method::DELETE],
+    },
+    Route {
+        pattern: "/debug/echo",
+        methods: ANY,
+    },
+    Route {
+        pattern: "/debug/runtime",
+        methods: GET,
+    },
+    Route {
+        pattern: "/debug/recent",
+        methods: GET,
+    },
+    Route {
+        pattern: "/debug/block/:ms",
+        methods: GET,
+    },
+    Route {
+        pattern: "/admin/maintenance",
+        methods: &[Method::GET, Method::POST],
+    },
+    Route {
+        pattern: "/admin/shutdown",
+        methods: &[Method::POST],
+    },
+];
+
+// Methods some route answers at `path` (a route path, after `BASE_PATH`);
+// empty when no route matches
+pub fn methods_for(path: &str) -> Vec<Method> {
+    let mut methods: Vec<Method> = Vec::new();
+    for route in ROUTES.iter().filter(|route| route.matches(path)) {
+        for method in route.methods {
+            if !methods.contains(method) {
+                methods.push(method.clone());
+            }
+            // Every GET route answers HEAD too
+            if method == Method::GET && !methods.contains(&Method::HEAD) {
+                methods.push(Method::HEAD);
+            }
+        }
+    }
+    methods
+}
+
+// Whether the route that would answer `path` is switched on. Paths no route
+// matches are left to the 404 fallback.
+fn route_enabled(path: &str, config: &Config) -> bool {
+    ROUTES
+        .iter()
+        .find(|route| route.matches(path))
+        .is_none_or(|route| config.route_toggles.enabled(route.pattern))
+}
+
+// The pattern of the route that would answer `path`
+fn route_pattern(path: &str) -> Option<&'static str> {
+    ROUTES
+        .iter()
+        .find(|route| route.matches(path))
+        .map(|route| route.pattern)
+}
+
+// Whether `pattern` is registered in `ROUTES`
+pub(crate) fn is_route_pattern(pattern: &str) -> bool {
+    ROUTES.iter().any(|route| route.pattern == pattern)
+}
+
+// Whether a route at `path` takes `method`
+fn accepts(path: &str, method: &Method) -> bool {
+    ROUTES
+        .iter()
+        .filter(|route| route.matches(path))
+        .any(|route| {
+            route.methods == ANY
+                || route.methods.contains(method)
+                || (method == Method::HEAD && route.methods.contains(&Method::GET))
+        })
+}
+
+// The methods of the route registered as `pattern`
+fn route_methods(pattern: &str) -> &'static [Method] {
+    ROUTES
+        .iter()
+        .find(|route| route.pattern == pattern)
+        .map_or(&[], |route| route.methods)
+}
+
+// Health and readiness checks, never redirected to HTTPS
+const PROBE_PATHS: &[&str] = &["/health", "/livez", "/readyz"];
+
+// Routes that keep working in maintenance mode: the admin site's, apart from
+// the welcome page
+fn maintenance_exempt(path: &str) -> bool {
+    path != "/" && Site::Admin.serves(path)
+}
+
+// Route handlers
+fn handle_root(lang: &'static str) -> Response<Body> {
+    let response = JsonResponse {
+        message: i18n::localize(Message::Welcome, lang).to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    with_language(json_response(StatusCode::OK, &response), lang)
+}
+
+// Where browsers ask for the site icon: the host root, whatever `BASE_PATH`
+const FAVICON_PATH: &str = "/favicon.ico";
+
+// The configured icon, or an empty 204 so browsers stop asking with 404s.
+// A browser revalidating with `If-Modified-Since` gets a bodiless 304 while
+// the file is unchanged.
+fn handle_favicon(config: &Config, headers: &hyper::HeaderMap) -> Response<Body> {
+    let mut response = Response::builder()
+        .header("Server", "rust-http-server/1.0")
+        .header("Cache-Control", "public, max-age=86400");
+    let Some(icon) = &config.favicon else {
+        return response
+            .status(StatusCode::NO_CONTENT)
+            .body(full(Bytes::new()))
+            .unwrap();
+    };
+    if let Some(modified) = icon.modified {
+        response = response.header(
+            hyper::header::LAST_MODIFIED,
+            http_date::format_http_date(modified),
+        );
+        if http_date::not_modified_since(headers, modified) {
+            return response
+                .status(StatusCode::NOT_MODIFIED)
+                .body(full(Bytes::new()))
+                .unwrap();
+        }
+    }
+    response
+        .status(StatusCode::OK)
+        .header("Content-Type", icon.content_type)
+        .body(full(icon.bytes.clone()))
+        .unwrap()
+}
+
+// 503 once `unhealthy_after_errors` 5xx responses have been sent in a row,
+// so an orchestrator can restart an instance that keeps failing
+fn handle_health(lang: &'static str, state: &AppState) -> Response<Body> {
+    let threshold = state.config.unhealthy_after_errors;
+    let errors = state.stats.consecutive_errors();
+    if threshold > 0 && errors >= threshold {
+        return service_unavailable(
+            &format!("Unhealthy after {} consecutive server errors", errors),
+            1,
+        );
+    }
+
+    // Hit by load balancers at high rates, so the body comes from a
+    // pre-serialized template rather than serde
+    static TEMPLATES: OnceLock<Vec<(&'static str, TimestampedJson)>> = OnceLock::new();
+    let templates = TEMPLATES.get_or_init(|| {
+        i18n::SUPPORTED_LANGUAGES
+            .iter()
+            .map(|&lang| {
+                let message = i18n::localize(Message::Healthy, lang);
+                (lang, TimestampedJson::new(message))
+            })
+            .collect()
+    });
+    let template = templates
+        .iter()
+        .find(|(supported, _)| *supported == lang)
+        .unwrap_or(&templates[0]);
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .header("Server", "rust-http-server/1.0")
+        .body(full(if timestamp::is_utc() {
+            template.1.render_utc(chrono::Utc::now())
+        } else {
+            template.1.render(chrono::Local::now())
+        }))
+        .unwrap();
+    with_language(response, lang)
+}
+
+// Upgrades to a WebSocket and echoes messages on a task of its own
+fn handle_websocket<B: RequestBody>(mut req: Request<B>, state: &AppState) -> Response<Body> {
+    let Some(accept) = websocket::handshake_accept(req.headers()) else {
+        return handle_upgrade_required();
+    };
+
+    let guard = websocket::ActiveGuard::new(&state.stats.active_websockets);
+    let max_message = state.config.max_body_bytes;
+    let upgrade = hyper::upgrade::on(&mut req);
+    tokio::task::spawn(async move {
+        let _guard = guard;
+        match upgrade.await {
+            Ok(upgraded) => {
+                if let Err(err) = websocket::echo(TokioIo::new(upgraded), max_message).await {
+                    eprintln!("WebSocket error: {:?}", err);
+                }
+            }
+            Err(err) => eprintln!("WebSocket upgrade failed: {:?}", err),
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header("Upgrade", "websocket")
+        .header("Connection", "Upgrade")
+        .header(websocket::ACCEPT_HEADER, accept)
+        .header("Server", "rust-http-server/1.0")
+        .body(full(Bytes::new()))
+        .unwrap()
+}
+
+// Serializations `/stats` can answer in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatsFormat {
+    Json,
+    Prometheus,
+    Csv,
+}
+
+// `?format=` wins over Accept; otherwise the best-quality format Accept
+// allows, with ties (and no Accept at all) going to JSON. `None` for an
+// unknown `?format=`.
+fn stats_format(
+    headers: &hyper::HeaderMap,
+    params: &HashMap<String, String>,
+) -> Option<StatsFormat> {
+    if let Some(format) = params.get("format") {
+        return match format.to_ascii_lowercase().as_str() {
+            "json" => Some(StatsFormat::Json),
+            "prometheus" | "prom" => Some(StatsFormat::Prometheus),
+            "csv" => Some(StatsFormat::Csv),
+            _ => None,
+        };
+    }
+
+    let candidates = [
+        (
+            StatsFormat::Json,
+            accept_quality(headers, "application", "json"),
+        ),
+        (
+            StatsFormat::Prometheus,
+            accept_quality(headers, "text", "plain"),
+        ),
+        (StatsFormat::Csv, accept_quality(headers, "text", "csv")),
+    ];
+    let (format, _) = candidates
+        .into_iter()
+        .fold((StatsFormat::Json, 0.0), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        });
+    Some(format)
+}
+
+fn handle_stats<B>(req: &Request<B>, state: &AppState, format: StatsFormat) -> Response<Body> {
+    let total_requests = req
+        .extensions()
+        .get::<RequestNumber>()
+        .map_or_else(|| state.stats.get_total_requests(), |number| number.0);
+    let stats = collect_stats(
+        &state.stats,
+        state.body_budget.as_ref(),
+        state.rate_limiter.as_deref(),
+        total_requests,
+    );
+    let mut response = match format {
+        StatsFormat::Json => json_response(StatusCode::OK, &stats),
+        StatsFormat::Prometheus => {
+            let Some(text) = stats_prometheus(&stats) else {
+                return handle_internal_error();
+            };
+            Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "text/plain; version=0.0.4; charset=utf-8")
+                .header("Server", "rust-http-server/1.0")
+                .body(full(text))
+                .unwrap()
+        }
+        StatsFormat::Csv => {
+            let Some(csv) = stats_csv(&stats) else {
+                return handle_internal_error();
+            };
+            Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "text/csv; charset=utf-8")
+                .header("Content-Disposition", "attachment; filename=\"stats.csv\"")
+                .header("Server", "rust-http-server/1.0")
+                .body(full(csv))
+                .unwrap()
+        }
+    };
+    response.headers_mut().insert(
+        hyper::header::VARY,
+        hyper::header::HeaderValue::from_static("Accept"),
+    );
+    response
+}
+
+// `?format=` wins over Accept; otherwise OpenMetrics only when Accept
+// prefers it to plain text, so scrapers that don't ask keep getting 0.0.4.
+// `None` for an unknown `?format=`.
+fn metrics_format(
+    headers: &hyper::HeaderMap,
+    params: &HashMap<String, String>,
+) -> Option<MetricsFormat> {
+    if let Some(format) = params.get("format") {
+        return match format.to_ascii_lowercase().as_str() {
+            "prometheus" | "prom" => Some(MetricsFormat::Prometheus),
+            "openmetrics" => Some(MetricsFormat::OpenMetrics),
+            _ => None,
+        };
+    }
+
+    let openmetrics = accept_quality(headers, "application", "openmetrics-text");
+    if openmetrics > accept_quality(headers, "text", "plain") {
+        Some(MetricsFormat::OpenMetrics)
+    } else {
+        Some(MetricsFormat::Prometheus)
+    }
+}
+
+// The `/stats` samples plus the request latency histogram
+fn handle_metrics<B>(req: &Request<B>, state: &AppState, format: MetricsFormat) -> Response<Body> {
+    let total_requests = req
+        .extensions()
+        .get::<RequestNumber>()
+        .map_or_else(|| state.stats.get_total_requests(), |number| number.0);
+    let stats = collect_stats(
+        &state.stats,
+        state.body_budget.as_ref(),
+        state.rate_limiter.as_deref(),
+        total_requests,
+    );
+    let latency = state.stats.latency.snapshot();
+    let Some(families) = MetricFamilies::new(&stats, Some(latency), format) else {
+        return handle_internal_error();
+    };
+    let content_type = match format {
+        MetricsFormat::Prometheus => "text/plain; version=0.0.4; charset=utf-8",
+        MetricsFormat::OpenMetrics => "application/openmetrics-text; version=1.0.0; charset=utf-8",
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", content_type)
+        .header("Vary", "Accept")
+        .header("Server", "rust-http-server/1.0")
+        .body(TextStream::new(families).boxed())
+        .unwrap()
+}
+
+// OpenAPI description of the routes this host serves with this config.
+// Sent as is: neither the field case nor the envelope apply to it.
+fn handle_openapi(site: Site, config: &Config) -> Response<Body> {
+    let routes = ROUTES
+        .iter()
+        .filter(|route| site.serves(route.pattern) && config.route_toggles.enabled(route.pattern))
+        .map(|route| (route.pattern, route.methods));
+    let document = openapi::document(routes, &config.base_path);
+    let Ok(json) = serde_json::to_string(&document) else {
+        return handle_internal_error();
+    };
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .header("Server", "rust-http-server/1.0")
+        .body(full(json))
+        .unwrap()
+}
+
+fn handle_docs() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/html; charset=utf-8")
+        .header("Server", "rust-http-server/1.0")
+        .body(full(openapi::DOCS_PAGE))
+        .unwrap()
+}
+
+// What makes two GETs the same request to the response cache: everything
+// that picks the response apart from its content coding, which is applied
+// to cached responses afresh. `None` for requests that aren't cached, such
+// as range requests and requests with credentials.
+fn cache_key<B>(req: &Request<B>) -> Option<String> {
+    if req.method() != Method::GET || req.headers().contains_key(hyper::header::RANGE) {
+        return None;
+    }
+    // A shared cache must not store, or answer, a request with credentials
+    // (RFC 9111 section 3.5)
+    if req.headers().contains_key(hyper::header::AUTHORIZATION)
+        || req.headers().contains_key(hyper::header::COOKIE)
+    {
+        return None;
+    }
+    let header = |name| {
+        req.headers()
+            .get(name)
+            .map_or(&b""[..], |value: &hyper::header::HeaderValue| {
+                value.as_bytes()
+            })
+    };
+    let key = [
+        header(hyper::header::HOST),
+        req.uri()
+            .path_and_query()
+            .map_or(&b""[..], |target| target.as_str().as_bytes()),
+        header(hyper::header::ACCEPT),
+        header(hyper::header::ACCEPT_LANGUAGE),
+    ]
+    .join(&b'\n');
+    Some(String::from_utf8_lossy(&key).into_owned())
+}
+
+// Snapshot of the counters shared by every stats format
+// `total_requests` is passed in so the caller decides which requests count
+fn collect_stats(
+    stats: &ServerStats,
+    body_budget: Option<&BodyBudget>,
+    rate_limiter: Option<&dyn RateLimiter>,
+    total_requests: u64,
+) -> StatsResponse {
+    let uptime = stats.get_uptime_seconds();
+    // Rates are over this process's uptime, so leave out restored counts
+    let rps = if uptime > 0 {
+        let served = total_requests - stats.restored.total_requests;
+        finite_or_zero(served as f64 / uptime as f64)
+    } else {
+        0.0
+    };
+    let accepted_connections = stats.accepted_connections.load(Ordering::Relaxed);
+    let cps = if uptime > 0 {
+        let accepted = accepted_connections - stats.restored.accepted_connections;
+        finite_or_zero(accepted as f64 / uptime as f64)
+    } else {
+        0.0
+    };
+
+    // Only responses that were actually gzipped are counted
+    let uncompressed_bytes = stats.uncompressed_bytes.load(Ordering::Relaxed);
+    let compressed_bytes = stats.compressed_bytes.load(Ordering::Relaxed);
+    let compression_ratio = if compressed_bytes > 0 {
+        finite_or_zero(uncompressed_bytes as f64 / compressed_bytes as f64)
+    } else {
+        0.0
+    };
+
+    StatsResponse {
+        total_requests,
+        uptime_seconds: uptime,
+        uptime_human: format_duration(uptime),
+        requests_per_second: rps,
+        uncompressed_bytes,
+        compressed_bytes,
+        compression_ratio,
+        active_websockets: stats.active_websockets.load(Ordering::Relaxed),
+        cancelled_requests: stats.cancelled_requests.load(Ordering::Relaxed),
+        client_disconnects: stats.client_disconnects.load(Ordering::Relaxed),
+        accepted_connections,
+        connections_per_second: cps,
+        recent_connections_per_second: stats.accept_rate.recent_per_sec(Instant::now()),
+        body_budget_bytes: body_budget.map(BodyBudget::total),
+        body_budget_available_bytes: body_budget.map(BodyBudget::available),
+        body_budget_rejections: stats.body_budget_rejections.load(Ordering::Relaxed),
+        consecutive_errors: stats.consecutive_errors(),
+        rate_limiter_tracked_ips: rate_limiter.map(|limiter| limiter.tracked()),
+        by_http_version: stats.http_version_counts(),
+        by_route: stats.route_counts(),
+    }
+}
+
+// "2d 3h 14m 5s": every unit from the largest non-zero one down to seconds
+pub fn format_duration(secs: u64) -> String {
+    let units = [
+        (secs / 86_400, "d"),
+        (secs / 3_600 % 24, "h"),
+        (secs / 60 % 60, "m"),
+        (secs % 60, "s"),
+    ];
+    let first = units
+        .iter()
+        .position(|&(value, _)| value > 0)
+        .unwrap_or(units.len() - 1);
+    units[first..]
+        .iter()
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// JSON has no representation for NaN or infinity, so derived rates that
+// come out non-finite are reported as 0
+fn finite_or_zero(value: f64) -> f64 {
+    if value.is_finite() {
+        value
+    } else {
+        0.0
+    }
+}
+
+// Renders stats as a header row plus one value row. Columns come from
+// serializing `StatsResponse`, so they follow its fields automatically;
+// nested breakdowns get one column each, e.g. `by_http_version.HTTP/1.1`.
+fn stats_csv(stats: &StatsResponse) -> Option<String> {
+    let serde_json::Value::Object(fields) = serde_json::to_value(stats).ok()? else {
+        return None;
+    };
+
+    let mut columns = Vec::new();
+    for (name, value) in fields {
+        match value {
+            serde_json::Value::Object(breakdown) => columns.extend(
+                breakdown
+                    .into_iter()
+                    .map(|(key, value)| (format!("{}.{}", name, key), value)),
+            ),
+            value => columns.push((name, value)),
+        }
+    }
+
+    let header: Vec<String> = columns.iter().map(|(name, _)| csv_field(name)).collect();
+    let values: Vec<String> = columns
+        .iter()
+        .map(|(_, value)| match value {
+            serde_json::Value::String(text) => csv_field(text),
+            other => csv_field(&other.to_string()),
+        })
+        .collect();
+
+    Some(format!("{}\r\n{}\r\n", header.join(","), values.join(",")))
+}
+
+// Text exposition formats `/metrics` can answer in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetricsFormat {
+    // Prometheus text 0.0.4
+    Prometheus,
+    // OpenMetrics 1.0, for scrapers that validate it strictly
+    OpenMetrics,
+}
+
+// Renders stats in the Prometheus text exposition format, one
+// `rust_http_server_<field>` sample per numeric field of `StatsResponse`.
+// Breakdowns listed in `STATS_LABELS` become one labelled sample per key.
+fn stats_prometheus(stats: &StatsResponse) -> Option<String> {
+    metrics_text(stats, None, MetricsFormat::Prometheus)
+}
+
+// `stats_prometheus` in either format, followed by the `latency` histogram
+// when given. OpenMetrics adds a `# UNIT` line for fields named after their
+// unit, `_total` on counter samples, trace exemplars on histogram buckets
+// and the closing `# EOF`.
+fn metrics_text(
+    stats: &StatsResponse,
+    latency: Option<HistogramSnapshot>,
+    format: MetricsFormat,
+) -> Option<String> {
+    Some(MetricFamilies::new(stats, latency, format)?.collect())
+}
+
+// The families of `metrics_text`, one `# TYPE` line and its samples at a
+// time, so `/metrics` can send each as it is rendered
+struct MetricFamilies {
+    fields: serde_json::map::IntoIter,
+    latency: Option<HistogramSnapshot>,
+    openmetrics: bool,
+    eof_sent: bool,
+}
+
+impl MetricFamilies {
+    fn new(
+        stats: &StatsResponse,
+        latency: Option<HistogramSnapshot>,
+        format: MetricsFormat,
+    ) -> Option<Self> {
+        let serde_json::Value::Object(fields) = serde_json::to_value(stats).ok()? else {
+            return None;
+        };
+        Some(Self {
+            fields: fields.into_iter(),
+            latency,
+            openmetrics: format == MetricsFormat::OpenMetrics,
+            eof_sent: false,
+        })
+    }
+
+    // The family for stats field `name`; `None` for fields that aren't a
+    // number or a labelled breakdown
+    fn field(&self, name: &str, value: &serde_json::Value) -> Option<String> {
+        let openmetrics = self.openmetrics;
+        let counter = STATS_COUNTERS.contains(&name);
+        let kind = if counter { "counter" } else { "gauge" };
+        let suffix = if counter && openmetrics { "_total" } else { "" };
+        let mut text = format!("# TYPE rust_http_server_{name} {kind}\n");
+        let unit = ["seconds", "bytes", "ratio"]
+            .into_iter()
+            .find(|unit| name.ends_with(&format!("_{}", unit)));
+        if let (true, Some(unit)) = (openmetrics, unit) {
+            text.push_str(&format!("# UNIT rust_http_server_{name} {unit}\n"));
+        }
+        if let Some(value) = value.as_f64() {
+            text.push_str(&format!("rust_http_server_{name}{suffix} {value}\n"));
+        } else if let (Some(breakdown), Some((_, label))) = (
+            value.as_object(),
+            STATS_LABELS.iter().find(|(field, _)| *field == name),
+        ) {
+            for (key, value) in breakdown {
+                let Some(value) = value.as_f64() else {
+                    continue;
+                };
+                text.push_str(&format!(
+                    "rust_http_server_{name}{suffix}{{{label}=\"{key}\"}} {value}\n"
+                ));
+            }
+        } else {
+            return None;
+        }
+        Some(text)
+    }
+
+    fn histogram(&self, latency: &HistogramSnapshot) -> String {
+        let name = "rust_http_server_request_duration_seconds";
+        let mut text = format!("# TYPE {name} histogram\n");
+        if self.openmetrics {
+            text.push_str(&format!("# UNIT {name} seconds\n"));
+        }
+        for bucket in &latency.buckets {
+            let le = if bucket.le.is_infinite() {
+                "+Inf".to_string()
+            } else {
+                format!("{:?}", bucket.le)
+            };
+            text.push_str(&format!("{name}_bucket{{le=\"{le}\"}} {}", bucket.count));
+            // Plain Prometheus text has no syntax for exemplars
+            if let (true, Some(exemplar)) = (self.openmetrics, &bucket.exemplar) {
+                let at = exemplar
+                    .at
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                text.push_str(&format!(
+                    " # {{trace_id=\"{}\"}} {} {:.3}",
+                    otel::encode_hex(&exemplar.trace_id),
+                    exemplar.seconds,
+                    at
+                ));
+            }
+            text.push('\n');
+        }
+        text.push_str(&format!("{name}_sum {}\n", latency.sum_seconds));
+        text.push_str(&format!("{name}_count {}\n", latency.count));
+        text
+    }
+}
+
+impl Iterator for MetricFamilies {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while let Some((name, value)) = self.fields.next() {
+            if let Some(family) = self.field(&name, &value) {
+                return Some(family);
+            }
+        }
+        if let Some(latency) = self.latency.take() {
+            return Some(self.histogram(&latency));
+        }
+        if self.openmetrics && !self.eof_sent {
+            self.eof_sent = true;
+            return Some("# EOF\n".to_string());
+        }
+        None
+    }
+}
+
+// Breakdown fields of `StatsResponse` and the label their keys go under
+const STATS_LABELS: &[(&str, &str)] = &[("by_http_version", "version"), ("by_route", "route")];
+
+// Stats fields that only ever grow; everything else is exported as a gauge
+const STATS_COUNTERS: &[&str] = &[
+    "total_requests",
+    "uncompressed_bytes",
+    "compressed_bytes",
+    "cancelled_requests",
+    "client_disconnects",
+    "accepted_connections",
+    "body_budget_rejections",
+    "by_http_version",
+    "by_route",
+];
+
+// Quotes a CSV field when it contains a delimiter, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn handle_echo(message: &str, format: EchoFormat) -> Response<Body> {
+    let mut response = match format {
+        EchoFormat::Json => {
+            let response = JsonResponse {
+                message: format!("Echo: {}", message),
+                timestamp: timestamp_rfc3339(),
+                server: "rust-http-server/1.0".to_string(),
+            };
+            json_response(StatusCode::OK, &response)
+        }
+        EchoFormat::Text => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/plain; charset=utf-8")
+            .header("Server", "rust-http-server/1.0")
+            .body(full(message.to_string()))
+            .unwrap(),
+    };
+    response.headers_mut().append(
+        hyper::header::VARY,
+        hyper::header::HeaderValue::from_static("Accept"),
+    );
+    response
+}
+
+// `GET /echo/:msg`, with the message repeated `?repeat=` times. Bodies above
+// the stream threshold are generated as they are sent rather than up front.
+fn handle_echo_repeat(
+    message: &str,
+    params: &HashMap<String, String>,
+    format: EchoFormat,
+    state: &AppState,
+) -> Response<Body> {
+    let config = &state.config;
+
+    let repeat = match params.get("repeat").map(|repeat| repeat.parse::<usize>()) {
+        None => 1,
+        Some(Ok(repeat)) if repeat > 0 => repeat,
+        Some(_) => return handle_bad_request("repeat must be a positive integer"),
+    };
+    let total = match message.len().checked_mul(repeat) {
+        Some(total) if total <= config.max_echo_bytes => total,
+        _ => {
+            return handle_bad_request(&format!(
+                "repeat would generate more than {} bytes",
+                config.max_echo_bytes
+            ))
+        }
+    };
+    if total <= config.echo_stream_threshold_bytes {
+        return handle_echo(&message.repeat(repeat), format);
+    }
+
+    // The same bytes `handle_echo` would produce, assembled around the repeats.
+    // JSON escaping is per character, so the escaped message repeats cleanly.
+    let escaped = |s: &str| {
+        let quoted = serde_json::Value::from(s).to_string();
+        quoted[1..quoted.len() - 1].to_string()
+    };
+    let (content_type, prefix, chunk, suffix) = match format {
+        EchoFormat::Json => (
+            "application/json",
+            format!("{{\"message\":\"{}", escaped("Echo: ")),
+            escaped(message),
+            format!(
+                "\",\"timestamp\":\"{}\",\"server\":\"rust-http-server/1.0\"}}",
+                timestamp_rfc3339()
+            ),
+        ),
+        EchoFormat::Text => (
+            "text/plain; charset=utf-8",
+            String::new(),
+            message.to_string(),
+            String::new(),
+        ),
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", content_type)
+        .header("Server", "rust-http-server/1.0")
+        .header(hyper::header::VARY, "Accept")
+        .body(
+            RepeatStream::new(prefix.into(), chunk.as_bytes(), repeat, suffix.into())
+                .with_cancel_guard(CancelGuard::new(
+                    &state.stats.cancelled_requests,
+                    format!("GET /echo/{} (response body)", message),
+                ))
+                .boxed(),
+        )
+        .unwrap()
+}
+
+fn handle_echo_stream(
+    message: &str,
+    params: &HashMap<String, String>,
+    range: Option<&str>,
+    state: &AppState,
+) -> Response<Body> {
+    let config = &state.config;
+
+    let count = match params.get("count").map(|count| count.parse::<usize>()) {
+        None => 10,
+        Some(Ok(count)) if count <= config.max_stream_chunks => count,
+        Some(_) => {
+            return handle_bad_request(&format!(
+                "count must be an integer no greater than {}",
+                config.max_stream_chunks
+            ))
+        }
+    };
+    let interval = match params.get("interval_ms").map(|ms| ms.parse::<u64>()) {
+        None => None,
+        Some(Ok(ms)) if ms <= config.max_delay_ms => Some(Duration::from_millis(ms)),
+        Some(_) => {
+            return handle_bad_request(&format!(
+                "interval_ms must be an integer no greater than {}",
+                config.max_delay_ms
+            ))
+        }
+    };
+
+    let chunk = Bytes::from(format!("{}\n", message));
+    let stream = EchoStream::new(chunk, count, interval);
+    let total = stream.len();
+
+    // Resuming clients ask for the rest of the stream from a byte offset
+    let mut response = Response::builder().header("Accept-Ranges", "bytes");
+    let stream = match range::resolve(range, total) {
+        ByteRange::Full => {
+            response = response.status(StatusCode::OK);
+            stream
+        }
+        ByteRange::Partial { start, end } => {
+            response = response.status(StatusCode::PARTIAL_CONTENT).header(
+                "Content-Range",
+                format!("bytes {}-{}/{}", start, end - 1, total),
+            );
+            stream.with_range(start, end)
+        }
+        ByteRange::Unsatisfiable => return handle_range_not_satisfiable(total),
+    };
+
+    response
+        .header("Content-Type", "text/plain; charset=utf-8")
+        // hyper matches declared trailer names case-sensitively against the
+        // (lowercase) trailer field names, so declare it in lowercase
+        .header("Trailer", stream::CHECKSUM_TRAILER)
+        .header("Server", "rust-http-server/1.0")
+        .body(
+            stream
+                .with_cancel_guard(CancelGuard::new(
+                    &state.stats.cancelled_requests,
+                    format!("GET /echo/stream/{} (response body)", message),
+                ))
+                .boxed(),
+        )
+        .unwrap()
+}
+
+// Where `/echo-header/:msg` puts the message
+const ECHO_HEADER: &str = "x-echo";
+
+// `GET /echo-header/:msg`: the decoded message as `X-Echo` and no body.
+// Anything but printable ASCII is refused rather than dropped or escaped, so
+// `%0D%0A` can't start a header of its own.
+fn handle_echo_header(message: &str) -> Response<Body> {
+    let message: Vec<u8> = percent_encoding::percent_decode_str(message).collect();
+    if !message.iter().all(|byte| matches!(byte, b' '..=b'~')) {
+        return handle_bad_request("Message must be printable ASCII to fit in a header");
+    }
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header(ECHO_HEADER, message)
+        .header("Server", "rust-http-server/1.0")
+        .body(full(Vec::new()))
+        .unwrap()
+}
+
+// `count` JSON objects, one per line, produced as the client reads them
+fn handle_ndjson(params: &HashMap<String, String>, state: &AppState) -> Response<Body> {
+    let max = state.config.max_ndjson_lines;
+    let count = match params.get("count").map(|count| count.parse::<usize>()) {
+        None => 10.min(max),
+        Some(Ok(count)) if count <= max => count,
+        Some(_) => {
+            return handle_bad_request(&format!("count must be an integer no greater than {}", max))
+        }
+    };
+
+    let stream = NdjsonStream::new(count).with_cancel_guard(CancelGuard::new(
+        &state.stats.cancelled_requests,
+        "GET /stream/ndjson (response body)".to_string(),
+    ));
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/x-ndjson")
+        .header("Server", "rust-http-server/1.0")
+        .body(stream.boxed())
+        .unwrap()
+}
+
+async fn handle_echo_body<B: RequestBody>(req: Request<B>, state: &AppState) -> Response<Body> {
+    let format = echo_format(req.headers(), &state.config);
+    let body = match read_body(req, state).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+
+    handle_echo(&String::from_utf8_lossy(&body), format)
+}
+
+// `GET` reports maintenance mode; `POST {"enabled": bool}` switches it
+async fn handle_maintenance<B: RequestBody>(req: Request<B>, state: &AppState) -> Response<Body> {
+    let Some(token) = &state.config.admin_token else {
+        return handle_forbidden("Admin API disabled: ADMIN_TOKEN is not set");
+    };
+    if !admin::authorized(req.headers(), token) {
+        return handle_unauthorized();
+    }
+
+    match *req.method() {
+        hyper::Method::GET => {}
+        hyper::Method::POST => {
+            let body = match read_body(req, state).await {
+                Ok(body) => body,
+                Err(response) => return response,
+            };
+            let Some(enabled) = serde_json::from_slice::<serde_json::Value>(&body)
+                .ok()
+                .and_then(|body| body.get("enabled")?.as_bool())
+            else {
+                return handle_bad_request("Expected a JSON body like {\"enabled\": true}");
+            };
+            if state.maintenance.swap(enabled, Ordering::Relaxed) != enabled {
+                println!(
+                    "Maintenance mode {}",
+                    if enabled { "enabled" } else { "disabled" }
+                );
+            }
+        }
+        _ => return handle_method_not_allowed(route_methods("/admin/maintenance")),
+    }
+
+    let response = MaintenanceResponse {
+        maintenance: state.maintenance.load(Ordering::Relaxed),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::OK, &response)
+}
+
+// Starts a graceful shutdown. Repeated or concurrent calls all get 202, but
+// only the first one triggers anything.
+fn handle_admin_shutdown<B>(
+    req: Request<B>,
+    params: &HashMap<String, String>,
+    state: &AppState,
+) -> Response<Body> {
+    let Some(token) = &state.config.admin_token else {
+        return handle_forbidden("Admin API disabled: ADMIN_TOKEN is not set");
+    };
+    if !admin::authorized(req.headers(), token) {
+        return handle_unauthorized();
+    }
+
+    let drain = match params.get("drain").map(String::as_str) {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(_) => return handle_bad_request("drain must be true or false"),
+    };
+    let delay = Duration::from_millis(state.config.shutdown_drain_delay_ms);
+    let message = if drain {
+        if state.begin_drain_then_shutdown(delay) {
+            println!("Shutdown requested via /admin/shutdown?drain=true");
+            format!("Draining for {}ms, then shutting down", delay.as_millis())
+        } else {
+            "Already shutting down".to_string()
+        }
+    } else if state.begin_shutdown() {
+        println!("Shutdown requested via /admin/shutdown");
+        "Shutting down".to_string()
+    } else {
+        "Already shutting down".to_string()
+    };
+    let response = JsonResponse {
+        message,
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::ACCEPTED, &response)
+}
+
+// Snapshot of the runtime's metrics, for telling whether the runtime itself
+// is the bottleneck under load
+fn handle_debug_runtime() -> Response<Body> {
+    let handle = tokio::runtime::Handle::current();
+    let metrics = handle.metrics();
+    let worker_stats = (0..metrics.num_workers())
+        .map(|index| WorkerMetrics {
+            index,
+            busy_ms: metrics.worker_total_busy_duration(index).as_millis() as u64,
+            park_count: metrics.worker_park_count(index),
+        })
+        .collect();
+    let flavor = match handle.runtime_flavor() {
+        tokio::runtime::RuntimeFlavor::CurrentThread => "current_thread",
+        tokio::runtime::RuntimeFlavor::MultiThread => "multi_thread",
+        _ => "other",
+    };
+    let response = RuntimeMetrics {
+        flavor: flavor.to_string(),
+        workers: metrics.num_workers(),
+        alive_tasks: metrics.num_alive_tasks(),
+        global_queue_depth: metrics.global_queue_depth(),
+        worker_stats,
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::OK, &response)
+}
+
+// The requests `state.recent` holds, oldest first; empty unless
+// `recent_requests` is set. This request isn't among them yet.
+fn handle_debug_recent(state: &AppState) -> Response<Body> {
+    let response = RecentResponse {
+        capacity: state.recent.as_ref().map_or(0, RecentRequests::capacity),
+        requests: state
+            .recent
+            .as_ref()
+            .map(RecentRequests::snapshot)
+            .unwrap_or_default(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::OK, &response)
+}
+
+// Reflects the request back as JSON, whatever its method
+async fn handle_debug_echo<B: RequestBody>(req: Request<B>, state: &AppState) -> Response<Body> {
+    let method = req.method().to_string();
+    let uri = req.uri().to_string();
+    let version = format!("{:?}", req.version());
+    let headers = dump_headers(req.headers());
+    let body = match read_body(req, state).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+
+    let (body, body_encoding) = match String::from_utf8(body.to_vec()) {
+        Ok(body) => (body, "utf-8"),
+        Err(_) => (
+            base64::engine::general_purpose::STANDARD.encode(&body[..]),
+            "base64",
+        ),
+    };
+    let response = ReflectedRequest {
+        method,
+        uri,
+        version,
+        headers,
+        body,
+        body_encoding,
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::OK, &response)
+}
+
+// A header value as text for logs and reflected JSON. Values aren't always
+// UTF-8 (`obs-text` bytes are legal on the wire), so UTF-8 runs are kept and
+// any other byte becomes `\xNN` rather than vanishing into U+FFFD.
+pub fn header_text(value: &hyper::header::HeaderValue) -> Cow<'_, str> {
+    if let Ok(text) = value.to_str() {
+        return Cow::Borrowed(text);
+    }
+    let mut text = String::with_capacity(value.len());
+    for chunk in value.as_bytes().utf8_chunks() {
+        text.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            text.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    Cow::Owned(text)
+}
+
+// Headers as a JSON object keyed by (lowercase) name
+fn dump_headers(headers: &hyper::HeaderMap) -> serde_json::Map<String, serde_json::Value> {
+    headers
+        .keys()
+        .map(|name| {
+            let mut values = headers
+                .get_all(name)
+                .iter()
+                .map(|value| header_text(value).into_owned());
+            let value = match (values.next(), values.next()) {
+                (Some(only), None) => serde_json::Value::from(only),
+                (first, second) => first
+                    .into_iter()
+                    .chain(second)
+                    .chain(values)
+                    .map(serde_json::Value::from)
+                    .collect(),
+            };
+            (name.as_str().to_string(), value)
+        })
+        .collect()
+}
+
+async fn handle_kv<B: RequestBody>(req: Request<B>, key: &str, state: &AppState) -> Response<Body> {
+    let key = percent_encoding::percent_decode_str(key).decode_utf8_lossy();
+    if key.is_empty() {
+        return handle_bad_request("Key must not be empty");
+    }
+    let store = &state.kv;
+
+    match *req.method() {
+        hyper::Method::GET => match store.get(&key) {
+            Some(value) => kv_value_response(StatusCode::OK, value),
+            None => handle_key_not_found(&key),
+        },
+        hyper::Method::DELETE => {
+            if store.delete(&key) {
+                Response::builder()
+                    .status(StatusCode::NO_CONTENT)
+                    .header("Server", "rust-http-server/1.0")
+                    .body(full(Bytes::new()))
+                    .unwrap()
+            } else {
+                handle_key_not_found(&key)
+            }
+        }
+        hyper::Method::PUT | hyper::Method::PATCH => {
+            let is_put = req.method() == hyper::Method::PUT;
+            let body = match read_body(req, state).await {
+                Ok(body) => body,
+                Err(response) => return response,
+            };
+            let Ok(value) = String::from_utf8(body.to_vec()) else {
+                return handle_bad_request("Value must be valid UTF-8");
+            };
+
+            if is_put {
+                let status = if store.put(&key, value.clone()) {
+                    StatusCode::CREATED
+                } else {
+                    StatusCode::OK
+                };
+                return kv_value_response(status, value);
+            }
+            match store.patch(&key, &value) {
+                Ok(value) => kv_value_response(StatusCode::OK, value),
+                Err(PatchError::NotFound) => handle_key_not_found(&key),
+                Err(PatchError::NotJson) => {
+                    let response = JsonResponse {
+                        message: format!("Value of {} is not JSON and can't be patched", key),
+                        timestamp: timestamp_rfc3339(),
+                        server: "rust-http-server/1.0".to_string(),
+                    };
+                    json_response(StatusCode::CONFLICT, &response)
+                }
+                Err(PatchError::InvalidPatch) => {
+                    handle_bad_request("PATCH body must be a JSON merge patch")
+                }
+            }
+        }
+        _ => handle_method_not_allowed(route_methods("/kv/:key")),
+    }
+}
+
+// A stored value as the response body; JSON values are labelled as such
+fn kv_value_response(status: StatusCode, value: String) -> Response<Body> {
+    let content_type = if serde_json::from_str::<serde::de::IgnoredAny>(&value).is_ok() {
+        "application/json"
+    } else {
+        "text/plain; charset=utf-8"
+    };
+    Response::builder()
+        .status(status)
+        .header("Content-Type", content_type)
+        .header("Server", "rust-http-server/1.0")
+        .body(full(value))
+        .unwrap()
+}
+
+fn handle_key_not_found(key: &str) -> Response<Body> {
+    let response = JsonResponse {
+        message: format!("Key not found: {}", key),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::NOT_FOUND, &response)
+}
+
+// Reads a request body of at most `max_body_bytes`, or the error response
+// to send instead
+async fn read_body<B: RequestBody>(
+    req: Request<B>,
+    state: &AppState,
+) -> Result<BufferedBody, Response<Body>> {
+    let config = &state.config;
+    let coding = match content_coding(req.headers()) {
+        Ok(coding) => coding,
+        Err(coding) => {
+            return Err(handle_unsupported_media_type(&format!(
+                "Content-Encoding {:?} is not supported; only gzip is",
+                coding
+            )))
+        }
+    };
+
+    // A declared length over the limit is refused before reading anything,
+    // rather than after reading `max_body_bytes` of it (or waiting on a
+    // body that never comes). The rest of the body is never read, so the
+    // connection can't be reused either.
+    let declared = req
+        .headers()
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+    if declared.is_some_and(|length| length > config.max_body_bytes as u64) {
+        let mut response = handle_payload_too_large(config);
+        response.headers_mut().insert(
+            hyper::header::CONNECTION,
+            hyper::header::HeaderValue::from_static("close"),
+        );
+        return Err(response);
+    }
+
+    let mut verifier = match config
+        .verify_body_digest
+        .then(|| ExpectedDigests::from_headers(req.headers()))
+        .transpose()
+    {
+        Ok(expected) => expected.flatten().map(BodyVerifier::new),
+        Err(message) => return Err(handle_bad_request(&message)),
+    };
+
+    // Reserved before reading: the declared length, or for a chunked or
+    // gzipped body the most it may grow to
+    let reservation = match &state.body_budget {
+        Some(budget) => {
+            let chunked = req.headers().contains_key(hyper::header::TRANSFER_ENCODING);
+            let needed = match declared {
+                Some(_) if coding == ContentCoding::Gzip => config.max_body_bytes as u64,
+                Some(length) => length,
+                None if chunked => config.max_body_bytes as u64,
+                None => 0,
+            };
+            match budget.reserve(needed).await {
+                Ok(reservation) => Some(reservation),
+                Err(backlog) => {
+                    state
+                        .stats
+                        .body_budget_rejections
+                        .fetch_add(1, Ordering::Relaxed);
+                    let mut response = backpressure(
+                        "Too many request bodies in flight; please retry later",
+                        backlog,
+                    );
+                    response.headers_mut().insert(
+                        hyper::header::CONNECTION,
+                        hyper::header::HeaderValue::from_static("close"),
+                    );
+                    return Err(response);
+                }
+            }
+        }
+        None => None,
+    };
+
+    // A client that sends its headers and then stalls would otherwise hold
+    // its connection (and its reservation) until the request timeout
+    let collect = async {
+        let mut body = std::pin::pin!(Limited::new(req.into_body(), config.max_body_bytes));
+        let mut bytes = Vec::new();
+        while let Some(frame) = body.frame().await {
+            if let Ok(data) = frame?.into_data() {
+                if let Some(verifier) = &mut verifier {
+                    verifier.update(&data);
+                }
+                bytes.extend_from_slice(&data);
+            }
+        }
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(Bytes::from(bytes))
+    };
+    let collected = if config.body_read_timeout_ms > 0 {
+        match tokio::time::timeout(Duration::from_millis(config.body_read_timeout_ms), collect)
+            .await
+        {
+            Ok(collected) => collected,
+            Err(_) => {
+                let mut response = handle_body_read_timeout();
+                response.headers_mut().insert(
+                    hyper::header::CONNECTION,
+                    hyper::header::HeaderValue::from_static("close"),
+                );
+                return Err(response);
+            }
+        }
+    } else {
+        collect.await
+    };
+    let bytes = match collected {
+        Ok(bytes) => bytes,
+        Err(err) if err.is::<LengthLimitError>() => return Err(handle_payload_too_large(config)),
+        Err(_) => return Err(handle_bad_request("Failed to read request body")),
+    };
+    if let Some(algorithm) = verifier.and_then(BodyVerifier::mismatch) {
+        return Err(handle_bad_request(&format!(
+            "Request body does not match its {} checksum",
+            algorithm
+        )));
+    }
+    // The limit applies to the decompressed size too, and is enforced while
+    // inflating, so a tiny upload can't expand into gigabytes
+    let bytes = match coding {
+        ContentCoding::Identity => bytes,
+        ContentCoding::Gzip => match inflate::gunzip(&bytes, config.max_body_bytes) {
+            Ok(decompressed) => Bytes::from(decompressed),
+            Err(InflateError::TooLarge) => return Err(handle_payload_too_large(config)),
+            Err(InflateError::Invalid) => {
+                return Err(handle_bad_request("Request body is not valid gzip"))
+            }
+        },
+    };
+    Ok(BufferedBody::new(bytes, reservation))
+}
+
+// How a request body is encoded, from its Content-Encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContentCoding {
+    Identity,
+    Gzip,
+}
+
+// `identity` entries are ignored; gzip may be applied once. Anything else
+// comes back as the coding that can't be undone.
+fn content_coding(headers: &hyper::HeaderMap) -> Result<ContentCoding, String> {
+    let mut result = ContentCoding::Identity;
+    for coding in headers
+        .get_all(hyper::header::CONTENT_ENCODING)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or("\u{fffd}").split(','))
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty() && !coding.eq_ignore_ascii_case("identity"))
+    {
+        let gzip = coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip");
+        if !gzip || result == ContentCoding::Gzip {
+            return Err(coding.to_string());
+        }
+        result = ContentCoding::Gzip;
+    }
+    Ok(result)
+}
+
+async fn handle_delay(ms: &str, config: &Config) -> Response<Body> {
+    let ms: u64 = match ms.parse() {
+        Ok(ms) => ms,
+        Err(_) => return handle_bad_request("Delay must be a non-negative integer"),
+    };
+    if ms > config.max_delay_ms {
+        return handle_bad_request(&format!(
+            "Delay exceeds maximum of {}ms",
+            config.max_delay_ms
+        ));
+    }
+
+    tokio::time::sleep(Duration::from_millis(ms)).await;
+
+    let response = JsonResponse {
+        message: format!("Delayed {}ms", ms),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::OK, &response)
+}
+
+// Like `/delay/:ms`, but the wait is blocking work (`std::thread::sleep`)
+// standing in for CPU-bound or synchronous code. Run on an async worker, it
+// would stall every connection scheduled there; `spawn_blocking` moves it
+// to Tokio's blocking pool instead. Once started it can't be cancelled, so
+// it runs to the end even if the client goes away.
+async fn handle_debug_block(ms: &str, config: &Config) -> Response<Body> {
+    let ms: u64 = match ms.parse() {
+        Ok(ms) => ms,
+        Err(_) => return handle_bad_request("Block time must be a non-negative integer"),
+    };
+    if ms > config.max_delay_ms {
+        return handle_bad_request(&format!(
+            "Block time exceeds maximum of {}ms",
+            config.max_delay_ms
+        ));
+    }
+
+    let blocked = tokio::task::spawn_blocking(move || {
+        std::thread::sleep(Duration::from_millis(ms));
+    })
+    .await;
+    if blocked.is_err() {
+        return handle_internal_error();
+    }
+
+    let response = JsonResponse {
+        message: format!("Blocked {}ms", ms),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::OK, &response)
+}
+
+fn handle_error(code: &str) -> Response<Body> {
+    let status = match code.parse::<u16>() {
+        Ok(code @ 400..=599) => StatusCode::from_u16(code).ok(),
+        _ => None,
+    };
+    let Some(status) = status else {
+        return handle_bad_request("Error code must be an HTTP status between 400 and 599");
+    };
+
+    let response = JsonResponse {
+        message: status
+            .canonical_reason()
+            .unwrap_or("Unknown Error")
+            .to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(status, &response)
+}
+
+fn handle_range_not_satisfiable(total: usize) -> Response<Body> {
+    let response = JsonResponse {
+        message: format!("Range not satisfiable: the stream is {} bytes", total),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    let mut response = json_response(StatusCode::RANGE_NOT_SATISFIABLE, &response);
+    response.headers_mut().insert(
+        "Content-Range",
+        hyper::header::HeaderValue::from_str(&format!("bytes */{}", total)).unwrap(),
+    );
+    response
+}
+
+// 308 rather than 301, so a redirected POST stays a POST with its body
+fn handle_permanent_redirect(location: &str) -> Response<Body> {
+    let response = JsonResponse {
+        message: format!("Moved to {}", location),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    let mut response = json_response(StatusCode::PERMANENT_REDIRECT, &response);
+    if let Ok(location) = hyper::header::HeaderValue::from_str(location) {
+        response
+            .headers_mut()
+            .insert(hyper::header::LOCATION, location);
+    }
+    response
+}
+
+fn handle_bad_request(message: &str) -> Response<Body> {
+    let response = JsonResponse {
+        message: message.to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::BAD_REQUEST, &response)
+}
+
+fn handle_not_implemented(message: &str) -> Response<Body> {
+    let response = JsonResponse {
+        message: message.to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::NOT_IMPLEMENTED, &response)
+}
+
+fn handle_payload_too_large(config: &Config) -> Response<Body> {
+    let response = JsonResponse {
+        message: format!(
+            "Request body exceeds maximum of {} bytes",
+            config.max_body_bytes
+        ),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::PAYLOAD_TOO_LARGE, &response)
+}
+
+fn handle_idempotency_key_reused() -> Response<Body> {
+    let response = JsonResponse {
+        message: "Idempotency-Key was already used with a different request body".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::UNPROCESSABLE_ENTITY, &response)
+}
+
+fn handle_idempotency_key_in_progress() -> Response<Body> {
+    let response = JsonResponse {
+        message: "A request with this Idempotency-Key is still being processed".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    let mut response = json_response(StatusCode::CONFLICT, &response);
+    response.headers_mut().insert(
+        hyper::header::RETRY_AFTER,
+        hyper::header::HeaderValue::from_static("1"),
+    );
+    response
+}
+
+fn handle_not_acceptable(message: &str) -> Response<Body> {
+    let response = JsonResponse {
+        message: message.to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::NOT_ACCEPTABLE, &response)
+}
+
+// Names the one coding the server can undo, as RFC 7694 suggests
+fn handle_unsupported_media_type(message: &str) -> Response<Body> {
+    let response = JsonResponse {
+        message: message.to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    let mut response = json_response(StatusCode::UNSUPPORTED_MEDIA_TYPE, &response);
+    response.headers_mut().insert(
+        hyper::header::ACCEPT_ENCODING,
+        hyper::header::HeaderValue::from_static("gzip"),
+    );
+    response
+}
+
+fn handle_too_many_requests(retry_after: Duration) -> Response<Body> {
+    let response = JsonResponse {
+        message: "Too Many Requests".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    let mut response = json_response(StatusCode::TOO_MANY_REQUESTS, &response);
+    let retry_after_secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    response
+        .headers_mut()
+        .insert("Retry-After", retry_after_secs.into());
+    response
+}
+
+// 200 whenever the process can answer at all, warming up or not: what an
+// orchestrator should restart on, where `/readyz` only decides traffic
+fn handle_livez() -> Response<Body> {
+    let response = JsonResponse {
+        message: "Alive".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::OK, &response)
+}
+
+// 200 once warm-up is done, 503 before. Unlike `/health`, which only says
+// the process is up, this is what a load balancer should gate traffic on.
+fn handle_readyz(state: &AppState) -> Response<Body> {
+    if state.shutting_down.load(Ordering::Relaxed) {
+        return service_unavailable("Shutting down", 1);
+    }
+    if !state.ready.load(Ordering::Relaxed) {
+        return service_unavailable("Warming up", 1);
+    }
+    let response = JsonResponse {
+        message: "Ready".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::OK, &response)
+}
+
+fn handle_method_not_allowed(methods: &[Method]) -> Response<Body> {
+    let response = JsonResponse {
+        message: "Method Not Allowed".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    let mut response = json_response(StatusCode::METHOD_NOT_ALLOWED, &response);
+    response
+        .headers_mut()
+        .insert(hyper::header::ALLOW, cors::method_list(methods));
+    response
+}
+
+// The answer to a HEAD request: the headers of the GET response, including
+// the length its body would have had, and no body
+fn head_response(response: Response<Body>) -> Response<Body> {
+    let (mut parts, body) = response.into_parts();
+    if let Some(length) = body.size_hint().exact() {
+        parts
+            .headers
+            .entry(hyper::header::CONTENT_LENGTH)
+            .or_insert_with(|| length.into());
+    }
+    Response::from_parts(parts, full(Bytes::new()))
+}
+
+// `OPTIONS` outside a CORS preflight (RFC 9110 section 9.3.7)
+fn handle_options(methods: &[Method]) -> Response<Body> {
+    let mut methods = methods.to_vec();
+    methods.push(Method::OPTIONS);
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header(hyper::header::ALLOW, cors::method_list(&methods))
+        .header("Server", "rust-http-server/1.0")
+        .body(full(Bytes::new()))
+        .unwrap()
+}
+
+fn handle_unauthorized() -> Response<Body> {
+    let response = JsonResponse {
+        message: "Unauthorized".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    let mut response = json_response(StatusCode::UNAUTHORIZED, &response);
+    response.headers_mut().insert(
+        hyper::header::WWW_AUTHENTICATE,
+        hyper::header::HeaderValue::from_static("Bearer"),
+    );
+    response
+}
+
+fn handle_forbidden(message: &str) -> Response<Body> {
+    let response = JsonResponse {
+        message: message.to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::FORBIDDEN, &response)
+}
+
+fn handle_body_read_timeout() -> Response<Body> {
+    let response = JsonResponse {
+        message: "Timed out reading request body".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::REQUEST_TIMEOUT, &response)
+}
+
+fn handle_gateway_timeout() -> Response<Body> {
+    let response = JsonResponse {
+        message: "Request deadline exceeded".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::GATEWAY_TIMEOUT, &response)
+}
+
+fn handle_upgrade_required() -> Response<Body> {
+    let response = JsonResponse {
+        message: "WebSocket upgrade required".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    let mut response = json_response(StatusCode::UPGRADE_REQUIRED, &response);
+    let headers = response.headers_mut();
+    headers.insert(
+        hyper::header::UPGRADE,
+        hyper::header::HeaderValue::from_static("websocket"),
+    );
+    headers.insert(
+        websocket::VERSION_HEADER,
+        hyper::header::HeaderValue::from_static("13"),
+    );
+    response
+}
+
+fn handle_unknown_host() -> Response<Body> {
+    let response = JsonResponse {
+        message: "Unknown host".to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    json_response(StatusCode::NOT_FOUND, &response)
+}
+
+// Built by hand rather than through `json_response`, which relies on it
+fn handle_internal_error() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .header("Content-Type", "application/json")
+        .header("Server", "rust-http-server/1.0")
+        .body(full(r#"{"message":"Internal Server Error"}"#))
+        .unwrap()
+}
+
+// Suggestions are given with `base_path` in front, as clients must send them
+fn handle_not_found(path: &str, lang: &'static str, site: Site, config: &Config) -> Response<Body> {
+    let base_path = config.base_path.as_str();
+    let routes: Vec<&'static str> = ROUTES
+        .iter()
+        .map(|route| route.pattern)
+        .filter(|route| site.serves(route) && config.route_toggles.enabled(route))
+        .collect();
+    let response = NotFoundResponse {
+        message: i18n::localize(Message::NotFound, lang).to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+        suggestions: suggest::suggest(path, &routes)
+            .into_iter()
+            .map(|route| format!("{}{}", base_path.trim_end_matches('/'), route))
+            .collect(),
+    };
+    with_language(json_response(StatusCode::NOT_FOUND, &response), lang)
+}
+
+// Where a request falls in `total_requests`, counting itself. `/stats`
+// reports this rather than reloading the counter, so its own request is
+// always included and requests that arrive while it is being built are not.
+#[derive(Clone, Copy)]
+struct RequestNumber(u64);
+
+// Marks responses whose JSON the server generated itself
+#[derive(Clone, Copy)]
+struct ServerJson;
+
+tokio::task_local! {
+    // `MAX_RESPONSE_SIZE` for the request being handled, so `json_response`
+    // can enforce it without every handler passing the config down
+    static MAX_RESPONSE_SIZE: usize;
+}
+
+// Collects serialized JSON, refusing to grow past `limit` bytes
+struct CappedWriter {
+    buf: Vec<u8>,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl std::io::Write for CappedWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        if self.buf.len() + data.len() > self.limit {
+            self.exceeded = true;
+            return Err(std::io::Error::other("response too large"));
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Helper function. Serialization stops as soon as the body would pass
+// `MAX_RESPONSE_SIZE`, so a runaway response is never built in full.
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Body> {
+    let limit = match MAX_RESPONSE_SIZE.try_with(|&limit| limit) {
+        Ok(limit) if limit > 0 => limit,
+        _ => usize::MAX,
+    };
+    let mut writer = CappedWriter {
+        buf: Vec::new(),
+        limit,
+        exceeded: false,
+    };
+    let (status, json) = match serde_json::to_writer(&mut writer, body) {
+        Ok(()) => (status, writer.buf),
+        Err(_) if writer.exceeded => {
+            eprintln!(
+                "Response would exceed MAX_RESPONSE_SIZE of {} bytes; sent 500 instead",
+                limit
+            );
+            let response = JsonResponse {
+                message: format!("Response would exceed the maximum of {} bytes", limit),
+                timestamp: timestamp_rfc3339(),
+                server: "rust-http-server/1.0".to_string(),
+            };
+            // Well under the smallest limit allowed
+            let json = serde_json::to_vec(&response).unwrap_or_default();
+            (StatusCode::INTERNAL_SERVER_ERROR, json)
+        }
+        Err(err) => {
+            eprintln!("Failed to serialize response: {}", err);
+            return handle_internal_error();
+        }
+    };
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("Server", "rust-http-server/1.0")
+        .extension(ServerJson)
+        .body(full(json))
+        .unwrap()
+}
+
+// Applies the configured field case and envelope. Only responses built by
+// `json_response`; stored KV values are user data.
+async fn shape_json(response: Response<Body>, config: &Config) -> Response<Body> {
+    if response.extensions().get::<ServerJson>().is_none() {
+        return response;
+    }
+    let response = if config.json_envelope {
+        envelope::apply(response).await
+    } else {
+        response
+    };
+    json_case::apply(response, config.json_field_case).await
+}
+
+// Marks a localized response with the language it was served in
+fn with_language(mut response: Response<Body>, lang: &'static str) -> Response<Body> {
+    let headers = response.headers_mut();
+    headers.insert(
+        hyper::header::CONTENT_LANGUAGE,
+        hyper::header::HeaderValue::from_static(lang),
+    );
+    headers.append(
+        hyper::header::VARY,
+        hyper::header::HeaderValue::from_static("Accept-Language"),
+    );
+    response
+}
+
+// The response for a request whose body can't be framed safely, if any.
+// The body's extent is unknown either way, so the connection can't be
+// reused.
+fn reject_framing(headers: &hyper::HeaderMap) -> Option<Response<Body>> {
+    let mut response = if let Some(problem) = conflicting_framing(headers) {
+        handle_bad_request(problem)
+    } else {
+        match transfer_codings(headers) {
+            Ok(()) => return None,
+            Err(TransferCodingError::Unsupported(coding)) => handle_not_implemented(&format!(
+                "Transfer-Encoding {:?} is not supported; only chunked is",
+                coding
+            )),
+            Err(TransferCodingError::RepeatedChunked) => {
+                handle_bad_request("Transfer-Encoding must apply chunked only once")
+            }
+        }
+    };
+    response.headers_mut().insert(
+        hyper::header::CONNECTION,
+        hyper::header::HeaderValue::from_static("close"),
+    );
+    Some(response)
+}
+
+// Whether the request's `Connection` header lists `keep-alive`, which an
+// HTTP/1.0 client must send for its connection to outlive the response
+fn asks_keep_alive(headers: &hyper::HeaderMap) -> bool {
+    headers
+        .get_all(hyper::header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|token| token.trim().eq_ignore_ascii_case("keep-alive"))
+}
+
+// States what becomes of an HTTP/1.0 connection after `response`, rather
+// than leaving it to hyper's defaults: `keep-alive` only when the client
+// asked for it and `HTTP1_0_KEEP_ALIVE` allows it, `close` otherwise. A
+// response that already closes the connection keeps doing so, as does a
+// streamed one: HTTP/1.0 has no chunked encoding, so its end is the close.
+// The response is marked HTTP/1.0 too; hyper would otherwise take it for an
+// HTTP/1.1 one and swap a `close` for the `keep-alive` the client asked for.
+fn set_http1_0_connection(response: &mut Response<Body>, keep_alive: bool) {
+    let keep_alive = keep_alive && response.body().size_hint().exact().is_some();
+    *response.version_mut() = hyper::Version::HTTP_10;
+    let headers = response.headers_mut();
+    let closing = headers
+        .get(hyper::header::CONNECTION)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"close"));
+    let value = if keep_alive && !closing {
+        "keep-alive"
+    } else {
+        "close"
+    };
+    headers.insert(
+        hyper::header::CONNECTION,
+        hyper::header::HeaderValue::from_static(value),
+    );
+}
+
+// The response for a request target only a proxy takes, if any. An
+// absolute-form target (`GET http://other.example/ HTTP/1.1`) names a
+// resource on whichever origin it gives, and an authority-form one
+// (`example.com:443`) no resource at all; this server answers for neither,
+// so both are refused rather than routed on their path. CONNECT, the method
+// authority-form belongs to, was refused with the other proxy methods.
+fn reject_target(uri: &hyper::Uri) -> Option<Response<Body>> {
+    if uri.scheme().is_some() {
+        Some(handle_bad_request(
+            "Absolute-form request targets are only accepted by proxies",
+        ))
+    } else if uri.authority().is_some() {
+        Some(handle_bad_request(
+            "Authority-form request targets are only accepted by proxies",
+        ))
+    } else {
+        None
+    }
+}
+
+// The response for a method refused before routing, if any. TRACE would
+// reflect headers such as cookies back to a script (cross-site tracing), and
+// this isn't a proxy, so neither it nor CONNECT is served on any path; a
+// method outside the standard ones is one the server doesn't implement,
+// unless it reaches a route that takes any method at all.
+fn reject_method(method: &Method, path: &str, base_path: &str) -> Option<Response<Body>> {
+    let path = strip_base_path(path, base_path);
+    let allowed = || path.map(methods_for).unwrap_or_default();
+    if method == Method::TRACE {
+        Some(handle_method_not_allowed(&allowed()))
+    } else if method == Method::CONNECT {
+        // The client may already be sending what it meant to tunnel
+        let mut response = handle_method_not_allowed(&allowed());
+        response.headers_mut().insert(
+            hyper::header::CONNECTION,
+            hyper::header::HeaderValue::from_static("close"),
+        );
+        Some(response)
+    } else if [
+        Method::GET,
+        Method::HEAD,
+        Method::POST,
+        Method::PUT,
+        Method::PATCH,
+        Method::DELETE,
+        Method::OPTIONS,
+    ]
+    .contains(method)
+        || path.is_some_and(|path| accepts(path, method))
+    {
+        None
+    } else {
+        Some(handle_not_implemented(&format!(
+            "Method {} is not implemented",
+            method
+        )))
+    }
+}
+
+// Why a Transfer-Encoding list was refused
+enum TransferCodingError {
+    // A coding other than chunked, such as gzip
+    Unsupported(String),
+    RepeatedChunked,
+}
+
+// Checks that Transfer-Encoding, across all its header lines, is exactly
+// `chunked` if present. hyper refuses lists that don't end in chunked
+// before they get here, but passes `gzip, chunked`, which would hand the
+// handler a still-compressed body.
+fn transfer_codings(headers: &hyper::HeaderMap) -> Result<(), TransferCodingError> {
+    let mut chunked = false;
+    for coding in headers
+        .get_all(hyper::header::TRANSFER_ENCODING)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or("\u{fffd}").split(','))
+        .map(str::trim)
+        .filter(|coding| !coding.is_empty())
+    {
+        if !coding.eq_ignore_ascii_case("chunked") {
+            return Err(TransferCodingError::Unsupported(coding.to_string()));
+        }
+        if chunked {
+            return Err(TransferCodingError::RepeatedChunked);
+        }
+        chunked = true;
+    }
+    Ok(())
+}
+
+// Rejects requests whose body length could be read more than one way,
+// which is what request smuggling between proxies relies on. hyper already
+// folds repeated identical Content-Length headers into one, as RFC 9112
+// allows, so anything still plural here disagrees.
+fn conflicting_framing(headers: &hyper::HeaderMap) -> Option<&'static str> {
+    let content_lengths: Vec<&str> = headers
+        .get_all(hyper::header::CONTENT_LENGTH)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or("").split(','))
+        .collect();
+    if content_lengths.len() > 1 {
+        return Some("Multiple Content-Length values");
+    }
+    if !content_lengths.is_empty() && headers.contains_key(hyper::header::TRANSFER_ENCODING) {
+        return Some("Content-Length and Transfer-Encoding are mutually exclusive");
+    }
+    // CGI-style gateways read `_` and `-` in header names alike, so to them
+    // these are the framing headers this server just ignored
+    if headers
+        .keys()
+        .any(|name| matches!(name.as_str(), "transfer_encoding" | "content_length"))
+    {
+        return Some("Framing header names must use hyphens, not underscores");
+    }
+    None
+}
+
+// Picks the echo format from the Accept header: whichever of text/plain and
+// application/json it ranks higher, or the configured default on a tie
+fn echo_format(headers: &hyper::HeaderMap, config: &Config) -> EchoFormat {
+    let text = accept_quality(headers, "text", "plain");
+    let json = accept_quality(headers, "application", "json");
+    if text > json {
+        EchoFormat::Text
+    } else if json > text {
+        EchoFormat::Json
+    } else {
+        config.echo_format
+    }
+}
+
+// The q value Accept gives `type/subtype`, taken from its most specific
+// matching range. No Accept header accepts everything.
+fn accept_quality(headers: &hyper::HeaderMap, type_: &str, subtype: &str) -> f32 {
+    let mut values = headers
+        .get_all(hyper::header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .peekable();
+    if values.peek().is_none() {
+        return 1.0;
+    }
+
+    // (specificity, q) of the best matching range so far
+    let mut best: Option<(u8, f32)> = None;
+    for range in values.flat_map(|value| value.split(',')) {
+        let mut params = range.split(';');
+        let Some((range_type, range_subtype)) = params.next().unwrap_or("").trim().split_once('/')
+        else {
+            continue;
+        };
+        let specificity = match (range_type, range_subtype) {
+            (t, s) if t.eq_ignore_ascii_case(type_) && s.eq_ignore_ascii_case(subtype) => 2,
+            (t, "*") if t.eq_ignore_ascii_case(type_) => 1,
+            ("*", "*") => 0,
+            _ => continue,
+        };
+        let q = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if best.is_none_or(|(best_specificity, _)| specificity > best_specificity) {
+            best = Some((specificity, q));
+        }
+    }
+
+    best.map_or(0.0, |(_, q)| q)
+}
+
+// Parses a query string into decoded key/value pairs; later duplicates win.
+// More than `MAX_QUERY_PARAMS` pairs, or a key or value longer than
+// `MAX_QUERY_PARAM_BYTES` before decoding, is an error naming the limit, and
+// is found before anything is decoded or hashed.
+fn parse_query(query: Option<&str>, config: &Config) -> Result<HashMap<String, String>, String> {
+    let decode = |s: &str| {
+        percent_encoding::percent_decode_str(&s.replace('+', " "))
+            .decode_utf8_lossy()
+            .into_owned()
+    };
+
+    let pairs: Vec<_> = query
+        .unwrap_or("")
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .take(config.max_query_params.saturating_add(1))
+        .collect();
+    if pairs.len() > config.max_query_params {
+        return Err(format!(
+            "Too many query parameters; at most {} are allowed",
+            config.max_query_params
+        ));
+    }
+    let max_bytes = config.max_query_param_bytes;
+    if pairs
+        .iter()
+        .any(|(key, value)| key.len() > max_bytes || value.len() > max_bytes)
+    {
+        return Err(format!(
+            "Query parameter too long; keys and values are limited to {} bytes",
+            max_bytes
+        ));
+    }
+    Ok(pairs
+        .into_iter()
+        .map(|(key, value)| (decode(key), decode(value)))
+        .collect())
+}
+
+// The answer to every request on a connection shed by `SLOW_START_MS`: come
+// back in a second, when the ramp has let the rate up, on a new connection
+async fn handle_slow_start(config: &Config) -> Response<Body> {
+    let mut response = service_unavailable("Starting up; try again shortly", 1);
+    response.headers_mut().insert(
+        hyper::header::CONNECTION,
+        hyper::header::HeaderValue::from_static("close"),
+    );
+    shape_json(response, config).await
+}
+
+// Binds `addr` and serves it with `config` until `shutdown` resolves
+pub async fn run(
+    addr: SocketAddr,
+    config: Config,
+    shutdown: impl Future<Output = ()>,
+) -> std::io::Result<()> {
+    let listener = bind(addr)?;
+    serve(listener, AppState::new(config), shutdown).await
+}
+
+// The multi-threaded runtime `main` serves on, with every thread it starts
+// named `worker_thread_name-<n>` so profilers and debuggers can tell them
+// apart. Blocking-pool threads share the numbering.
+pub fn runtime(config: &Config) -> std::io::Result<tokio::runtime::Runtime> {
+    let prefix = config.worker_thread_name.clone();
+    let next = AtomicU64::new(0);
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .thread_name_fn(move || format!("{}-{}", prefix, next.fetch_add(1, Ordering::Relaxed)))
+        .build()
+}
+
+// Whether a connection failed because the peer went away: a broken pipe
+// or reset while writing the response
+fn is_client_disconnect(err: &hyper::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io.kind(),
+                std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::ConnectionReset
+            );
+        }
+        source = err.source();
+    }
+    false
+}
+
+// How long the accept loop waits after a transient accept error, growing
+// from the first to the second while errors keep coming
+const ACCEPT_BACKOFF_MIN: Duration = Duration::from_millis(10);
+const ACCEPT_BACKOFF_MAX: Duration = Duration::from_millis(100);
+
+// Accept loop: serves every connection on its own task until `shutdown`
+// resolves, then waits for in-flight connections to finish. Transient
+// accept errors are logged and retried; any other ends it with the error.
+pub async fn serve<L: Listener>(
+    listener: L,
+    state: AppState,
+    shutdown: impl Future<Output = ()>,
+) -> std::io::Result<()> {
+    // Every connection task holds a receiver; closing them all means drained.
+    // hyper-util's GracefulShutdown can't watch connections with upgrades.
+    let (drain, _) = watch::channel(());
+    tokio::pin!(shutdown);
+    let (stop_flusher, flusher_stopped) = oneshot::channel();
+    let flusher = state
+        .spans
+        .as_ref()
+        .map(|spans| spans.spawn_flusher(flusher_stopped));
+    let sweeper = state
+        .rate_limiter
+        .as_ref()
+        .filter(|_| state.config.rate_limit_sweep_interval_ms > 0)
+        .map(|limiter| {
+            limiter.spawn_sweeper(
+                Duration::from_millis(state.config.rate_limit_sweep_interval_ms),
+                Duration::from_millis(state.config.rate_limit_idle_ttl_ms),
+            )
+        });
+
+    let mut slow_start = (state.config.slow_start_ms > 0).then(|| {
+        SlowStart::new(
+            Instant::now(),
+            Duration::from_millis(state.config.slow_start_ms),
+            state.config.slow_start_accept_per_sec,
+        )
+    });
+
+    // Numbers connections for their tracing spans, in accept order
+    let mut next_connection: u64 = 0;
+    // Doubles while accept keeps failing, so running out of descriptors
+    // doesn't become a busy loop
+    let mut accept_backoff = ACCEPT_BACKOFF_MIN;
+    let mut rejections = RejectionLog::new();
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = &mut shutdown => break,
+            _ = state.shutdown.notified() => break,
+        };
+        let (stream, remote_addr) = match accepted {
+            Ok(accepted) => {
+                accept_backoff = ACCEPT_BACKOFF_MIN;
+                accepted
+            }
+            Err(err) if listener::is_transient(&err) => {
+                eprintln!(
+                    "Accept failed: {}; retrying in {}ms",
+                    err,
+                    accept_backoff.as_millis()
+                );
+                tokio::time::sleep(accept_backoff).await;
+                accept_backoff = (accept_backoff * 2).min(ACCEPT_BACKOFF_MAX);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let accept_rate = state.stats.record_accept();
+        let warn_above = state.config.accept_rate_warn_per_sec;
+        if warn_above > 0.0
+            && accept_rate > warn_above
+            && state.stats.accept_rate.warning_due(Instant::now())
+        {
+            eprintln!(
+                "Warning: accepting {:.1} connections/s over the last {}s (threshold {})",
+                accept_rate,
+                accept_rate::WINDOW_SECS,
+                warn_above
+            );
+        }
+        // Over-limit clients are turned away before any HTTP is spoken
+        let slot = match (&state.connection_limiter, remote_addr) {
+            (Some(limiter), Some(addr)) => match limiter.try_acquire(addr.ip()) {
+                Some(slot) => Some(slot),
+                None => {
+                    if let Some(suppressed) = rejections.record(Instant::now()) {
+                        let more = match suppressed {
+                            0 => String::new(),
+                            n => format!(" ({} more rejected since the last report)", n),
+                        };
+                        eprintln!(
+                            "Rejecting connection from {}: too many open connections{}",
+                            addr.ip(),
+                            more
+                        );
+                    }
+                    continue;
+                }
+            },
+            _ => None,
+        };
+        // Past the ramp's rate, a connection still gets an answer it can act
+        // on, and no request on it reaches a handler
+        let shed = slow_start
+            .as_mut()
+            .is_some_and(|ramp| !ramp.admit(Instant::now()));
+        if shed && state.config.log_level <= LogLevel::Debug {
+            eprintln!(
+                "Shedding connection{}: slow start",
+                remote_addr.map_or_else(String::new, |addr| format!(" from {}", addr.ip()))
+            );
+        }
+        if let Err(err) = L::configure(&stream, &state.config) {
+            eprintln!("Failed to configure connection: {:?}", err);
+        }
+        let io = TokioIo::new(stream);
+        let state_clone = state.clone();
+        let stats = state.stats.clone();
+        let log_level = state.config.log_level;
+
+        let mut draining = drain.subscribe();
+        let connection = tracing::info_span!(
+            "connection",
+            id = next_connection,
+            peer = remote_addr.map(tracing::field::display)
+        );
+        next_connection += 1;
+
+        let task = async move {
+            let _slot = slot;
+            let mut builder = http1::Builder::new();
+            // hyper panics below its minimum, which only an unvalidated
+            // `Config` could ask for
+            builder.max_buf_size(
+                state_clone
+                    .config
+                    .http1_max_buf_size
+                    .max(config::MIN_HTTP1_BUF_SIZE),
+            );
+            if let Some(writev) = state_clone.config.http1_writev {
+                builder.writev(writev);
+            }
+            let conn = builder
+                .serve_connection(
+                    io,
+                    service_fn(move |req| {
+                        let state = state_clone.clone();
+                        async move {
+                            if shed {
+                                return Ok(handle_slow_start(&state.config).await);
+                            }
+                            handle_request(req, state, remote_addr).await
+                        }
+                    }),
+                )
+                .with_upgrades();
+            tokio::pin!(conn);
+
+            let result = tokio::select! {
+                result = conn.as_mut() => result,
+                _ = draining.changed() => {
+                    conn.as_mut().graceful_shutdown();
+                    conn.await
+                }
+            };
+            match result {
+                Ok(()) => {}
+                // A client hanging up mid-response is routine, not a fault
+                Err(err) if is_client_disconnect(&err) => {
+                    stats.client_disconnects.fetch_add(1, Ordering::Relaxed);
+                    if log_level <= LogLevel::Debug {
+                        eprintln!("Client disconnected: {}", err);
+                    }
+                }
+                Err(err) => eprintln!("Error serving connection: {:?}", err),
+            }
+        };
+        // Everything the connection runs, its requests included, is
+        // attributed to it by tokio-console and tracing-based profilers
+        tokio::task::spawn(task.instrument(connection));
+    }
+
+    // Close the listening socket first so the port is released while
+    // in-flight connections drain.
+    drop(listener);
+    if let Some(sweeper) = sweeper {
+        sweeper.abort();
+    }
+    state.closing.store(true, Ordering::Relaxed);
+    drain.send_replace(());
+    drain.closed().await;
+
+    // The last spans only exist once every connection is done
+    if let Some(flusher) = flusher {
+        let _ = stop_flusher.send(());
+        let _ = flusher.await;
+    }
+
+    // Saved once nothing can count any more
+    if let Some(path) = &state.config.stats_snapshot_path {
+        if let Err(err) = state.stats.snapshot().save(path) {
+            eprintln!(
+                "Failed to save stats snapshot to {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Poll;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+use crate::Config;
+
+// Anything the accept loop can pull connections from
+pub trait Listener: Send + 'static {
+    type Io: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    // Yields the next connection and, for IP transports, the peer address
+    fn accept(&self) -> impl Future<Output = io::Result<(Self::Io, Option<SocketAddr>)>> + Send;
+
+    // Applies per-connection socket options right after accept
+    fn configure(_io: &Self::Io, _config: &Config) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Listener for TcpListener {
+    type Io = TcpStream;
+
+    async fn accept(&self) -> io::Result<(TcpStream, Option<SocketAddr>)> {
+        let (stream, peer) = TcpListener::accept(self).await?;
+        Ok((stream, Some(peer)))
+    }
+
+    fn configure(stream: &TcpStream, config: &Config) -> io::Result<()> {
+        // Small JSON responses shouldn't wait on Nagle's algorithm
+        stream.set_nodelay(config.tcp_nodelay)
+    }
+}
+
+// Whether an accept error is about the moment rather than the listener:
+// out of file descriptors or memory, or a connection that went away before
+// it was taken. The accept loop backs off and tries again on these.
+pub fn is_transient(err: &io::Error) -> bool {
+    // EMFILE and ENFILE, which have the same numbers on Linux, macOS and
+    // the BSDs but no `ErrorKind` of their own
+    #[cfg(unix)]
+    if matches!(err.raw_os_error(), Some(23 | 24)) {
+        return true;
+    }
+    matches!(
+        err.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::OutOfMemory
+    )
+}
+
+// Binds the listening socket with SO_REUSEADDR so a restarting process
+// doesn't hit "address already in use" while old connections sit in TIME_WAIT.
+pub fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+    bind_socket(addr, false, DEFAULT_BACKLOG)
+}
+
+// The `listen` backlog `bind` uses. The kernel caps whatever is asked for:
+// `net.core.somaxconn` on Linux (4096 since 5.4, 128 before),
+// `kern.ipc.somaxconn` on macOS and the BSDs (128 by default).
+pub const DEFAULT_BACKLOG: u32 = 1024;
+
+// `bind`, optionally with SO_REUSEPORT: other sockets that also set it can
+// then bind the same address, and the kernel spreads new connections
+// across all of them. That lets a new process start listening before the
+// old one exits, but also means neither gets every connection.
+//
+// `backlog` bounds the connections the kernel completes before they're
+// accepted; once it is full, new SYNs are dropped and clients retry after
+// a timeout, so a burst needs a backlog at least as large as itself.
+pub fn bind_socket(addr: SocketAddr, reuse_port: bool, backlog: u32) -> io::Result<TcpListener> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket.set_reuseaddr(true)?;
+    if reuse_port {
+        #[cfg(unix)]
+        socket.set_reuseport(true)?;
+        #[cfg(not(unix))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "SO_REUSEPORT is only supported on Unix platforms",
+        ));
+    }
+    socket.bind(addr)?;
+    socket.listen(backlog)
+}
+
+// Several TCP listeners served by one accept loop
+pub struct TcpListeners {
+    listeners: Vec<TcpListener>,
+    // Where the next accept starts looking, moved on each time so a busy
+    // listener can't keep the ones after it waiting
+    next: AtomicUsize,
+}
+
+impl TcpListeners {
+    pub fn local_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.listeners.iter().map(TcpListener::local_addr).collect()
+    }
+}
+
+impl Listener for TcpListeners {
+    type Io = TcpStream;
+
+    async fn accept(&self) -> io::Result<(TcpStream, Option<SocketAddr>)> {
+        std::future::poll_fn(|cx| {
+            let count = self.listeners.len();
+            let start = self.next.fetch_add(1, Ordering::Relaxed) % count.max(1);
+            for offset in 0..count {
+                let listener = &self.listeners[(start + offset) % count];
+                if let Poll::Ready(accepted) = listener.poll_accept(cx) {
+                    return Poll::Ready(accepted.map(|(stream, peer)| (stream, Some(peer))));
+                }
+            }
+            Poll::Pending
+        })
+        .await
+    }
+
+    fn configure(stream: &TcpStream, config: &Config) -> io::Result<()> {
+        <TcpListener as Listener>::configure(stream, config)
+    }
+}
+
+// Binds every address, in order. If one fails, the ones already bound are
+// closed before returning, so a failed start never leaves a port held; the
+// error names the address that couldn't be bound.
+pub fn bind_all(addrs: &[SocketAddr], reuse_port: bool, backlog: u32) -> io::Result<TcpListeners> {
+    let mut listeners = Vec::with_capacity(addrs.len());
+    for &addr in addrs {
+        match bind_socket(addr, reuse_port, backlog) {
+            Ok(listener) => listeners.push(listener),
+            Err(err) => {
+                drop(listeners);
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("failed to bind {}: {}", addr, err),
+                ));
+            }
+        }
+    }
+    Ok(TcpListeners {
+        listeners,
+        next: AtomicUsize::new(0),
+    })
+}
+
+// Unix domain socket listener that removes its socket file when dropped
+#[cfg(unix)]
+pub struct UnixSocketListener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixSocketListener {
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+
+        let path = path.as_ref().to_path_buf();
+
+        // A previous process that didn't shut down cleanly leaves its socket
+        // file behind; only ever remove sockets, never regular files.
+        match std::fs::symlink_metadata(&path) {
+            Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(&path)?,
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        Ok(Self { listener, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixSocketListener {
+    type Io = UnixStream;
+
+    async fn accept(&self) -> io::Result<(UnixStream, Option<SocketAddr>)> {
+        let (stream, _) = self.listener.accept().await?;
+        Ok((stream, None))
+    }
+}
+
+#[cfg(unix)]
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,34 @@
+// Sampled request logging. Under heavy load one line per request costs more
+// than it tells; errors are always logged, successes at the configured rate.
+
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use hyper::StatusCode;
+
+// Whether a request answered with `status` should be logged when successes
+// are logged at `rate` (0.0 to 1.0)
+pub fn should_log(status: StatusCode, rate: f64) -> bool {
+    if status.is_client_error() || status.is_server_error() || rate >= 1.0 {
+        return true;
+    }
+    rate > 0.0 && random_unit() < rate
+}
+
+thread_local! {
+    // xorshift64* state, seeded per thread from std's randomly keyed hasher
+    static STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+// Uniform in [0, 1). Not cryptographic; it only has to spread samples out.
+fn random_unit() -> f64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
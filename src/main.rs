@@ -1,193 +1,194 @@
-use std::convert::Infallible;
 use std::net::SocketAddr;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::Instant;
-
-use hyper::server::conn::http1;
-use hyper::service::service_fn;
-use hyper::{body::Incoming as IncomingBody, Request, Response, StatusCode};
-use hyper_util::rt::TokioIo;
-use tokio::net::TcpListener;
-use http_body_util::Full;
-use hyper::body::Bytes;
-use serde::Serialize;
-use chrono::Local;
-
-// Server statistics
-#[derive(Clone)]
-struct ServerStats {
-    total_requests: Arc<AtomicU64>,
-    start_time: Instant,
-}
-
-impl ServerStats {
-    fn new() -> Self {
-        Self {
-            total_requests: Arc::new(AtomicU64::new(0)),
-            start_time: Instant::now(),
+use std::time::Duration;
+
+#[cfg(unix)]
+use rust_http_server::UnixSocketListener;
+use rust_http_server::{bind_all, runtime, self_probe, serve, AppState, Config, Warmup};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = match Config::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            // Distinct exit statuses let orchestration tell the kinds apart
+            eprintln!("Configuration error: {}", err);
+            std::process::exit(err.exit_code());
         }
+    };
+    if std::env::args()
+        .skip(1)
+        .any(|arg| arg == "--quiet" || arg == "-q")
+    {
+        config.quiet = true;
     }
-
-    fn increment_requests(&self) {
-        self.total_requests.fetch_add(1, Ordering::Relaxed);
-    }
-
-    fn get_total_requests(&self) -> u64 {
-        self.total_requests.load(Ordering::Relaxed)
-    }
-
-    fn get_uptime_seconds(&self) -> u64 {
-        self.start_time.elapsed().as_secs()
-    }
-}
-
-// Response structures
-#[derive(Serialize)]
-struct JsonResponse {
-    message: String,
-    timestamp: String,
-    server: String,
+    // Built by hand rather than with #[tokio::main] so its threads are named
+    // from the config
+    runtime(&config)?.block_on(run(config))
 }
 
-#[derive(Serialize)]
-struct StatsResponse {
-    total_requests: u64,
-    uptime_seconds: u64,
-    requests_per_second: f64,
-}
-
-// Main request handler
-async fn handle_request(
-    req: Request<IncomingBody>,
-    stats: ServerStats,
-) -> Result<Response<Full<Bytes>>, Infallible> {
-    stats.increment_requests();
-
-    let path = req.uri().path();
-    let method = req.method();
-
-    println!(
-        "[{}] {} {} - Request #{}",
-        Local::now().format("%Y-%m-%d %H:%M:%S"),
-        method,
-        path,
-        stats.get_total_requests()
-    );
-
-    let response = match (method, path) {
-        (&hyper::Method::GET, "/") => handle_root(),
-        (&hyper::Method::GET, "/health") => handle_health(),
-        (&hyper::Method::GET, "/stats") => handle_stats(stats),
-        (&hyper::Method::GET, path) if path.starts_with("/echo/") => {
-            let message = &path[6..];
-            handle_echo(message)
+async fn run(config: Config) -> Result<(), Box<dyn std::error::Error>> {
+    let quiet = config.quiet;
+    let unix_socket = config.unix_socket.clone();
+    // Called once bound, so a `:0` port shows as the one the kernel picked
+    let announce = |listen: &str| {
+        if quiet {
+            println!("level=info msg=\"listening\" addr={}", listen);
+        } else {
+            print_banner(listen);
         }
-        _ => handle_not_found(),
     };
 
-    Ok(response)
-}
-
-// Route handlers
-fn handle_root() -> Response<Full<Bytes>> {
-    let response = JsonResponse {
-        message: "Welcome to Rust HTTP Server!".to_string(),
-        timestamp: Local::now().to_rfc3339(),
-        server: "rust-http-server/1.0".to_string(),
-    };
-    json_response(StatusCode::OK, &response)
-}
+    let listen_addrs = config.listen_addrs.clone();
+    let reuse_port = config.reuse_port;
+    let listen_backlog = config.listen_backlog;
+    let startup_probe = config.startup_probe;
+    let probe_timeout = Duration::from_millis(config.startup_probe_timeout_ms);
+    let base_path = config.base_path.clone();
+    let probe_host = config.probe_host();
+    let startup_delay = Duration::from_millis(config.startup_delay_ms);
+    let state = AppState::new(config);
+    // Tasks pushed here hold /readyz at 503 until they finish, while the
+    // listener is already accepting
+    let mut warmups: Vec<Warmup> = Vec::new();
+    if !startup_delay.is_zero() {
+        warmups.push(Box::pin(tokio::time::sleep(startup_delay)));
+    }
 
-fn handle_health() -> Response<Full<Bytes>> {
-    let response = JsonResponse {
-        message: "Server is healthy".to_string(),
-        timestamp: Local::now().to_rfc3339(),
-        server: "rust-http-server/1.0".to_string(),
-    };
-    json_response(StatusCode::OK, &response)
-}
+    match unix_socket {
+        #[cfg(unix)]
+        Some(path) => {
+            let listen = format!("unix:{}", path.display());
+            let listener = UnixSocketListener::bind(path)?;
+            announce(&listen);
+            state.ready_after(warmups);
+            serve(listener, state, shutdown_signal(quiet)).await?;
+        }
+        #[cfg(not(unix))]
+        Some(_) => return Err("UNIX_SOCKET is only supported on Unix platforms".into()),
+        None => {
+            let listener = match bind_all(&listen_addrs, reuse_port, listen_backlog) {
+                Ok(listener) => listener,
+                Err(err) => {
+                    eprintln!("Startup error: {}", err);
+                    std::process::exit(1);
+                }
+            };
+            let listen = listener
+                .local_addrs()?
+                .iter()
+                .map(|addr| format!("http://{}", addr))
+                .collect::<Vec<_>>()
+                .join(", ");
+            announce(&listen);
+            if startup_probe {
+                for addr in listener.local_addrs()? {
+                    let base_path = base_path.clone();
+                    let probe_host = probe_host.clone();
+                    warmups.push(Box::pin(async move {
+                        probe_or_exit(
+                            addr,
+                            &base_path,
+                            probe_host.as_deref(),
+                            probe_timeout,
+                            quiet,
+                        )
+                        .await
+                    }));
+                }
+            }
+            state.ready_after(warmups);
+            serve(listener, state, shutdown_signal(quiet)).await?;
+        }
+    }
 
-fn handle_stats(stats: ServerStats) -> Response<Full<Bytes>> {
-    let uptime = stats.get_uptime_seconds();
-    let total_requests = stats.get_total_requests();
-    let rps = if uptime > 0 {
-        total_requests as f64 / uptime as f64
+    if quiet {
+        println!("level=info msg=\"stopped\"");
     } else {
-        0.0
-    };
-
-    let response = StatsResponse {
-        total_requests,
-        uptime_seconds: uptime,
-        requests_per_second: rps,
-    };
-    json_response(StatusCode::OK, &response)
-}
-
-fn handle_echo(message: &str) -> Response<Full<Bytes>> {
-    let response = JsonResponse {
-        message: format!("Echo: {}", message),
-        timestamp: Local::now().to_rfc3339(),
-        server: "rust-http-server/1.0".to_string(),
-    };
-    json_response(StatusCode::OK, &response)
-}
-
-fn handle_not_found() -> Response<Full<Bytes>> {
-    let response = JsonResponse {
-        message: "Not Found".to_string(),
-        timestamp: Local::now().to_rfc3339(),
-        server: "rust-http-server/1.0".to_string(),
-    };
-    json_response(StatusCode::NOT_FOUND, &response)
+        println!("👋 Server stopped.");
+    }
+    Ok(())
 }
 
-// Helper function
-fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Full<Bytes>> {
-    let json = serde_json::to_string(body).unwrap();
-    Response::builder()
-        .status(status)
-        .header("Content-Type", "application/json")
-        .header("Server", "rust-http-server/1.0")
-        .body(Full::new(Bytes::from(json)))
-        .unwrap()
+// Runs the startup self-probe against `addr`, exiting if it fails: a
+// server that can't reach its own port won't be reachable by anyone else
+async fn probe_or_exit(
+    addr: SocketAddr,
+    base_path: &str,
+    host: Option<&str>,
+    timeout: Duration,
+    quiet: bool,
+) {
+    match self_probe::probe(addr, base_path, host, timeout).await {
+        Ok(()) if quiet => {
+            println!("level=info msg=\"startup probe passed\" addr={}", addr);
+        }
+        Ok(()) => println!("✅ Startup probe of http://{} passed", addr),
+        Err(err) => {
+            eprintln!("Startup error: startup probe failed: {}", err);
+            std::process::exit(1);
+        }
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8080));
-    let stats = ServerStats::new();
-
+// Interactive startup banner; suppressed by --quiet / QUIET=1
+fn print_banner(listen: &str) {
     println!("🚀 Starting Rust HTTP Server...");
-    println!("📡 Listening on http://{}", addr);
+    println!("📡 Listening on {}", listen);
     println!("📊 Available endpoints:");
     println!("   GET  /           - Root endpoint");
+    println!("   GET  /favicon.ico - Favicon (204 unless FAVICON_PATH is set)");
     println!("   GET  /health     - Health check");
-    println!("   GET  /stats      - Server statistics");
+    println!("   GET  /livez      - Liveness (200 while the process is up)");
+    println!("   GET  /readyz     - Readiness (503 until warm-up finishes)");
+    println!("   GET  /stats      - Server statistics (JSON, Prometheus or CSV)");
+    println!("   GET  /stats.csv  - Server statistics as CSV");
+    println!("   GET  /metrics    - Prometheus or OpenMetrics (Accept), with latency");
+    println!("   GET  /openapi.json - OpenAPI 3.0 description of the routes");
+    println!("   GET  /docs       - Swagger UI for /openapi.json");
     println!("   GET  /echo/:msg  - Echo message");
+    println!("   GET  /echo-header/:msg - Echo message in the X-Echo header");
+    println!("   GET  /echo/stream/:msg - Stream a message with a checksum trailer");
+    println!("   POST /echo       - Echo request body");
+    println!("   GET  /stream/ndjson - Stream JSON lines (?count=N)");
+    println!("   GET  /delay/:ms  - Respond after a delay");
+    println!("   GET  /error/:code - Respond with an error status");
+    println!("   GET  /ws         - WebSocket echo");
+    println!("   GET|PUT|PATCH|DELETE /kv/:key - In-memory key-value store");
+    println!("   ANY  /debug/echo - Reflect the request as JSON");
+    println!("   GET  /debug/runtime - Tokio runtime metrics");
+    println!("   GET  /debug/recent - The last RECENT_REQUESTS requests");
+    println!("   GET  /debug/block/:ms - Blocking work, run off the async workers");
+    println!("   GET|POST /admin/maintenance - Maintenance mode (needs ADMIN_TOKEN)");
+    println!("   POST /admin/shutdown - Graceful shutdown (needs ADMIN_TOKEN)");
     println!("\n✨ Server ready! Press Ctrl+C to stop.\n");
+}
 
-    let listener = TcpListener::bind(addr).await?;
+// Resolves on Ctrl+C, or SIGTERM on Unix
+async fn shutdown_signal(quiet: bool) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
 
-    loop {
-        let (stream, _) = listener.accept().await?;
-        let io = TokioIo::new(stream);
-        let stats_clone = stats.clone();
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
 
-        tokio::task::spawn(async move {
-            if let Err(err) = http1::Builder::new()
-                .serve_connection(
-                    io,
-                    service_fn(move |req| {
-                        let stats = stats_clone.clone();
-                        handle_request(req, stats)
-                    }),
-                )
-                .await
-            {
-                eprintln!("Error serving connection: {:?}", err);
-            }
-        });
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+
+    if quiet {
+        println!("level=info msg=\"shutdown signal received, draining connections\"");
+    } else {
+        println!("\n🛑 Shutdown signal received, draining connections...");
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,135 @@
+// Request path normalization, applied once before anything looks at the
+// path.
+//
+// Routing matches raw path strings, so `/health`, `//health` and
+// `/echo/../health` would otherwise be three different things, and a
+// maintenance or site check could disagree with the route that finally
+// answers. Percent-encoding is left alone apart from refusing encoded
+// control characters: `/health%00` must never be taken for `/health`.
+//
+// Optionally, non-ASCII text is also brought to Unicode NFC, so a name sent
+// with a precomposed `é` and one sent as `e` plus a combining accent reach
+// the same route and the same key.
+
+use std::borrow::Cow;
+
+use unicode_normalization::UnicodeNormalization;
+
+// Why a path was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathError {
+    // `%00`-`%1F` or `%7F` somewhere in the path
+    ControlCharacter,
+}
+
+// Strips any `#fragment`, collapses repeated slashes and resolves `.` and
+// `..` segments (never above the root). A trailing slash is kept, so
+// `/kv/` and `/kv` stay distinct.
+pub fn normalize_path(path: &str) -> Result<Cow<'_, str>, PathError> {
+    // Clients never send fragments, but hyper passes along whatever the
+    // request line held
+    let path = path.split('#').next().unwrap_or("");
+    if has_encoded_control(path) {
+        return Err(PathError::ControlCharacter);
+    }
+    // `*` (OPTIONS *) isn't a path to normalize
+    if !path.starts_with('/') || is_normal(path) {
+        return Ok(Cow::Borrowed(path));
+    }
+
+    let mut segments: Vec<&str> = Vec::new();
+    let mut trailing_slash = false;
+    for segment in path.split('/') {
+        trailing_slash = matches!(segment, "" | "." | "..");
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len());
+    for segment in &segments {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if trailing_slash || segments.is_empty() {
+        normalized.push('/');
+    }
+    Ok(Cow::Owned(normalized))
+}
+
+// `path` with its non-ASCII characters in Unicode NFC. They are usually
+// percent-encoded UTF-8; only those escapes are decoded, so `%2F` and the
+// like keep their meaning. Composed characters are re-encoded as uppercase
+// escapes. Paths that are already NFC, or whose escapes aren't UTF-8, are
+// returned unchanged.
+pub fn compose_path(path: Cow<'_, str>) -> Cow<'_, str> {
+    if !path.contains('%') && path.is_ascii() {
+        return path;
+    }
+    let Ok(decoded) = String::from_utf8(decode_non_ascii(&path)) else {
+        return path;
+    };
+    if unicode_normalization::is_nfc(&decoded) {
+        return path;
+    }
+
+    let mut composed = String::with_capacity(path.len());
+    for c in decoded.nfc() {
+        if c.is_ascii() {
+            composed.push(c);
+        } else {
+            for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                composed.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    Cow::Owned(composed)
+}
+
+// `path` as bytes with every escape of a non-ASCII byte decoded
+fn decode_non_ascii(path: &str) -> Vec<u8> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes.get(i..i + 3) {
+            Some([b'%', high, low]) => std::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .filter(|byte| !byte.is_ascii()),
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+// Whether `path` is already what `normalize_path` would make of it
+fn is_normal(path: &str) -> bool {
+    !path.contains("//")
+        && !path
+            .split('/')
+            .any(|segment| segment == "." || segment == "..")
+}
+
+fn has_encoded_control(path: &str) -> bool {
+    path.as_bytes().windows(3).any(|window| match window {
+        [b'%', high, low] if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {
+            matches!(high, b'0' | b'1') || (*high == b'7' && low.eq_ignore_ascii_case(&b'f'))
+        }
+        _ => false,
+    })
+}
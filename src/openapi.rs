@@ -0,0 +1,436 @@
+// OpenAPI 3.0 description of the server for `GET /openapi.json`, and the
+// Swagger UI page `GET /docs` renders it with.
+//
+// Paths and methods come from the route table, so a route can't go
+// missing; what each operation takes and answers is written down in
+// `operation`. Unannotated routes still appear, marked as undocumented.
+
+use hyper::Method;
+use serde_json::{json, Map, Value};
+
+// Summary given to operations `operation` has no annotation for
+pub const UNDOCUMENTED: &str = "Undocumented route";
+
+// Swagger UI, loaded from a CDN so the binary doesn't carry its assets. The
+// spec URL is relative, so the page works under `BASE_PATH` too.
+pub const DOCS_PAGE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>rust-http-server API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
+
+// The document for `routes`, given as (pattern, methods) in table order.
+// `base_path` becomes the server URL every path is relative to.
+pub fn document<'a>(
+    routes: impl IntoIterator<Item = (&'a str, &'a [Method])>,
+    base_path: &str,
+) -> Value {
+    let mut paths = Map::new();
+    for (pattern, methods) in routes {
+        let mut item = Map::new();
+        for method in methods {
+            let mut operation = operation(pattern, method);
+            let parameters = path_parameters(pattern)
+                .chain(
+                    operation
+                        .get("parameters")
+                        .and_then(Value::as_array)
+                        .into_iter()
+                        .flatten()
+                        .cloned(),
+                )
+                .collect::<Vec<_>>();
+            if !parameters.is_empty() {
+                operation["parameters"] = Value::Array(parameters);
+            }
+            item.insert(method.as_str().to_ascii_lowercase(), operation);
+        }
+        paths.insert(openapi_path(pattern), Value::Object(item));
+    }
+
+    let base_path = base_path.trim_end_matches('/');
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "rust-http-server",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{"url": if base_path.is_empty() { "/" } else { base_path }}],
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {"bearer": {"type": "http", "scheme": "bearer"}},
+        },
+    })
+}
+
+// `/kv/:key` as `/kv/{key}`
+fn openapi_path(pattern: &str) -> String {
+    pattern
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => format!("{{{}}}", name),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn path_parameters(pattern: &str) -> impl Iterator<Item = Value> + '_ {
+    pattern
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix(':'))
+        .map(|name| {
+            let schema = match name {
+                "ms" | "code" => json!({"type": "integer", "minimum": 0}),
+                _ => json!({"type": "string"}),
+            };
+            json!({"name": name, "in": "path", "required": true, "schema": schema})
+        })
+}
+
+fn query(name: &str, schema: Value, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "schema": schema,
+        "description": description,
+    })
+}
+
+fn message(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Message"}}},
+    })
+}
+
+fn text(description: &str, media_type: &str) -> Value {
+    json!({
+        "description": description,
+        "content": {media_type: {"schema": {"type": "string"}}},
+    })
+}
+
+// What the echo routes answer, as a `Message` or plain text by Accept
+fn echo(description: &str) -> Value {
+    json!({
+        "description": format!("{}, as JSON or plain text by Accept", description),
+        "content": {
+            "application/json": {"schema": {"$ref": "#/components/schemas/Message"}},
+            "text/plain": {"schema": {"type": "string"}},
+        },
+    })
+}
+
+// JSON other than a `Message`, described in the README
+fn json_body(description: &str) -> Value {
+    json!({"description": description, "content": {"application/json": {}}})
+}
+
+fn empty(description: &str) -> Value {
+    json!({"description": description})
+}
+
+// What `method` on `pattern` takes and answers, without its path parameters
+fn operation(pattern: &str, method: &Method) -> Value {
+    match (pattern, method.as_str()) {
+        ("/", "GET") => json!({
+            "summary": "Greeting",
+            "responses": {"200": message("Greeting in the client's language")},
+        }),
+        ("/favicon.ico", "GET") => json!({
+            "summary": "Favicon",
+            "responses": {
+                "200": {"description": "The icon from FAVICON_PATH", "content": {"image/*": {}}},
+                "204": empty("No icon configured"),
+                "304": empty("The client's copy is current"),
+            },
+        }),
+        ("/health", "GET") => json!({
+            "summary": "Health check",
+            "responses": {
+                "200": message("Healthy"),
+                "503": message("Unhealthy after too many consecutive server errors"),
+            },
+        }),
+        ("/livez", "GET") => json!({
+            "summary": "Liveness",
+            "responses": {"200": message("Alive, warming up or not")},
+        }),
+        ("/readyz", "GET") => json!({
+            "summary": "Readiness",
+            "responses": {
+                "200": message("Ready for traffic"),
+                "503": message("Warming up or shutting down"),
+            },
+        }),
+        ("/stats", "GET") => json!({
+            "summary": "Server statistics",
+            "parameters": [query(
+                "format",
+                json!({"type": "string", "enum": ["json", "prometheus", "csv"]}),
+                "Overrides the format Accept picks",
+            )],
+            "responses": {
+                "200": {
+                    "description": "Counters since startup",
+                    "content": {
+                        "application/json": {"schema": {"$ref": "#/components/schemas/Stats"}},
+                        "text/plain": {"schema": {"type": "string"}},
+                        "text/csv": {"schema": {"type": "string"}},
+                    },
+                },
+                "400": message("Unknown format"),
+            },
+        }),
+        ("/stats.csv", "GET") => json!({
+            "summary": "Server statistics as a CSV download",
+            "responses": {"200": text("Header line and one row of values", "text/csv")},
+        }),
+        ("/metrics", "GET") => json!({
+            "summary": "Metrics for scrapers, with a latency histogram",
+            "parameters": [query(
+                "format",
+                json!({"type": "string", "enum": ["prometheus", "openmetrics"]}),
+                "Overrides the format Accept picks",
+            )],
+            "responses": {
+                "200": {
+                    "description": "Prometheus text 0.0.4 or OpenMetrics 1.0",
+                    "content": {
+                        "text/plain": {"schema": {"type": "string"}},
+                        "application/openmetrics-text": {"schema": {"type": "string"}},
+                    },
+                },
+                "400": message("Unknown format"),
+            },
+        }),
+        ("/openapi.json", "GET") => json!({
+            "summary": "This document",
+            "responses": {"200": json_body("OpenAPI 3.0 document")},
+        }),
+        ("/docs", "GET") => json!({
+            "summary": "Swagger UI for this document",
+            "responses": {"200": text("HTML page", "text/html")},
+        }),
+        ("/delay/:ms", "GET") => json!({
+            "summary": "Respond after a delay",
+            "responses": {
+                "200": message("Answered after `ms` milliseconds"),
+                "400": message("Not a number, or above MAX_DELAY_MS"),
+            },
+        }),
+        ("/error/:code", "GET") => json!({
+            "summary": "Answer with the given status",
+            "responses": {
+                "default": message("The status named by `code`"),
+                "400": message("Not a status code"),
+            },
+        }),
+        ("/echo/stream/:msg", "GET") => json!({
+            "summary": "Stream a message with a checksum trailer",
+            "parameters": [
+                query(
+                    "count",
+                    json!({"type": "integer", "minimum": 0, "default": 10}),
+                    "Chunks to send",
+                ),
+                query(
+                    "interval_ms",
+                    json!({"type": "integer", "minimum": 0}),
+                    "Pause between chunks",
+                ),
+            ],
+            "responses": {
+                "200": text("`msg` once per chunk", "text/plain"),
+                "206": text("The requested byte range", "text/plain"),
+                "400": message("Invalid count or interval_ms"),
+                "416": message("Range not satisfiable"),
+            },
+        }),
+        ("/echo/:msg", "GET") => json!({
+            "summary": "Echo a message",
+            "parameters": [query(
+                "repeat",
+                json!({"type": "integer", "minimum": 1}),
+                "Send the message this many times",
+            )],
+            "responses": {
+                "200": echo("The message"),
+                "400": message("Invalid repeat"),
+            },
+        }),
+        ("/echo-header/:msg", "GET") => json!({
+            "summary": "Echo a message in a header",
+            "responses": {
+                "204": {
+                    "description": "The percent-decoded message as X-Echo",
+                    "headers": {"X-Echo": {"schema": {"type": "string"}}},
+                },
+                "400": message("Message isn't printable ASCII"),
+            },
+        }),
+        ("/echo", "POST") => json!({
+            "summary": "Echo the request body",
+            "requestBody": {"content": {"*/*": {"schema": {"type": "string"}}}},
+            "responses": {
+                "200": echo("The body"),
+                "413": message("Body above MAX_BODY_BYTES"),
+            },
+        }),
+        ("/stream/ndjson", "GET") => json!({
+            "summary": "Stream JSON lines",
+            "parameters": [query(
+                "count",
+                json!({"type": "integer", "minimum": 0, "default": 10}),
+                "Lines to send",
+            )],
+            "responses": {
+                "200": text("One JSON object per line", "application/x-ndjson"),
+                "400": message("Invalid count"),
+            },
+        }),
+        ("/ws", "GET") => json!({
+            "summary": "WebSocket echo",
+            "responses": {
+                "101": empty("Upgraded; every message is sent back"),
+                "426": message("Not a WebSocket upgrade"),
+            },
+        }),
+        ("/kv/:key", "GET") => json!({
+            "summary": "Read a value",
+            "responses": {
+                "200": {
+                    "description": "The stored value",
+                    "content": {"application/json": {}, "text/plain": {}},
+                },
+                "404": message("No such key"),
+            },
+        }),
+        ("/kv/:key", "PUT") => json!({
+            "summary": "Store a value",
+            "requestBody": {"required": true, "content": {"*/*": {"schema": {"type": "string"}}}},
+            "responses": {
+                "200": empty("Replaced; the new value is returned"),
+                "201": empty("Created; the value is returned"),
+            },
+        }),
+        ("/kv/:key", "PATCH") => json!({
+            "summary": "Merge a JSON patch into a value",
+            "requestBody": {"required": true, "content": {"application/merge-patch+json": {}}},
+            "responses": {
+                "200": {"description": "The patched value", "content": {"application/json": {}}},
+                "400": message("Not a JSON merge patch"),
+                "404": message("No such key"),
+                "409": message("The stored value isn't JSON"),
+            },
+        }),
+        ("/kv/:key", "DELETE") => json!({
+            "summary": "Delete a value",
+            "responses": {"204": empty("Deleted"), "404": message("No such key")},
+        }),
+        ("/debug/echo", _) => json!({
+            "summary": "Reflect the request back",
+            "responses": {"200": json_body("Method, path, headers and body as received")},
+        }),
+        ("/debug/runtime", "GET") => json!({
+            "summary": "Tokio runtime metrics",
+            "responses": {"200": json_body("Workers, tasks and queue depth")},
+        }),
+        ("/debug/recent", "GET") => json!({
+            "summary": "The last RECENT_REQUESTS requests",
+            "responses": {"200": json_body("Request summaries, oldest first")},
+        }),
+        ("/debug/block/:ms", "GET") => json!({
+            "summary": "Run blocking work on the blocking pool",
+            "responses": {
+                "200": message("Blocked for `ms` milliseconds"),
+                "400": message("Not a number, or above MAX_DELAY_MS"),
+            },
+        }),
+        ("/admin/maintenance", "GET") => json!({
+            "summary": "Maintenance mode state",
+            "security": [{"bearer": []}],
+            "responses": {
+                "200": json_body("Whether it is on"),
+                "401": message("Missing or wrong token"),
+                "403": message("ADMIN_TOKEN is not set"),
+            },
+        }),
+        ("/admin/maintenance", "POST") => json!({
+            "summary": "Switch maintenance mode",
+            "security": [{"bearer": []}],
+            "requestBody": {"required": true, "content": {"application/json": {"schema": {
+                "type": "object",
+                "properties": {"enabled": {"type": "boolean"}},
+                "required": ["enabled"],
+            }}}},
+            "responses": {
+                "200": json_body("The new state"),
+                "400": message("No `enabled` boolean"),
+                "401": message("Missing or wrong token"),
+                "403": message("ADMIN_TOKEN is not set"),
+            },
+        }),
+        ("/admin/shutdown", "POST") => json!({
+            "summary": "Shut down gracefully",
+            "security": [{"bearer": []}],
+            "parameters": [query(
+                "drain",
+                json!({"type": "boolean", "default": false}),
+                "Report not ready for SHUTDOWN_DRAIN_DELAY_MS first",
+            )],
+            "responses": {
+                "202": message("Shutting down, or already shutting down"),
+                "401": message("Missing or wrong token"),
+                "403": message("ADMIN_TOKEN is not set"),
+            },
+        }),
+        _ => json!({
+            "summary": UNDOCUMENTED,
+            "responses": {"default": empty("Not described")},
+        }),
+    }
+}
+
+fn schemas() -> Value {
+    json!({
+        "Message": {
+            "type": "object",
+            "properties": {
+                "message": {"type": "string"},
+                "timestamp": {"type": "string", "format": "date-time"},
+                "server": {"type": "string"},
+            },
+            "required": ["message", "timestamp", "server"],
+        },
+        "Stats": {
+            "type": "object",
+            "description": "Counters described in the README; breakdowns map labels to counts",
+            "properties": {
+                "total_requests": {"type": "integer"},
+                "uptime_seconds": {"type": "integer"},
+                "uptime_human": {"type": "string"},
+                "requests_per_second": {"type": "number"},
+                "by_http_version": {"type": "object", "additionalProperties": {"type": "integer"}},
+                "by_route": {"type": "object", "additionalProperties": {"type": "integer"}},
+            },
+            "additionalProperties": true,
+        },
+    })
+}
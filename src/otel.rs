@@ -0,0 +1,299 @@
+// Request spans in OpenTelemetry's model, exported over OTLP/HTTP.
+//
+// Every request handled while `OTEL_TRACES` is on becomes one server span.
+// An incoming W3C `traceparent` header makes it a child of the caller's
+// span, so traces continue across services. Finished spans queue in memory
+// and are POSTed as OTLP JSON to `<endpoint>/v1/traces` once a second, and
+// once more on shutdown.
+
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Method, Request, StatusCode, Uri};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use serde_json::json;
+use tokio::sync::oneshot;
+
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+// How often queued spans are sent
+pub const EXPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+// Spans held while the collector is slow or down; newer ones are dropped
+const MAX_QUEUED_SPANS: usize = 2048;
+
+// The caller's side of a trace, from `traceparent`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    // The caller recorded its span; when it didn't, neither do we
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    // Parses `00-<32 hex trace id>-<16 hex span id>-<2 hex flags>`. Later
+    // versions may append fields, which are ignored; all-zero ids and
+    // version `ff` are invalid.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut fields = value.trim().split('-');
+        let version = fields.next()?;
+        let trace_id = fields.next()?;
+        let span_id = fields.next()?;
+        let flags = fields.next()?;
+        let [version] = decode_hex::<1>(version)?;
+        if version == 0xff || (version == 0 && fields.next().is_some()) {
+            return None;
+        }
+        let trace_id: [u8; 16] = decode_hex(trace_id)?;
+        let span_id: [u8; 8] = decode_hex(span_id)?;
+        let [flags] = decode_hex::<1>(flags)?;
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            sampled: flags & 1 == 1,
+        })
+    }
+}
+
+// One handled request
+#[derive(Debug, Clone)]
+pub struct Span {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub parent_span_id: Option<[u8; 8]>,
+    pub method: Method,
+    pub path: String,
+    // The matched route pattern, e.g. `/echo/:msg`
+    pub route: Option<&'static str>,
+    pub status: StatusCode,
+    pub start: SystemTime,
+    pub end: SystemTime,
+}
+
+impl Span {
+    // A span for a request that started at `start`, continuing `parent`
+    // when the caller sent one
+    pub fn start(
+        parent: Option<TraceContext>,
+        method: Method,
+        path: String,
+        start: SystemTime,
+    ) -> Self {
+        Self {
+            trace_id: parent.map_or_else(random_id, |parent| parent.trace_id),
+            span_id: random_id(),
+            parent_span_id: parent.map(|parent| parent.span_id),
+            method,
+            path,
+            route: None,
+            status: StatusCode::OK,
+            start,
+            end: start,
+        }
+    }
+
+    // Named `METHOD route` so spans group by route rather than by every
+    // distinct path
+    fn name(&self) -> String {
+        format!("{} {}", self.method, self.route.unwrap_or(&self.path))
+    }
+
+    fn to_otlp(&self) -> serde_json::Value {
+        let mut attributes = vec![
+            string_attribute("http.request.method", self.method.as_str()),
+            string_attribute("url.path", &self.path),
+            json!({
+                "key": "http.response.status_code",
+                "value": {"intValue": self.status.as_u16().to_string()},
+            }),
+        ];
+        if let Some(route) = self.route {
+            attributes.push(string_attribute("http.route", route));
+        }
+        let mut span = json!({
+            "traceId": encode_hex(&self.trace_id),
+            "spanId": encode_hex(&self.span_id),
+            "name": self.name(),
+            // SPAN_KIND_SERVER
+            "kind": 2,
+            "startTimeUnixNano": unix_nanos(self.start).to_string(),
+            "endTimeUnixNano": unix_nanos(self.end).to_string(),
+            "attributes": attributes,
+        });
+        if let Some(parent) = &self.parent_span_id {
+            span["parentSpanId"] = json!(encode_hex(parent));
+        }
+        // Server spans only count 5xx as errors; 4xx is the client's fault
+        if self.status.is_server_error() {
+            span["status"] = json!({"code": 2});
+        }
+        span
+    }
+}
+
+// Queues finished spans and ships them to the collector
+#[derive(Clone)]
+pub struct SpanExporter {
+    endpoint: Uri,
+    service_name: String,
+    queue: Arc<Mutex<Vec<Span>>>,
+    // Kept across flushes, so its pool holds the connection to the collector
+    // open rather than dialling it once a second
+    client: Client<HttpConnector, Full<Bytes>>,
+}
+
+impl SpanExporter {
+    // `endpoint` is the collector's base URL; spans go to `/v1/traces`
+    // under it
+    pub fn new(endpoint: &Uri, service_name: &str) -> Self {
+        let base = endpoint.to_string();
+        let endpoint = format!("{}/v1/traces", base.trim_end_matches('/'))
+            .parse()
+            .unwrap_or_else(|_| endpoint.clone());
+        Self {
+            endpoint,
+            service_name: service_name.to_string(),
+            queue: Arc::new(Mutex::new(Vec::new())),
+            client: Client::builder(TokioExecutor::new()).build_http(),
+        }
+    }
+
+    // Queues `span`, unless the queue is full or another request is
+    // queueing one right now: a dropped span costs less than a request
+    // waiting on tracing
+    pub fn record(&self, span: Span) {
+        if let Ok(mut queue) = self.queue.try_lock() {
+            if queue.len() < MAX_QUEUED_SPANS {
+                queue.push(span);
+            }
+        }
+    }
+
+    // Sends everything queued so far. A failed export is logged and its
+    // spans are dropped; tracing must never hold up serving.
+    pub async fn flush(&self) {
+        let spans = std::mem::take(&mut *self.queue.lock().unwrap());
+        if spans.is_empty() {
+            return;
+        }
+        let count = spans.len();
+        if let Err(err) = self.export(spans).await {
+            eprintln!(
+                "Failed to export {} spans to {}: {}",
+                count, self.endpoint, err
+            );
+        }
+    }
+
+    // Flushes every `EXPORT_INTERVAL` until `stop` fires, then once more
+    // for whatever was recorded since
+    pub fn spawn_flusher(&self, stop: oneshot::Receiver<()>) -> tokio::task::JoinHandle<()> {
+        let exporter = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(EXPORT_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            tokio::pin!(stop);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => exporter.flush().await,
+                    _ = &mut stop => break,
+                }
+            }
+            exporter.flush().await;
+        })
+    }
+
+    async fn export(&self, spans: Vec<Span>) -> Result<(), String> {
+        let spans: Vec<serde_json::Value> = spans.iter().map(Span::to_otlp).collect();
+        let body = json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [string_attribute("service.name", &self.service_name)],
+                },
+                "scopeSpans": [{
+                    "scope": {"name": "rust-http-server", "version": env!("CARGO_PKG_VERSION")},
+                    "spans": spans,
+                }],
+            }],
+        });
+
+        let request = Request::post(self.endpoint.clone())
+            .header("Content-Type", "application/json")
+            .body(Full::new(Bytes::from(body.to_string())))
+            .map_err(|err| err.to_string())?;
+        let response = tokio::time::timeout(EXPORT_INTERVAL * 5, self.client.request(request))
+            .await
+            .map_err(|_| "timed out".to_string())?
+            .map_err(|err| err.to_string())?;
+        let status = response.status();
+        // Drain the body so the connection closes cleanly
+        let _ = response.into_body().collect().await;
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(format!("collector answered {}", status))
+        }
+    }
+}
+
+fn string_attribute(key: &str, value: &str) -> serde_json::Value {
+    json!({"key": key, "value": {"stringValue": value}})
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos())
+}
+
+// Lowercase hex only, as `traceparent` requires
+fn decode_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    let lowercase_hex = |byte: u8| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte);
+    if hex.len() != N * 2 || !hex.bytes().all(lowercase_hex) {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+pub(crate) fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+thread_local! {
+    // xorshift64* state, seeded per thread from std's randomly keyed hasher
+    static STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+// Random non-zero id. Not cryptographic; ids only have to be unique.
+fn random_id<const N: usize>() -> [u8; N] {
+    let mut id = [0; N];
+    STATE.with(|state| {
+        for chunk in id.chunks_mut(8) {
+            let mut x = state.get();
+            x ^= x >> 12;
+            x ^= x << 25;
+            x ^= x >> 27;
+            state.set(x);
+            let value = x.wrapping_mul(0x2545_F491_4F6C_DD1D).to_be_bytes();
+            chunk.copy_from_slice(&value[..chunk.len()]);
+        }
+    });
+    if id == [0; N] {
+        id[N - 1] = 1;
+    }
+    id
+}
@@ -0,0 +1,109 @@
+// Pre-serialized bodies for fixed-shape JSON responses.
+//
+// `json_response` runs serde and copies the result into `Bytes` on every
+// request. Responses whose only varying field is the timestamp instead keep
+// everything before it serialized up front, and each request writes the
+// timestamp and closing bytes straight into one buffer sized up front.
+
+use chrono::{DateTime, Datelike, Offset, TimeZone, Timelike, Utc};
+use hyper::body::Bytes;
+
+// Longest timestamp the format can produce (nanosecond precision)
+const TIMESTAMP_MAX_LEN: usize = "2025-10-26T18:29:36.123456789+00:00".len();
+
+const SUFFIX: &[u8] = br#"","server":"rust-http-server/1.0"}"#;
+
+// A `{"message", "timestamp", "server"}` body with a fixed message
+pub struct TimestampedJson {
+    // `{"message":"...","timestamp":"`
+    prefix: Box<[u8]>,
+}
+
+impl TimestampedJson {
+    pub fn new(message: &str) -> Self {
+        let message = serde_json::Value::from(message);
+        Self {
+            prefix: format!("{{\"message\":{},\"timestamp\":\"", message)
+                .into_bytes()
+                .into_boxed_slice(),
+        }
+    }
+
+    // The body, stamped with `now`. Byte-for-byte what `json_response` would
+    // produce for the equivalent `JsonResponse`.
+    pub fn render<Tz: TimeZone>(&self, now: DateTime<Tz>) -> Bytes {
+        let mut body = Vec::with_capacity(self.prefix.len() + TIMESTAMP_MAX_LEN + SUFFIX.len());
+        body.extend_from_slice(&self.prefix);
+        write_rfc3339(&mut body, &now, false);
+        body.extend_from_slice(SUFFIX);
+        Bytes::from(body)
+    }
+
+    // The body stamped with `now` in `USE_UTC_TIMESTAMPS` style, ending `Z`
+    pub fn render_utc(&self, now: DateTime<Utc>) -> Bytes {
+        let mut body = Vec::with_capacity(self.prefix.len() + TIMESTAMP_MAX_LEN + SUFFIX.len());
+        body.extend_from_slice(&self.prefix);
+        write_rfc3339(&mut body, &now, true);
+        body.extend_from_slice(SUFFIX);
+        Bytes::from(body)
+    }
+}
+
+// Writes `now` exactly as `DateTime::to_rfc3339` would, or with `zulu` as
+// `to_rfc3339_opts(SecondsFormat::AutoSi, true)` would. chrono's generic
+// formatter costs more than the serde path this replaces, so the digits are
+// laid down by hand.
+fn write_rfc3339<Tz: TimeZone>(out: &mut Vec<u8>, now: &DateTime<Tz>, zulu: bool) {
+    let mut second = now.second();
+    let mut nanos = now.nanosecond();
+    // chrono represents a leap second as nanoseconds past 1_000_000_000
+    if nanos >= 1_000_000_000 {
+        second += 1;
+        nanos -= 1_000_000_000;
+    }
+
+    write_digits(out, now.year().unsigned_abs(), 4);
+    out.push(b'-');
+    write_digits(out, now.month(), 2);
+    out.push(b'-');
+    write_digits(out, now.day(), 2);
+    out.push(b'T');
+    write_digits(out, now.hour(), 2);
+    out.push(b':');
+    write_digits(out, now.minute(), 2);
+    out.push(b':');
+    write_digits(out, second, 2);
+
+    // Shortest of 0, 3, 6 or 9 fractional digits that is exact
+    if nanos != 0 {
+        out.push(b'.');
+        if nanos.is_multiple_of(1_000_000) {
+            write_digits(out, nanos / 1_000_000, 3);
+        } else if nanos.is_multiple_of(1_000) {
+            write_digits(out, nanos / 1_000, 6);
+        } else {
+            write_digits(out, nanos, 9);
+        }
+    }
+
+    let offset = now.offset().fix().local_minus_utc();
+    if zulu && offset == 0 {
+        out.push(b'Z');
+        return;
+    }
+    out.push(if offset < 0 { b'-' } else { b'+' });
+    let offset = offset.unsigned_abs() / 60;
+    write_digits(out, offset / 60, 2);
+    out.push(b':');
+    write_digits(out, offset % 60, 2);
+}
+
+// Writes the low `width` decimal digits of `value`, zero-padded
+fn write_digits(out: &mut Vec<u8>, mut value: u32, width: usize) {
+    let start = out.len();
+    out.resize(start + width, b'0');
+    for slot in out[start..].iter_mut().rev() {
+        *slot = b'0' + (value % 10) as u8;
+        value /= 10;
+    }
+}
@@ -0,0 +1,64 @@
+// Single byte ranges (RFC 9110 section 14) for generated bodies of known
+// length. Multiple ranges would need multipart/byteranges, so like any other
+// unparseable Range header they are ignored and the full body is sent.
+
+pub const RANGE_HEADER: &str = "range";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ByteRange {
+    // No usable Range header: send everything
+    Full,
+    // Bytes `start..end` of the body
+    Partial { start: usize, end: usize },
+    // A valid range that doesn't overlap the body
+    Unsatisfiable,
+}
+
+// Resolves a `Range` header value against a body of `total` bytes
+pub fn resolve(header: Option<&str>, total: usize) -> ByteRange {
+    let Some(spec) = header.and_then(|value| value.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((first, last)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let parse = |s: &str| -> Option<usize> {
+        let s = s.trim();
+        // usize::from_str accepts a leading '+', which a range can't have
+        s.bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| s.parse().ok())?
+    };
+
+    if first.trim().is_empty() {
+        // Suffix range: the last `n` bytes
+        return match parse(last) {
+            Some(0) => ByteRange::Unsatisfiable,
+            Some(_) if total == 0 => ByteRange::Unsatisfiable,
+            Some(n) => ByteRange::Partial {
+                start: total.saturating_sub(n),
+                end: total,
+            },
+            None => ByteRange::Full,
+        };
+    }
+
+    let Some(start) = parse(first) else {
+        return ByteRange::Full;
+    };
+    let end = if last.trim().is_empty() {
+        total
+    } else {
+        match parse(last) {
+            Some(last) if last >= start => last.saturating_add(1).min(total),
+            _ => return ByteRange::Full,
+        }
+    };
+    if start >= total {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial { start, end }
+}
@@ -0,0 +1,311 @@
+// Per-client rate limiting, by token bucket or leaky bucket.
+//
+// Both key clients by network prefix rather than full address, so rotating
+// through the addresses of one IPv6 /64 doesn't buy a fresh allowance every
+// time. A token bucket lets a client spend its burst at once; a leaky bucket
+// instead holds requests back so they are served no faster than the rate,
+// queueing up to the burst and turning away the rest.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinHandle;
+
+// Independently locked parts of the client map, so a sweep only ever holds
+// up the clients in the shard it is on
+const SHARDS: usize = 16;
+
+// The slowest rate either limiter takes: waits are `1 / rate` long, and much
+// slower rates make them too long for a `Duration`
+pub const MIN_RATE_PER_SEC: f64 = 1.0 / 86_400.0;
+
+// Which limiter `RATE_LIMIT_ALGORITHM` picks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitAlgorithm {
+    TokenBucket,
+    LeakyBucket,
+}
+
+impl FromStr for RateLimitAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "token_bucket" => Ok(RateLimitAlgorithm::TokenBucket),
+            "leaky_bucket" => Ok(RateLimitAlgorithm::LeakyBucket),
+            other => Err(format!(
+                "unknown rate limit algorithm {:?} (expected token_bucket or leaky_bucket)",
+                other
+            )),
+        }
+    }
+}
+
+// What the server asks of a rate limiter, whichever algorithm is behind it
+pub trait RateLimiter: Send + Sync {
+    // Admits a request from `ip`, with how long to hold it before serving
+    // (always zero for a token bucket), or returns how long until one would
+    // be admitted
+    fn check(&self, ip: IpAddr) -> Result<Duration, Duration>;
+
+    // Drops clients untouched for `idle_ttl` whose state is back to a new
+    // client's by `now`, so removing them changes nothing but memory.
+    // Returns how many went.
+    fn sweep(&self, now: Instant, idle_ttl: Duration) -> usize;
+
+    // Client prefixes with state right now
+    fn tracked(&self) -> usize;
+
+    // Sweeps every `interval` until the returned task is aborted
+    fn spawn_sweeper(&self, interval: Duration, idle_ttl: Duration) -> JoinHandle<()>;
+}
+
+// The limiter `algorithm` names, with the same rate, burst and prefixes
+pub fn new_limiter(
+    algorithm: RateLimitAlgorithm,
+    rate_per_sec: f64,
+    burst: u32,
+    ipv4_prefix: u8,
+    ipv6_prefix: u8,
+) -> Arc<dyn RateLimiter> {
+    match algorithm {
+        RateLimitAlgorithm::TokenBucket => Arc::new(TokenBucket::new(
+            rate_per_sec,
+            burst,
+            ipv4_prefix,
+            ipv6_prefix,
+        )),
+        RateLimitAlgorithm::LeakyBucket => Arc::new(LeakyBucket::new(
+            rate_per_sec,
+            burst,
+            ipv4_prefix,
+            ipv6_prefix,
+        )),
+    }
+}
+
+// Per-prefix state of type `S`, sharded
+struct Clients<S> {
+    shards: Arc<[Mutex<HashMap<IpAddr, S>>; SHARDS]>,
+    hasher: RandomState,
+    ipv4_prefix: u8,
+    ipv6_prefix: u8,
+}
+
+impl<S> Clone for Clients<S> {
+    fn clone(&self) -> Self {
+        Self {
+            shards: self.shards.clone(),
+            hasher: self.hasher.clone(),
+            ipv4_prefix: self.ipv4_prefix,
+            ipv6_prefix: self.ipv6_prefix,
+        }
+    }
+}
+
+impl<S> Clients<S> {
+    fn new(ipv4_prefix: u8, ipv6_prefix: u8) -> Self {
+        Self {
+            shards: Arc::new(std::array::from_fn(|_| Mutex::new(HashMap::new()))),
+            hasher: RandomState::new(),
+            ipv4_prefix: ipv4_prefix.min(32),
+            ipv6_prefix: ipv6_prefix.min(128),
+        }
+    }
+
+    // Runs `f` on the state of `ip`'s prefix, created by `new` if missing
+    fn with<R>(&self, ip: IpAddr, new: impl FnOnce() -> S, f: impl FnOnce(&mut S) -> R) -> R {
+        let key = normalize_ip(ip, self.ipv4_prefix, self.ipv6_prefix);
+        let shard = &self.shards[self.hasher.hash_one(key) as usize % SHARDS];
+        let mut clients = shard.lock().unwrap();
+        f(clients.entry(key).or_insert_with(new))
+    }
+
+    // Keeps the clients `keep` accepts, returning how many went
+    fn retain(&self, mut keep: impl FnMut(&S) -> bool) -> usize {
+        let mut removed = 0;
+        for shard in self.shards.iter() {
+            let mut clients = shard.lock().unwrap();
+            let before = clients.len();
+            clients.retain(|_, state| keep(state));
+            removed += before - clients.len();
+        }
+        removed
+    }
+
+    fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().len())
+            .sum()
+    }
+}
+
+fn spawn_sweeper<L>(limiter: L, interval: Duration, idle_ttl: Duration) -> JoinHandle<()>
+where
+    L: RateLimiter + 'static,
+{
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // The first tick is immediate, and there is nothing to sweep yet
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            limiter.sweep(Instant::now(), idle_ttl);
+        }
+    })
+}
+
+// Token bucket state for one client prefix
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+// Tokens refill at the rate up to the burst, and each request takes one, so
+// a client that has been quiet can send its whole burst at once
+#[derive(Clone)]
+pub struct TokenBucket {
+    clients: Clients<Bucket>,
+    rate_per_sec: f64,
+    burst: f64,
+}
+
+impl TokenBucket {
+    pub fn new(rate_per_sec: f64, burst: u32, ipv4_prefix: u8, ipv6_prefix: u8) -> Self {
+        Self {
+            clients: Clients::new(ipv4_prefix, ipv6_prefix),
+            rate_per_sec,
+            burst: f64::from(burst.max(1)),
+        }
+    }
+}
+
+impl RateLimiter for TokenBucket {
+    // Takes a token for `ip`, or returns how long until one is available
+    fn check(&self, ip: IpAddr) -> Result<Duration, Duration> {
+        let now = Instant::now();
+        let new = || Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        };
+        self.clients.with(ip, new, |bucket| {
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.rate_per_sec).min(self.burst);
+            bucket.last_refill = now;
+
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                Ok(Duration::ZERO)
+            } else {
+                let missing = 1.0 - bucket.tokens;
+                Err(Duration::from_secs_f64(missing / self.rate_per_sec))
+            }
+        })
+    }
+
+    // A new client starts with a full bucket, so only full ones go
+    fn sweep(&self, now: Instant, idle_ttl: Duration) -> usize {
+        self.clients.retain(|bucket| {
+            let idle = now.saturating_duration_since(bucket.last_refill);
+            let refilled = bucket.tokens + idle.as_secs_f64() * self.rate_per_sec >= self.burst;
+            idle < idle_ttl || !refilled
+        })
+    }
+
+    fn tracked(&self) -> usize {
+        self.clients.len()
+    }
+
+    fn spawn_sweeper(&self, interval: Duration, idle_ttl: Duration) -> JoinHandle<()> {
+        spawn_sweeper(self.clone(), interval, idle_ttl)
+    }
+}
+
+// Requests leave at the rate, one every `1 / rate` seconds; those arriving
+// faster wait their turn, and once `burst` are waiting the rest are turned
+// away. Only the time the queue drains is kept: it is the slot the next
+// request gets.
+#[derive(Clone)]
+pub struct LeakyBucket {
+    clients: Clients<Instant>,
+    interval: Duration,
+    // How far ahead of now the queue may reach before requests are refused
+    capacity: Duration,
+}
+
+impl LeakyBucket {
+    pub fn new(rate_per_sec: f64, burst: u32, ipv4_prefix: u8, ipv6_prefix: u8) -> Self {
+        let interval = Duration::from_secs_f64(1.0 / rate_per_sec);
+        Self {
+            clients: Clients::new(ipv4_prefix, ipv6_prefix),
+            interval,
+            capacity: interval * burst.max(1).saturating_sub(1),
+        }
+    }
+}
+
+impl RateLimiter for LeakyBucket {
+    // Queues a request from `ip`, returning how long it must wait for its
+    // slot, or how long until there is room in the queue
+    fn check(&self, ip: IpAddr) -> Result<Duration, Duration> {
+        let now = Instant::now();
+        self.clients.with(
+            ip,
+            || now,
+            |drained| {
+                let slot = (*drained).max(now);
+                let wait = slot - now;
+                if wait > self.capacity {
+                    return Err(wait - self.capacity);
+                }
+                *drained = slot + self.interval;
+                Ok(wait)
+            },
+        )
+    }
+
+    // A queue that drained in the past is as good as a new one
+    fn sweep(&self, now: Instant, idle_ttl: Duration) -> usize {
+        self.clients
+            .retain(|drained| now.saturating_duration_since(*drained) < idle_ttl)
+    }
+
+    fn tracked(&self) -> usize {
+        self.clients.len()
+    }
+
+    fn spawn_sweeper(&self, interval: Duration, idle_ttl: Duration) -> JoinHandle<()> {
+        spawn_sweeper(self.clone(), interval, idle_ttl)
+    }
+}
+
+// Masks `ip` down to its network prefix: IPv4 to `ipv4_prefix` bits and
+// IPv6 to `ipv6_prefix` bits. IPv4-mapped IPv6 addresses are treated as IPv4.
+pub fn normalize_ip(ip: IpAddr, ipv4_prefix: u8, ipv6_prefix: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => IpAddr::V4(mask_v4(v4, ipv4_prefix)),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(mask_v4(v4, ipv4_prefix)),
+            None => IpAddr::V6(mask_v6(v6, ipv6_prefix)),
+        },
+    }
+}
+
+fn mask_v4(ip: Ipv4Addr, prefix: u8) -> Ipv4Addr {
+    let prefix = u32::from(prefix.min(32));
+    let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+    Ipv4Addr::from(u32::from(ip) & mask)
+}
+
+fn mask_v6(ip: Ipv6Addr, prefix: u8) -> Ipv6Addr {
+    let prefix = u32::from(prefix.min(128));
+    let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+    Ipv6Addr::from(u128::from(ip) & mask)
+}
@@ -0,0 +1,58 @@
+// The last few requests the server answered, kept for `GET /debug/recent`
+// so an issue can be looked into without access to the request log.
+//
+// One short lock per request, so it is off unless `RECENT_REQUESTS` is set.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+// What is kept of one request. The query is left out, as it may carry
+// tokens or other secrets, and sensitive headers are redacted.
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestSummary {
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    // When the request arrived, RFC 3339
+    pub timestamp: String,
+    // Until the response head was ready; a streamed body may take longer
+    pub latency_ms: f64,
+    // Request headers, after `REDACT_HEADERS`; repeats become arrays
+    pub headers: serde_json::Map<String, serde_json::Value>,
+}
+
+// Ring buffer of the latest `capacity` summaries; `capacity` is at least 1
+#[derive(Clone)]
+pub struct RecentRequests {
+    capacity: usize,
+    entries: Arc<Mutex<VecDeque<RequestSummary>>>,
+}
+
+impl RecentRequests {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Keeps `summary`, dropping the oldest once `capacity` are held
+    pub fn record(&self, summary: RequestSummary) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(summary);
+    }
+
+    // Oldest first
+    pub fn snapshot(&self) -> Vec<RequestSummary> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+}
@@ -0,0 +1,84 @@
+// Redaction of sensitive headers before they are logged or kept, so
+// credentials never end up in log files or `/debug/recent`.
+//
+// Every place the server writes or stores request headers goes through
+// `redact_headers`; headers sent back to the client that sent them, as
+// `/debug/echo` does, are its own and aren't touched.
+
+use std::str::FromStr;
+
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::HeaderMap;
+
+// What a redacted header's value is replaced with
+pub const REDACTED: &str = "***";
+
+// Redacted unless `REDACT_HEADERS` says otherwise
+pub const DEFAULT_REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+];
+
+// Header names whose values are redacted, parsed from a comma-separated
+// list. Names are case-insensitive, like the headers themselves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedactList {
+    names: Vec<HeaderName>,
+}
+
+impl RedactList {
+    // Redacts nothing
+    pub fn none() -> Self {
+        Self { names: Vec::new() }
+    }
+
+    pub fn contains(&self, name: &HeaderName) -> bool {
+        self.names.contains(name)
+    }
+}
+
+impl Default for RedactList {
+    fn default() -> Self {
+        Self {
+            names: DEFAULT_REDACTED_HEADERS
+                .iter()
+                .map(|name| HeaderName::from_static(name))
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for RedactList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let names = s
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| format!("{:?} is not a valid header name", name))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { names })
+    }
+}
+
+// A copy of `headers` with the value of every header on `redact` replaced
+// by `REDACTED`, each repeat included
+pub fn redact_headers(headers: &HeaderMap, redact: &RedactList) -> HeaderMap {
+    let mut redacted = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        let value = if redact.contains(name) {
+            HeaderValue::from_static(REDACTED)
+        } else {
+            value.clone()
+        };
+        redacted.append(name.clone(), value);
+    }
+    redacted
+}
@@ -0,0 +1,76 @@
+// Absolute URLs for redirects, as the client addressed the server.
+//
+// Behind a TLS-terminating proxy the request that arrives here is plain
+// HTTP addressed to the proxy's upstream, so the scheme and host a `Location`
+// needs come from `X-Forwarded-Proto` and `X-Forwarded-Host` when the proxy
+// sets them. The server has no TLS of its own: without those headers a
+// request was made over `http`. (Absolute-form targets, which carry their
+// own scheme and authority, are refused before a redirect is considered.)
+
+use hyper::header::HOST;
+use hyper::http::uri::Authority;
+use hyper::{HeaderMap, Uri};
+
+pub const FORWARDED_PROTO_HEADER: &str = "x-forwarded-proto";
+pub const FORWARDED_HOST_HEADER: &str = "x-forwarded-host";
+
+// The first entry of a comma-separated forwarding header: each proxy on the
+// way appends its own, so the first is the one the client talked to
+fn first_forwarded<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    let value = headers.get(name)?.to_str().ok()?;
+    let first = value.split(',').next()?.trim();
+    (!first.is_empty()).then_some(first)
+}
+
+// `https` or `http`, whichever the client used. An unrecognised
+// `X-Forwarded-Proto` is ignored rather than echoed into a URL.
+pub fn request_scheme(uri: &Uri, headers: &HeaderMap) -> &'static str {
+    let forwarded = first_forwarded(headers, FORWARDED_PROTO_HEADER);
+    match forwarded.or(uri.scheme_str()) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("https") => "https",
+        _ => "http",
+    }
+}
+
+// The host (and port, if given) the client used: `X-Forwarded-Host`, then
+// `Host`, then the authority of an absolute-form target. Values that aren't
+// a valid authority are skipped, so a `Location` can't be made to point at
+// something a header smuggled in.
+pub fn request_host(uri: &Uri, headers: &HeaderMap) -> Option<String> {
+    let valid = |host: &str| {
+        !host.contains('@') && host.parse::<Authority>().is_ok_and(|a| a.as_str() == host)
+    };
+    first_forwarded(headers, FORWARDED_HOST_HEADER)
+        .filter(|host| valid(host))
+        .or_else(|| {
+            headers
+                .get(HOST)
+                .and_then(|value| value.to_str().ok())
+                .filter(|host| valid(host))
+        })
+        .map(str::to_string)
+        .or_else(|| uri.authority().map(|authority| authority.to_string()))
+}
+
+// `target` (a path and query) as an absolute URL on the origin the client
+// used, or `None` when the request doesn't say which host that was
+pub fn absolute_url(uri: &Uri, headers: &HeaderMap, target: &str) -> Option<String> {
+    let host = request_host(uri, headers)?;
+    Some(format!(
+        "{}://{}{}",
+        request_scheme(uri, headers),
+        host,
+        target
+    ))
+}
+
+// Where an `http` request should be sent to reach the same resource over
+// `https`; `None` if it already arrived over `https` or names no host
+pub fn https_location(uri: &Uri, headers: &HeaderMap) -> Option<String> {
+    if request_scheme(uri, headers) == "https" {
+        return None;
+    }
+    let host = request_host(uri, headers)?;
+    let target = uri.path_and_query().map_or("/", |target| target.as_str());
+    Some(format!("https://{}{}", host, target))
+}
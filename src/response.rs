@@ -0,0 +1,55 @@
+// Responses several parts of the server send alike.
+//
+// Maintenance mode, readiness, the body memory budget and any future
+// downstream failure all turn clients away with a 503. Building it in one
+// place keeps the body shape and the `Retry-After` header the same
+// whichever of them did.
+
+use hyper::{Response, StatusCode};
+use serde::Serialize;
+
+use crate::body_budget::Backlog;
+use crate::timestamp::timestamp_rfc3339;
+use crate::{json_response, Body, JsonResponse};
+
+#[derive(Serialize)]
+struct BackpressureResponse {
+    message: String,
+    queue_depth: usize,
+    retry_after_secs: u64,
+    timestamp: String,
+    server: String,
+}
+
+// `503 Service Unavailable` telling the client to come back after
+// `retry_after_secs`
+pub fn service_unavailable(message: &str, retry_after_secs: u64) -> Response<Body> {
+    let response = JsonResponse {
+        message: message.to_string(),
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    let mut response = json_response(StatusCode::SERVICE_UNAVAILABLE, &response);
+    response
+        .headers_mut()
+        .insert(hyper::header::RETRY_AFTER, retry_after_secs.into());
+    response
+}
+
+// `503 Service Unavailable` for a request turned away by a full queue, with
+// the queue's depth and the estimated wait in the body as well as in
+// `Retry-After`
+pub fn backpressure(message: &str, backlog: Backlog) -> Response<Body> {
+    let response = BackpressureResponse {
+        message: message.to_string(),
+        queue_depth: backlog.queue_depth,
+        retry_after_secs: backlog.retry_after_secs,
+        timestamp: timestamp_rfc3339(),
+        server: "rust-http-server/1.0".to_string(),
+    };
+    let mut response = json_response(StatusCode::SERVICE_UNAVAILABLE, &response);
+    response
+        .headers_mut()
+        .insert(hyper::header::RETRY_AFTER, backlog.retry_after_secs.into());
+    response
+}
@@ -0,0 +1,158 @@
+// Short-lived caching of GET responses, per route.
+//
+// Routes are named by their pattern in `ROUTES`, as with route timeouts,
+// each with how long its responses are kept. A miss is single-flight: every
+// identical request arriving while the first is still being answered waits
+// for that answer instead of computing its own, so an expensive route isn't
+// hit by a thundering herd each time its entry expires.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http_body_util::BodyExt;
+use hyper::body::Bytes;
+use hyper::header::{HeaderMap, HeaderValue};
+use hyper::{Response, StatusCode};
+use tokio::sync::OnceCell;
+
+use crate::{full, Body};
+
+// `HIT` on responses served from the cache or shared with another request,
+// `MISS` on the one that computed them
+pub const CACHE_HEADER: &str = "x-cache";
+
+// Route pattern -> how long its responses are kept, parsed from
+// `pattern=ms,pattern=ms`. Unlisted routes aren't cached.
+#[derive(Debug, Clone, Default)]
+pub struct CacheRoutes {
+    ttls: HashMap<String, Duration>,
+}
+
+impl CacheRoutes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Keeps responses from the route registered as `pattern` for `ttl`
+    pub fn with_route(mut self, pattern: &str, ttl: Duration) -> Self {
+        self.ttls.insert(pattern.to_string(), ttl);
+        self
+    }
+
+    // How long `route`'s responses are kept; `None` when they aren't
+    pub fn ttl(&self, route: Option<&str>) -> Option<Duration> {
+        route.and_then(|route| self.ttls.get(route)).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ttls.is_empty()
+    }
+
+    // The patterns given a TTL
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.ttls.keys().map(String::as_str)
+    }
+}
+
+impl FromStr for CacheRoutes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .try_fold(CacheRoutes::new(), |routes, entry| {
+                let (pattern, ttl) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected route=ms, got {:?}", entry))?;
+                let ttl = match ttl.trim().parse::<u64>() {
+                    Ok(ms) if ms > 0 => Duration::from_millis(ms),
+                    _ => {
+                        return Err(format!(
+                            "expected a positive number of milliseconds for {:?}",
+                            pattern.trim()
+                        ))
+                    }
+                };
+                Ok(routes.with_route(pattern.trim(), ttl))
+            })
+    }
+}
+
+// A response as it is kept: status, headers and the whole body
+struct Entry {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    expires: Instant,
+}
+
+impl Entry {
+    fn response(&self, cache: &'static str) -> Response<Body> {
+        let mut response = Response::new(full(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+            .headers_mut()
+            .insert(CACHE_HEADER, HeaderValue::from_static(cache));
+        response
+    }
+}
+
+// Cached responses by request key. An entry is created empty by the first
+// request for its key and filled once that request's response is ready.
+#[derive(Clone, Default)]
+pub struct ResponseCache {
+    entries: Arc<Mutex<HashMap<String, Arc<OnceCell<Entry>>>>>,
+}
+
+impl ResponseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The response kept under `key`, or the one `compute` produces, kept for
+    // `ttl` if it is a success. While `compute` runs, requests for the same
+    // key wait for it; should it be cancelled, one of them takes over.
+    pub async fn get_or_compute<F>(&self, key: String, ttl: Duration, compute: F) -> Response<Body>
+    where
+        F: Future<Output = Response<Body>>,
+    {
+        let cell = {
+            let mut entries = self.entries.lock().unwrap();
+            let now = Instant::now();
+            entries.retain(|_, cell| cell.get().is_none_or(|entry| entry.expires > now));
+            entries.entry(key).or_default().clone()
+        };
+
+        let mut computed = false;
+        let entry = cell
+            .get_or_init(|| {
+                computed = true;
+                async move {
+                    let (parts, body) = compute.await.into_parts();
+                    let body = match body.collect().await {
+                        Ok(collected) => collected.to_bytes(),
+                        Err(never) => match never {},
+                    };
+                    // Errors are only shared with requests already waiting
+                    let expires = if parts.status.is_success() {
+                        Instant::now() + ttl
+                    } else {
+                        Instant::now()
+                    };
+                    Entry {
+                        status: parts.status,
+                        headers: parts.headers,
+                        body,
+                        expires,
+                    }
+                }
+            })
+            .await;
+        entry.response(if computed { "MISS" } else { "HIT" })
+    }
+}
@@ -0,0 +1,140 @@
+// Operator-chosen static response headers, per route.
+//
+// Routes are named by their pattern in `ROUTES`, as with `CACHE_CONTROL`;
+// `/debug/*` names every route whose pattern starts with `/debug/`, and `*`
+// every response, including `404`s that match no route. Headers go on every
+// status, so an `X-Robots-Tag` still covers a route's errors.
+
+use std::str::FromStr;
+
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::Response;
+
+// Headers that describe the message rather than the resource. hyper sets
+// them to match the body and connection, and a configured copy would
+// contradict that.
+const RESERVED: &[&str] = &[
+    "connection",
+    "content-length",
+    "keep-alive",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+// Which routes an entry covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteSelector {
+    // `*`
+    All,
+    // `/debug/*`, holding `/debug/`
+    Prefix(String),
+    // A route pattern as registered
+    Route(String),
+}
+
+impl RouteSelector {
+    fn parse(pattern: &str) -> Self {
+        match pattern {
+            "*" => RouteSelector::All,
+            _ => match pattern.strip_suffix('*') {
+                Some(prefix) => RouteSelector::Prefix(prefix.to_string()),
+                None => RouteSelector::Route(pattern.to_string()),
+            },
+        }
+    }
+
+    // Whether the route registered as `route` is covered; `None` is a path
+    // no route matches, which only `*` covers
+    pub fn matches(&self, route: Option<&str>) -> bool {
+        match (self, route) {
+            (RouteSelector::All, _) => true,
+            (RouteSelector::Prefix(prefix), Some(route)) => route.starts_with(prefix.as_str()),
+            (RouteSelector::Route(pattern), Some(route)) => pattern == route,
+            (_, None) => false,
+        }
+    }
+}
+
+// Route selector -> header, parsed from `pattern=Name: value;pattern=Name:
+// value`. Entries are separated by `;` because values may contain commas; a
+// route gets several headers, or several values of one, from several
+// entries.
+#[derive(Debug, Clone, Default)]
+pub struct RouteHeaders {
+    entries: Vec<(RouteSelector, HeaderName, HeaderValue)>,
+}
+
+impl RouteHeaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Sends `name: value` from the routes `pattern` selects
+    pub fn with_header(mut self, pattern: &str, name: HeaderName, value: HeaderValue) -> Self {
+        self.entries
+            .push((RouteSelector::parse(pattern), name, value));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // The selectors given headers, `*` included
+    pub fn selectors(&self) -> impl Iterator<Item = &RouteSelector> {
+        self.entries.iter().map(|(selector, _, _)| selector)
+    }
+
+    // Adds the headers for `route` (`None` for paths no route matches).
+    // A configured header replaces any the handler sent under that name.
+    pub fn apply<B>(&self, route: Option<&str>, response: &mut Response<B>) {
+        let mut matching = self
+            .entries
+            .iter()
+            .filter(|(selector, _, _)| selector.matches(route))
+            .peekable();
+        if matching.peek().is_none() {
+            return;
+        }
+        let headers = response.headers_mut();
+        let mut replaced: Vec<&HeaderName> = Vec::new();
+        for (_, name, value) in matching {
+            if !replaced.contains(&name) {
+                headers.remove(name);
+                replaced.push(name);
+            }
+            headers.append(name.clone(), value.clone());
+        }
+    }
+}
+
+impl FromStr for RouteHeaders {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(';')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .try_fold(RouteHeaders::new(), |headers, entry| {
+                let (pattern, header) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected route=Name: value, got {:?}", entry))?;
+                let (name, value) = header
+                    .split_once(':')
+                    .ok_or_else(|| format!("expected Name: value, got {:?}", header.trim()))?;
+                let name = HeaderName::from_bytes(name.trim().as_bytes())
+                    .map_err(|_| format!("{:?} is not a valid header name", name.trim()))?;
+                if RESERVED.contains(&name.as_str()) {
+                    return Err(format!(
+                        "{} is set by the server and can't be configured",
+                        name
+                    ));
+                }
+                let value = HeaderValue::from_str(value.trim())
+                    .map_err(|_| format!("invalid value {:?} for {}", value.trim(), name))?;
+                Ok(headers.with_header(pattern.trim(), name, value))
+            })
+    }
+}
@@ -0,0 +1,65 @@
+// Per-route overrides of `REQUEST_TIMEOUT_MS`.
+//
+// One global timeout fits few servers: `/stats` should answer at once,
+// while a long `/delay` is the whole point of that route. Routes are named
+// by their pattern in `ROUTES`, as with route toggles, and may also opt out
+// of the timeout entirely. Deadlines a caller propagates still apply.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Route pattern -> timeout (`None` for none at all), parsed from
+// `pattern=ms,pattern=off`. Routes without an entry use the global one.
+#[derive(Debug, Clone, Default)]
+pub struct RouteTimeouts {
+    overrides: HashMap<String, Option<Duration>>,
+}
+
+impl RouteTimeouts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Gives the route registered as `pattern` its own timeout, or none
+    pub fn with_route(mut self, pattern: &str, timeout: Option<Duration>) -> Self {
+        self.overrides.insert(pattern.to_string(), timeout);
+        self
+    }
+
+    // The timeout for `route` (`None` for paths no route matches), falling
+    // back to `default`
+    pub fn timeout(&self, route: Option<&str>, default: Option<Duration>) -> Option<Duration> {
+        route
+            .and_then(|route| self.overrides.get(route))
+            .copied()
+            .unwrap_or(default)
+    }
+
+    // The patterns given an override
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.overrides.keys().map(String::as_str)
+    }
+}
+
+impl FromStr for RouteTimeouts {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .try_fold(RouteTimeouts::new(), |timeouts, entry| {
+                let (pattern, timeout) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected route=ms|off, got {:?}", entry))?;
+                let timeout = match timeout.trim() {
+                    "off" | "0" => None,
+                    ms => Some(Duration::from_millis(ms.parse().map_err(|_| {
+                        format!("expected milliseconds or off for {:?}", pattern.trim())
+                    })?)),
+                };
+                Ok(timeouts.with_route(pattern.trim(), timeout))
+            })
+    }
+}
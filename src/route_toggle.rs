@@ -0,0 +1,67 @@
+// Per-route on/off switches, so operators can shrink the exposed surface
+// without recompiling.
+//
+// Routes are named by their pattern as registered in `ROUTES`
+// (`/debug/echo`, `/kv/:key`). A disabled route answers exactly like one
+// that doesn't exist: 404, no `Allow` list, no suggestions.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Route pattern -> enabled, parsed from `pattern=on,pattern=off`. Routes
+// without an entry keep their default.
+#[derive(Debug, Clone, Default)]
+pub struct RouteToggles {
+    overrides: HashMap<String, bool>,
+}
+
+impl RouteToggles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Turns the route registered as `pattern` on or off
+    pub fn with_route(mut self, pattern: &str, enabled: bool) -> Self {
+        self.overrides.insert(pattern.to_string(), enabled);
+        self
+    }
+
+    pub fn enabled(&self, pattern: &str) -> bool {
+        self.overrides
+            .get(pattern)
+            .copied()
+            .unwrap_or_else(|| enabled_by_default(pattern))
+    }
+
+    // The patterns given an explicit setting
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.overrides.keys().map(String::as_str)
+    }
+}
+
+// Debug routes reflect whatever a client sends, so release builds leave
+// them off unless asked
+pub fn enabled_by_default(pattern: &str) -> bool {
+    cfg!(debug_assertions) || !pattern.starts_with("/debug/")
+}
+
+impl FromStr for RouteToggles {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .try_fold(RouteToggles::new(), |toggles, entry| {
+                let (pattern, enabled) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected route=on|off, got {:?}", entry))?;
+                let enabled = match enabled.trim().to_ascii_lowercase().as_str() {
+                    "1" | "true" | "yes" | "on" => true,
+                    "0" | "false" | "no" | "off" => false,
+                    _ => return Err(format!("expected on or off for {:?}", pattern.trim())),
+                };
+                Ok(toggles.with_route(pattern.trim(), enabled))
+            })
+    }
+}
@@ -0,0 +1,58 @@
+// Startup self-check: a `GET /health` sent to the server's own listening
+// address, proving the socket actually answers before the server calls
+// itself ready. A wildcard address is probed over loopback, so this
+// catches a port that can't be reached from the machine itself, not
+// firewalls further out.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::header::HOST;
+use hyper::Request;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+
+// Where to connect to reach a listener bound to `addr`: itself, or
+// loopback when it is bound to every interface
+pub fn target(addr: SocketAddr) -> SocketAddr {
+    let ip = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    SocketAddr::new(ip, addr.port())
+}
+
+// Sends `GET {base_path}/health` to the listener bound to `addr`, naming
+// `host` in the `Host` header when given (the address otherwise); an error
+// says what went wrong if no 200 came back within `timeout`
+pub async fn probe(
+    addr: SocketAddr,
+    base_path: &str,
+    host: Option<&str>,
+    timeout: Duration,
+) -> Result<(), String> {
+    let uri = format!("http://{}{}/health", target(addr), base_path);
+    let mut request = Request::get(&uri);
+    if let Some(host) = host {
+        request = request.header(HOST, host);
+    }
+    let request = request
+        .body(Empty::<Bytes>::new())
+        .map_err(|err| err.to_string())?;
+    let client = Client::builder(TokioExecutor::new()).build_http();
+    let response = tokio::time::timeout(timeout, client.request(request))
+        .await
+        .map_err(|_| format!("{} didn't answer within {}ms", uri, timeout.as_millis()))?
+        .map_err(|err| format!("{}: {}", uri, err))?;
+    let status = response.status();
+    // Drain the body so the connection closes cleanly
+    let _ = response.into_body().collect().await;
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(format!("{} answered {}", uri, status))
+    }
+}
@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+// Share of the full rate allowed the moment the server starts
+const INITIAL_FRACTION: f64 = 0.1;
+
+// A cap on the accept rate for a while after startup, so a freshly restarted
+// instance isn't flattened by every client reconnecting at once. The cap
+// climbs linearly from a tenth of `full_rate` to all of it over `window`,
+// then stops applying. Only the accept loop touches it, so it needs no
+// locking.
+pub struct SlowStart {
+    start: Instant,
+    window: Duration,
+    full_rate: f64,
+    // Connections that may be admitted right now; refilled at the current
+    // rate and never more than a tenth of a second's worth, so a quiet spell
+    // early on can't be spent as one burst
+    tokens: f64,
+    last: Instant,
+}
+
+impl SlowStart {
+    pub fn new(start: Instant, window: Duration, full_rate: f64) -> Self {
+        Self {
+            start,
+            window,
+            full_rate,
+            tokens: 1.0,
+            last: start,
+        }
+    }
+
+    // Connections per second allowed at `now`; `None` once the ramp is over
+    pub fn rate(&self, now: Instant) -> Option<f64> {
+        let elapsed = now.saturating_duration_since(self.start);
+        if elapsed >= self.window {
+            return None;
+        }
+        let progress = elapsed.as_secs_f64() / self.window.as_secs_f64();
+        let fraction = INITIAL_FRACTION + (1.0 - INITIAL_FRACTION) * progress;
+        Some(self.full_rate * fraction)
+    }
+
+    // Whether a connection accepted at `now` may be served
+    pub fn admit(&mut self, now: Instant) -> bool {
+        let Some(rate) = self.rate(now) else {
+            return true;
+        };
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        self.tokens = (self.tokens + rate * elapsed).min((rate / 10.0).max(1.0));
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// Lifetime counters persisted across restarts.
+//
+// With `STATS_SNAPSHOT_PATH` set, the cumulative `/stats` counters are
+// written there on graceful shutdown and read back on startup, so totals
+// keep counting from where the previous process stopped. Gauges (open
+// WebSockets) and uptime always start fresh.
+
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+// The cumulative counters, as stored on disk. Fields missing from an older
+// file read as zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StatsSnapshot {
+    pub total_requests: u64,
+    pub uncompressed_bytes: u64,
+    pub compressed_bytes: u64,
+    pub cancelled_requests: u64,
+    pub client_disconnects: u64,
+    pub accepted_connections: u64,
+    pub body_budget_rejections: u64,
+    pub by_http_version: HttpVersionCounts,
+}
+
+// Requests per HTTP version, keyed the way `/stats` reports them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpVersionCounts {
+    #[serde(rename = "HTTP/0.9")]
+    pub http_09: u64,
+    #[serde(rename = "HTTP/1.0")]
+    pub http_10: u64,
+    #[serde(rename = "HTTP/1.1")]
+    pub http_11: u64,
+    #[serde(rename = "HTTP/2")]
+    pub http_2: u64,
+    #[serde(rename = "HTTP/3")]
+    pub http_3: u64,
+}
+
+impl StatsSnapshot {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = std::fs::read(path)?;
+        serde_json::from_slice(&contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    // `load`, but a missing or unreadable snapshot starts every counter at
+    // zero rather than stopping the server
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        match Self::load(path) {
+            Ok(snapshot) => snapshot,
+            Err(err) => {
+                eprintln!(
+                    "Warning: no usable stats snapshot at {} ({}); counters start at zero",
+                    path.display(),
+                    err
+                );
+                Self::default()
+            }
+        }
+    }
+
+    // Writes to a temporary file beside `path` and renames it into place,
+    // so a crash mid-write can't leave a truncated snapshot behind
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let json = serde_json::to_vec_pretty(self).map_err(io::Error::other)?;
+        std::fs::write(&temporary, json)?;
+        std::fs::rename(&temporary, path)
+    }
+}
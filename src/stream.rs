@@ -0,0 +1,328 @@
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use hyper::body::{Body, Bytes, Frame, SizeHint};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use tokio::time::Sleep;
+
+use crate::cancel::CancelGuard;
+use crate::compression::Crc32;
+
+pub const CHECKSUM_TRAILER: &str = "x-checksum";
+
+// Streaming body that yields `chunk` `count` times, optionally pausing
+// `interval` between chunks, and ends with an `X-Checksum` trailer holding
+// the CRC-32 (hex) of every byte sent.
+pub struct EchoStream {
+    chunk: Bytes,
+    remaining: usize,
+    // Where the first chunk starts and the last chunk ends, for ranges that
+    // begin or end mid-chunk
+    first_offset: usize,
+    last_end: usize,
+    interval: Option<Duration>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    checksum: Crc32,
+    trailers_sent: bool,
+    cancel_guard: Option<CancelGuard>,
+}
+
+impl EchoStream {
+    pub fn new(chunk: Bytes, count: usize, interval: Option<Duration>) -> Self {
+        Self {
+            last_end: chunk.len(),
+            chunk,
+            remaining: count,
+            first_offset: 0,
+            interval,
+            sleep: None,
+            checksum: Crc32::new(),
+            trailers_sent: false,
+            cancel_guard: None,
+        }
+    }
+
+    // Bytes in the whole stream
+    pub fn len(&self) -> usize {
+        self.chunk.len() * self.remaining
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Sends only bytes `start..end` of the stream, which must be a non-empty
+    // range within `len()`
+    pub fn with_range(mut self, start: usize, end: usize) -> Self {
+        debug_assert!(start < end && end <= self.len());
+        let chunk_len = self.chunk.len();
+        let first_chunk = start / chunk_len;
+        let last_chunk = (end - 1) / chunk_len;
+        self.remaining = last_chunk - first_chunk + 1;
+        self.first_offset = start % chunk_len;
+        self.last_end = (end - 1) % chunk_len + 1;
+        self
+    }
+
+    // Counts the stream as cancelled if it is dropped before the trailers go out
+    pub fn with_cancel_guard(mut self, guard: CancelGuard) -> Self {
+        self.cancel_guard = Some(guard);
+        self
+    }
+}
+
+// Largest data frame `RepeatStream` yields
+const REPEAT_FRAME_BYTES: usize = 16 * 1024;
+
+// Streaming body of `prefix`, `chunk` repeated `count` times, then `suffix`.
+// The repeats are batched into frames of about 16 KiB, so memory use stays
+// flat however large `count` is.
+pub struct RepeatStream {
+    prefix: Option<Bytes>,
+    batch: Bytes,
+    batches: usize,
+    last: Option<Bytes>,
+    suffix: Option<Bytes>,
+    cancel_guard: Option<CancelGuard>,
+}
+
+impl RepeatStream {
+    pub fn new(prefix: Bytes, chunk: &[u8], count: usize, suffix: Bytes) -> Self {
+        let per_frame = (REPEAT_FRAME_BYTES / chunk.len().max(1)).clamp(1, count.max(1));
+        let rest = count % per_frame;
+        Self {
+            prefix: (!prefix.is_empty()).then_some(prefix),
+            batch: Bytes::from(chunk.repeat(per_frame)),
+            batches: count / per_frame,
+            last: (rest > 0).then(|| Bytes::from(chunk.repeat(rest))),
+            suffix: (!suffix.is_empty()).then_some(suffix),
+            cancel_guard: None,
+        }
+    }
+
+    // Counts the stream as cancelled if it is dropped before the last frame
+    pub fn with_cancel_guard(mut self, guard: CancelGuard) -> Self {
+        self.cancel_guard = Some(guard);
+        self
+    }
+
+    fn next_data(&mut self) -> Option<Bytes> {
+        if let Some(prefix) = self.prefix.take() {
+            return Some(prefix);
+        }
+        if self.batches > 0 {
+            self.batches -= 1;
+            return Some(self.batch.clone());
+        }
+        self.last.take().or_else(|| self.suffix.take())
+    }
+}
+
+// Streaming body of `count` JSON lines, `{"index":N,"timestamp":"..."}`.
+// Each line is rendered when it is polled, so nothing is buffered ahead
+// and the timestamps say when the line was actually produced.
+pub struct NdjsonStream {
+    next: usize,
+    count: usize,
+    cancel_guard: Option<CancelGuard>,
+}
+
+impl NdjsonStream {
+    pub fn new(count: usize) -> Self {
+        Self {
+            next: 0,
+            count,
+            cancel_guard: None,
+        }
+    }
+
+    // Counts the stream as cancelled if it is dropped before the last line.
+    // An empty stream has nothing left to cancel.
+    pub fn with_cancel_guard(mut self, guard: CancelGuard) -> Self {
+        if self.is_end_stream() {
+            guard.finish();
+        } else {
+            self.cancel_guard = Some(guard);
+        }
+        self
+    }
+}
+
+// Streaming body of the text `groups` yields, one frame per group. Each
+// group is rendered when it is polled, so only one is held at a time however
+// many the iterator has.
+pub struct TextStream<I> {
+    groups: I,
+    done: bool,
+}
+
+impl<I> TextStream<I> {
+    pub fn new(groups: I) -> Self {
+        Self {
+            groups,
+            done: false,
+        }
+    }
+}
+
+impl<I> Body for TextStream<I>
+where
+    I: Iterator<Item = String> + Unpin,
+{
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        match self.groups.next() {
+            Some(group) => Poll::Ready(Some(Ok(Frame::data(Bytes::from(group))))),
+            None => {
+                self.done = true;
+                Poll::Ready(None)
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        // Unknown up front: chunked, and left alone by compression
+        SizeHint::default()
+    }
+}
+
+impl Body for NdjsonStream {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        if self.next >= self.count {
+            return Poll::Ready(None);
+        }
+        let line = format!(
+            "{{\"index\":{},\"timestamp\":\"{}\"}}\n",
+            self.next,
+            crate::timestamp::timestamp_rfc3339()
+        );
+        self.next += 1;
+        // As with `RepeatStream`, hyper stops polling after the last frame
+        if self.is_end_stream() {
+            if let Some(guard) = self.cancel_guard.take() {
+                guard.finish();
+            }
+        }
+        Poll::Ready(Some(Ok(Frame::data(Bytes::from(line)))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.next >= self.count
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        // Unknown up front: chunked, and left alone by compression
+        SizeHint::default()
+    }
+}
+
+impl Body for RepeatStream {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        let data = self.next_data();
+        // hyper stops polling once `is_end_stream` holds, so finish the guard
+        // with the last frame rather than on the `None` after it
+        if self.is_end_stream() {
+            if let Some(guard) = self.cancel_guard.take() {
+                guard.finish();
+            }
+        }
+        Poll::Ready(data.map(|data| Ok(Frame::data(data))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.prefix.is_none() && self.batches == 0 && self.last.is_none() && self.suffix.is_none()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        // Unknown up front so compression leaves the body alone instead of
+        // buffering it
+        SizeHint::default()
+    }
+}
+
+impl Body for EchoStream {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        let this = &mut *self;
+
+        if this.remaining == 0 {
+            if this.trailers_sent {
+                return Poll::Ready(None);
+            }
+            this.trailers_sent = true;
+            if let Some(guard) = this.cancel_guard.take() {
+                guard.finish();
+            }
+            let mut trailers = HeaderMap::new();
+            trailers.insert(
+                HeaderName::from_static(CHECKSUM_TRAILER),
+                HeaderValue::from_str(&format!("{:08x}", this.checksum.finish())).unwrap(),
+            );
+            return Poll::Ready(Some(Ok(Frame::trailers(trailers))));
+        }
+
+        if let Some(sleep) = this.sleep.as_mut() {
+            if sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.sleep = None;
+        }
+
+        this.remaining -= 1;
+        let end = if this.remaining == 0 {
+            this.last_end
+        } else {
+            this.chunk.len()
+        };
+        let data = this.chunk.slice(this.first_offset..end);
+        this.first_offset = 0;
+        this.checksum.update(&data);
+        if this.remaining > 0 {
+            this.sleep = this
+                .interval
+                .map(|interval| Box::pin(tokio::time::sleep(interval)));
+        }
+        Poll::Ready(Some(Ok(Frame::data(data))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0 && self.trailers_sent
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        // Unknown up front so hyper uses chunked encoding, which trailers need
+        SizeHint::default()
+    }
+}
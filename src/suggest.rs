@@ -0,0 +1,64 @@
+// "Did you mean?" suggestions for unknown paths.
+//
+// Routes are compared segment-aware: `:param` segments in a pattern take the
+// value of the request's segment in the same position, so `/dealy/250` is
+// measured against `/delay/250` rather than the literal `/delay/:ms`.
+
+// Edits allowed before a route stops being a plausible suggestion
+pub const MAX_DISTANCE: usize = 2;
+
+// Most suggestions returned for a single path
+pub const MAX_SUGGESTIONS: usize = 3;
+
+// Up to `MAX_SUGGESTIONS` route patterns closest to `path`, nearest first.
+// Ties keep the order of `routes`.
+pub fn suggest(path: &str, routes: &[&'static str]) -> Vec<&'static str> {
+    // Short paths get a tighter bound, or "/x" would match every short route
+    let threshold = MAX_DISTANCE.min(path.chars().count() / 3);
+
+    let mut scored: Vec<(usize, &'static str)> = routes
+        .iter()
+        .map(|&route| (levenshtein(path, &fill_params(route, path)), route))
+        .filter(|&(distance, _)| distance <= threshold)
+        .collect();
+    scored.sort_by_key(|&(distance, _)| distance);
+
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, route)| route)
+        .collect()
+}
+
+// Substitutes `:param` segments of `pattern` with the matching segments of
+// `path`, dropping params the path doesn't reach (so `/delay` is close to
+// `/delay/:ms`)
+fn fill_params(pattern: &str, path: &str) -> String {
+    let mut actual = path.split('/');
+    pattern
+        .split('/')
+        .filter_map(|segment| match (segment.starts_with(':'), actual.next()) {
+            (true, value) => value,
+            (false, _) => Some(segment),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Levenshtein edit distance over chars, using a single rolling row
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
@@ -0,0 +1,47 @@
+// Timestamps the server writes into responses and logs.
+//
+// Local time by default; with `USE_UTC_TIMESTAMPS` every timestamp is UTC
+// and written with `Z`, so servers in different zones agree. The choice is
+// process-wide, like the local zone it replaces: `AppState::new` sets it,
+// and handlers that never see the config read it from here. chrono already
+// falls back to UTC when the local zone can't be read, so none of this can
+// fail.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat, Utc};
+
+static UTC: AtomicBool = AtomicBool::new(false);
+
+pub fn set_utc(utc: bool) {
+    UTC.store(utc, Ordering::Relaxed);
+}
+
+pub fn is_utc() -> bool {
+    UTC.load(Ordering::Relaxed)
+}
+
+// The current time, in UTC or the local zone
+pub fn now() -> DateTime<FixedOffset> {
+    if is_utc() {
+        Utc::now().fixed_offset()
+    } else {
+        Local::now().fixed_offset()
+    }
+}
+
+// `time` as RFC 3339, ending in `Z` rather than `+00:00` in UTC mode.
+// Otherwise the same as `to_rfc3339`.
+pub fn rfc3339(time: &DateTime<FixedOffset>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, is_utc())
+}
+
+// The current time as RFC 3339, for response bodies
+pub fn timestamp_rfc3339() -> String {
+    rfc3339(&now())
+}
+
+// The current time as request log lines start with it
+pub fn log_time() -> String {
+    now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
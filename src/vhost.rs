@@ -0,0 +1,174 @@
+// Host-based virtual hosting.
+//
+// A `HostTable` maps hostnames to the `Site` they serve. It is consulted
+// before path matching, so `admin.example.com/echo/hi` is a 404 even though
+// `/echo/:msg` exists on the API host. An empty table turns the feature off.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use hyper::header::HOST;
+use hyper::Request;
+
+// A group of routes served under one hostname
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Site {
+    // Every route
+    All,
+    // Echo (including WebSocket and the request reflector), NDJSON
+    // streaming, delay, error injection and the KV store
+    Api,
+    // Health, readiness, statistics and the admin API
+    Admin,
+}
+
+impl Site {
+    // Whether `path` (or a route pattern) belongs to this site. `/`,
+    // `/favicon.ico` and the API description are served everywhere.
+    pub fn serves(self, path: &str) -> bool {
+        match self {
+            Site::All => true,
+            Site::Api => {
+                path == "/"
+                    || path == "/favicon.ico"
+                    || path == "/openapi.json"
+                    || path == "/docs"
+                    || path == "/echo"
+                    || path.starts_with("/echo/")
+                    || path.starts_with("/echo-header/")
+                    || path.starts_with("/delay/")
+                    || path.starts_with("/error/")
+                    || path == "/ws"
+                    || path.starts_with("/kv/")
+                    || path == "/debug/echo"
+                    || path.starts_with("/debug/block/")
+                    || path == "/stream/ndjson"
+            }
+            Site::Admin => {
+                matches!(
+                    path,
+                    "/" | "/favicon.ico"
+                        | "/openapi.json"
+                        | "/docs"
+                        | "/health"
+                        | "/livez"
+                        | "/readyz"
+                        | "/stats"
+                        | "/stats.csv"
+                        | "/metrics"
+                        | "/debug/runtime"
+                        | "/debug/recent"
+                ) || path.starts_with("/admin/")
+            }
+        }
+    }
+}
+
+impl FromStr for Site {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "all" => Ok(Site::All),
+            "api" => Ok(Site::Api),
+            "admin" => Ok(Site::Admin),
+            other => Err(format!(
+                "unknown site {:?} (expected all, api or admin)",
+                other
+            )),
+        }
+    }
+}
+
+// Hostname -> site table, parsed from `host=site,host=site`
+#[derive(Debug, Clone, Default)]
+pub struct HostTable {
+    sites: HashMap<String, Site>,
+}
+
+impl HostTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds or replaces the site served for `host`
+    pub fn with_host(mut self, host: &str, site: Site) -> Self {
+        self.sites.insert(normalize_host(host), site);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    // The site for `host`, or for `default_host` when `host` is missing or
+    // unconfigured. `None` means the request should be refused.
+    pub fn resolve(&self, host: Option<&str>, default_host: Option<&str>) -> Option<Site> {
+        host.and_then(|host| self.sites.get(&normalize_host(host)))
+            .or_else(|| default_host.and_then(|host| self.sites.get(&normalize_host(host))))
+            .copied()
+    }
+
+    // A configured hostname whose site serves `path`: `preferred` if it
+    // does, else the first such name in sorted order, so a request sent
+    // with it is routed there
+    pub fn host_serving(&self, path: &str, preferred: Option<&str>) -> Option<String> {
+        if let Some(host) = preferred {
+            if self
+                .resolve(Some(host), None)
+                .is_some_and(|site| site.serves(path))
+            {
+                return Some(normalize_host(host));
+            }
+        }
+        let mut hosts: Vec<&String> = self
+            .sites
+            .iter()
+            .filter(|(_, site)| site.serves(path))
+            .map(|(host, _)| host)
+            .collect();
+        hosts.sort();
+        hosts.first().map(|host| host.to_string())
+    }
+}
+
+impl FromStr for HostTable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .try_fold(HostTable::new(), |table, entry| {
+                let (host, site) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected host=site, got {:?}", entry))?;
+                if host.trim().is_empty() {
+                    return Err(format!("missing hostname in {:?}", entry));
+                }
+                Ok(table.with_host(host, site.parse()?))
+            })
+    }
+}
+
+// The hostname a request was sent to: the Host header, else the URI
+// authority (absolute-form requests)
+pub fn request_host<B>(req: &Request<B>) -> Option<&str> {
+    req.headers()
+        .get(HOST)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| req.uri().authority().map(|authority| authority.as_str()))
+}
+
+// Lowercases and strips the port and any trailing dot: `API.example.com.:80`
+// and `api.example.com` are the same host
+fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    let host = if let Some(rest) = host.strip_prefix('[') {
+        // IPv6 literal, e.g. [::1]:8080
+        rest.split(']').next().unwrap_or(rest)
+    } else {
+        host.rsplit_once(':').map_or(host, |(name, _)| name)
+    };
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
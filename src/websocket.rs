@@ -0,0 +1,284 @@
+// WebSocket echo over hyper's upgrade mechanism (RFC 6455).
+//
+// Only what an echo server needs is implemented: the opening handshake,
+// masked client frames, fragmentation, ping/pong and the closing handshake.
+// Extensions and subprotocols are never negotiated.
+
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use hyper::header::{CONNECTION, UPGRADE};
+use hyper::HeaderMap;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+// Appended to the client's key before hashing, per RFC 6455 section 1.3
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+pub const KEY_HEADER: &str = "sec-websocket-key";
+pub const VERSION_HEADER: &str = "sec-websocket-version";
+pub const ACCEPT_HEADER: &str = "sec-websocket-accept";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+pub const OPCODE_TEXT: u8 = 0x1;
+pub const OPCODE_BINARY: u8 = 0x2;
+pub const OPCODE_CLOSE: u8 = 0x8;
+pub const OPCODE_PING: u8 = 0x9;
+pub const OPCODE_PONG: u8 = 0xA;
+
+// Close status codes sent by the server
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_INVALID_DATA: u16 = 1007;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+// The `Sec-WebSocket-Accept` value for a valid upgrade request, or `None`
+// when the headers don't ask for a version 13 WebSocket
+pub fn handshake_accept(headers: &HeaderMap) -> Option<String> {
+    let has_token = |name, token: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|value| value.trim().eq_ignore_ascii_case(token))
+    };
+
+    if !has_token(UPGRADE, "websocket") || !has_token(CONNECTION, "upgrade") {
+        return None;
+    }
+    if headers.get(VERSION_HEADER)?.as_bytes() != b"13" {
+        return None;
+    }
+    let key = headers.get(KEY_HEADER)?.to_str().ok()?.trim();
+    (!key.is_empty()).then(|| accept_key(key))
+}
+
+// base64(SHA-1(key + GUID))
+pub fn accept_key(key: &str) -> String {
+    let mut input = key.as_bytes().to_vec();
+    input.extend_from_slice(ACCEPT_GUID.as_bytes());
+    STANDARD.encode(sha1(&input))
+}
+
+// Keeps a gauge raised for as long as the guard is alive
+pub struct ActiveGuard(Arc<AtomicU64>);
+
+impl ActiveGuard {
+    pub fn new(gauge: &Arc<AtomicU64>) -> Self {
+        gauge.fetch_add(1, Ordering::Relaxed);
+        Self(gauge.clone())
+    }
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+// One frame as read off the wire, already unmasked
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+enum ReadError {
+    Io(io::Error),
+    Close(u16),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+// Echoes text and binary messages until the client closes the connection.
+// Messages larger than `max_message` bytes close it with 1009.
+pub async fn echo<S>(mut io: S, max_message: usize) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // Opcode and payload of a fragmented message still being received
+    let mut partial: Option<(u8, Vec<u8>)> = None;
+
+    loop {
+        let frame = match read_frame(&mut io, max_message).await {
+            Ok(frame) => frame,
+            Err(ReadError::Close(code)) => return close(&mut io, code).await,
+            Err(ReadError::Io(err)) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(ReadError::Io(err)) => return Err(err),
+        };
+
+        match frame.opcode {
+            OPCODE_PING => write_frame(&mut io, OPCODE_PONG, &frame.payload).await?,
+            OPCODE_PONG => {}
+            OPCODE_CLOSE => {
+                // Echo the status code back, as the closing handshake expects
+                let code = match frame.payload.len() {
+                    0 => Vec::new(),
+                    1 => return close(&mut io, CLOSE_PROTOCOL_ERROR).await,
+                    _ => frame.payload[..2].to_vec(),
+                };
+                write_frame(&mut io, OPCODE_CLOSE, &code).await?;
+                return Ok(());
+            }
+            OPCODE_TEXT | OPCODE_BINARY if partial.is_none() => {
+                partial = Some((frame.opcode, frame.payload));
+            }
+            OPCODE_CONTINUATION if partial.is_some() => {
+                let (_, message) = partial.as_mut().unwrap();
+                if message.len() + frame.payload.len() > max_message {
+                    return close(&mut io, CLOSE_TOO_BIG).await;
+                }
+                message.extend_from_slice(&frame.payload);
+            }
+            _ => return close(&mut io, CLOSE_PROTOCOL_ERROR).await,
+        }
+
+        if frame.fin && frame.opcode < OPCODE_CLOSE {
+            let (opcode, message) = partial.take().unwrap();
+            if opcode == OPCODE_TEXT && std::str::from_utf8(&message).is_err() {
+                return close(&mut io, CLOSE_INVALID_DATA).await;
+            }
+            write_frame(&mut io, opcode, &message).await?;
+        }
+    }
+}
+
+async fn read_frame<S>(io: &mut S, max_message: usize) -> Result<Frame, ReadError>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut head = [0u8; 2];
+    io.read_exact(&mut head).await?;
+
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0F;
+    let masked = head[1] & 0x80 != 0;
+    // No extensions are negotiated, so RSV1-3 must be clear, and clients
+    // must mask every frame
+    if head[0] & 0x70 != 0 || !masked {
+        return Err(ReadError::Close(CLOSE_PROTOCOL_ERROR));
+    }
+
+    let len = match head[1] & 0x7F {
+        126 => u64::from(io.read_u16().await?),
+        127 => io.read_u64().await?,
+        len => u64::from(len),
+    };
+    let is_control = opcode & 0x8 != 0;
+    if is_control && (!fin || len > 125) {
+        return Err(ReadError::Close(CLOSE_PROTOCOL_ERROR));
+    }
+    if len > max_message as u64 {
+        return Err(ReadError::Close(CLOSE_TOO_BIG));
+    }
+
+    let mut mask = [0u8; 4];
+    io.read_exact(&mut mask).await?;
+    let mut payload = vec![0u8; len as usize];
+    io.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok(Frame {
+        fin,
+        opcode,
+        payload,
+    })
+}
+
+// Writes one unmasked, unfragmented frame
+pub async fn write_frame<S>(io: &mut S, opcode: u8, payload: &[u8]) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    io.write_all(&frame).await?;
+    io.flush().await
+}
+
+async fn close<S>(io: &mut S, code: u16) -> io::Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    write_frame(io, OPCODE_CLOSE, &code.to_be_bytes()).await
+}
+
+// SHA-1 (FIPS 180-4). Broken for signatures, but it is what the WebSocket
+// handshake specifies.
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xEFCD_AB89,
+        0x98BA_DCFE,
+        0x1032_5476,
+        0xC3D2_E1F0,
+    ];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, word) in digest.chunks_exact_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
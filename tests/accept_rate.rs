@@ -0,0 +1,91 @@
+// Connection accept rate in /stats
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{send_raw, start_server};
+use rust_http_server::accept_rate::{AcceptRate, WINDOW_SECS};
+use rust_http_server::Config;
+
+#[test]
+fn averages_over_uptime_until_the_window_fills() {
+    let start = Instant::now();
+    let rate = AcceptRate::new(start);
+
+    for _ in 0..6 {
+        rate.record(start);
+    }
+    assert_eq!(rate.recent_per_sec(start), 6.0);
+    // Two seconds in, the same six are spread over two seconds
+    assert_eq!(rate.recent_per_sec(start + Duration::from_secs(1)), 3.0);
+}
+
+#[test]
+fn old_seconds_slide_out_of_the_window() {
+    let start = Instant::now();
+    let rate = AcceptRate::new(start);
+
+    for _ in 0..50 {
+        rate.record(start);
+    }
+    let later = start + Duration::from_secs(WINDOW_SECS + 5);
+    for _ in 0..20 {
+        rate.record(later);
+    }
+    assert_eq!(rate.recent_per_sec(later), 20.0 / WINDOW_SECS as f64);
+    assert_eq!(
+        rate.recent_per_sec(later + Duration::from_secs(WINDOW_SECS)),
+        0.0
+    );
+}
+
+#[test]
+fn warns_at_most_once_per_window() {
+    let start = Instant::now();
+    let rate = AcceptRate::new(start);
+
+    assert!(rate.warning_due(start));
+    assert!(!rate.warning_due(start + Duration::from_secs(1)));
+    assert!(rate.warning_due(start + Duration::from_secs(WINDOW_SECS)));
+}
+
+#[test]
+fn concurrent_accepts_are_all_counted() {
+    let start = Instant::now();
+    let rate = AcceptRate::new(start);
+
+    std::thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                for _ in 0..1000 {
+                    rate.record(start);
+                }
+            });
+        }
+    });
+    assert_eq!(rate.recent_per_sec(start), 8000.0);
+}
+
+#[tokio::test]
+async fn stats_report_accepted_connections() {
+    let addr = start_server(Config::default()).await;
+    let request = b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    for _ in 0..3 {
+        send_raw(addr, request).await;
+    }
+
+    let (_, body) = send_raw(
+        addr,
+        b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["accepted_connections"], 4);
+    assert!(stats["connections_per_second"].is_f64(), "{}", body);
+    assert!(
+        stats["recent_connections_per_second"].as_f64().unwrap() > 0.0,
+        "{}",
+        body
+    );
+}
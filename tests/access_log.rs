@@ -0,0 +1,105 @@
+// LOG_FORMAT=clf|combined writes request log lines in Apache's Common and
+// Combined Log Formats
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+
+use chrono::DateTime;
+use hyper::{Method, StatusCode, Version};
+use rust_http_server::access_log::{format_clf, AccessLogEntry, LogFormat};
+use rust_http_server::Config;
+
+fn entry() -> AccessLogEntry {
+    AccessLogEntry {
+        remote_ip: Some("127.0.0.1".parse().unwrap()),
+        received: DateTime::parse_from_rfc3339("2026-10-14T09:05:03+02:00").unwrap(),
+        method: Method::GET,
+        target: "/echo/hello?lang=fr".to_string(),
+        version: Version::HTTP_11,
+        status: StatusCode::OK,
+        bytes: Some(97),
+        referer: Some("https://example.com/".to_string()),
+        user_agent: Some("curl/8.5.0".to_string()),
+    }
+}
+
+#[test]
+fn common_log_format() {
+    assert_eq!(
+        format_clf(&entry(), false),
+        r#"127.0.0.1 - - [14/Oct/2026:09:05:03 +0200] "GET /echo/hello?lang=fr HTTP/1.1" 200 97"#
+    );
+}
+
+#[test]
+fn combined_log_format() {
+    assert_eq!(
+        format_clf(&entry(), true),
+        r#"127.0.0.1 - - [14/Oct/2026:09:05:03 +0200] "GET /echo/hello?lang=fr HTTP/1.1" 200 97 "https://example.com/" "curl/8.5.0""#
+    );
+}
+
+#[test]
+fn unknown_fields_are_dashes() {
+    let entry = AccessLogEntry {
+        remote_ip: None,
+        method: Method::DELETE,
+        target: "/kv/a".to_string(),
+        version: Version::HTTP_10,
+        status: StatusCode::NO_CONTENT,
+        bytes: Some(0),
+        referer: None,
+        user_agent: None,
+        ..entry()
+    };
+    assert_eq!(
+        format_clf(&entry, true),
+        r#"- - - [14/Oct/2026:09:05:03 +0200] "DELETE /kv/a HTTP/1.0" 204 - "-" "-""#
+    );
+
+    // A streamed body's length isn't known when the line is written
+    let streamed = AccessLogEntry {
+        bytes: None,
+        ..self::entry()
+    };
+    assert!(format_clf(&streamed, false).ends_with(" 200 -"));
+}
+
+#[test]
+fn ipv6_clients() {
+    let entry = AccessLogEntry {
+        remote_ip: Some(IpAddr::V6(Ipv6Addr::LOCALHOST)),
+        ..entry()
+    };
+    assert!(format_clf(&entry, false).starts_with("::1 - - ["));
+}
+
+#[test]
+fn header_values_cannot_break_out_of_their_quotes() {
+    let entry = AccessLogEntry {
+        referer: Some(r#"a" 500 "b"#.to_string()),
+        user_agent: Some("bot\\1\nforged\u{7f}".to_string()),
+        ..entry()
+    };
+    let line = format_clf(&entry, true);
+    assert!(
+        line.ends_with(r#" "a\" 500 \"b" "bot\\1\x0aforged\x7f""#),
+        "{}",
+        line
+    );
+    assert_eq!(line.lines().count(), 1);
+}
+
+#[test]
+fn read_from_log_format() {
+    let load = |value: &str| {
+        let vars = HashMap::from([("LOG_FORMAT".to_string(), value.to_string())]);
+        Config::from_lookup(|key| vars.get(key).cloned())
+    };
+    assert_eq!(Config::default().log_format, LogFormat::Default);
+    assert_eq!(load("clf").unwrap().log_format, LogFormat::Clf);
+    assert_eq!(load("Common").unwrap().log_format, LogFormat::Clf);
+    assert_eq!(load(" combined ").unwrap().log_format, LogFormat::Combined);
+    assert_eq!(load("default").unwrap().log_format, LogFormat::Default);
+    assert!(load("json").is_err());
+}
@@ -0,0 +1,235 @@
+// Graceful shutdown through the token-protected admin API, which must start
+// exactly once however many requests ask for it, optionally after a drain
+// delay spent reporting not ready
+
+mod common;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::{get, send_raw};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::{Request, StatusCode};
+use rust_http_server::{bind, handle_request, serve, AppState, Config};
+use tokio::net::TcpStream;
+use tokio::sync::Barrier;
+
+const TOKEN: &str = "s3cret";
+
+fn config() -> Config {
+    Config {
+        admin_token: Some(TOKEN.to_string()),
+        ..Config::default()
+    }
+}
+
+fn shutdown_request() -> Request<Full<Bytes>> {
+    Request::post("/admin/shutdown")
+        .header("Host", "localhost")
+        .header("Authorization", format!("Bearer {}", TOKEN))
+        .body(Full::new(Bytes::new()))
+        .unwrap()
+}
+
+async fn post_shutdown(addr: SocketAddr, authorization: &str) -> (String, String) {
+    post_shutdown_to(addr, "/admin/shutdown", authorization).await
+}
+
+async fn post_shutdown_to(addr: SocketAddr, target: &str, authorization: &str) -> (String, String) {
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+        target, authorization
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_requests_trigger_one_shutdown() {
+    for _ in 0..50 {
+        let state = AppState::new(config());
+        let barrier = Arc::new(Barrier::new(2));
+        let tasks: Vec<_> = (0..2)
+            .map(|_| {
+                let state = state.clone();
+                let barrier = barrier.clone();
+                tokio::spawn(async move {
+                    barrier.wait().await;
+                    let response = handle_request(shutdown_request(), state, None)
+                        .await
+                        .unwrap();
+                    let status = response.status();
+                    let body = response.into_body().collect().await.unwrap().to_bytes();
+                    (status, String::from_utf8(body.to_vec()).unwrap())
+                })
+            })
+            .collect();
+
+        let mut started = 0;
+        for task in tasks {
+            let (status, body) = task.await.unwrap();
+            assert_eq!(status, StatusCode::ACCEPTED, "unexpected body: {}", body);
+            if body.contains("\"Shutting down\"") {
+                started += 1;
+            } else {
+                assert!(
+                    body.contains("\"Already shutting down\""),
+                    "unexpected body: {}",
+                    body
+                );
+            }
+        }
+        assert_eq!(started, 1, "shutdown sequence should start exactly once");
+
+        tokio::time::timeout(Duration::from_secs(1), state.shutdown.notified())
+            .await
+            .expect("shutdown was never triggered");
+    }
+}
+
+#[tokio::test]
+async fn shutdown_request_stops_the_server() {
+    let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(serve(
+        listener,
+        AppState::new(config()),
+        std::future::pending(),
+    ));
+
+    let (head, body) = post_shutdown(addr, &format!("Authorization: Bearer {}\r\n", TOKEN)).await;
+    assert!(
+        head.starts_with("HTTP/1.1 202"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(body.contains("Shutting down"), "unexpected body: {}", body);
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not shut down")
+        .unwrap()
+        .unwrap();
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn drain_reports_not_ready_before_shutting_down() {
+    let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(serve(
+        listener,
+        AppState::new(Config {
+            shutdown_drain_delay_ms: 300,
+            ..config()
+        }),
+        std::future::pending(),
+    ));
+    let authorization = format!("Authorization: Bearer {}\r\n", TOKEN);
+
+    let (head, _) = get(addr, "/readyz", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+    let started = Instant::now();
+    let (head, body) = post_shutdown_to(addr, "/admin/shutdown?drain=true", &authorization).await;
+    assert!(
+        head.starts_with("HTTP/1.1 202"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(
+        body.contains("Draining for 300ms, then shutting down"),
+        "unexpected body: {}",
+        body
+    );
+
+    // Load balancers see it leaving, but it still serves everything else
+    let (head, body) = get(addr, "/readyz", "").await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+    assert!(body.contains("Shutting down"), "{}", body);
+    let (head, _) = get(addr, "/health", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+    let (_, body) = post_shutdown_to(addr, "/admin/shutdown?drain=true", &authorization).await;
+    assert!(body.contains("Already shutting down"), "{}", body);
+    assert!(!server.is_finished());
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not shut down")
+        .unwrap()
+        .unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn rejects_a_bad_drain_value() {
+    let addr = common::start_server(config()).await;
+
+    let (head, body) = post_shutdown_to(
+        addr,
+        "/admin/shutdown?drain=soon",
+        &format!("Authorization: Bearer {}\r\n", TOKEN),
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+    assert!(body.contains("drain must be true or false"), "{}", body);
+
+    let (head, _) = get(addr, "/readyz", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
+
+#[tokio::test]
+async fn shutdown_requires_the_admin_token() {
+    let addr = common::start_server(config()).await;
+
+    let (head, _) = post_shutdown(addr, "").await;
+    assert!(
+        head.starts_with("HTTP/1.1 401"),
+        "unexpected head: {}",
+        head
+    );
+    let (head, _) = post_shutdown(addr, "Authorization: Bearer wrong\r\n").await;
+    assert!(
+        head.starts_with("HTTP/1.1 401"),
+        "unexpected head: {}",
+        head
+    );
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /admin/shutdown HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 405"),
+        "unexpected head: {}",
+        head
+    );
+
+    // Still serving
+    let (head, _) = send_raw(
+        addr,
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn shutdown_is_disabled_without_a_token() {
+    let addr = common::start_server(Config::default()).await;
+
+    let (head, _) = post_shutdown(addr, &format!("Authorization: Bearer {}\r\n", TOKEN)).await;
+    assert!(
+        head.starts_with("HTTP/1.1 403"),
+        "unexpected head: {}",
+        head
+    );
+}
@@ -0,0 +1,69 @@
+// BASE_PATH: routes served under a prefix
+
+mod common;
+
+use common::{get, start_server};
+use rust_http_server::Config;
+
+fn under_api() -> Config {
+    Config {
+        base_path: "/api".to_string(),
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn routes_under_the_base_path() {
+    let addr = start_server(under_api()).await;
+
+    for path in ["/api", "/api/", "/api/health", "/api/echo/hi", "/api/stats"] {
+        let (head, _) = get(addr, path, "").await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}: {}", path, head);
+    }
+
+    let (_, body) = get(addr, "/api/echo/hi", "").await;
+    assert!(
+        body.contains("\"message\":\"Echo: hi\""),
+        "unexpected body: {}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn paths_outside_the_base_path_are_not_found() {
+    let addr = start_server(under_api()).await;
+
+    for path in ["/", "/health", "/apihealth", "/other/api/health"] {
+        let (head, _) = get(addr, path, "").await;
+        assert!(head.starts_with("HTTP/1.1 404"), "{}: {}", path, head);
+    }
+}
+
+#[tokio::test]
+async fn suggestions_include_the_base_path() {
+    let addr = start_server(Config {
+        base_path: "/api/".to_string(),
+        ..Config::default()
+    })
+    .await;
+
+    let (_, body) = get(addr, "/api/helth", "").await;
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["suggestions"], serde_json::json!(["/api/health"]));
+
+    let (_, body) = get(addr, "/health", "").await;
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["suggestions"], serde_json::json!(["/api/health"]));
+}
+
+#[tokio::test]
+async fn empty_base_path_serves_from_the_root() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = get(addr, "/health", "").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+}
@@ -0,0 +1,192 @@
+// BODY_BUDGET_BYTES caps the memory held by all buffered request bodies
+// together, turning away bodies that can't get their share in time
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::body_budget::{Backlog, BodyBudget};
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn config() -> Config {
+    Config {
+        max_body_bytes: 1000,
+        body_budget_bytes: 1000,
+        body_budget_timeout_ms: 200,
+        ..Config::default()
+    }
+}
+
+// Starts a POST /echo declaring `length` bytes and sends only the first
+// one, so the server holds its reservation while waiting for the rest
+async fn start_upload(addr: SocketAddr, length: usize) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let head = format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\nx",
+        length
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    // Give the server time to reserve before anything else arrives
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream
+}
+
+async fn finish_upload(mut stream: TcpStream, length: usize) -> String {
+    stream
+        .write_all("x".repeat(length - 1).as_bytes())
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+async fn upload(addr: SocketAddr, length: usize) -> (String, String) {
+    let request = format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        length,
+        "x".repeat(length)
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+async fn stats(addr: SocketAddr) -> serde_json::Value {
+    let (_, body) = send_raw(
+        addr,
+        b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    serde_json::from_str(&body).unwrap()
+}
+
+#[tokio::test]
+async fn bodies_over_the_remaining_budget_get_503() {
+    let addr = start_server(config()).await;
+    let held = start_upload(addr, 700).await;
+
+    let stats_while_held = stats(addr).await;
+    assert_eq!(stats_while_held["body_budget_bytes"], 1000);
+    assert_eq!(stats_while_held["body_budget_available_bytes"], 300);
+
+    let (head, body) = upload(addr, 600).await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+    assert_eq!(header(&head, "retry-after"), Some("1"));
+    assert_eq!(header(&head, "connection"), Some("close"));
+    assert!(body.contains("request bodies in flight"), "{}", body);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["queue_depth"], 1);
+    assert_eq!(body["retry_after_secs"], 1);
+
+    // What is left still fits a small body
+    let (head, _) = upload(addr, 300).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+    let response = finish_upload(held, 700).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    // Released once the held upload is answered
+    let (head, _) = upload(addr, 600).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    let stats = stats(addr).await;
+    assert_eq!(stats["body_budget_available_bytes"], 1000);
+    assert_eq!(stats["body_budget_rejections"], 1);
+}
+
+#[tokio::test]
+async fn waits_for_the_budget_to_free_up() {
+    let addr = start_server(Config {
+        body_budget_timeout_ms: 2000,
+        ..config()
+    })
+    .await;
+    let held = start_upload(addr, 1000).await;
+
+    let waiting = tokio::spawn(async move { upload(addr, 500).await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!waiting.is_finished());
+
+    finish_upload(held, 1000).await;
+    let (head, _) = waiting.await.unwrap();
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
+
+#[tokio::test]
+async fn rejections_report_the_queue_and_its_drain_time() {
+    let budget = BodyBudget::new(10, Duration::from_millis(100));
+    // Four released in the last second
+    for _ in 0..4 {
+        drop(budget.reserve(10).await.unwrap());
+    }
+    let _held = budget.reserve(10).await.unwrap();
+
+    let waiters: Vec<_> = (0..10)
+        .map(|_| {
+            let budget = budget.clone();
+            tokio::spawn(async move { budget.reserve(1).await.err().unwrap() })
+        })
+        .collect();
+    let mut backlogs = Vec::new();
+    for waiter in waiters {
+        backlogs.push(waiter.await.unwrap());
+    }
+    backlogs.sort_by_key(|backlog| backlog.queue_depth);
+
+    // Each sees the queue as it was when it gave up, itself included
+    let depths: Vec<usize> = backlogs.iter().map(|backlog| backlog.queue_depth).collect();
+    assert_eq!(depths, (1..=10).collect::<Vec<_>>());
+    // Ten queued, draining at four a second
+    assert_eq!(
+        backlogs[9],
+        Backlog {
+            queue_depth: 10,
+            retry_after_secs: 3
+        }
+    );
+}
+
+#[tokio::test]
+async fn retry_after_matches_the_estimate_in_the_body() {
+    let addr = start_server(Config {
+        body_budget_timeout_ms: 1500,
+        ..config()
+    })
+    .await;
+    let held = start_upload(addr, 1000).await;
+
+    let (head, body) = upload(addr, 500).await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["queue_depth"], 1);
+    // Nothing has been released yet, so it suggests another timeout's wait
+    assert_eq!(body["retry_after_secs"], 2);
+    assert_eq!(header(&head, "retry-after"), Some("2"));
+    drop(held);
+}
+
+#[tokio::test]
+async fn chunked_bodies_reserve_the_body_limit() {
+    let addr = start_server(config()).await;
+    let held = start_upload(addr, 2).await;
+
+    // Its size is unknown, so it needs room for MAX_BODY_BYTES
+    let (head, _) = send_raw(
+        addr,
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\n\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+    drop(held);
+}
+
+#[tokio::test]
+async fn off_by_default() {
+    let addr = start_server(Config::default()).await;
+    let stats = stats(addr).await;
+    assert!(stats["body_budget_bytes"].is_null());
+    assert!(stats["body_budget_available_bytes"].is_null());
+    assert_eq!(stats["body_budget_rejections"], 0);
+}
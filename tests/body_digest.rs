@@ -0,0 +1,169 @@
+// VERIFY_BODY_DIGEST checks request bodies against Content-MD5 and Digest
+// (MD5, SHA-256), answering 400 when they don't match
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{send_raw, start_server};
+use rust_http_server::digest::{ExpectedDigests, Md5, Sha256};
+use rust_http_server::Config;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn md5(data: &[u8]) -> String {
+    let mut md5 = Md5::new();
+    md5.update(data);
+    hex(&md5.finish())
+}
+
+fn sha256(data: &[u8]) -> String {
+    let mut sha256 = Sha256::new();
+    sha256.update(data);
+    hex(&sha256.finish())
+}
+
+#[test]
+fn hashes_match_known_digests() {
+    assert_eq!(md5(b""), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(md5(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(
+        md5(b"The quick brown fox jumps over the lazy dog"),
+        "9e107d9d372bb6826bd81d3542a419d6"
+    );
+    assert_eq!(
+        sha256(b""),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+    assert_eq!(
+        sha256(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    // 56 bytes: the padding needs a block of its own
+    let two_blocks = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    assert_eq!(md5(two_blocks), "8215ef0796a20bcaaae116d3876c664a");
+    assert_eq!(
+        sha256(two_blocks),
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    );
+}
+
+#[test]
+fn pieces_hash_like_the_whole() {
+    let data = [b'a'; 1000];
+    let mut md5 = Md5::new();
+    let mut sha256 = Sha256::new();
+    for piece in data.chunks(37) {
+        md5.update(piece);
+        sha256.update(piece);
+    }
+    assert_eq!(hex(&md5.finish()), "cabe45dcc9ae5b66ba86600cca6b8ba8");
+    assert_eq!(
+        hex(&sha256.finish()),
+        "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+    );
+}
+
+#[test]
+fn reads_both_headers() {
+    let headers = |pairs: &[(&'static str, &'static str)]| {
+        let mut map = hyper::HeaderMap::new();
+        for (name, value) in pairs {
+            map.append(*name, value.parse().unwrap());
+        }
+        ExpectedDigests::from_headers(&map)
+    };
+
+    assert_eq!(headers(&[]), Ok(None));
+    // Algorithms this server can't check are skipped
+    assert_eq!(headers(&[("digest", "UNIXsum=30637")]), Ok(None));
+    let expected = headers(&[(
+        "digest",
+        "SHA-256=uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=, unixsum=1",
+    )])
+    .unwrap()
+    .unwrap();
+    assert!(expected.sha256.is_some() && expected.md5.is_none());
+
+    assert!(headers(&[("content-md5", "not base64!")]).is_err());
+    assert!(headers(&[("content-md5", "YWJj")]).is_err());
+    assert!(headers(&[
+        ("content-md5", "XrY7u+Ae7tCTyyK7j1rNww=="),
+        ("digest", "md5=1B2M2Y8AsgTpgAmY7PhCfg=="),
+    ])
+    .is_err());
+}
+
+async fn post(addr: SocketAddr, body: &str, checksum: &str) -> (String, String) {
+    let request = format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n{}\r\n{}",
+        body.len(),
+        checksum,
+        body
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+fn verifying() -> Config {
+    Config {
+        verify_body_digest: true,
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn matching_checksums_are_accepted() {
+    let addr = start_server(verifying()).await;
+
+    for checksum in [
+        "Content-MD5: XrY7u+Ae7tCTyyK7j1rNww==\r\n",
+        "Digest: SHA-256=uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=\r\n",
+        "Digest: md5=XrY7u+Ae7tCTyyK7j1rNww==, sha-256=uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=\r\n",
+        "",
+    ] {
+        let (head, body) = post(addr, "hello world", checksum).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}: {}", checksum, head);
+        assert!(body.contains("hello world"), "{}", body);
+    }
+}
+
+#[tokio::test]
+async fn mismatches_are_rejected() {
+    let addr = start_server(verifying()).await;
+
+    let (head, body) = post(
+        addr,
+        "hello world!",
+        "Content-MD5: XrY7u+Ae7tCTyyK7j1rNww==\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+    assert!(body.contains("MD5 checksum"), "{}", body);
+
+    let (head, body) = post(
+        addr,
+        "hello world!",
+        "Digest: SHA-256=uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+    assert!(body.contains("SHA-256 checksum"), "{}", body);
+
+    let (head, _) = post(addr, "hello world", "Content-MD5: nope\r\n").await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+}
+
+#[tokio::test]
+async fn checksums_are_ignored_when_off() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = post(
+        addr,
+        "hello world!",
+        "Content-MD5: XrY7u+Ae7tCTyyK7j1rNww==\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
@@ -0,0 +1,75 @@
+// Oversized bodies are refused by their declared length, before any of
+// them is read
+
+mod common;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::Config;
+
+#[tokio::test]
+async fn rejects_declared_length_over_limit_without_reading() {
+    let addr = start_server(Config {
+        max_body_bytes: 1024,
+        ..Config::default()
+    })
+    .await;
+
+    // The body is never sent: the 413 and the close must come from the
+    // declaration alone, or this times out
+    let (head, body) = send_raw(
+        addr,
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1000000000000\r\n\r\n",
+    )
+    .await;
+
+    assert!(
+        head.starts_with("HTTP/1.1 413"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(header(&head, "connection"), Some("close"));
+    assert!(body.contains("1024"), "unexpected body: {}", body);
+}
+
+#[tokio::test]
+async fn no_continue_for_declared_length_over_limit() {
+    let addr = start_server(Config {
+        max_body_bytes: 1024,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = send_raw(
+        addr,
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4096\r\nExpect: 100-continue\r\n\r\n",
+    )
+    .await;
+
+    assert!(
+        head.starts_with("HTTP/1.1 413"),
+        "unexpected status: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn accepts_declared_length_at_limit() {
+    let addr = start_server(Config {
+        max_body_bytes: 5,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, body) = send_raw(
+        addr,
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+    )
+    .await;
+
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("hello"), "unexpected body: {}", body);
+}
@@ -0,0 +1,130 @@
+// BODY_READ_TIMEOUT_MS bounds how long a request body may take to arrive,
+// so a client that stalls after its headers doesn't hold a slot forever
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use common::{header, start_server};
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn config() -> Config {
+    Config {
+        body_read_timeout_ms: 200,
+        ..Config::default()
+    }
+}
+
+async fn send_partial(addr: SocketAddr, request: &str) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    stream
+}
+
+async fn read_response(stream: &mut TcpStream) -> String {
+    let mut response = String::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .expect("no response to a stalled body")
+        .unwrap();
+    response
+}
+
+#[tokio::test]
+async fn stalled_body_gets_408() {
+    let addr = start_server(config()).await;
+
+    let started = Instant::now();
+    let mut stream = send_partial(
+        addr,
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nabc",
+    )
+    .await;
+    let response = read_response(&mut stream).await;
+    assert!(
+        response.starts_with("HTTP/1.1 408"),
+        "unexpected response: {}",
+        response
+    );
+    assert!(
+        response.contains("Timed out reading request body"),
+        "unexpected response: {}",
+        response
+    );
+    let (head, _) = response.split_once("\r\n\r\n").unwrap();
+    assert_eq!(header(head, "connection"), Some("close"));
+    assert!(started.elapsed() >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn stalled_chunked_body_gets_408() {
+    let addr = start_server(config()).await;
+
+    let mut stream = send_partial(
+        addr,
+        "POST /debug/echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n",
+    )
+    .await;
+    let response = read_response(&mut stream).await;
+    assert!(
+        response.starts_with("HTTP/1.1 408"),
+        "unexpected response: {}",
+        response
+    );
+}
+
+#[tokio::test]
+async fn slow_body_within_the_timeout_is_read() {
+    let addr = start_server(config()).await;
+
+    let mut stream = send_partial(
+        addr,
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 6\r\n\r\nabc",
+    )
+    .await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream.write_all(b"def").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert!(
+        response.starts_with("HTTP/1.1 200"),
+        "unexpected response: {}",
+        response
+    );
+    assert!(
+        response.contains("abcdef"),
+        "unexpected response: {}",
+        response
+    );
+}
+
+#[tokio::test]
+async fn zero_disables_the_timeout() {
+    let addr = start_server(Config {
+        body_read_timeout_ms: 0,
+        ..Config::default()
+    })
+    .await;
+
+    let mut stream = send_partial(
+        addr,
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 6\r\n\r\nabc",
+    )
+    .await;
+    let mut buf = [0; 1];
+    assert!(
+        tokio::time::timeout(Duration::from_millis(400), stream.read(&mut buf))
+            .await
+            .is_err(),
+        "server answered before the body was complete"
+    );
+    stream.write_all(b"def").await.unwrap();
+    let response = read_response(&mut stream).await;
+    assert!(
+        response.contains("abcdef"),
+        "unexpected response: {}",
+        response
+    );
+}
@@ -0,0 +1,101 @@
+// CACHE_CONTROL sets Cache-Control per route, with `*` for the rest
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::cache_policy::CachePolicy;
+use rust_http_server::Config;
+
+fn config(policy: &str) -> Config {
+    Config {
+        cache_control: policy.parse().unwrap(),
+        ..Config::default()
+    }
+}
+
+async fn cache_control(addr: SocketAddr, path: &str) -> Option<String> {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    );
+    let (head, _) = send_raw(addr, request.as_bytes()).await;
+    header(&head, "cache-control").map(str::to_string)
+}
+
+#[tokio::test]
+async fn routes_get_their_own_policy() {
+    let addr = start_server(config("/stats=no-store; /echo/:msg=public, max-age=3600")).await;
+
+    assert_eq!(
+        cache_control(addr, "/stats").await.as_deref(),
+        Some("no-store")
+    );
+    assert_eq!(
+        cache_control(addr, "/echo/hello").await.as_deref(),
+        Some("public, max-age=3600")
+    );
+    assert_eq!(cache_control(addr, "/health").await, None);
+}
+
+#[tokio::test]
+async fn wildcard_fills_in_without_overriding_defaults() {
+    let addr = start_server(config("*=no-cache")).await;
+    assert_eq!(
+        cache_control(addr, "/health").await.as_deref(),
+        Some("no-cache")
+    );
+    // The favicon's own caching is kept...
+    assert_eq!(
+        cache_control(addr, "/favicon.ico").await.as_deref(),
+        Some("public, max-age=86400")
+    );
+
+    // ...unless its route is named
+    let addr = start_server(config("*=no-cache;/favicon.ico=public, max-age=60")).await;
+    assert_eq!(
+        cache_control(addr, "/favicon.ico").await.as_deref(),
+        Some("public, max-age=60")
+    );
+}
+
+#[tokio::test]
+async fn errors_are_never_given_a_policy() {
+    let addr = start_server(config("/error/:code=public, max-age=3600;*=public")).await;
+    assert_eq!(cache_control(addr, "/error/503").await, None);
+    assert_eq!(cache_control(addr, "/no/such/route").await, None);
+}
+
+#[tokio::test]
+async fn matches_routes_under_the_base_path() {
+    let addr = start_server(Config {
+        base_path: "/api".to_string(),
+        ..config("/stats=no-store")
+    })
+    .await;
+    assert_eq!(
+        cache_control(addr, "/api/stats").await.as_deref(),
+        Some("no-store")
+    );
+}
+
+#[test]
+fn parses_policies() {
+    assert!(""
+        .parse::<CachePolicy>()
+        .unwrap()
+        .patterns()
+        .next()
+        .is_none());
+    for invalid in ["/stats", "/stats=", "/stats=no-store\u{1}"] {
+        assert!(invalid.parse::<CachePolicy>().is_err(), "{:?}", invalid);
+    }
+}
+
+#[test]
+fn unknown_routes_are_rejected() {
+    let vars = |key: &str| (key == "CACHE_CONTROL").then(|| "/nope=no-store".to_string());
+    let err = Config::from_lookup(vars).unwrap_err();
+    assert_eq!(err.key(), "CACHE_CONTROL");
+}
@@ -0,0 +1,106 @@
+// Handlers are abandoned when the client disconnects mid-request
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use common::{send_raw, start_server};
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn cancelled_requests(addr: SocketAddr) -> u64 {
+    let (_, body) = send_raw(
+        addr,
+        b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    stats["cancelled_requests"].as_u64().unwrap()
+}
+
+// Sends `request`, then hangs up without waiting for the response
+async fn send_and_disconnect(addr: SocketAddr, request: &[u8]) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    drop(stream);
+}
+
+// Polls until `cancelled_requests` reaches `expected` or a second passes
+async fn wait_for_cancelled(addr: SocketAddr, expected: u64) -> u64 {
+    let deadline = Instant::now() + Duration::from_secs(1);
+    loop {
+        let cancelled = cancelled_requests(addr).await;
+        if cancelled >= expected || Instant::now() > deadline {
+            return cancelled;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn disconnect_cancels_a_pending_delay() {
+    let addr = start_server(Config::default()).await;
+
+    send_and_disconnect(addr, b"GET /delay/5000 HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+
+    // Well before the 5s sleep would have finished
+    assert_eq!(wait_for_cancelled(addr, 1).await, 1);
+}
+
+#[tokio::test]
+async fn disconnect_cancels_a_streaming_body() {
+    let addr = start_server(Config::default()).await;
+
+    // Would take 10s to stream in full
+    send_and_disconnect(
+        addr,
+        b"GET /echo/stream/hi?count=100&interval_ms=100 HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+
+    assert_eq!(wait_for_cancelled(addr, 1).await, 1);
+}
+
+#[tokio::test]
+async fn truncated_body_fails_fast_instead_of_waiting() {
+    let addr = start_server(Config::default()).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    // Promises 100 bytes, delivers 5 and stops sending
+    stream
+        .write_all(b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 100\r\n\r\nhello")
+        .await
+        .unwrap();
+    stream.shutdown().await.unwrap();
+
+    let mut response = String::new();
+    tokio::time::timeout(Duration::from_secs(1), stream.read_to_string(&mut response))
+        .await
+        .expect("server kept waiting for the rest of the body")
+        .unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 400"),
+        "unexpected response: {}",
+        response
+    );
+}
+
+#[tokio::test]
+async fn completed_requests_are_not_counted() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /delay/10 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(cancelled_requests(addr).await, 0);
+}
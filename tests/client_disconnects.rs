@@ -0,0 +1,72 @@
+// Clients hanging up mid-response are counted as disconnects, not errors
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use common::{send_raw, start_server};
+use rust_http_server::{Config, LogLevel};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn client_disconnects(addr: SocketAddr) -> u64 {
+    let (_, body) = send_raw(
+        addr,
+        b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    stats["client_disconnects"].as_u64().unwrap()
+}
+
+// Starts a large response, reads a little of it, then resets the connection
+async fn reset_mid_response(addr: SocketAddr) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /echo/0123456789abcdef?repeat=1000000 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut head = [0u8; 64];
+    stream.read_exact(&mut head).await.unwrap();
+    // Zero linger turns the close into an RST, so the server's next write fails
+    stream.set_linger(Some(Duration::ZERO)).unwrap();
+    drop(stream);
+}
+
+async fn wait_for_disconnects(addr: SocketAddr, expected: u64) -> u64 {
+    let deadline = Instant::now() + Duration::from_secs(2);
+    loop {
+        let disconnects = client_disconnects(addr).await;
+        if disconnects >= expected || Instant::now() > deadline {
+            return disconnects;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn reset_mid_response_is_a_client_disconnect() {
+    let addr = start_server(Config {
+        log_level: LogLevel::Debug,
+        ..Config::default()
+    })
+    .await;
+    assert_eq!(client_disconnects(addr).await, 0);
+
+    reset_mid_response(addr).await;
+    assert_eq!(wait_for_disconnects(addr, 1).await, 1);
+}
+
+#[tokio::test]
+async fn completed_responses_are_not_disconnects() {
+    let addr = start_server(Config::default()).await;
+    for _ in 0..3 {
+        send_raw(
+            addr,
+            b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+    }
+    assert_eq!(client_disconnects(addr).await, 0);
+}
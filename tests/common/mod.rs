@@ -0,0 +1,99 @@
+// Helpers shared by the integration tests
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use rust_http_server::{serve, AppState, Config};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// Starts a server with `config` on an ephemeral port
+pub async fn start_server(config: Config) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(
+        listener,
+        AppState::new(config),
+        std::future::pending(),
+    ));
+    addr
+}
+
+// Writes `request` verbatim on a fresh connection and returns the
+// response head and body once the server closes the connection.
+pub async fn send_raw(addr: SocketAddr, request: &[u8]) -> (String, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request).await.unwrap();
+
+    let mut response = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+        .await
+        .expect("timed out waiting for response")
+        .unwrap();
+    let response = String::from_utf8_lossy(&response).into_owned();
+    match response.split_once("\r\n\r\n") {
+        Some((head, body)) => (head.to_string(), body.to_string()),
+        None => (response, String::new()),
+    }
+}
+
+// Sends `GET target` with `extra_headers` (each ending in "\r\n") on a
+// fresh connection and returns the response head and body. The Host
+// header is `localhost` unless `extra_headers` sets one.
+pub async fn get(addr: SocketAddr, target: &str, extra_headers: &str) -> (String, String) {
+    let has_host = extra_headers
+        .lines()
+        .any(|line| line.to_ascii_lowercase().starts_with("host:"));
+    let host = if has_host { "" } else { "Host: localhost\r\n" };
+    let request = format!(
+        "GET {} HTTP/1.1\r\n{}Connection: close\r\n{}\r\n",
+        target, host, extra_headers
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+// Looks up a header value in a response head (case-insensitive name)
+pub fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+// Decodes a chunked message body into its data and trailer lines
+pub fn decode_chunked(body: &str) -> (String, Vec<String>) {
+    let mut data = String::new();
+    let mut rest = body;
+    loop {
+        let (size_line, after) = rest.split_once("\r\n").expect("truncated chunk size");
+        let size = usize::from_str_radix(size_line.split(';').next().unwrap().trim(), 16)
+            .expect("invalid chunk size");
+        if size == 0 {
+            let trailers = after
+                .split("\r\n")
+                .take_while(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect();
+            return (data, trailers);
+        }
+        data.push_str(&after[..size]);
+        rest = &after[size + 2..];
+    }
+}
+
+// The CSV columns a JSON `/stats` body maps to: one per field, except that
+// breakdowns like by_http_version spread over one column per key
+pub fn stats_columns(json: &serde_json::Value) -> Vec<String> {
+    json.as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(key, value)| match value.as_object() {
+            Some(breakdown) => breakdown
+                .keys()
+                .map(|inner| format!("{}.{}", key, inner))
+                .collect(),
+            None => vec![key.clone()],
+        })
+        .collect()
+}
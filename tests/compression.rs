@@ -0,0 +1,249 @@
+// gzip response compression, Accept-Encoding negotiation and its /stats
+// accounting
+
+mod common;
+
+use common::{header, send_raw, start_server};
+use hyper::header::{HeaderValue, ACCEPT_ENCODING};
+use hyper::HeaderMap;
+use rust_http_server::compression::{crc32, gzip, negotiate, Negotiated};
+use rust_http_server::inflate::gunzip;
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn post_echo(body: &str, accept_encoding: Option<&str>) -> Vec<u8> {
+    let accept = accept_encoding
+        .map(|value| format!("Accept-Encoding: {}\r\n", value))
+        .unwrap_or_default();
+    format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+        accept,
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+// Like `send_raw`, but keeps the (binary) body as bytes
+async fn send_raw_bytes(addr: std::net::SocketAddr, request: &[u8]) -> (String, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+    let head = String::from_utf8(response[..end].to_vec()).unwrap();
+    (head, response[end + 4..].to_vec())
+}
+
+async fn stats(addr: std::net::SocketAddr) -> serde_json::Value {
+    let (_, body) = send_raw(
+        addr,
+        b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    serde_json::from_str(&body).unwrap()
+}
+
+#[test]
+fn crc32_matches_reference_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(b""), 0);
+}
+
+#[test]
+fn gzip_output_has_valid_framing() {
+    let data = "hello hello hello hello hello".repeat(20);
+    let compressed = gzip(data.as_bytes());
+
+    assert_eq!(&compressed[..3], &[0x1f, 0x8b, 0x08]);
+    let trailer = &compressed[compressed.len() - 8..];
+    assert_eq!(
+        u32::from_le_bytes(trailer[..4].try_into().unwrap()),
+        crc32(data.as_bytes())
+    );
+    assert_eq!(
+        u32::from_le_bytes(trailer[4..].try_into().unwrap()) as usize,
+        data.len()
+    );
+    assert!(compressed.len() < data.len() / 4);
+}
+
+#[tokio::test]
+async fn large_responses_are_gzipped_and_counted() {
+    let addr = start_server(Config::default()).await;
+    let body = "compress me ".repeat(500);
+
+    let (head, compressed) = send_raw_bytes(addr, &post_echo(&body, Some("gzip, deflate"))).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(header(&head, "content-encoding"), Some("gzip"));
+    assert!(
+        head.lines()
+            .any(|line| line.eq_ignore_ascii_case("vary: Accept-Encoding")),
+        "missing Vary: Accept-Encoding: {}",
+        head
+    );
+    assert_eq!(&compressed[..2], &[0x1f, 0x8b]);
+
+    let stats = stats(addr).await;
+    let uncompressed = stats["uncompressed_bytes"].as_u64().unwrap();
+    let compressed_bytes = stats["compressed_bytes"].as_u64().unwrap();
+    assert_eq!(compressed_bytes, compressed.len() as u64);
+    assert!(uncompressed > body.len() as u64);
+    assert!(stats["compression_ratio"].as_f64().unwrap() > 1.0);
+}
+
+#[tokio::test]
+async fn responses_stay_identity_without_gzip_support() {
+    let addr = start_server(Config::default()).await;
+    let body = "compress me ".repeat(500);
+
+    let (head, _) = send_raw(addr, &post_echo(&body, None)).await;
+    assert_eq!(header(&head, "content-encoding"), None);
+
+    let (head, _) = send_raw(addr, &post_echo(&body, Some("gzip;q=0"))).await;
+    assert_eq!(header(&head, "content-encoding"), None);
+
+    let stats = stats(addr).await;
+    assert_eq!(stats["uncompressed_bytes"], 0);
+    assert_eq!(stats["compressed_bytes"], 0);
+    assert_eq!(stats["compression_ratio"], 0.0);
+}
+
+#[tokio::test]
+async fn disabled_compression_skips_accounting() {
+    let addr = start_server(Config {
+        compression: false,
+        ..Config::default()
+    })
+    .await;
+    let body = "compress me ".repeat(500);
+
+    let (head, _) = send_raw(addr, &post_echo(&body, Some("gzip"))).await;
+    assert_eq!(header(&head, "content-encoding"), None);
+
+    let stats = stats(addr).await;
+    assert_eq!(stats["uncompressed_bytes"], 0);
+    assert_eq!(stats["compressed_bytes"], 0);
+}
+
+fn negotiated(accept_encoding: Option<&str>, gzip_available: bool) -> Negotiated {
+    let mut headers = HeaderMap::new();
+    if let Some(value) = accept_encoding {
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_str(value).unwrap());
+    }
+    negotiate(&headers, gzip_available)
+}
+
+#[test]
+fn negotiation_follows_q_values() {
+    assert_eq!(negotiated(None, true), Negotiated::Identity);
+    assert_eq!(negotiated(Some(""), true), Negotiated::Identity);
+    assert_eq!(negotiated(Some("gzip"), true), Negotiated::Gzip);
+    assert_eq!(negotiated(Some("br, *"), true), Negotiated::Gzip);
+    assert_eq!(negotiated(Some("gzip;q=0"), true), Negotiated::Identity);
+    assert_eq!(negotiated(Some("*, gzip;q=0"), true), Negotiated::Identity);
+    assert_eq!(negotiated(Some("br"), true), Negotiated::Identity);
+
+    assert_eq!(
+        negotiated(Some("gzip, identity;q=0"), true),
+        Negotiated::GzipOnly
+    );
+    assert_eq!(negotiated(Some("*;q=0, gzip"), true), Negotiated::GzipOnly);
+    assert_eq!(
+        negotiated(Some("identity;q=0"), true),
+        Negotiated::NotAcceptable
+    );
+    assert_eq!(negotiated(Some("*;q=0"), true), Negotiated::NotAcceptable);
+    assert_eq!(
+        negotiated(Some("br, identity;q=0"), true),
+        Negotiated::NotAcceptable
+    );
+    assert_eq!(
+        negotiated(Some("*;q=0, identity"), true),
+        Negotiated::Identity
+    );
+
+    // Without gzip to offer, refusing identity leaves nothing
+    assert_eq!(negotiated(Some("gzip"), false), Negotiated::Identity);
+    assert_eq!(
+        negotiated(Some("gzip, identity;q=0"), false),
+        Negotiated::NotAcceptable
+    );
+}
+
+#[tokio::test]
+async fn refusing_everything_gets_406() {
+    let addr = start_server(Config::default()).await;
+
+    for accept in ["identity;q=0", "*;q=0", "br, identity;q=0"] {
+        let (head, body) = send_raw(addr, &post_echo("hi", Some(accept))).await;
+        assert!(
+            head.starts_with("HTTP/1.1 406"),
+            "{}: unexpected head: {}",
+            accept,
+            head
+        );
+        assert!(body.contains("gzip and identity"), "{}: {}", accept, body);
+    }
+
+    // Nothing was processed, so nothing was echoed or counted as compressed
+    assert_eq!(stats(addr).await["compressed_bytes"], 0);
+}
+
+#[tokio::test]
+async fn refusing_identity_forces_gzip() {
+    let addr = start_server(Config::default()).await;
+
+    // Well under COMPRESSION_MIN_BYTES, so normally sent as is
+    let (head, body) = send_raw_bytes(addr, &post_echo("tiny", Some("gzip, identity;q=0"))).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "content-encoding"), Some("gzip"));
+    let body = String::from_utf8(gunzip(&body, 1 << 20).unwrap()).unwrap();
+    assert!(body.contains("Echo: tiny"), "unexpected body: {}", body);
+}
+
+#[tokio::test]
+async fn refusing_identity_without_compression_gets_406() {
+    let addr = start_server(Config {
+        compression: false,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, body) = send_raw(addr, &post_echo("hi", Some("gzip, identity;q=0"))).await;
+    assert!(
+        head.starts_with("HTTP/1.1 406"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(body.contains("only identity"), "unexpected body: {}", body);
+}
+
+#[tokio::test]
+async fn bodiless_responses_are_never_gzipped() {
+    let addr = start_server(Config::default()).await;
+
+    for request in [
+        "GET /favicon.ico HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nAccept-Encoding: gzip, identity;q=0\r\n\r\n",
+        "HEAD /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nAccept-Encoding: gzip, identity;q=0\r\n\r\n",
+    ] {
+        let (head, body) = send_raw(addr, request.as_bytes()).await;
+        assert!(
+            head.starts_with("HTTP/1.1 204") || head.starts_with("HTTP/1.1 200"),
+            "unexpected head: {}",
+            head
+        );
+        assert_eq!(header(&head, "content-encoding"), None, "{}", head);
+        assert_eq!(body, "");
+    }
+    assert_eq!(stats(addr).await["compressed_bytes"], 0);
+}
@@ -0,0 +1,180 @@
+// Config loading reports unusable values instead of ignoring them
+
+use std::collections::HashMap;
+
+use rust_http_server::{Config, ConfigError};
+
+fn load(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    Config::from_lookup(|key| vars.get(key).cloned())
+}
+
+#[test]
+fn unset_variables_keep_the_defaults() {
+    let config = load(&[]).unwrap();
+    assert_eq!(config.max_delay_ms, Config::default().max_delay_ms);
+}
+
+#[test]
+fn reads_set_variables() {
+    let config = load(&[("MAX_DELAY_MS", " 250 "), ("COMPRESSION", "off")]).unwrap();
+    assert_eq!(config.max_delay_ms, 250);
+    assert!(!config.compression);
+}
+
+#[test]
+fn base_path_loses_its_trailing_slash() {
+    assert_eq!(load(&[("BASE_PATH", "/api/")]).unwrap().base_path, "/api");
+    assert_eq!(load(&[("BASE_PATH", "/")]).unwrap().base_path, "");
+}
+
+#[test]
+fn unparseable_values_are_invalid() {
+    for (key, value) in [
+        ("MAX_DELAY_MS", "soon"),
+        ("COMPRESSION", "maybe"),
+        ("ECHO_FORMAT", "xml"),
+        ("VIRTUAL_HOSTS", "api.example.com"),
+        ("FAVICON_PATH", "/nonexistent/favicon.ico"),
+        ("BASE_PATH", "api"),
+        ("LISTEN_ADDRS", "127.0.0.1"),
+        ("LISTEN_ADDRS", " , "),
+        ("ROUTE_TIMEOUTS", "/stats=soon"),
+        ("ROUTE_TIMEOUTS", "/nowhere=100"),
+        ("RATE_LIMIT_ALGORITHM", "fixed_window"),
+        ("RESPONSE_CACHE", "/delay/:ms=soon"),
+        ("RESPONSE_CACHE", "/delay/:ms=0"),
+        ("RESPONSE_CACHE", "/nowhere=100"),
+        ("RESPONSE_CACHE", "/echo=100"),
+        ("RESPONSE_CACHE", "/ws=100"),
+        ("RESPONSE_CACHE", "/admin/maintenance=100"),
+        ("RESPONSE_CACHE", "/debug/echo=100"),
+        ("ROUTE_HEADERS", "/health=X-Robots-Tag"),
+        ("ROUTE_HEADERS", "/health=Bad Name: x"),
+        ("ROUTE_HEADERS", "/health=Content-Length: 5"),
+        ("ROUTE_HEADERS", "/nowhere=X-Robots-Tag: noindex"),
+        ("ROUTE_HEADERS", "/nowhere/*=X-Robots-Tag: noindex"),
+        ("HTTP1_WRITEV", "sometimes"),
+        ("HTTP1_0_KEEP_ALIVE", "sometimes"),
+        ("SLOW_START_MS", "soon"),
+        ("VERIFY_BODY_DIGEST", "maybe"),
+        ("LOG_FORMAT", "json"),
+    ] {
+        let err = load(&[(key, value)]).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::Invalid { .. }),
+            "{}: {:?}",
+            key,
+            err
+        );
+        assert_eq!(err.key(), key);
+        assert_eq!(err.exit_code(), 2);
+        assert!(err.to_string().contains(key), "{}", err);
+    }
+}
+
+#[test]
+fn out_of_range_values() {
+    for (key, value) in [
+        ("LOG_SAMPLE_RATE", "1.5"),
+        ("RATE_LIMIT_PER_SEC", "-1"),
+        ("RATE_LIMIT_PER_SEC", "1e-300"),
+        ("RATE_LIMIT_IPV4_PREFIX", "40"),
+        ("RATE_LIMIT_IPV6_PREFIX", "129"),
+        ("ACCEPT_RATE_WARN_PER_SEC", "NaN"),
+        ("SLOW_START_ACCEPT_PER_SEC", "0"),
+        ("SLOW_START_ACCEPT_PER_SEC", "inf"),
+        ("LISTEN_BACKLOG", "0"),
+        ("LISTEN_BACKLOG", "70000"),
+        ("BODY_BUDGET_BYTES", "5000000000"),
+        ("HTTP1_MAX_BUF_SIZE", "4096"),
+        ("MAX_RESPONSE_SIZE", "100"),
+        ("RECENT_REQUESTS", "100001"),
+        ("MAX_QUERY_PARAMS", "0"),
+        ("MAX_QUERY_PARAM_BYTES", "0"),
+    ] {
+        let err = load(&[(key, value)]).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::OutOfRange { .. }),
+            "{}: {:?}",
+            key,
+            err
+        );
+        assert_eq!(err.key(), key);
+        assert_eq!(err.exit_code(), 3);
+    }
+    assert_eq!(
+        load(&[("LOG_SAMPLE_RATE", "1.5")]).unwrap_err().to_string(),
+        "LOG_SAMPLE_RATE=1.5 is out of range: expected between 0 and 1"
+    );
+}
+
+#[test]
+fn conflicting_settings() {
+    let cases: [&[(&str, &str)]; 7] = [
+        &[("DEFAULT_HOST", "api.example.com")],
+        &[("LISTEN_ADDRS", "127.0.0.1:8080,127.0.0.1:8080")],
+        &[("LISTEN_ADDRS", "0.0.0.0:8080, 127.0.0.1:8080")],
+        &[
+            ("VIRTUAL_HOSTS", "api.example.com=api"),
+            ("DEFAULT_HOST", "admin.example.com"),
+        ],
+        &[("MAINTENANCE", "1")],
+        &[("MAX_BODY_BYTES", "2048"), ("BODY_BUDGET_BYTES", "1024")],
+        // Only the admin site serves /health
+        &[
+            ("VIRTUAL_HOSTS", "api.example.com=api"),
+            ("STARTUP_PROBE", "1"),
+        ],
+    ];
+    for vars in cases {
+        let err = load(vars).unwrap_err();
+        assert!(
+            matches!(err, ConfigError::Conflict { .. }),
+            "{:?}: {:?}",
+            vars,
+            err
+        );
+        assert_eq!(err.exit_code(), 4);
+    }
+
+    load(&[
+        ("VIRTUAL_HOSTS", "api.example.com=api"),
+        ("DEFAULT_HOST", "API.example.com"),
+        ("MAINTENANCE", "1"),
+        ("ADMIN_TOKEN", "s3cret"),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn duplicate_listen_addrs_name_the_address() {
+    let err = load(&[("LISTEN_ADDRS", "127.0.0.1:9000,[::1]:9000,127.0.0.1:9000")]).unwrap_err();
+    assert_eq!(err.key(), "LISTEN_ADDRS");
+    assert_eq!(
+        err.to_string(),
+        "LISTEN_ADDRS lists 127.0.0.1:9000 more than once"
+    );
+
+    // Different ports, families or ephemeral ports don't collide
+    let config = load(&[(
+        "LISTEN_ADDRS",
+        "127.0.0.1:9000,127.0.0.1:9001,[::]:9000,127.0.0.1:0,127.0.0.1:0",
+    )])
+    .unwrap();
+    assert_eq!(config.listen_addrs.len(), 5);
+}
+
+#[test]
+fn otlp_endpoint_must_be_http_when_tracing() {
+    let endpoint = ("OTEL_EXPORTER_OTLP_ENDPOINT", "https://collector:4318");
+    let err = load(&[("OTEL_TRACES", "on"), endpoint]).unwrap_err();
+    assert!(matches!(err, ConfigError::Invalid { .. }), "{:?}", err);
+    assert_eq!(err.key(), "OTEL_EXPORTER_OTLP_ENDPOINT");
+
+    // Only checked when it is used
+    assert!(load(&[endpoint]).is_ok());
+}
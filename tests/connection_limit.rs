@@ -0,0 +1,122 @@
+// Per-IP cap on simultaneous open connections
+
+mod common;
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use common::start_server;
+use rust_http_server::conn_limit::{ConnectionLimiter, RejectionLog};
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// Opens a keep-alive connection and completes one request on it, so the
+// server has definitely accepted (and counted) it
+async fn open_connection(addr: SocketAddr) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut buf = [0u8; 1024];
+    let n = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(buf[..n].starts_with(b"HTTP/1.1 200"));
+    stream
+}
+
+// Whether the server hangs up on a fresh connection without answering
+async fn is_rejected(addr: SocketAddr) -> bool {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let _ = stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await;
+    let mut response = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+        .await
+        .unwrap();
+    read.is_err() || response.is_empty()
+}
+
+#[test]
+fn slots_are_released_when_guards_drop() {
+    let limiter = ConnectionLimiter::new(2);
+    let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    let other = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    let first = limiter.try_acquire(ip).unwrap();
+    let _second = limiter.try_acquire(ip).unwrap();
+    assert!(limiter.try_acquire(ip).is_none());
+    assert!(limiter.try_acquire(other).is_some());
+    assert_eq!(limiter.active(ip), 2);
+
+    drop(first);
+    assert_eq!(limiter.active(ip), 1);
+    assert!(limiter.try_acquire(ip).is_some());
+}
+
+#[test]
+fn ipv4_mapped_addresses_share_the_ipv4_count() {
+    let limiter = ConnectionLimiter::new(1);
+    let ip: IpAddr = "192.0.2.1".parse().unwrap();
+    let mapped: IpAddr = "::ffff:192.0.2.1".parse().unwrap();
+
+    let _guard = limiter.try_acquire(ip).unwrap();
+    assert!(limiter.try_acquire(mapped).is_none());
+}
+
+#[tokio::test]
+async fn connections_over_the_limit_are_refused() {
+    let addr = start_server(Config {
+        max_connections_per_ip: 2,
+        ..Config::default()
+    })
+    .await;
+
+    let first = open_connection(addr).await;
+    let _second = open_connection(addr).await;
+    assert!(is_rejected(addr).await, "third connection was served");
+
+    // Closing one frees its slot
+    drop(first);
+    let mut accepted = false;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        if !is_rejected(addr).await {
+            accepted = true;
+            break;
+        }
+    }
+    assert!(
+        accepted,
+        "slot was not released after the connection closed"
+    );
+}
+
+#[tokio::test]
+async fn unlimited_by_default() {
+    let addr = start_server(Config::default()).await;
+
+    let mut connections = Vec::new();
+    for _ in 0..30 {
+        connections.push(open_connection(addr).await);
+    }
+    assert!(!is_rejected(addr).await);
+}
+
+#[test]
+fn rejections_are_logged_at_most_once_a_second() {
+    let mut log = RejectionLog::new();
+    let start = Instant::now();
+
+    assert_eq!(log.record(start), Some(0));
+    for millis in [1, 10, 500, 999] {
+        assert_eq!(log.record(start + Duration::from_millis(millis)), None);
+    }
+    // The next line owns up to the ones skipped
+    assert_eq!(log.record(start + Duration::from_secs(1)), Some(4));
+    assert_eq!(log.record(start + Duration::from_secs(5)), Some(0));
+}
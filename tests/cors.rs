@@ -0,0 +1,122 @@
+// OPTIONS and CORS preflights answer with each route's registered methods
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{header, send_raw, start_server};
+use hyper::Method;
+use rust_http_server::{methods_for, Config};
+
+async fn request(addr: SocketAddr, head: &str) -> (String, String) {
+    let request = format!("{}\r\nHost: localhost\r\nConnection: close\r\n\r\n", head);
+    send_raw(addr, request.as_bytes()).await
+}
+
+async fn preflight(addr: SocketAddr, path: &str) -> String {
+    request(
+        addr,
+        &format!(
+            "OPTIONS {} HTTP/1.1\r\nOrigin: https://app.example.com\r\nAccess-Control-Request-Method: PUT\r\nAccess-Control-Request-Headers: content-type, x-trace",
+            path
+        ),
+    )
+    .await
+    .0
+}
+
+#[test]
+fn looks_up_methods_by_path() {
+    assert_eq!(
+        methods_for("/kv/foo"),
+        [
+            Method::GET,
+            Method::HEAD,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE
+        ]
+    );
+    assert_eq!(methods_for("/echo"), [Method::POST]);
+    assert_eq!(methods_for("/echo/stream/hi"), [Method::GET, Method::HEAD]);
+    assert_eq!(
+        methods_for("/admin/maintenance"),
+        [Method::GET, Method::HEAD, Method::POST]
+    );
+    assert!(methods_for("/kv/").is_empty());
+    assert!(methods_for("/nope").is_empty());
+}
+
+#[tokio::test]
+async fn preflight_lists_the_route_methods() {
+    let addr = start_server(Config::default()).await;
+
+    let head = preflight(addr, "/kv/foo").await;
+    assert!(
+        head.starts_with("HTTP/1.1 204"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(
+        header(&head, "access-control-allow-methods"),
+        Some("GET, HEAD, PUT, PATCH, DELETE")
+    );
+    assert_eq!(header(&head, "access-control-allow-origin"), Some("*"));
+    assert_eq!(
+        header(&head, "access-control-allow-headers"),
+        Some("content-type, x-trace")
+    );
+    assert!(header(&head, "access-control-max-age").is_some());
+
+    let head = preflight(addr, "/health").await;
+    assert_eq!(
+        header(&head, "access-control-allow-methods"),
+        Some("GET, HEAD")
+    );
+}
+
+#[tokio::test]
+async fn preflight_for_unknown_paths_is_not_found() {
+    let addr = start_server(Config::default()).await;
+
+    let head = preflight(addr, "/nope").await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected head: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn plain_options_reports_allow() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = request(addr, "OPTIONS /echo HTTP/1.1").await;
+    assert!(
+        head.starts_with("HTTP/1.1 204"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "allow"), Some("POST, OPTIONS"));
+}
+
+#[tokio::test]
+async fn wrong_methods_are_not_allowed() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = request(addr, "POST /health HTTP/1.1\r\nContent-Length: 0").await;
+    assert!(
+        head.starts_with("HTTP/1.1 405"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "allow"), Some("GET, HEAD"));
+
+    let (head, _) = request(addr, "GET /echo HTTP/1.1").await;
+    assert!(
+        head.starts_with("HTTP/1.1 405"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "allow"), Some("POST"));
+}
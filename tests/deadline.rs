@@ -0,0 +1,162 @@
+// Deadline propagation from X-Request-Deadline / grpc-timeout
+
+mod common;
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use common::{send_raw, start_server};
+use hyper::header::HeaderValue;
+use hyper::HeaderMap;
+use rust_http_server::deadline::{effective_budget, parse_grpc_timeout, remaining_until, Budget};
+use rust_http_server::Config;
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    map
+}
+
+#[test]
+fn parses_grpc_timeout_units() {
+    assert_eq!(parse_grpc_timeout("2H"), Some(Duration::from_secs(7200)));
+    assert_eq!(parse_grpc_timeout("3M"), Some(Duration::from_secs(180)));
+    assert_eq!(parse_grpc_timeout("5S"), Some(Duration::from_secs(5)));
+    assert_eq!(parse_grpc_timeout("250m"), Some(Duration::from_millis(250)));
+    assert_eq!(parse_grpc_timeout("10u"), Some(Duration::from_micros(10)));
+    assert_eq!(parse_grpc_timeout("99n"), Some(Duration::from_nanos(99)));
+
+    assert_eq!(parse_grpc_timeout("100"), None);
+    assert_eq!(parse_grpc_timeout("m"), None);
+    assert_eq!(parse_grpc_timeout("-5S"), None);
+    assert_eq!(parse_grpc_timeout("123456789S"), None);
+    assert_eq!(parse_grpc_timeout("5x"), None);
+}
+
+#[test]
+fn remaining_until_handles_past_and_future() {
+    let now = UNIX_EPOCH + Duration::from_millis(10_000);
+    assert_eq!(
+        remaining_until(12_500, now),
+        Some(Duration::from_millis(2_500))
+    );
+    assert_eq!(remaining_until(10_000, now), None);
+    assert_eq!(remaining_until(9_000, now), None);
+}
+
+#[test]
+fn smallest_limit_wins() {
+    let configured = Some(Duration::from_secs(30));
+
+    assert_eq!(
+        effective_budget(&HeaderMap::new(), configured),
+        Budget::Remaining(Duration::from_secs(30))
+    );
+    assert_eq!(effective_budget(&HeaderMap::new(), None), Budget::Unlimited);
+
+    let grpc = headers(&[("grpc-timeout", "100m".to_string())]);
+    assert_eq!(
+        effective_budget(&grpc, configured),
+        Budget::Remaining(Duration::from_millis(100))
+    );
+
+    // A propagated budget larger than the configured timeout doesn't extend it
+    let generous = headers(&[("grpc-timeout", "1H".to_string())]);
+    assert_eq!(
+        effective_budget(&generous, configured),
+        Budget::Remaining(Duration::from_secs(30))
+    );
+
+    let deadline = headers(&[("x-request-deadline", (now_ms() + 5_000).to_string())]);
+    match effective_budget(&deadline, configured) {
+        Budget::Remaining(remaining) => assert!(remaining <= Duration::from_secs(5)),
+        other => panic!("unexpected budget: {:?}", other),
+    }
+}
+
+#[test]
+fn past_deadline_is_expired() {
+    let past = headers(&[("x-request-deadline", (now_ms() - 1_000).to_string())]);
+    assert_eq!(effective_budget(&past, None), Budget::Expired);
+
+    let zero = headers(&[("grpc-timeout", "0m".to_string())]);
+    assert_eq!(effective_budget(&zero, None), Budget::Expired);
+}
+
+#[tokio::test]
+async fn expired_deadline_is_rejected_on_arrival() {
+    let addr = start_server(Config::default()).await;
+    let request = format!(
+        "GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nX-Request-Deadline: {}\r\n\r\n",
+        now_ms() - 1_000
+    );
+
+    let (head, body) = send_raw(addr, request.as_bytes()).await;
+    assert!(
+        head.starts_with("HTTP/1.1 504"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(
+        body.contains("deadline exceeded"),
+        "unexpected body: {}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn propagated_budget_cuts_slow_handlers_short() {
+    let addr = start_server(Config::default()).await;
+    let started = Instant::now();
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /delay/2000 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\ngrpc-timeout: 100m\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 504"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(started.elapsed() < Duration::from_millis(1_500));
+}
+
+#[tokio::test]
+async fn configured_timeout_applies_without_headers() {
+    let addr = start_server(Config {
+        request_timeout_ms: 100,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /delay/1000 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 504"),
+        "unexpected status: {}",
+        head
+    );
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /delay/10 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+}
@@ -0,0 +1,83 @@
+// GET /debug/block/:ms runs blocking work on the blocking pool, so it never
+// starves the async workers serving everything else
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{get, start_server};
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::Config;
+
+// Debug routes are off by default in release builds
+fn config() -> Config {
+    Config {
+        route_toggles: RouteToggles::new().with_route("/debug/block/:ms", true),
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn blocks_for_the_requested_time() {
+    let addr = start_server(config()).await;
+
+    let started = Instant::now();
+    let (head, body) = get(addr, "/debug/block/100", "").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(body.contains("Blocked 100ms"), "unexpected body: {}", body);
+    assert!(started.elapsed() >= Duration::from_millis(100));
+}
+
+// A single-threaded runtime, so a blocking sleep on the async worker would
+// hold up every other request
+#[tokio::test(flavor = "current_thread")]
+async fn concurrent_blocking_calls_leave_other_requests_alone() {
+    let addr = start_server(config()).await;
+
+    let started = Instant::now();
+    let blockers: Vec<_> = (0..8)
+        .map(|_| tokio::spawn(async move { get(addr, "/debug/block/500", "").await }))
+        .collect();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let health_started = Instant::now();
+    let (head, _) = get(addr, "/health", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert!(
+        health_started.elapsed() < Duration::from_millis(250),
+        "/health took {:?} behind blocking calls",
+        health_started.elapsed()
+    );
+
+    for blocker in blockers {
+        let (head, _) = blocker.await.unwrap();
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    }
+    // They ran side by side on the blocking pool, not one after another
+    assert!(
+        started.elapsed() < Duration::from_millis(2000),
+        "blocking calls took {:?}",
+        started.elapsed()
+    );
+}
+
+#[tokio::test]
+async fn rejects_bad_and_excessive_times() {
+    let addr = start_server(Config {
+        max_delay_ms: 1000,
+        ..config()
+    })
+    .await;
+
+    let (head, body) = get(addr, "/debug/block/soon", "").await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+    assert!(body.contains("non-negative integer"), "{}", body);
+
+    let (head, body) = get(addr, "/debug/block/1001", "").await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+    assert!(body.contains("maximum of 1000ms"), "{}", body);
+}
@@ -0,0 +1,126 @@
+// ANY /debug/echo reflects the request back as JSON, escaping header bytes
+// that aren't UTF-8
+
+mod common;
+
+use common::{send_raw, start_server};
+use hyper::header::HeaderValue;
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::{header_text, Config};
+
+// Debug routes are off by default in release builds
+fn config() -> Config {
+    Config {
+        route_toggles: RouteToggles::new().with_route("/debug/echo", true),
+        ..Config::default()
+    }
+}
+
+fn parse(body: &str) -> serde_json::Value {
+    serde_json::from_str(body).unwrap_or_else(|_| panic!("not JSON: {}", body))
+}
+
+#[tokio::test]
+async fn reflects_method_uri_headers_and_body() {
+    let addr = start_server(config()).await;
+
+    let (head, body) = send_raw(
+        addr,
+        b"PUT /debug/echo?a=1&b=two HTTP/1.1\r\nHost: localhost\r\nX-Trace: one\r\nX-Trace: two\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+
+    let body = parse(&body);
+    assert_eq!(body["method"], "PUT");
+    assert_eq!(body["uri"], "/debug/echo?a=1&b=two");
+    assert_eq!(body["version"], "HTTP/1.1");
+    assert_eq!(body["headers"]["host"], "localhost");
+    assert_eq!(
+        body["headers"]["x-trace"],
+        serde_json::json!(["one", "two"])
+    );
+    assert_eq!(body["body"], "hello");
+    assert_eq!(body["body_encoding"], "utf-8");
+}
+
+#[tokio::test]
+async fn accepts_any_method() {
+    let addr = start_server(config()).await;
+
+    for method in ["GET", "DELETE", "OPTIONS", "PROPFIND"] {
+        let request = format!(
+            "{} /debug/echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            method
+        );
+        let (head, body) = send_raw(addr, request.as_bytes()).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}: {}", method, head);
+        assert_eq!(parse(&body)["method"], method);
+    }
+}
+
+#[tokio::test]
+async fn base64_encodes_binary_bodies() {
+    let addr = start_server(config()).await;
+
+    let mut request =
+        b"POST /debug/echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\nConnection: close\r\n\r\n"
+            .to_vec();
+    request.extend_from_slice(&[0xff, 0x00, 0xfe]);
+    let (_, body) = send_raw(addr, &request).await;
+
+    let body = parse(&body);
+    assert_eq!(body["body"], "/wD+");
+    assert_eq!(body["body_encoding"], "base64");
+}
+
+#[tokio::test]
+async fn escapes_header_bytes_that_are_not_utf8() {
+    let addr = start_server(Config {
+        log_headers: true,
+        ..config()
+    })
+    .await;
+
+    let mut request = b"GET /debug/echo HTTP/1.1\r\nHost: localhost\r\nX-Raw: caf".to_vec();
+    request.extend_from_slice(&[0xc3, 0xa9, b' ', 0xff, 0xfe]);
+    request.extend_from_slice(b"\r\nConnection: close\r\n\r\n");
+    let (head, body) = send_raw(addr, &request).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(parse(&body)["headers"]["x-raw"], "caf\u{e9} \\xff\\xfe");
+}
+
+#[test]
+fn header_text_borrows_plain_values() {
+    let value = HeaderValue::from_static("text/plain");
+    assert!(matches!(
+        header_text(&value),
+        std::borrow::Cow::Borrowed("text/plain")
+    ));
+    let value = HeaderValue::from_bytes(b"\xe2\x82").unwrap();
+    assert_eq!(header_text(&value), "\\xe2\\x82");
+}
+
+#[tokio::test]
+async fn limits_the_body() {
+    let addr = start_server(Config {
+        max_body_bytes: 4,
+        ..config()
+    })
+    .await;
+
+    let (head, _) = send_raw(
+        addr,
+        b"POST /debug/echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 413"),
+        "unexpected head: {}",
+        head
+    );
+}
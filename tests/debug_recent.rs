@@ -0,0 +1,119 @@
+// GET /debug/recent lists the last RECENT_REQUESTS requests, oldest first
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{get, start_server};
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::Config;
+
+// Debug routes are off by default in release builds
+fn config(recent_requests: usize) -> Config {
+    Config {
+        recent_requests,
+        route_toggles: RouteToggles::new().with_route("/debug/recent", true),
+        ..Config::default()
+    }
+}
+
+async fn recent(addr: SocketAddr) -> serde_json::Value {
+    let (head, body) = get(addr, "/debug/recent", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    serde_json::from_str(&body).unwrap_or_else(|_| panic!("not JSON: {}", body))
+}
+
+#[tokio::test]
+async fn lists_requests_oldest_first() {
+    let addr = start_server(config(10)).await;
+    get(addr, "/health", "").await;
+    get(addr, "/echo/hi?token=s3cret", "").await;
+    get(addr, "/error/418", "").await;
+
+    let body = recent(addr).await;
+    assert_eq!(body["capacity"], 10);
+    let requests = body["requests"].as_array().unwrap();
+    let seen: Vec<(&str, &str, u64)> = requests
+        .iter()
+        .map(|request| {
+            (
+                request["method"].as_str().unwrap(),
+                request["path"].as_str().unwrap(),
+                request["status"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    // The query is left out; this request isn't listed yet
+    assert_eq!(
+        seen,
+        [
+            ("GET", "/health", 200),
+            ("GET", "/echo/hi", 200),
+            ("GET", "/error/418", 418)
+        ]
+    );
+    for request in requests {
+        assert!(
+            request["latency_ms"].as_f64().unwrap() >= 0.0,
+            "{}",
+            request
+        );
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(request["timestamp"].as_str().unwrap()).is_ok()
+        );
+    }
+}
+
+#[tokio::test]
+async fn keeps_only_the_latest() {
+    let addr = start_server(config(3)).await;
+    for i in 0..10 {
+        get(addr, &format!("/echo/{}", i), "").await;
+    }
+
+    let body = recent(addr).await;
+    let paths: Vec<&str> = body["requests"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|request| request["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths, ["/echo/7", "/echo/8", "/echo/9"]);
+
+    // Its own earlier request now counts among them
+    let body = recent(addr).await;
+    assert_eq!(body["requests"][2]["path"], "/debug/recent");
+}
+
+#[tokio::test]
+async fn latency_covers_the_handler() {
+    let addr = start_server(config(5)).await;
+    get(addr, "/delay/50", "").await;
+
+    let body = recent(addr).await;
+    let latency = body["requests"][0]["latency_ms"].as_f64().unwrap();
+    assert!(latency >= 50.0, "latency {}", latency);
+}
+
+#[tokio::test]
+async fn empty_unless_configured() {
+    let addr = start_server(config(0)).await;
+    get(addr, "/health", "").await;
+
+    let body = recent(addr).await;
+    assert_eq!(body["capacity"], 0);
+    assert_eq!(body["requests"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn can_be_switched_off() {
+    let addr = start_server(Config {
+        recent_requests: 10,
+        route_toggles: RouteToggles::new().with_route("/debug/recent", false),
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = get(addr, "/debug/recent", "").await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+}
@@ -0,0 +1,82 @@
+// GET /debug/runtime reports the Tokio runtime's metrics as JSON
+
+mod common;
+
+use common::{send_raw, start_server};
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::Config;
+
+// Debug routes are off by default in release builds
+fn config() -> Config {
+    Config {
+        route_toggles: RouteToggles::new().with_route("/debug/runtime", true),
+        ..Config::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 3)]
+async fn reports_runtime_metrics() {
+    let addr = start_server(config()).await;
+
+    let (head, body) = send_raw(
+        addr,
+        b"GET /debug/runtime HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+
+    let body: serde_json::Value =
+        serde_json::from_str(&body).unwrap_or_else(|_| panic!("not JSON: {}", body));
+    assert_eq!(body["flavor"], "multi_thread");
+    assert_eq!(body["workers"], 3);
+    // At least the accept loop and this connection's task
+    assert!(body["alive_tasks"].as_u64().unwrap() >= 2, "{}", body);
+    assert!(body["global_queue_depth"].is_u64(), "{}", body);
+    let workers = body["worker_stats"].as_array().unwrap();
+    assert_eq!(workers.len(), 3);
+    for (index, worker) in workers.iter().enumerate() {
+        assert_eq!(worker["index"], index);
+        assert!(worker["busy_ms"].is_u64(), "{}", worker);
+        assert!(worker["park_count"].is_u64(), "{}", worker);
+    }
+}
+
+#[tokio::test]
+async fn only_answers_get() {
+    let addr = start_server(config()).await;
+
+    let (head, _) = send_raw(
+        addr,
+        b"POST /debug/runtime HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 405"),
+        "unexpected head: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn can_be_switched_off() {
+    let addr = start_server(Config {
+        route_toggles: RouteToggles::new().with_route("/debug/runtime", false),
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /debug/runtime HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected head: {}",
+        head
+    );
+}
@@ -0,0 +1,104 @@
+// text/plain echo, chosen by Accept or the ECHO_FORMAT default
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::{Config, EchoFormat};
+
+async fn echo(addr: SocketAddr, accept: Option<&str>) -> (String, String) {
+    let accept = accept
+        .map(|accept| format!("Accept: {}\r\n", accept))
+        .unwrap_or_default();
+    let request = format!(
+        "GET /echo/hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}\r\n",
+        accept
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+fn content_type(head: &str) -> &str {
+    header(head, "content-type").unwrap_or("")
+}
+
+#[test]
+fn parses_echo_format() {
+    assert_eq!("json".parse::<EchoFormat>(), Ok(EchoFormat::Json));
+    assert_eq!(" TEXT ".parse::<EchoFormat>(), Ok(EchoFormat::Text));
+    assert!("xml".parse::<EchoFormat>().is_err());
+}
+
+#[tokio::test]
+async fn json_by_default() {
+    let addr = start_server(Config::default()).await;
+
+    for accept in [None, Some("*/*"), Some("application/json")] {
+        let (head, body) = echo(addr, accept).await;
+        assert_eq!(
+            content_type(&head),
+            "application/json",
+            "accept {:?}",
+            accept
+        );
+        assert!(
+            body.contains("\"message\":\"Echo: hello\""),
+            "unexpected body: {}",
+            body
+        );
+    }
+}
+
+#[tokio::test]
+async fn text_when_the_client_prefers_it() {
+    let addr = start_server(Config::default()).await;
+
+    for accept in [
+        "text/plain",
+        "text/*",
+        "application/json;q=0.5, text/plain",
+        "text/html, text/plain;q=0.9, */*;q=0.1",
+    ] {
+        let (head, body) = echo(addr, Some(accept)).await;
+        assert!(
+            head.starts_with("HTTP/1.1 200"),
+            "unexpected status: {}",
+            head
+        );
+        assert_eq!(
+            content_type(&head),
+            "text/plain; charset=utf-8",
+            "accept {:?}",
+            accept
+        );
+        assert_eq!(body, "hello");
+    }
+}
+
+#[tokio::test]
+async fn configured_default_applies_on_a_tie() {
+    let addr = start_server(Config {
+        echo_format: EchoFormat::Text,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, body) = echo(addr, None).await;
+    assert_eq!(content_type(&head), "text/plain; charset=utf-8");
+    assert_eq!(body, "hello");
+
+    let (head, _) = echo(addr, Some("application/json")).await;
+    assert_eq!(content_type(&head), "application/json");
+}
+
+#[tokio::test]
+async fn post_echo_honours_the_format() {
+    let addr = start_server(Config::default()).await;
+    let (head, body) = send_raw(
+        addr,
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nAccept: text/plain\r\nContent-Length: 8\r\n\r\nraw\nbody",
+    )
+    .await;
+    assert_eq!(content_type(&head), "text/plain; charset=utf-8");
+    assert_eq!(body, "raw\nbody");
+}
@@ -0,0 +1,61 @@
+// GET /echo-header/:msg returns the message in X-Echo, refusing anything
+// that could break out of the header
+
+mod common;
+
+use common::{get, header, send_raw, start_server};
+use rust_http_server::Config;
+
+#[tokio::test]
+async fn echoes_into_a_header() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = get(addr, "/echo-header/Hello%20World%21", "").await;
+    assert!(head.starts_with("HTTP/1.1 204"), "{}", head);
+    assert_eq!(header(&head, "x-echo"), Some("Hello World!"));
+    assert!(body.is_empty(), "{}", body);
+}
+
+#[tokio::test]
+async fn rejects_header_injection() {
+    let addr = start_server(Config::default()).await;
+
+    for target in [
+        "/echo-header/a%0D%0ASet-Cookie:%20x=1",
+        "/echo-header/a%0Ab",
+        "/echo-header/nul%00",
+        "/echo-header/tab%09",
+        "/echo-header/caf%C3%A9",
+    ] {
+        // Encoded control characters are already refused by path normalization
+        let (head, _) = get(addr, target, "").await;
+        assert!(head.starts_with("HTTP/1.1 400"), "{}: {}", target, head);
+        assert!(header(&head, "set-cookie").is_none(), "{}", head);
+        assert!(header(&head, "x-echo").is_none(), "{}", head);
+    }
+
+    let (_, body) = get(addr, "/echo-header/caf%C3%A9", "").await;
+    assert!(body.contains("printable ASCII"), "{}", body);
+}
+
+#[tokio::test]
+async fn is_an_api_route() {
+    let addr = start_server(Config {
+        virtual_hosts: "api.example.com=api, admin.example.com=admin"
+            .parse()
+            .unwrap(),
+        ..Config::default()
+    })
+    .await;
+
+    let request = |host: &str| {
+        format!(
+            "GET /echo-header/hi HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            host
+        )
+    };
+    let (head, _) = send_raw(addr, request("api.example.com").as_bytes()).await;
+    assert!(head.starts_with("HTTP/1.1 204"), "{}", head);
+    let (head, _) = send_raw(addr, request("admin.example.com").as_bytes()).await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+}
@@ -0,0 +1,120 @@
+// `GET /echo/:msg?repeat=`: buffered when small, streamed when large
+
+mod common;
+
+use common::{decode_chunked, get, header, start_server};
+use rust_http_server::Config;
+
+fn small_threshold() -> Config {
+    Config {
+        echo_stream_threshold_bytes: 16,
+        max_echo_bytes: 1024 * 1024,
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn small_repeats_are_buffered() {
+    let addr = start_server(small_threshold()).await;
+
+    let (head, body) = get(addr, "/echo/ab?repeat=3", "Accept: application/json\r\n").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(header(&head, "content-length").is_some(), "head: {}", head);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["message"], "Echo: ababab");
+}
+
+#[tokio::test]
+async fn large_repeats_are_streamed() {
+    let addr = start_server(small_threshold()).await;
+
+    let (head, body) = get(
+        addr,
+        "/echo/a%22b?repeat=10000",
+        "Accept: application/json\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "transfer-encoding"), Some("chunked"));
+    assert_eq!(header(&head, "content-type"), Some("application/json"));
+
+    let (data, _) = decode_chunked(&body);
+    let body: serde_json::Value = serde_json::from_str(&data).unwrap();
+    assert_eq!(
+        body["message"],
+        format!("Echo: {}", "a%22b".repeat(10_000)).as_str()
+    );
+    assert_eq!(body["server"], "rust-http-server/1.0");
+    assert!(body["timestamp"].is_string());
+}
+
+#[tokio::test]
+async fn large_text_repeats_are_streamed() {
+    let addr = start_server(small_threshold()).await;
+
+    let (head, body) = get(addr, "/echo/xyz?repeat=7001", "Accept: text/plain\r\n").await;
+    assert_eq!(header(&head, "transfer-encoding"), Some("chunked"));
+    let (data, _) = decode_chunked(&body);
+    assert_eq!(data, "xyz".repeat(7001));
+
+    // Sent in full, so not counted as cancelled
+    let (_, stats) = get(addr, "/stats", "Accept: application/json\r\n").await;
+    let stats: serde_json::Value = serde_json::from_str(&stats).unwrap();
+    assert_eq!(stats["cancelled_requests"], 0);
+}
+
+#[tokio::test]
+async fn rejects_repeats_over_the_cap() {
+    let addr = start_server(Config {
+        max_echo_bytes: 100,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, body) = get(addr, "/echo/abcd?repeat=26", "Accept: application/json\r\n").await;
+    assert!(
+        head.starts_with("HTTP/1.1 400"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(
+        body.contains("more than 100 bytes"),
+        "unexpected body: {}",
+        body
+    );
+
+    let (head, _) = get(addr, "/echo/abcd?repeat=25", "Accept: application/json\r\n").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn rejects_invalid_repeat_counts() {
+    let addr = start_server(Config::default()).await;
+
+    for repeat in ["0", "-1", "many", "99999999999999999999999"] {
+        let (head, _) = get(
+            addr,
+            &format!("/echo/hi?repeat={}", repeat),
+            "Accept: application/json\r\n",
+        )
+        .await;
+        assert!(
+            head.starts_with("HTTP/1.1 400"),
+            "repeat {:?}: unexpected head: {}",
+            repeat,
+            head
+        );
+    }
+}
@@ -0,0 +1,115 @@
+// `Expect: 100-continue` with a chunked upload: the interim response comes
+// first, then the body is read in full, however it is split up on the wire
+
+mod common;
+
+use std::time::Duration;
+
+use common::start_server;
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+const HEAD: &[u8] = b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nExpect: 100-continue\r\nConnection: close\r\n\r\n";
+
+// Reads up to and including the blank line ending a response head
+async fn read_head(stream: &mut TcpStream) -> String {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0u8; 1];
+        let read = timeout(Duration::from_secs(5), stream.read(&mut byte))
+            .await
+            .expect("timed out waiting for a response head")
+            .unwrap();
+        assert_eq!(read, 1, "connection closed mid-head: {:?}", head);
+        head.push(byte[0]);
+    }
+    String::from_utf8(head).unwrap()
+}
+
+async fn upload(config: Config, chunks: &[&[u8]]) -> (String, String, String) {
+    let addr = start_server(config).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(HEAD).await.unwrap();
+
+    // Nothing of the body is sent until the server asks for it
+    let interim = read_head(&mut stream).await;
+
+    for chunk in chunks {
+        stream.write_all(chunk).await.unwrap();
+        stream.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let head = read_head(&mut stream).await;
+    let mut body = String::new();
+    timeout(Duration::from_secs(5), stream.read_to_string(&mut body))
+        .await
+        .expect("timed out waiting for the response body")
+        .unwrap();
+    (interim, head, body)
+}
+
+#[tokio::test]
+async fn continue_then_echoes_the_chunked_body() {
+    let (interim, head, body) = upload(
+        Config::default(),
+        &[
+            b"5\r\nhello\r\n",
+            // A chunk split across writes, with an extension
+            b"6;note=split\r\n wo",
+            b"rld\r\n",
+            b"0\r\n\r\n",
+        ],
+    )
+    .await;
+
+    assert_eq!(interim, "HTTP/1.1 100 Continue\r\n\r\n");
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["message"], "Echo: hello world");
+}
+
+#[tokio::test]
+async fn trailers_after_the_last_chunk_are_accepted() {
+    let (interim, head, body) = upload(
+        Config::default(),
+        &[b"3\r\nabc\r\n", b"0\r\nX-Checksum: 1234\r\n\r\n"],
+    )
+    .await;
+
+    assert!(interim.starts_with("HTTP/1.1 100"), "{}", interim);
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("Echo: abc"), "unexpected body: {}", body);
+}
+
+#[tokio::test]
+async fn chunked_body_over_the_limit_is_rejected_after_continue() {
+    // No Content-Length to refuse up front, so the limit applies while
+    // reading. The server answers as soon as it has read past the limit,
+    // so nothing is sent after the oversized chunk.
+    let (interim, head, _) = upload(
+        Config {
+            max_body_bytes: 8,
+            ..Config::default()
+        },
+        &[b"10\r\n0123456789abcdef\r\n"],
+    )
+    .await;
+
+    assert!(interim.starts_with("HTTP/1.1 100"), "{}", interim);
+    assert!(
+        head.starts_with("HTTP/1.1 413"),
+        "unexpected status: {}",
+        head
+    );
+}
@@ -0,0 +1,162 @@
+// /favicon.ico: 204 by default, or the configured icon
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use common::{get, header, send_raw, start_server};
+use hyper::body::Bytes;
+use rust_http_server::{Config, Favicon};
+
+#[tokio::test]
+async fn no_content_by_default() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = get(addr, "/favicon.ico", "").await;
+    assert!(
+        head.starts_with("HTTP/1.1 204"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(body.is_empty());
+    assert!(header(&head, "cache-control").is_some(), "head: {}", head);
+}
+
+#[tokio::test]
+async fn serves_the_configured_icon() {
+    let addr = start_server(Config {
+        favicon: Some(Favicon {
+            bytes: Bytes::from_static(b"<svg/>"),
+            content_type: "image/svg+xml",
+            modified: None,
+        }),
+        ..Config::default()
+    })
+    .await;
+
+    let (head, body) = get(addr, "/favicon.ico", "").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "content-type"), Some("image/svg+xml"));
+    assert_eq!(body, "<svg/>");
+}
+
+#[tokio::test]
+async fn served_at_the_root_under_a_base_path() {
+    let addr = start_server(Config {
+        base_path: "/api".to_string(),
+        ..Config::default()
+    })
+    .await;
+
+    for path in ["/favicon.ico", "/api/favicon.ico"] {
+        let (head, _) = get(addr, path, "").await;
+        assert!(head.starts_with("HTTP/1.1 204"), "{}: {}", path, head);
+    }
+}
+
+#[test]
+fn loads_icons_by_extension() {
+    let path = std::env::temp_dir().join(format!("favicon-test-{}.PNG", std::process::id()));
+    std::fs::write(&path, b"\x89PNG").unwrap();
+    let icon = Favicon::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(icon.content_type, "image/png");
+    assert_eq!(&icon.bytes[..], b"\x89PNG");
+    assert!(Favicon::load("/nonexistent/favicon.ico").is_err());
+}
+
+fn icon_modified_at(modified: SystemTime) -> Config {
+    Config {
+        favicon: Some(Favicon {
+            bytes: Bytes::from_static(b"<svg/>"),
+            content_type: "image/svg+xml",
+            modified: Some(modified),
+        }),
+        ..Config::default()
+    }
+}
+
+async fn get_if_modified_since(addr: SocketAddr, since: &str) -> (String, String) {
+    let request = format!(
+        "GET /favicon.ico HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nIf-Modified-Since: {}\r\n\r\n",
+        since
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+#[tokio::test]
+async fn sends_last_modified() {
+    // 1994-11-06 08:49:37.5 UTC
+    let modified = UNIX_EPOCH + Duration::from_millis(784_111_777_500);
+    let addr = start_server(icon_modified_at(modified)).await;
+
+    let (head, _) = get(addr, "/favicon.ico", "").await;
+    assert_eq!(
+        header(&head, "last-modified"),
+        Some("Sun, 06 Nov 1994 08:49:37 GMT")
+    );
+}
+
+#[tokio::test]
+async fn unchanged_icons_are_not_modified() {
+    let modified = UNIX_EPOCH + Duration::from_millis(784_111_777_500);
+    let addr = start_server(icon_modified_at(modified)).await;
+
+    // The exact Last-Modified value, later dates, and the obsolete forms
+    for since in [
+        "Sun, 06 Nov 1994 08:49:37 GMT",
+        "Mon, 07 Nov 1994 00:00:00 GMT",
+        "Sunday, 06-Nov-94 08:49:37 GMT",
+        "Sun Nov  6 08:49:37 1994",
+    ] {
+        let (head, body) = get_if_modified_since(addr, since).await;
+        assert!(head.starts_with("HTTP/1.1 304"), "{}: {}", since, head);
+        assert!(body.is_empty());
+        assert!(header(&head, "last-modified").is_some());
+        assert!(header(&head, "content-type").is_none());
+    }
+}
+
+#[tokio::test]
+async fn changed_icons_are_sent_in_full() {
+    let modified = UNIX_EPOCH + Duration::from_secs(784_111_777);
+    let addr = start_server(icon_modified_at(modified)).await;
+
+    for since in [
+        // Earlier than the modification
+        "Sun, 06 Nov 1994 08:49:36 GMT",
+        // Unparseable or in the future: ignored
+        "not a date",
+        "Sun, 06 Nov 1994 08:49:37 CET",
+        "Fri, 31 Dec 9999 23:59:59 GMT",
+    ] {
+        let (head, body) = get_if_modified_since(addr, since).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}: {}", since, head);
+        assert_eq!(body, "<svg/>");
+    }
+
+    // If-None-Match takes precedence over If-Modified-Since
+    let (head, _) = send_raw(
+        addr,
+        b"GET /favicon.ico HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nIf-None-Match: \"x\"\r\nIf-Modified-Since: Mon, 07 Nov 1994 00:00:00 GMT\r\n\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
+
+#[test]
+fn loaded_icons_carry_their_modification_time() {
+    let path = std::env::temp_dir().join(format!("favicon-mtime-{}.ico", std::process::id()));
+    std::fs::write(&path, b"icon").unwrap();
+    let expected = std::fs::metadata(&path).unwrap().modified().unwrap();
+    let icon = Favicon::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(icon.modified, Some(expected));
+}
@@ -0,0 +1,191 @@
+// Requests with ambiguous body framing are rejected instead of guessed at
+
+mod common;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::Config;
+
+async fn assert_rejected(request: &[u8]) {
+    let addr = start_server(Config::default()).await;
+    let (head, _) = send_raw(addr, request).await;
+    assert!(
+        head.starts_with("HTTP/1.1 400"),
+        "unexpected status: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn rejects_conflicting_content_length() {
+    assert_rejected(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nContent-Length: 3\r\n\r\nhello",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn rejects_content_length_list() {
+    assert_rejected(b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5, 5\r\n\r\nhello")
+        .await;
+}
+
+#[tokio::test]
+async fn rejects_content_length_with_chunked() {
+    assert_rejected(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn rejected_requests_close_the_connection() {
+    let addr = start_server(Config::default()).await;
+    // A smuggled second request hides behind the ambiguous first one
+    let (head, body) = send_raw(
+        addr,
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\nGET /health HTTP/1.1\r\nHost: localhost\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 400"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(header(&head, "connection"), Some("close"));
+    assert!(
+        body.contains("mutually exclusive"),
+        "unexpected body: {}",
+        body
+    );
+    assert!(
+        !body.contains("HTTP/1.1 200"),
+        "second request was served: {}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn well_framed_bodies_are_accepted() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = send_raw(
+        addr,
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 5\r\n\r\nhello",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+
+    let (head, _) = send_raw(
+        addr,
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+}
+
+async fn status_of(request: &[u8]) -> (String, String) {
+    let addr = start_server(Config::default()).await;
+    send_raw(addr, request).await
+}
+
+#[tokio::test]
+async fn rejects_content_length_with_chunked_on_bodiless_routes() {
+    assert_rejected(
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn unsupported_transfer_coding_is_not_implemented() {
+    let (head, body) = status_of(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 501"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(header(&head, "connection"), Some("close"));
+    assert!(body.contains("gzip"), "unexpected body: {}", body);
+}
+
+#[tokio::test]
+async fn transfer_codings_across_header_lines_are_checked() {
+    let (head, _) = status_of(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: deflate\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 501"),
+        "unexpected status: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn rejects_chunked_applied_twice() {
+    assert_rejected(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked, chunked\r\n\r\n0\r\n\r\n",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn rejects_transfer_coding_not_ending_in_chunked() {
+    assert_rejected(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: gzip\r\n\r\nhello",
+    )
+    .await;
+    assert_rejected(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked, gzip\r\n\r\n0\r\n\r\n",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn rejects_transfer_encoding_on_http_1_0() {
+    let (head, _) = status_of(
+        b"POST /echo HTTP/1.0\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+    )
+    .await;
+    assert!(head.contains(" 400 "), "unexpected status: {}", head);
+}
+
+#[tokio::test]
+async fn content_length_after_chunked_is_ignored() {
+    // hyper drops a Content-Length that follows Transfer-Encoding, so the
+    // body is framed by chunked alone
+    let (head, body) = status_of(
+        b"POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("hello"), "unexpected body: {}", body);
+}
+
+#[tokio::test]
+async fn te_request_header_is_accepted() {
+    let (head, _) = status_of(
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nTE: trailers, gzip;q=0.5\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+}
@@ -0,0 +1,105 @@
+// UNHEALTHY_AFTER_ERRORS: /health answers 503 after N consecutive 5xx
+// responses and recovers with the next 2xx
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{get, start_server};
+use rust_http_server::Config;
+
+fn config(errors: u64) -> Config {
+    Config {
+        unhealthy_after_errors: errors,
+        ..Config::default()
+    }
+}
+
+async fn health(addr: SocketAddr) -> u16 {
+    let (head, _) = get(addr, "/health", "").await;
+    head[9..12].parse().unwrap()
+}
+
+#[tokio::test]
+async fn fails_after_consecutive_server_errors() {
+    let addr = start_server(config(3)).await;
+
+    get(addr, "/error/500", "").await;
+    get(addr, "/error/503", "").await;
+    assert_eq!(health(addr).await, 200);
+    get(addr, "/error/502", "").await;
+
+    let (head, body) = get(addr, "/health", "").await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+    assert!(
+        body.contains("Unhealthy after 3 consecutive server errors"),
+        "{}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn recovers_after_a_success() {
+    let addr = start_server(config(2)).await;
+    get(addr, "/error/500", "").await;
+    get(addr, "/error/500", "").await;
+    assert_eq!(health(addr).await, 503);
+
+    get(addr, "/echo/hi", "").await;
+    assert_eq!(health(addr).await, 200);
+}
+
+#[tokio::test]
+async fn client_errors_neither_count_nor_reset() {
+    let addr = start_server(config(3)).await;
+    get(addr, "/error/500", "").await;
+    get(addr, "/nowhere", "").await;
+    get(addr, "/error/500", "").await;
+    get(addr, "/error/400", "").await;
+    get(addr, "/error/500", "").await;
+
+    assert_eq!(health(addr).await, 503);
+}
+
+#[tokio::test]
+async fn health_checks_themselves_are_left_out() {
+    let addr = start_server(config(2)).await;
+    get(addr, "/error/500", "").await;
+    // A healthy answer doesn't end the run of errors
+    assert_eq!(health(addr).await, 200);
+    get(addr, "/error/500", "").await;
+
+    // Nor do unhealthy ones extend it past what traffic caused
+    for _ in 0..3 {
+        assert_eq!(health(addr).await, 503);
+    }
+    let (_, body) = get(addr, "/health", "").await;
+    assert!(body.contains("after 2 consecutive"), "{}", body);
+}
+
+#[tokio::test]
+async fn count_is_in_stats() {
+    let addr = start_server(config(0)).await;
+    for _ in 0..4 {
+        get(addr, "/error/500", "").await;
+    }
+
+    let (_, body) = get(addr, "/stats", "").await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["consecutive_errors"], 4);
+
+    // The /stats request was a success, so the run is over
+    let (_, body) = get(addr, "/stats", "").await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["consecutive_errors"], 0);
+}
+
+#[tokio::test]
+async fn off_by_default() {
+    let addr = start_server(Config::default()).await;
+    for _ in 0..10 {
+        get(addr, "/error/500", "").await;
+    }
+
+    assert_eq!(health(addr).await, 200);
+}
@@ -0,0 +1,84 @@
+// HTTP1_MAX_BUF_SIZE and HTTP1_WRITEV tune each connection's buffering
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{send_raw, start_server};
+use rust_http_server::config::{Config, DEFAULT_HTTP1_MAX_BUF_SIZE};
+
+fn load(vars: &[(&str, &str)]) -> Config {
+    Config::from_lookup(|key| {
+        vars.iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value.to_string())
+    })
+    .unwrap()
+}
+
+async fn echo(addr: SocketAddr, body: &str) -> (String, String) {
+    let request = format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+#[test]
+fn reads_buffer_settings() {
+    let config = load(&[]);
+    assert_eq!(config.http1_max_buf_size, DEFAULT_HTTP1_MAX_BUF_SIZE);
+    assert_eq!(config.http1_writev, None);
+
+    let config = load(&[("HTTP1_MAX_BUF_SIZE", "65536"), ("HTTP1_WRITEV", "off")]);
+    assert_eq!(config.http1_max_buf_size, 65536);
+    assert_eq!(config.http1_writev, Some(false));
+    assert_eq!(load(&[("HTTP1_WRITEV", "Auto")]).http1_writev, None);
+    assert_eq!(load(&[("HTTP1_WRITEV", "1")]).http1_writev, Some(true));
+}
+
+#[tokio::test]
+async fn large_bodies_pass_through_the_smallest_buffer() {
+    let body = "x".repeat(100_000);
+    for writev in [None, Some(true), Some(false)] {
+        let addr = start_server(Config {
+            http1_max_buf_size: 8192,
+            http1_writev: writev,
+            ..Config::default()
+        })
+        .await;
+        let (head, echoed) = echo(addr, &body).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{:?}: {}", writev, head);
+        assert!(echoed.contains(&body), "{:?}: body lost", writev);
+    }
+}
+
+// The buffer also bounds the request head, which has to fit in it whole
+#[tokio::test]
+async fn request_heads_must_fit_the_buffer() {
+    let request = format!(
+        "GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nX-Padding: {}\r\n\r\n",
+        "p".repeat(20_000)
+    );
+
+    let addr = start_server(Config {
+        http1_max_buf_size: 8192,
+        ..Config::default()
+    })
+    .await;
+    let (head, _) = send_raw(addr, request.as_bytes()).await;
+    assert!(
+        head.starts_with("HTTP/1.1 431"),
+        "unexpected head: {}",
+        head
+    );
+
+    let addr = start_server(Config::default()).await;
+    let (head, _) = send_raw(addr, request.as_bytes()).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+}
@@ -0,0 +1,132 @@
+// Baseline suite: the server started through `run` on an ephemeral port,
+// exercised with a real HTTP client.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::{Request, StatusCode};
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use rust_http_server::{run, Config};
+use serde_json::Value;
+use tokio::sync::oneshot;
+
+struct TestServer {
+    addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    handle: tokio::task::JoinHandle<std::io::Result<()>>,
+}
+
+impl TestServer {
+    async fn start() -> Self {
+        // Reserve an ephemeral port, then hand it to `run`
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+        let handle = tokio::spawn(run(addr, Config::default(), async {
+            let _ = shutdown_rx.await;
+        }));
+
+        // Wait until the listener is up
+        for _ in 0..50 {
+            if tokio::net::TcpStream::connect(addr).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        Self {
+            addr,
+            shutdown: Some(shutdown),
+            handle,
+        }
+    }
+
+    async fn get(&self, path: &str) -> (StatusCode, Value) {
+        let client = Client::builder(TokioExecutor::new()).build_http::<Empty<Bytes>>();
+        let request = Request::get(format!("http://{}{}", self.addr, path))
+            .body(Empty::new())
+            .unwrap();
+        let response = client.request(request).await.unwrap();
+        let status = response.status();
+        assert_eq!(
+            response.headers()["content-type"],
+            "application/json",
+            "unexpected content type for {}",
+            path
+        );
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    async fn stop(mut self) {
+        self.shutdown.take().unwrap().send(()).unwrap();
+        self.handle.await.unwrap().unwrap();
+    }
+}
+
+fn assert_message_shape(body: &Value) {
+    assert!(body["message"].is_string(), "missing message: {}", body);
+    assert!(body["timestamp"].is_string(), "missing timestamp: {}", body);
+    assert_eq!(body["server"], "rust-http-server/1.0");
+}
+
+#[tokio::test]
+async fn root_returns_welcome() {
+    let server = TestServer::start().await;
+    let (status, body) = server.get("/").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_message_shape(&body);
+    assert_eq!(body["message"], "Welcome to Rust HTTP Server!");
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn health_reports_healthy() {
+    let server = TestServer::start().await;
+    let (status, body) = server.get("/health").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_message_shape(&body);
+    assert_eq!(body["message"], "Server is healthy");
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn stats_has_numeric_counters() {
+    let server = TestServer::start().await;
+    let (status, body) = server.get("/stats").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["total_requests"].is_u64(), "bad stats: {}", body);
+    assert!(body["uptime_seconds"].is_u64(), "bad stats: {}", body);
+    assert!(body["requests_per_second"].is_f64(), "bad stats: {}", body);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn echo_returns_message() {
+    let server = TestServer::start().await;
+    let (status, body) = server.get("/echo/hello").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_message_shape(&body);
+    assert_eq!(body["message"], "Echo: hello");
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn unknown_path_is_not_found() {
+    let server = TestServer::start().await;
+    let (status, body) = server.get("/does-not-exist").await;
+
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_message_shape(&body);
+    assert_eq!(body["message"], "Not Found");
+    server.stop().await;
+}
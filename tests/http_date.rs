@@ -0,0 +1,65 @@
+// HTTP-date parsing accepts all three RFC 9110 forms and nothing else
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rust_http_server::http_date::{format_http_date, parse_http_date};
+
+// 1994-11-06 08:49:37 UTC, the RFC's example date
+fn example() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(784_111_777)
+}
+
+#[test]
+fn formats_imf_fixdate() {
+    assert_eq!(format_http_date(example()), "Sun, 06 Nov 1994 08:49:37 GMT");
+    // Fractions of a second are dropped
+    assert_eq!(
+        format_http_date(example() + Duration::from_millis(999)),
+        "Sun, 06 Nov 1994 08:49:37 GMT"
+    );
+}
+
+#[test]
+fn parses_every_form() {
+    for value in [
+        "Sun, 06 Nov 1994 08:49:37 GMT",
+        "Sunday, 06-Nov-94 08:49:37 GMT",
+        "Sun Nov  6 08:49:37 1994",
+        " Sun, 06 Nov 1994 08:49:37 GMT ",
+    ] {
+        assert_eq!(parse_http_date(value), Some(example()), "{:?}", value);
+    }
+}
+
+#[test]
+fn two_digit_years_are_never_far_in_the_future() {
+    // Two-digit years up to 50 years ahead are this century; anything
+    // further is the last one
+    let parsed = parse_http_date("Thursday, 01-Jan-98 00:00:00 GMT").unwrap();
+    assert_eq!(format_http_date(parsed), "Thu, 01 Jan 1998 00:00:00 GMT");
+    let parsed = parse_http_date("Friday, 01-Jan-27 00:00:00 GMT").unwrap();
+    assert_eq!(format_http_date(parsed), "Fri, 01 Jan 2027 00:00:00 GMT");
+}
+
+#[test]
+fn rejects_other_formats() {
+    for value in [
+        "",
+        "yesterday",
+        // Not GMT
+        "Sun, 06 Nov 1994 08:49:37 PST",
+        "Sun, 06 Nov 1994 08:49:37 +0000",
+        // Weekday disagrees with the date
+        "Mon, 06 Nov 1994 08:49:37 GMT",
+        "Monday, 06-Nov-94 08:49:37 GMT",
+        // Single-digit day in IMF-fixdate
+        "Sun, 6 Nov 1994 08:49:37 GMT",
+        "Sun, 06 Nov 1994 25:49:37 GMT",
+        "Sunday, 06-Nov-1994 08:49:37 GMT",
+        // Before the Unix epoch
+        "Wed, 31 Dec 1969 23:59:59 GMT",
+        "1994-11-06T08:49:37Z",
+    ] {
+        assert_eq!(parse_http_date(value), None, "{:?}", value);
+    }
+}
@@ -0,0 +1,158 @@
+// /stats counts requests by HTTP version, and HTTP/1.0 connections only
+// stay open when the client asks for keep-alive and HTTP1_0_KEEP_ALIVE allows
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn stats(addr: SocketAddr, target: &str) -> String {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        target
+    );
+    send_raw(addr, request.as_bytes()).await.1
+}
+
+#[tokio::test]
+async fn counts_requests_by_version() {
+    let addr = start_server(Config::default()).await;
+    for _ in 0..2 {
+        send_raw(addr, b"GET /health HTTP/1.0\r\nHost: localhost\r\n\r\n").await;
+    }
+
+    let stats: serde_json::Value = serde_json::from_str(&stats(addr, "/stats").await).unwrap();
+    // The /stats request itself is the HTTP/1.1 one
+    assert_eq!(
+        stats["by_http_version"],
+        serde_json::json!({"HTTP/0.9": 0, "HTTP/1.0": 2, "HTTP/1.1": 1, "HTTP/2": 0, "HTTP/3": 0})
+    );
+}
+
+#[tokio::test]
+async fn versions_in_prometheus_and_csv() {
+    let addr = start_server(Config::default()).await;
+    send_raw(addr, b"GET /health HTTP/1.0\r\nHost: localhost\r\n\r\n").await;
+
+    let text = stats(addr, "/stats?format=prometheus").await;
+    assert!(
+        text.contains("# TYPE rust_http_server_by_http_version counter\n"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("\nrust_http_server_by_http_version{version=\"HTTP/1.0\"} 1\n"),
+        "{}",
+        text
+    );
+
+    let csv = stats(addr, "/stats?format=csv").await;
+    let mut rows = csv.lines().map(|row| row.split(',').collect::<Vec<_>>());
+    let (header_row, values) = (rows.next().unwrap(), rows.next().unwrap());
+    let column = header_row
+        .iter()
+        .position(|&name| name == "by_http_version.HTTP/1.0")
+        .expect("no HTTP/1.0 column");
+    assert_eq!(values[column], "1");
+}
+
+async fn read_response(stream: &mut TcpStream) -> String {
+    let mut buf = vec![0; 4096];
+    let n = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+        .await
+        .expect("timed out waiting for response")
+        .unwrap();
+    String::from_utf8_lossy(&buf[..n]).into_owned()
+}
+
+#[tokio::test]
+async fn http_1_0_closes_by_default() {
+    let addr = start_server(Config::default()).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /health HTTP/1.0\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+
+    let response = read_until_closed(&mut stream).await;
+    assert!(response.starts_with("HTTP/1.0 200"), "{}", response);
+    assert_eq!(header(&response, "connection"), Some("close"));
+}
+
+// Reads until the server closes, which must happen after one response
+async fn read_until_closed(stream: &mut TcpStream) -> String {
+    let mut response = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+        .await
+        .expect("connection left open")
+        .unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+#[tokio::test]
+async fn http_1_0_keep_alive_can_be_turned_off() {
+    let addr = start_server(Config {
+        http1_0_keep_alive: false,
+        ..Config::default()
+    })
+    .await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /health HTTP/1.0\r\nHost: localhost\r\nConnection: keep-alive\r\n\r\n")
+        .await
+        .unwrap();
+
+    let response = read_until_closed(&mut stream).await;
+    assert!(response.starts_with("HTTP/1.0 200"), "{}", response);
+    assert_eq!(header(&response, "connection"), Some("close"));
+    assert_eq!(response.matches("HTTP/1.0 ").count(), 1, "{}", response);
+
+    // HTTP/1.1 connections are unaffected
+    let (head, _) = send_raw(
+        addr,
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
+
+#[tokio::test]
+async fn http_1_0_streamed_bodies_close() {
+    let addr = start_server(Config::default()).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.0\r\nHost: localhost\r\nConnection: keep-alive\r\n\r\n")
+        .await
+        .unwrap();
+
+    // Without chunked encoding the close is what ends the body
+    let response = read_until_closed(&mut stream).await;
+    assert!(response.starts_with("HTTP/1.0 200"), "{}", response);
+    assert_eq!(header(&response, "connection"), Some("close"));
+    assert_eq!(header(&response, "transfer-encoding"), None);
+    assert!(
+        response.contains("rust_http_server_total_requests "),
+        "{}",
+        response
+    );
+}
+
+#[tokio::test]
+async fn http_1_0_keep_alive_is_honoured() {
+    let addr = start_server(Config::default()).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    for _ in 0..2 {
+        stream
+            .write_all(b"GET /health HTTP/1.0\r\nHost: localhost\r\nConnection: keep-alive\r\n\r\n")
+            .await
+            .unwrap();
+        let response = read_response(&mut stream).await;
+        assert!(response.starts_with("HTTP/1.0 200"), "{}", response);
+        assert_eq!(header(&response, "connection"), Some("keep-alive"));
+    }
+}
@@ -0,0 +1,131 @@
+// HTTPS_REDIRECT sends plain-HTTP requests to their https:// URL, taking the
+// scheme and host from X-Forwarded-Proto and X-Forwarded-Host when a proxy
+// sets them
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{header, start_server};
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::{HeaderMap, Uri};
+use rust_http_server::redirect::{absolute_url, https_location, request_host, request_scheme};
+use rust_http_server::Config;
+
+fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    pairs
+        .iter()
+        .map(|(name, value)| {
+            (
+                HeaderName::from_static(name),
+                HeaderValue::from_static(value),
+            )
+        })
+        .collect()
+}
+
+async fn get(addr: SocketAddr, target: &str, extra_headers: &str) -> (String, String) {
+    let headers = format!("Host: example.com:8080\r\n{}", extra_headers);
+    common::get(addr, target, &headers).await
+}
+
+fn config() -> Config {
+    Config {
+        https_redirect: true,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn falls_back_to_the_request_itself() {
+    let uri: Uri = "/echo/hi?x=1".parse().unwrap();
+    let headers = headers(&[("host", "example.com:8080")]);
+    assert_eq!(request_scheme(&uri, &headers), "http");
+    assert_eq!(
+        absolute_url(&uri, &headers, "/elsewhere").as_deref(),
+        Some("http://example.com:8080/elsewhere")
+    );
+
+    let uri: Uri = "https://origin.example/path".parse().unwrap();
+    assert_eq!(request_scheme(&uri, &HeaderMap::new()), "https");
+    assert_eq!(
+        request_host(&uri, &HeaderMap::new()).as_deref(),
+        Some("origin.example")
+    );
+    assert_eq!(request_host(&"/".parse().unwrap(), &HeaderMap::new()), None);
+}
+
+#[test]
+fn prefers_forwarded_headers() {
+    let uri: Uri = "/echo/hi".parse().unwrap();
+    let headers = headers(&[
+        ("host", "backend:8080"),
+        ("x-forwarded-proto", "HTTPS, http"),
+        ("x-forwarded-host", "www.example.com, proxy.internal"),
+    ]);
+    assert_eq!(request_scheme(&uri, &headers), "https");
+    assert_eq!(
+        absolute_url(&uri, &headers, "/echo/hi/").as_deref(),
+        Some("https://www.example.com/echo/hi/")
+    );
+    assert_eq!(https_location(&uri, &headers), None);
+}
+
+#[test]
+fn ignores_unusable_forwarded_values() {
+    let uri: Uri = "/".parse().unwrap();
+    let headers = headers(&[
+        ("host", "example.com"),
+        ("x-forwarded-proto", "javascript"),
+        ("x-forwarded-host", "evil.example/phish"),
+    ]);
+    assert_eq!(
+        absolute_url(&uri, &headers, "/").as_deref(),
+        Some("http://example.com/")
+    );
+    let headers = self::headers(&[
+        ("host", "example.com"),
+        ("x-forwarded-host", "user@evil.example"),
+    ]);
+    assert_eq!(request_host(&uri, &headers).as_deref(), Some("example.com"));
+}
+
+#[tokio::test]
+async fn redirects_plain_http_requests() {
+    let addr = start_server(config()).await;
+
+    let (head, _) = get(addr, "/echo/hi?x=1", "").await;
+    assert!(head.starts_with("HTTP/1.1 308"), "{}", head);
+    assert_eq!(
+        header(&head, "location"),
+        Some("https://example.com:8080/echo/hi?x=1")
+    );
+
+    let (head, _) = get(addr, "/echo/hi", "X-Forwarded-Host: www.example.com\r\n").await;
+    assert_eq!(
+        header(&head, "location"),
+        Some("https://www.example.com/echo/hi")
+    );
+}
+
+#[tokio::test]
+async fn serves_requests_the_proxy_received_over_https() {
+    let addr = start_server(config()).await;
+
+    let (head, body) = get(addr, "/echo/hi", "X-Forwarded-Proto: https\r\n").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert!(body.contains("hi"), "{}", body);
+}
+
+#[tokio::test]
+async fn leaves_probes_and_the_default_alone() {
+    let addr = start_server(config()).await;
+    for path in ["/health", "/livez", "/readyz"] {
+        let (head, _) = get(addr, path, "").await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}: {}", path, head);
+    }
+
+    let addr = start_server(Config::default()).await;
+    let (head, _) = get(addr, "/echo/hi", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
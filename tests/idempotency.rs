@@ -0,0 +1,179 @@
+// Idempotency-Key handling on POST routes
+
+mod common;
+
+use std::time::Duration;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn post_echo(key: Option<&str>, body: &str) -> Vec<u8> {
+    let key_header = key
+        .map(|key| format!("Idempotency-Key: {}\r\n", key))
+        .unwrap_or_default();
+    format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+        key_header,
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+#[tokio::test]
+async fn repeated_key_replays_first_response() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, first) = send_raw(addr, &post_echo(Some("abc"), "first")).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(first.contains("Echo: first"), "unexpected body: {}", first);
+    assert_eq!(header(&head, "idempotent-replayed"), None);
+
+    // Same body, same key: the stored response comes back untouched
+    let (head, second) = send_raw(addr, &post_echo(Some("abc"), "first")).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(second, first);
+    assert_eq!(header(&head, "idempotent-replayed"), Some("true"));
+}
+
+#[tokio::test]
+async fn reused_key_with_another_body_is_refused() {
+    let addr = start_server(Config::default()).await;
+
+    let (_, first) = send_raw(addr, &post_echo(Some("abc"), "first")).await;
+    assert!(first.contains("Echo: first"), "unexpected body: {}", first);
+
+    let (head, body) = send_raw(addr, &post_echo(Some("abc"), "second")).await;
+    assert!(
+        head.starts_with("HTTP/1.1 422"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("different request body"), "{}", body);
+    assert_eq!(header(&head, "idempotent-replayed"), None);
+
+    // The stored response is still there for a faithful retry
+    let (head, body) = send_raw(addr, &post_echo(Some("abc"), "first")).await;
+    assert_eq!(body, first);
+    assert_eq!(header(&head, "idempotent-replayed"), Some("true"));
+}
+
+#[tokio::test]
+async fn keys_are_scoped_to_the_path() {
+    let addr = start_server(Config::default()).await;
+
+    let request = "POST /debug/echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nIdempotency-Key: abc\r\nContent-Length: 5\r\n\r\nfirst";
+    let (head, _) = send_raw(addr, request.as_bytes()).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    let (head, _) = send_raw(addr, request.as_bytes()).await;
+    assert_eq!(header(&head, "idempotent-replayed"), Some("true"));
+
+    // The same key on another route is a new request
+    let (head, body) = send_raw(addr, &post_echo(Some("abc"), "first")).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("Echo: first"), "unexpected body: {}", body);
+    assert_eq!(header(&head, "idempotent-replayed"), None);
+}
+
+#[tokio::test]
+async fn distinct_keys_and_missing_keys_are_processed() {
+    let addr = start_server(Config::default()).await;
+
+    let (_, body) = send_raw(addr, &post_echo(Some("one"), "first")).await;
+    assert!(body.contains("Echo: first"));
+    let (_, body) = send_raw(addr, &post_echo(Some("two"), "second")).await;
+    assert!(body.contains("Echo: second"));
+
+    let (_, body) = send_raw(addr, &post_echo(None, "third")).await;
+    assert!(body.contains("Echo: third"));
+    let (head, body) = send_raw(addr, &post_echo(None, "fourth")).await;
+    assert!(body.contains("Echo: fourth"));
+    assert_eq!(header(&head, "idempotent-replayed"), None);
+}
+
+#[tokio::test]
+async fn expired_keys_are_processed_again() {
+    let addr = start_server(Config {
+        idempotency_ttl_secs: 0,
+        ..Config::default()
+    })
+    .await;
+
+    let (_, body) = send_raw(addr, &post_echo(Some("abc"), "first")).await;
+    assert!(body.contains("Echo: first"));
+    let (head, body) = send_raw(addr, &post_echo(Some("abc"), "second")).await;
+    assert!(body.contains("Echo: second"));
+    assert_eq!(header(&head, "idempotent-replayed"), None);
+}
+
+#[tokio::test]
+async fn retry_during_the_first_request_is_not_processed_again() {
+    let addr = start_server(Config::default()).await;
+
+    // The first request is still sending its body, so still being answered
+    let request = post_echo(Some("abc"), "first");
+    let (head, body) = request.split_at(request.len() - 2);
+    let mut first = TcpStream::connect(addr).await.unwrap();
+    first.write_all(head).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (retry, _) = send_raw(addr, &post_echo(Some("abc"), "first")).await;
+    assert!(
+        retry.starts_with("HTTP/1.1 409"),
+        "unexpected status: {}",
+        retry
+    );
+    assert_eq!(header(&retry, "retry-after"), Some("1"));
+
+    first.write_all(body).await.unwrap();
+    let mut response = String::new();
+    first.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.contains("Echo: first"), "{}", response);
+
+    // Once answered, retries get that answer
+    let (head, body) = send_raw(addr, &post_echo(Some("abc"), "first")).await;
+    assert_eq!(header(&head, "idempotent-replayed"), Some("true"));
+    assert!(body.contains("Echo: first"), "{}", body);
+}
+
+#[tokio::test]
+async fn an_abandoned_first_request_frees_the_key() {
+    let addr = start_server(Config::default()).await;
+
+    let request = post_echo(Some("abc"), "first");
+    let mut first = TcpStream::connect(addr).await.unwrap();
+    first
+        .write_all(&request[..request.len() - 2])
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    drop(first);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (head, body) = send_raw(addr, &post_echo(Some("abc"), "again")).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("Echo: again"), "{}", body);
+}
@@ -0,0 +1,100 @@
+// camelCase field names for the server's JSON responses (JSON_FIELD_CASE)
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{send_raw, start_server};
+use rust_http_server::json_case::{rename_keys, to_camel_case, FieldCase};
+use rust_http_server::Config;
+use serde_json::json;
+
+async fn request(addr: SocketAddr, request_line: &str, body: &str) -> (String, String) {
+    let request = format!(
+        "{}\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        request_line,
+        body.len(),
+        body
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+fn camel_config() -> Config {
+    Config {
+        json_field_case: FieldCase::Camel,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn parses_field_case() {
+    assert_eq!("snake".parse::<FieldCase>(), Ok(FieldCase::Snake));
+    assert_eq!(" camelCase ".parse::<FieldCase>(), Ok(FieldCase::Camel));
+    assert!("kebab".parse::<FieldCase>().is_err());
+}
+
+#[test]
+fn converts_snake_case_names() {
+    assert_eq!(to_camel_case("requests_per_second"), "requestsPerSecond");
+    assert_eq!(to_camel_case("message"), "message");
+    assert_eq!(to_camel_case("_private"), "_private");
+}
+
+#[test]
+fn renames_nested_keys_but_not_values() {
+    let value = json!({"outer_key": [{"inner_key": "snake_value"}]});
+    assert_eq!(
+        rename_keys(value.clone(), FieldCase::Camel),
+        json!({"outerKey": [{"innerKey": "snake_value"}]})
+    );
+    assert_eq!(rename_keys(value.clone(), FieldCase::Snake), value);
+}
+
+#[tokio::test]
+async fn snake_case_by_default() {
+    let addr = start_server(Config::default()).await;
+
+    let (_, body) = request(addr, "GET /stats HTTP/1.1", "").await;
+    assert!(
+        body.contains("\"total_requests\""),
+        "unexpected body: {}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn camel_case_stats() {
+    let addr = start_server(camel_config()).await;
+
+    let (head, body) = request(addr, "GET /stats HTTP/1.1", "").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(
+        stats.get("totalRequests").is_some(),
+        "unexpected body: {}",
+        body
+    );
+    assert!(
+        stats.get("requestsPerSecond").is_some(),
+        "unexpected body: {}",
+        body
+    );
+    assert!(
+        stats.get("total_requests").is_none(),
+        "unexpected body: {}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn stored_values_are_left_alone() {
+    let addr = start_server(camel_config()).await;
+
+    request(addr, "PUT /kv/doc HTTP/1.1", r#"{"snake_key":1}"#).await;
+    let (_, body) = request(addr, "GET /kv/doc HTTP/1.1", "").await;
+    assert_eq!(body, r#"{"snake_key":1}"#);
+}
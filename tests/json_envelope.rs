@@ -0,0 +1,126 @@
+// JSON_ENVELOPE wraps the server's JSON responses in {data, error, meta}
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{send_raw, start_server};
+use hyper::StatusCode;
+use rust_http_server::envelope::wrap;
+use rust_http_server::json_case::FieldCase;
+use rust_http_server::Config;
+use serde_json::json;
+
+async fn request(addr: SocketAddr, request_line: &str, body: &str) -> (String, serde_json::Value) {
+    let request = format!(
+        "{}\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        request_line,
+        body.len(),
+        body
+    );
+    let (head, body) = send_raw(addr, request.as_bytes()).await;
+    let body = serde_json::from_str(&body).unwrap_or_else(|_| panic!("not JSON: {}", body));
+    (head, body)
+}
+
+fn config() -> Config {
+    Config {
+        json_envelope: true,
+        ..Config::default()
+    }
+}
+
+#[test]
+fn wraps_by_status_class() {
+    let body = json!({"message": "hi"});
+    assert_eq!(
+        wrap(body.clone(), StatusCode::OK),
+        json!({"data": {"message": "hi"}, "error": null, "meta": {"status": 200}})
+    );
+    assert_eq!(
+        wrap(body.clone(), StatusCode::NOT_FOUND),
+        json!({"data": null, "error": {"message": "hi"}, "meta": {"status": 404}})
+    );
+    assert_eq!(
+        wrap(body, StatusCode::SERVICE_UNAVAILABLE),
+        json!({"data": null, "error": {"message": "hi"}, "meta": {"status": 503}})
+    );
+}
+
+#[tokio::test]
+async fn off_by_default() {
+    let addr = start_server(Config::default()).await;
+
+    let (_, body) = request(addr, "GET /health HTTP/1.1", "").await;
+    assert!(body.get("data").is_none(), "unexpected body: {}", body);
+    assert!(body["message"].is_string(), "unexpected body: {}", body);
+}
+
+#[tokio::test]
+async fn successes_go_under_data() {
+    let addr = start_server(config()).await;
+
+    let (head, body) = request(addr, "GET /echo/hello HTTP/1.1", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(body["data"]["message"], "Echo: hello");
+    assert_eq!(body["error"], serde_json::Value::Null);
+    assert_eq!(body["meta"]["status"], 200);
+}
+
+#[tokio::test]
+async fn errors_go_under_error() {
+    let addr = start_server(config()).await;
+
+    let (head, body) = request(addr, "GET /error/418 HTTP/1.1", "").await;
+    assert!(head.starts_with("HTTP/1.1 418"), "{}", head);
+    assert_eq!(body["data"], serde_json::Value::Null);
+    assert!(body["error"]["message"].is_string(), "{}", body);
+    assert_eq!(body["meta"]["status"], 418);
+
+    let (head, body) = request(addr, "GET /nowhere HTTP/1.1", "").await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+    assert_eq!(body["meta"]["status"], 404);
+    assert!(body["error"].is_object(), "{}", body);
+}
+
+#[tokio::test]
+async fn covers_responses_sent_before_routing() {
+    let addr = start_server(Config {
+        maintenance: true,
+        admin_token: Some("s3cret".to_string()),
+        ..config()
+    })
+    .await;
+
+    let (head, body) = request(addr, "GET /echo/hello HTTP/1.1", "").await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+    assert_eq!(body["meta"]["status"], 503);
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("maintenance"));
+}
+
+#[tokio::test]
+async fn stored_values_are_left_alone() {
+    let addr = start_server(config()).await;
+
+    let (head, body) = request(addr, "PUT /kv/doc HTTP/1.1", r#"{"a":1}"#).await;
+    assert!(head.starts_with("HTTP/1.1 201"), "{}", head);
+    assert_eq!(body, json!({"a": 1}));
+    let (_, body) = request(addr, "GET /kv/doc HTTP/1.1", "").await;
+    assert_eq!(body, json!({"a": 1}));
+}
+
+#[tokio::test]
+async fn combines_with_camel_case() {
+    let addr = start_server(Config {
+        json_field_case: FieldCase::Camel,
+        ..config()
+    })
+    .await;
+
+    let (_, body) = request(addr, "GET /stats HTTP/1.1", "").await;
+    assert!(body["data"]["totalRequests"].is_u64(), "{}", body);
+    assert_eq!(body["meta"]["status"], 200);
+}
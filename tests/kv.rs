@@ -0,0 +1,168 @@
+// In-memory key-value resource at /kv/:key
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::kv::{merge_patch, KvStore, PatchError};
+use rust_http_server::Config;
+use serde_json::json;
+
+async fn request(
+    addr: SocketAddr,
+    method: &str,
+    key: &str,
+    body: Option<&str>,
+) -> (String, String) {
+    let body_part = body
+        .map(|body| format!("Content-Length: {}\r\n\r\n{}", body.len(), body))
+        .unwrap_or_else(|| "\r\n".to_string());
+    let request = format!(
+        "{} /kv/{} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}",
+        method, key, body_part
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+fn status(head: &str) -> &str {
+    head.split(' ').nth(1).unwrap_or("")
+}
+
+#[test]
+fn merge_patch_follows_rfc_7386() {
+    let mut target = json!({"title": "Goodbye!", "author": {"givenName": "John", "familyName": "Doe"}, "tags": ["example", "sample"]});
+    merge_patch(
+        &mut target,
+        json!({"title": "Hello!", "phoneNumber": "+01-123-456-7890", "author": {"familyName": null}, "tags": ["example"]}),
+    );
+    assert_eq!(
+        target,
+        json!({"title": "Hello!", "author": {"givenName": "John"}, "tags": ["example"], "phoneNumber": "+01-123-456-7890"})
+    );
+
+    let mut target = json!({"a": "b"});
+    merge_patch(&mut target, json!(["c"]));
+    assert_eq!(target, json!(["c"]));
+
+    let mut target = json!(["a"]);
+    merge_patch(&mut target, json!({"a": {"b": "c"}}));
+    assert_eq!(target, json!({"a": {"b": "c"}}));
+}
+
+#[test]
+fn store_operations() {
+    let store = KvStore::new();
+    assert!(store.put("a", "1".to_string()));
+    assert!(!store.put("a", "2".to_string()));
+    assert_eq!(store.get("a").as_deref(), Some("2"));
+
+    assert_eq!(store.patch("missing", "{}"), Err(PatchError::NotFound));
+    assert_eq!(store.patch("a", "not json"), Err(PatchError::InvalidPatch));
+    store.put("text", "plain".to_string());
+    assert_eq!(store.patch("text", "{}"), Err(PatchError::NotJson));
+
+    assert!(store.delete("a"));
+    assert!(!store.delete("a"));
+    assert_eq!(store.get("a"), None);
+}
+
+#[tokio::test]
+async fn put_creates_then_replaces() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = request(addr, "PUT", "greeting", Some("hello")).await;
+    assert_eq!(status(&head), "201");
+    assert_eq!(body, "hello");
+
+    let (head, _) = request(addr, "PUT", "greeting", Some("hi")).await;
+    assert_eq!(status(&head), "200");
+
+    let (head, body) = request(addr, "GET", "greeting", None).await;
+    assert_eq!(status(&head), "200");
+    assert_eq!(
+        header(&head, "content-type"),
+        Some("text/plain; charset=utf-8")
+    );
+    assert_eq!(body, "hi");
+}
+
+#[tokio::test]
+async fn patch_merges_json_values() {
+    let addr = start_server(Config::default()).await;
+    request(
+        addr,
+        "PUT",
+        "user",
+        Some(r#"{"name":"Ada","role":"admin"}"#),
+    )
+    .await;
+
+    let (head, body) = request(addr, "PATCH", "user", Some(r#"{"role":null,"lang":"en"}"#)).await;
+    assert_eq!(status(&head), "200");
+    assert_eq!(header(&head, "content-type"), Some("application/json"));
+    let value: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(value, json!({"name": "Ada", "lang": "en"}));
+
+    let (_, body) = request(addr, "GET", "user", None).await;
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+        value
+    );
+}
+
+#[tokio::test]
+async fn patch_errors() {
+    let addr = start_server(Config::default()).await;
+    request(addr, "PUT", "text", Some("plain")).await;
+
+    let (head, _) = request(addr, "PATCH", "missing", Some("{}")).await;
+    assert_eq!(status(&head), "404");
+    let (head, _) = request(addr, "PATCH", "text", Some("{}")).await;
+    assert_eq!(status(&head), "409");
+    let (head, _) = request(addr, "PATCH", "text", Some("{oops")).await;
+    assert_eq!(status(&head), "400");
+}
+
+#[tokio::test]
+async fn delete_removes_keys() {
+    let addr = start_server(Config::default()).await;
+    request(addr, "PUT", "gone", Some("soon")).await;
+
+    let (head, body) = request(addr, "DELETE", "gone", None).await;
+    assert_eq!(status(&head), "204");
+    assert!(body.is_empty());
+
+    let (head, body) = request(addr, "GET", "gone", None).await;
+    assert_eq!(status(&head), "404");
+    assert!(
+        body.contains("Key not found: gone"),
+        "unexpected body: {}",
+        body
+    );
+
+    let (head, _) = request(addr, "DELETE", "gone", None).await;
+    assert_eq!(status(&head), "404");
+}
+
+#[tokio::test]
+async fn keys_are_percent_decoded() {
+    let addr = start_server(Config::default()).await;
+    request(addr, "PUT", "two%20words", Some("v")).await;
+
+    let (_, body) = request(addr, "GET", "two%20words", None).await;
+    assert_eq!(body, "v");
+    let (head, _) = request(addr, "GET", "", None).await;
+    assert_eq!(status(&head), "400");
+}
+
+#[tokio::test]
+async fn other_methods_are_not_allowed() {
+    let addr = start_server(Config::default()).await;
+    let (head, _) = request(addr, "POST", "key", Some("v")).await;
+    assert_eq!(status(&head), "405");
+    assert_eq!(
+        header(&head, "allow"),
+        Some("GET, HEAD, PUT, PATCH, DELETE")
+    );
+}
@@ -0,0 +1,218 @@
+// Several TCP listeners share one accept loop, a failed bind releases the
+// addresses already bound, REUSE_PORT lets two servers share a port, and
+// transient accept errors don't stop the loop; accepted connections get
+// TCP_NODELAY as configured
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rust_http_server::{
+    bind, bind_all, bind_socket, serve, AppState, Config, Listener, DEFAULT_BACKLOG,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn health(addr: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn serves_every_listener() {
+    let any: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let listeners = bind_all(&[any, any], false, DEFAULT_BACKLOG).unwrap();
+    let addrs = listeners.local_addrs().unwrap();
+    assert_eq!(addrs.len(), 2);
+    assert_ne!(addrs[0], addrs[1]);
+
+    tokio::spawn(serve(
+        listeners,
+        AppState::new(Config::default()),
+        std::future::pending(),
+    ));
+    for addr in addrs {
+        let response = health(addr).await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    }
+}
+
+#[tokio::test]
+async fn failed_bind_releases_earlier_listeners() {
+    // One address that is free, and one that is already taken
+    let free = bind("127.0.0.1:0".parse().unwrap())
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let taken_addr = taken.local_addr().unwrap();
+
+    let err = match bind_all(&[free, taken_addr], false, DEFAULT_BACKLOG) {
+        Ok(_) => panic!("binding a taken address succeeded"),
+        Err(err) => err,
+    };
+    assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+    assert!(
+        err.to_string().contains(&taken_addr.to_string()),
+        "error doesn't name the address: {}",
+        err
+    );
+
+    bind(free).expect("the first address was left bound");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn reuse_port_lets_two_servers_share_an_address() {
+    let first = bind_socket("127.0.0.1:0".parse().unwrap(), true, DEFAULT_BACKLOG).unwrap();
+    let addr = first.local_addr().unwrap();
+
+    // What a new process does while the old one is still serving
+    let second =
+        bind_socket(addr, true, DEFAULT_BACKLOG).expect("second bind with SO_REUSEPORT failed");
+    assert_eq!(second.local_addr().unwrap(), addr);
+
+    // The old instance exits; the new one carries on alone
+    drop(first);
+    tokio::spawn(serve(
+        second,
+        AppState::new(Config::default()),
+        std::future::pending(),
+    ));
+    let response = health(addr).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+}
+
+#[tokio::test]
+async fn without_reuse_port_a_second_bind_fails() {
+    let first = bind_socket("127.0.0.1:0".parse().unwrap(), false, DEFAULT_BACKLOG).unwrap();
+    let addr = first.local_addr().unwrap();
+
+    let err = bind_socket(addr, false, DEFAULT_BACKLOG).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+    // Both sockets have to opt in
+    #[cfg(unix)]
+    assert!(bind_socket(addr, true, DEFAULT_BACKLOG).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn connections_beyond_the_backlog_wait() {
+    // Linux queues backlog + 1 completed connections; nothing accepts here
+    let listener = bind_socket("127.0.0.1:0".parse().unwrap(), false, 1).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let _queued = [
+        TcpStream::connect(addr).await.unwrap(),
+        TcpStream::connect(addr).await.unwrap(),
+    ];
+
+    let overflow = tokio::time::timeout(Duration::from_millis(300), TcpStream::connect(addr)).await;
+    assert!(overflow.is_err(), "connected past a full backlog");
+    drop(listener);
+}
+
+// A TCP listener whose accepts fail with the errors in `errors` first
+struct FailingListener {
+    inner: TcpListener,
+    errors: Vec<fn() -> io::Error>,
+    failed: Arc<AtomicUsize>,
+}
+
+impl Listener for FailingListener {
+    type Io = TcpStream;
+
+    async fn accept(&self) -> io::Result<(TcpStream, Option<SocketAddr>)> {
+        let failed = self.failed.fetch_add(1, Ordering::SeqCst);
+        if let Some(error) = self.errors.get(failed) {
+            return Err(error());
+        }
+        let (stream, peer) = self.inner.accept().await?;
+        Ok((stream, Some(peer)))
+    }
+}
+
+#[tokio::test]
+async fn transient_accept_errors_are_retried() {
+    let inner = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = inner.local_addr().unwrap();
+    let failed = Arc::new(AtomicUsize::new(0));
+    let listener = FailingListener {
+        inner,
+        errors: vec![|| io::Error::from_raw_os_error(24), || {
+            io::Error::from(io::ErrorKind::ConnectionAborted)
+        }],
+        failed: failed.clone(),
+    };
+    let server = tokio::spawn(serve(
+        listener,
+        AppState::new(Config::default()),
+        std::future::pending(),
+    ));
+
+    let response = health(addr).await;
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(failed.load(Ordering::SeqCst) >= 3);
+    assert!(!server.is_finished());
+}
+
+#[tokio::test]
+async fn other_accept_errors_end_the_loop() {
+    let inner = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let listener = FailingListener {
+        inner,
+        errors: vec![|| io::Error::from(io::ErrorKind::InvalidInput)],
+        failed: Arc::new(AtomicUsize::new(0)),
+    };
+    let result = serve(
+        listener,
+        AppState::new(Config::default()),
+        std::future::pending(),
+    )
+    .await;
+    assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
+
+#[tokio::test]
+async fn listeners_take_turns() {
+    let any: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let listeners = bind_all(&[any, any], false, DEFAULT_BACKLOG).unwrap();
+    let addrs = listeners.local_addrs().unwrap();
+
+    // Both have connections waiting; the first shouldn't be drained first
+    let mut clients = Vec::new();
+    for addr in [addrs[0], addrs[0], addrs[1], addrs[1]] {
+        clients.push(TcpStream::connect(addr).await.unwrap());
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let mut order = Vec::new();
+    for _ in 0..4 {
+        let (stream, _) = listeners.accept().await.unwrap();
+        order.push(stream.local_addr().unwrap());
+    }
+    assert_eq!(order, [addrs[0], addrs[1], addrs[0], addrs[1]]);
+}
+
+#[tokio::test]
+async fn accepted_connections_get_tcp_nodelay() {
+    for tcp_nodelay in [true, false] {
+        let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = Listener::accept(&listener).await.unwrap();
+        let config = Config {
+            tcp_nodelay,
+            ..Config::default()
+        };
+        TcpListener::configure(&stream, &config).unwrap();
+        assert_eq!(stream.nodelay().unwrap(), tcp_nodelay);
+    }
+}
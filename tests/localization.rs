@@ -0,0 +1,83 @@
+// Accept-Language negotiation and localized messages
+
+mod common;
+
+use common::{header, send_raw, start_server};
+use hyper::header::{HeaderValue, ACCEPT_LANGUAGE};
+use hyper::HeaderMap;
+use rust_http_server::i18n::{localize, negotiate, Message};
+use rust_http_server::Config;
+
+fn accept_language(value: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static(value));
+    headers
+}
+
+#[test]
+fn unknown_languages_fall_back_to_english() {
+    assert_eq!(localize(Message::Healthy, "en"), "Server is healthy");
+    assert_eq!(localize(Message::Healthy, "xx"), "Server is healthy");
+    assert_eq!(localize(Message::NotFound, "de"), "Nicht gefunden");
+}
+
+#[test]
+fn negotiates_the_preferred_supported_language() {
+    assert_eq!(negotiate(&HeaderMap::new()), "en");
+    assert_eq!(negotiate(&accept_language("fr")), "fr");
+    assert_eq!(
+        negotiate(&accept_language("fr-CH, fr;q=0.9, en;q=0.8")),
+        "fr"
+    );
+    assert_eq!(negotiate(&accept_language("en;q=0.5, DE;q=0.9")), "de");
+    assert_eq!(negotiate(&accept_language("ja, es;q=0.3")), "es");
+    assert_eq!(negotiate(&accept_language("ja, *;q=0.1")), "en");
+    assert_eq!(negotiate(&accept_language("ja")), "en");
+}
+
+#[test]
+fn zero_quality_means_not_acceptable() {
+    assert_eq!(negotiate(&accept_language("es;q=0, fr;q=0.2")), "fr");
+    assert_eq!(negotiate(&accept_language("es;q=0")), "en");
+}
+
+#[tokio::test]
+async fn handlers_answer_in_the_negotiated_language() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = send_raw(
+        addr,
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nAccept-Language: es-MX, en;q=0.5\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(header(&head, "content-language"), Some("es"));
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["message"], "El servidor está en buen estado");
+
+    let (head, body) = send_raw(
+        addr,
+        b"GET /missing HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nAccept-Language: fr\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected status: {}",
+        head
+    );
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["message"], "Introuvable");
+
+    let (head, body) = send_raw(
+        addr,
+        b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert_eq!(header(&head, "content-language"), Some("en"));
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["message"], "Welcome to Rust HTTP Server!");
+}
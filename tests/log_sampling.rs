@@ -0,0 +1,33 @@
+// LOG_SAMPLE_RATE: successes are sampled, errors always logged
+
+use hyper::StatusCode;
+use rust_http_server::log_sample::should_log;
+
+#[test]
+fn always_logs_errors() {
+    for status in [
+        StatusCode::NOT_FOUND,
+        StatusCode::TOO_MANY_REQUESTS,
+        StatusCode::BAD_GATEWAY,
+    ] {
+        assert!(should_log(status, 0.0), "{}", status);
+    }
+}
+
+#[test]
+fn rate_bounds() {
+    for _ in 0..1000 {
+        assert!(should_log(StatusCode::OK, 1.0));
+        assert!(!should_log(StatusCode::OK, 0.0));
+        assert!(!should_log(StatusCode::SWITCHING_PROTOCOLS, 0.0));
+    }
+}
+
+#[test]
+fn samples_successes_at_the_rate() {
+    let logged = (0..100_000)
+        .filter(|_| should_log(StatusCode::OK, 0.1))
+        .count();
+    // Expected 10000 with a standard deviation under 100
+    assert!((9_000..11_000).contains(&logged), "logged {}", logged);
+}
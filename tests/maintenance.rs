@@ -0,0 +1,196 @@
+// Maintenance mode, toggled through the token-protected admin API
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::Config;
+
+const TOKEN: &str = "s3cret";
+
+async fn request(addr: SocketAddr, head: &str, body: &str) -> (String, String) {
+    let request = format!(
+        "{}\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        head,
+        body.len(),
+        body
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+async fn set_maintenance(addr: SocketAddr, enabled: bool) -> (String, String) {
+    request(
+        addr,
+        &format!(
+            "POST /admin/maintenance HTTP/1.1\r\nAuthorization: Bearer {}",
+            TOKEN
+        ),
+        &format!("{{\"enabled\": {}}}", enabled),
+    )
+    .await
+}
+
+fn config() -> Config {
+    Config {
+        admin_token: Some(TOKEN.to_string()),
+        maintenance_retry_after_secs: 120,
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn toggles_maintenance_mode() {
+    let addr = start_server(config()).await;
+
+    let (head, body) = set_maintenance(addr, true).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(
+        body.contains("\"maintenance\":true"),
+        "unexpected body: {}",
+        body
+    );
+
+    for path in ["/", "/echo/hi", "/kv/key", "/debug/echo"] {
+        let (head, _) = request(addr, &format!("GET {} HTTP/1.1", path), "").await;
+        assert!(head.starts_with("HTTP/1.1 503"), "{}: {}", path, head);
+        assert_eq!(header(&head, "retry-after"), Some("120"), "{}", path);
+    }
+    for path in ["/health", "/stats", "/stats.csv"] {
+        let (head, _) = request(addr, &format!("GET {} HTTP/1.1", path), "").await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}: {}", path, head);
+    }
+
+    let (head, body) = set_maintenance(addr, false).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(
+        body.contains("\"maintenance\":false"),
+        "unexpected body: {}",
+        body
+    );
+    let (head, _) = request(addr, "GET /echo/hi HTTP/1.1", "").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn can_start_in_maintenance_mode() {
+    let addr = start_server(Config {
+        maintenance: true,
+        ..config()
+    })
+    .await;
+
+    let (head, _) = request(addr, "GET /echo/hi HTTP/1.1", "").await;
+    assert!(
+        head.starts_with("HTTP/1.1 503"),
+        "unexpected head: {}",
+        head
+    );
+
+    let (head, body) = request(
+        addr,
+        &format!(
+            "GET /admin/maintenance HTTP/1.1\r\nAuthorization: Bearer {}",
+            TOKEN
+        ),
+        "",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(
+        body.contains("\"maintenance\":true"),
+        "unexpected body: {}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn requires_the_admin_token() {
+    let addr = start_server(config()).await;
+
+    for auth in [
+        "",
+        "\r\nAuthorization: Bearer wrong",
+        "\r\nAuthorization: Basic s3cret",
+    ] {
+        let (head, _) = request(
+            addr,
+            &format!("POST /admin/maintenance HTTP/1.1{}", auth),
+            "{\"enabled\": true}",
+        )
+        .await;
+        assert!(head.starts_with("HTTP/1.1 401"), "{:?}: {}", auth, head);
+        assert_eq!(header(&head, "www-authenticate"), Some("Bearer"));
+    }
+
+    // Still serving normally
+    let (head, _) = request(addr, "GET /echo/hi HTTP/1.1", "").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn disabled_without_a_token() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = request(
+        addr,
+        "POST /admin/maintenance HTTP/1.1\r\nAuthorization: Bearer anything",
+        "{\"enabled\": true}",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 403"),
+        "unexpected head: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn rejects_malformed_toggles() {
+    let addr = start_server(config()).await;
+
+    let auth = format!(
+        "POST /admin/maintenance HTTP/1.1\r\nAuthorization: Bearer {}",
+        TOKEN
+    );
+    for body in ["", "true", "{\"enabled\": \"yes\"}"] {
+        let (head, _) = request(addr, &auth, body).await;
+        assert!(head.starts_with("HTTP/1.1 400"), "{:?}: {}", body, head);
+    }
+
+    let (head, _) = request(
+        addr,
+        &format!(
+            "DELETE /admin/maintenance HTTP/1.1\r\nAuthorization: Bearer {}",
+            TOKEN
+        ),
+        "",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 405"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "allow"), Some("GET, HEAD, POST"));
+}
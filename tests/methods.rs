@@ -0,0 +1,141 @@
+// TRACE and CONNECT are refused with 405 whatever the path, and methods
+// outside the standard set get 501 instead of falling through to a 404
+// unless their route takes any method
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::Config;
+
+async fn request(addr: SocketAddr, method: &str, target: &str) -> (String, String) {
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        method, target
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+#[tokio::test]
+async fn trace_is_disabled() {
+    let addr = start_server(Config {
+        route_toggles: RouteToggles::new().with_route("/debug/echo", true),
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = request(addr, "TRACE", "/health").await;
+    assert!(
+        head.starts_with("HTTP/1.1 405"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "allow"), Some("GET, HEAD"));
+
+    // Not even the reflector, which takes any other method
+    let (head, body) = request(addr, "TRACE", "/debug/echo").await;
+    assert!(head.starts_with("HTTP/1.1 405"), "{}", head);
+    assert!(!body.contains("\"method\""), "{}", body);
+
+    // Nor paths no route answers
+    let (head, _) = request(addr, "TRACE", "/nowhere").await;
+    assert!(head.starts_with("HTTP/1.1 405"), "{}", head);
+    assert_eq!(header(&head, "allow"), Some(""));
+}
+
+#[tokio::test]
+async fn connect_is_refused() {
+    let addr = start_server(Config::default()).await;
+
+    // The authority form a proxy would get
+    let (head, _) = send_raw(
+        addr,
+        b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 405"),
+        "unexpected head: {}",
+        head
+    );
+    // The client may already be sending what it wanted tunnelled
+    assert_eq!(header(&head, "connection"), Some("close"));
+
+    let (head, _) = request(addr, "CONNECT", "/health").await;
+    assert!(head.starts_with("HTTP/1.1 405"), "{}", head);
+}
+
+#[tokio::test]
+async fn unknown_methods_are_not_implemented() {
+    let addr = start_server(Config::default()).await;
+
+    for (method, path) in [
+        ("PROPFIND", "/health"),
+        ("FOO", "/kv/key"),
+        ("BREW", "/nowhere"),
+    ] {
+        let (head, body) = request(addr, method, path).await;
+        assert!(
+            head.starts_with("HTTP/1.1 501"),
+            "{} {}: unexpected head: {}",
+            method,
+            path,
+            head
+        );
+        assert!(
+            body.contains(&format!("Method {} is not implemented", method)),
+            "{}",
+            body
+        );
+    }
+}
+
+#[tokio::test]
+async fn routes_taking_any_method_still_get_unknown_ones() {
+    let addr = start_server(Config {
+        route_toggles: RouteToggles::new().with_route("/debug/echo", true),
+        ..Config::default()
+    })
+    .await;
+
+    let (head, body) = request(addr, "PROPFIND", "/debug/echo").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert!(body.contains("\"method\":\"PROPFIND\""), "{}", body);
+}
+
+#[tokio::test]
+async fn standard_methods_still_reach_their_routes() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = request(addr, "GET", "/health").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    let (head, _) = request(addr, "DELETE", "/health").await;
+    assert!(head.starts_with("HTTP/1.1 405"), "{}", head);
+    let (head, _) = request(addr, "GET", "/nowhere").await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+}
+
+#[tokio::test]
+async fn head_answers_wherever_get_does() {
+    let addr = start_server(Config::default()).await;
+
+    let (get, body) = request(addr, "GET", "/health").await;
+    let (head, empty) = request(addr, "HEAD", "/health").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(empty, "");
+    // The length the GET body has, not that of the empty one sent
+    assert_eq!(
+        header(&head, "content-length"),
+        Some(body.len().to_string().as_str())
+    );
+    assert_eq!(header(&head, "content-type"), header(&get, "content-type"));
+
+    let (head, _) = request(addr, "HEAD", "/kv/missing").await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+    // Still refused where GET is
+    let (head, _) = request(addr, "HEAD", "/echo").await;
+    assert!(head.starts_with("HTTP/1.1 405"), "{}", head);
+    assert_eq!(header(&head, "allow"), Some("POST"));
+}
@@ -0,0 +1,194 @@
+// GET /metrics serves the stats samples and a latency histogram, as
+// Prometheus text or, when Accept asks for it, OpenMetrics with exemplars,
+// streamed one metric family per chunk
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{decode_chunked, header, send_raw, start_server};
+use rust_http_server::Config;
+
+async fn metrics(addr: SocketAddr, extra_headers: &str) -> (String, String) {
+    let request = format!(
+        "GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}\r\n",
+        extra_headers
+    );
+    let (head, body) = send_raw(addr, request.as_bytes()).await;
+    assert_eq!(
+        header(&head, "transfer-encoding"),
+        Some("chunked"),
+        "{}",
+        head
+    );
+    (head, decode_chunked(&body).0)
+}
+
+const OPENMETRICS: &str = "Accept: application/openmetrics-text; version=1.0.0\r\n";
+
+const TRACEPARENT: &str =
+    "traceparent: 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01\r\n";
+
+#[tokio::test]
+async fn prometheus_by_default() {
+    let addr = start_server(Config::default()).await;
+    send_raw(
+        addr,
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+
+    let (head, body) = metrics(addr, "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(
+        header(&head, "content-type"),
+        Some("text/plain; version=0.0.4; charset=utf-8")
+    );
+    assert_eq!(header(&head, "vary"), Some("Accept"));
+    assert!(
+        body.contains("\nrust_http_server_total_requests "),
+        "{}",
+        body
+    );
+    assert!(body.contains("# TYPE rust_http_server_request_duration_seconds histogram\n"));
+    assert!(body.contains("rust_http_server_request_duration_seconds_bucket{le=\"0.001\"} "));
+    assert!(body.contains("rust_http_server_request_duration_seconds_bucket{le=\"+Inf\"} 1\n"));
+    assert!(body.contains("rust_http_server_request_duration_seconds_count 1\n"));
+    assert!(!body.contains("# EOF"), "{}", body);
+    assert!(!body.contains("# UNIT"), "{}", body);
+}
+
+#[tokio::test]
+async fn openmetrics_when_accepted() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = metrics(addr, OPENMETRICS).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(
+        header(&head, "content-type"),
+        Some("application/openmetrics-text; version=1.0.0; charset=utf-8")
+    );
+    assert!(body.ends_with("\n# EOF\n"), "{}", body);
+    assert!(body.contains("# TYPE rust_http_server_total_requests counter\n"));
+    assert!(
+        body.contains("\nrust_http_server_total_requests_total "),
+        "{}",
+        body
+    );
+    assert!(body.contains("# UNIT rust_http_server_uptime_seconds seconds\n"));
+    assert!(body.contains("# UNIT rust_http_server_request_duration_seconds seconds\n"));
+    // Gauges keep their name
+    assert!(
+        body.contains("\nrust_http_server_uptime_seconds "),
+        "{}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn plain_text_wins_ties_and_query_overrides() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = metrics(addr, "Accept: application/openmetrics-text, text/plain\r\n").await;
+    assert!(header(&head, "content-type")
+        .unwrap()
+        .starts_with("text/plain"));
+    assert!(!body.contains("# EOF"));
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /metrics?format=openmetrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(header(&head, "content-type")
+        .unwrap()
+        .starts_with("application/openmetrics-text"));
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /metrics?format=xml HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+}
+
+#[tokio::test]
+async fn traced_requests_become_exemplars() {
+    let addr = start_server(Config {
+        otel_traces: true,
+        // Nothing listens here; failed exports are only logged
+        otel_endpoint: "http://127.0.0.1:9".parse().unwrap(),
+        ..Config::default()
+    })
+    .await;
+    let request = format!(
+        "GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}\r\n",
+        TRACEPARENT
+    );
+    send_raw(addr, request.as_bytes()).await;
+
+    let (_, body) = metrics(addr, OPENMETRICS).await;
+    let bucket = body
+        .lines()
+        .find(|line| line.contains(" # {trace_id="))
+        .unwrap_or_else(|| panic!("no exemplar in:\n{}", body));
+    assert!(
+        bucket.starts_with("rust_http_server_request_duration_seconds_bucket{le="),
+        "{}",
+        bucket
+    );
+    assert!(
+        bucket.contains("# {trace_id=\"4bf92f3577b34da6a3ce929d0e0e4736\"} "),
+        "{}",
+        bucket
+    );
+
+    // Prometheus 0.0.4 has no exemplar syntax
+    let (_, body) = metrics(addr, "").await;
+    assert!(!body.contains("trace_id"), "{}", body);
+}
+
+#[tokio::test]
+async fn stats_prometheus_is_unchanged() {
+    let addr = start_server(Config::default()).await;
+
+    let (_, body) = send_raw(
+        addr,
+        b"GET /stats?format=prometheus HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        body.contains("\nrust_http_server_total_requests "),
+        "{}",
+        body
+    );
+    assert!(!body.contains("request_duration_seconds"), "{}", body);
+    assert!(!body.contains("# EOF"), "{}", body);
+}
+
+#[tokio::test]
+async fn families_are_sent_as_separate_chunks() {
+    let addr = start_server(Config::default()).await;
+
+    let request = b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+    let (head, body) = send_raw(addr, request).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(header(&head, "content-length"), None);
+
+    // Every chunk starts a family, so no family is split across two
+    let mut rest = body.as_str();
+    let mut chunks = 0;
+    loop {
+        let (size, after) = rest.split_once("\r\n").unwrap();
+        let size = usize::from_str_radix(size, 16).unwrap();
+        if size == 0 {
+            break;
+        }
+        assert!(after[..size].starts_with("# TYPE "), "{}", &after[..size]);
+        chunks += 1;
+        rest = &after[size + 2..];
+    }
+    let families = decode_chunked(&body).0.matches("# TYPE ").count();
+    assert_eq!(chunks, families);
+    assert!(chunks > 1, "{}", body);
+}
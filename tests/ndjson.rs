@@ -0,0 +1,107 @@
+// GET /stream/ndjson streams JSON lines as a chunked body
+
+mod common;
+
+use common::{decode_chunked, header, start_server};
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+async fn get(config: Config, target: &str) -> (String, String) {
+    let addr = start_server(config).await;
+    common::get(addr, target, "").await
+}
+
+fn lines(body: &str) -> Vec<serde_json::Value> {
+    let (data, _) = decode_chunked(body);
+    assert!(data.ends_with('\n'), "last line unterminated: {:?}", data);
+    data.lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn streams_count_indexed_lines() {
+    let (head, body) = get(Config::default(), "/stream/ndjson?count=5").await;
+
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(header(&head, "content-type"), Some("application/x-ndjson"));
+    assert_eq!(header(&head, "transfer-encoding"), Some("chunked"));
+
+    let lines = lines(&body);
+    assert_eq!(lines.len(), 5);
+    for (i, line) in lines.iter().enumerate() {
+        assert_eq!(line["index"], i);
+        let timestamp = line["timestamp"].as_str().unwrap();
+        chrono::DateTime::parse_from_rfc3339(timestamp).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn defaults_to_ten_lines() {
+    let (_, body) = get(Config::default(), "/stream/ndjson").await;
+    assert_eq!(lines(&body).len(), 10);
+}
+
+#[tokio::test]
+async fn zero_lines_is_an_empty_body() {
+    let (head, body) = get(Config::default(), "/stream/ndjson?count=0").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    // hyper sees the body is already over and sends `Content-Length: 0`
+    assert_eq!(header(&head, "content-length"), Some("0"));
+    assert_eq!(body, "");
+}
+
+#[tokio::test]
+async fn count_is_capped() {
+    let config = Config {
+        max_ndjson_lines: 3,
+        ..Config::default()
+    };
+    let (head, _) = get(config.clone(), "/stream/ndjson?count=3").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+
+    for target in ["/stream/ndjson?count=4", "/stream/ndjson?count=lots"] {
+        let (head, _) = get(config.clone(), target).await;
+        assert!(head.starts_with("HTTP/1.1 400"), "{}: {}", target, head);
+    }
+}
+
+#[tokio::test]
+async fn large_counts_start_streaming_immediately() {
+    // A million lines would take a while to buffer; the first ones arrive
+    // long before the rest could have been produced
+    let addr = start_server(Config {
+        max_ndjson_lines: 1_000_000,
+        ..Config::default()
+    })
+    .await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /stream/ndjson?count=1000000 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut received = Vec::new();
+    let mut buf = [0u8; 4096];
+    while !String::from_utf8_lossy(&received).contains("\"index\":1,") {
+        let read = tokio::time::timeout(std::time::Duration::from_secs(1), stream.read(&mut buf))
+            .await
+            .expect("no lines streamed within a second")
+            .unwrap();
+        assert!(read > 0, "connection closed early");
+        received.extend_from_slice(&buf[..read]);
+    }
+}
@@ -0,0 +1,145 @@
+// GET /openapi.json describes the routes from the route table, and /docs
+// serves the Swagger UI page for it
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{get, header, start_server};
+use rust_http_server::openapi::UNDOCUMENTED;
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::Config;
+use serde_json::Value;
+
+async fn document(addr: SocketAddr, host: &str) -> Value {
+    let (head, body) = get(addr, "/openapi.json", &format!("Host: {}\r\n", host)).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(header(&head, "content-type"), Some("application/json"));
+    serde_json::from_str(&body).unwrap_or_else(|_| panic!("not JSON: {}", body))
+}
+
+#[tokio::test]
+async fn documents_every_route() {
+    let addr = start_server(Config {
+        route_toggles: RouteToggles::new()
+            .with_route("/debug/echo", true)
+            .with_route("/debug/runtime", true),
+        ..Config::default()
+    })
+    .await;
+
+    let document = document(addr, "localhost").await;
+    assert_eq!(document["openapi"], "3.0.3");
+    assert_eq!(document["servers"][0]["url"], "/");
+    let paths = document["paths"].as_object().unwrap();
+    for path in [
+        "/",
+        "/health",
+        "/stats",
+        "/metrics",
+        "/openapi.json",
+        "/docs",
+    ] {
+        assert!(paths.contains_key(path), "{} missing", path);
+    }
+    for (path, item) in paths {
+        for (method, operation) in item.as_object().unwrap() {
+            assert_ne!(operation["summary"], UNDOCUMENTED, "{} {}", method, path);
+        }
+    }
+
+    let kv = &paths["/kv/{key}"];
+    let methods: Vec<_> = kv.as_object().unwrap().keys().cloned().collect();
+    assert_eq!(methods, ["get", "put", "patch", "delete"]);
+    assert_eq!(kv["get"]["parameters"][0]["name"], "key");
+    assert_eq!(kv["get"]["parameters"][0]["in"], "path");
+
+    let delay = &paths["/delay/{ms}"]["get"]["parameters"][0];
+    assert_eq!(delay["schema"]["type"], "integer");
+    let stats = &paths["/stats"]["get"]["parameters"][0];
+    assert_eq!(stats["name"], "format");
+    assert_eq!(stats["in"], "query");
+
+    assert_eq!(paths["/debug/echo"].as_object().unwrap().len(), 6);
+    assert!(document["components"]["schemas"]["Message"].is_object());
+}
+
+#[tokio::test]
+async fn leaves_out_routes_that_are_off() {
+    let addr = start_server(Config {
+        route_toggles: RouteToggles::new()
+            .with_route("/debug/runtime", false)
+            .with_route("/ws", false),
+        ..Config::default()
+    })
+    .await;
+
+    let document = document(addr, "localhost").await;
+    let paths = document["paths"].as_object().unwrap();
+    assert!(!paths.contains_key("/debug/runtime"));
+    assert!(!paths.contains_key("/ws"));
+    assert!(paths.contains_key("/echo/{msg}"));
+}
+
+#[tokio::test]
+async fn follows_the_host_site() {
+    let addr = start_server(Config {
+        virtual_hosts: "api.example.com=api, admin.example.com=admin"
+            .parse()
+            .unwrap(),
+        ..Config::default()
+    })
+    .await;
+
+    let api = document(addr, "api.example.com").await;
+    assert!(api["paths"].get("/echo/{msg}").is_some());
+    assert!(api["paths"].get("/stats").is_none());
+
+    let admin = document(addr, "admin.example.com").await;
+    assert!(admin["paths"].get("/stats").is_some());
+    assert!(admin["paths"].get("/echo/{msg}").is_none());
+    assert!(admin["paths"].get("/docs").is_some());
+}
+
+#[tokio::test]
+async fn base_path_is_the_server_url() {
+    let addr = start_server(Config {
+        base_path: "/api".to_string(),
+        ..Config::default()
+    })
+    .await;
+
+    let (head, body) = get(addr, "/api/openapi.json", "Host: localhost\r\n").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    let document: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(document["servers"][0]["url"], "/api");
+    assert!(document["paths"].get("/health").is_some());
+}
+
+#[tokio::test]
+async fn is_not_reshaped_like_server_json() {
+    let addr = start_server(Config {
+        json_envelope: true,
+        json_field_case: rust_http_server::json_case::FieldCase::Camel,
+        ..Config::default()
+    })
+    .await;
+
+    let document = document(addr, "localhost").await;
+    assert_eq!(document["openapi"], "3.0.3");
+    assert!(document.get("data").is_none());
+}
+
+#[tokio::test]
+async fn docs_page_loads_the_spec() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = get(addr, "/docs", "Host: localhost\r\n").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(
+        header(&head, "content-type"),
+        Some("text/html; charset=utf-8")
+    );
+    assert!(body.contains("SwaggerUIBundle"), "{}", body);
+    assert!(body.contains("url: \"openapi.json\""), "{}", body);
+}
@@ -0,0 +1,253 @@
+// OTEL_TRACES records a span per request, continues incoming traceparent
+// headers and exports over OTLP/HTTP JSON
+
+mod common;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use common::send_raw;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response};
+use hyper_util::rt::TokioIo;
+use rust_http_server::otel::{Span, SpanExporter, TraceContext};
+use rust_http_server::{bind, serve, AppState, Config};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+
+// A collector that accepts every export and hands over (path, body), and
+// counts the connections it was sent them on
+async fn start_collector() -> (
+    SocketAddr,
+    mpsc::UnboundedReceiver<(String, serde_json::Value)>,
+    Arc<AtomicUsize>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (exports, received) = mpsc::unbounded_channel();
+    let connections = Arc::new(AtomicUsize::new(0));
+    let accepted = connections.clone();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            accepted.fetch_add(1, Ordering::SeqCst);
+            let exports = exports.clone();
+            tokio::spawn(http1::Builder::new().serve_connection(
+                TokioIo::new(stream),
+                service_fn(move |req: Request<hyper::body::Incoming>| {
+                    let exports = exports.clone();
+                    async move {
+                        let path = req.uri().path().to_string();
+                        let body = req.into_body().collect().await.unwrap().to_bytes();
+                        let _ = exports.send((path, serde_json::from_slice(&body).unwrap()));
+                        Ok::<_, Infallible>(Response::new(Full::new(Bytes::from("{}"))))
+                    }
+                }),
+            ));
+        }
+    });
+    (addr, received, connections)
+}
+
+fn config(collector: SocketAddr) -> Config {
+    Config {
+        otel_traces: true,
+        otel_endpoint: format!("http://{}", collector).parse().unwrap(),
+        otel_service_name: "traces-test".to_string(),
+        ..Config::default()
+    }
+}
+
+// Serves `requests` one after another, then shuts down gracefully, which
+// flushes every span still queued
+async fn exported_spans(config: Config, requests: &[&str]) {
+    let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(serve(listener, AppState::new(config), async {
+        let _ = stopped.await;
+    }));
+    for request in requests {
+        send_raw(addr, request.as_bytes()).await;
+    }
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
+
+fn attribute<'a>(span: &'a serde_json::Value, key: &str) -> Option<&'a serde_json::Value> {
+    span["attributes"]
+        .as_array()?
+        .iter()
+        .find(|attribute| attribute["key"] == key)
+        .map(|attribute| &attribute["value"])
+}
+
+// The spans of every export received so far
+fn collect(
+    received: &mut mpsc::UnboundedReceiver<(String, serde_json::Value)>,
+) -> Vec<serde_json::Value> {
+    let mut spans = Vec::new();
+    while let Ok((path, export)) = received.try_recv() {
+        assert_eq!(path, "/v1/traces");
+        let resource = &export["resourceSpans"][0];
+        assert_eq!(
+            resource["resource"]["attributes"][0],
+            serde_json::json!({"key": "service.name", "value": {"stringValue": "traces-test"}})
+        );
+        spans.extend(
+            resource["scopeSpans"][0]["spans"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .cloned(),
+        );
+    }
+    spans
+}
+
+#[tokio::test]
+async fn exports_a_server_span_per_request() {
+    let (collector, mut received, _) = start_collector().await;
+    exported_spans(
+        config(collector),
+        &[
+            "GET /echo/hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            "GET /error/503 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        ],
+    )
+    .await;
+
+    let spans = collect(&mut received);
+    assert_eq!(spans.len(), 2, "{:?}", spans);
+    let echo = &spans[0];
+    assert_eq!(echo["name"], "GET /echo/:msg");
+    assert_eq!(echo["kind"], 2);
+    assert_eq!(echo["traceId"].as_str().unwrap().len(), 32);
+    assert_eq!(echo["spanId"].as_str().unwrap().len(), 16);
+    assert!(echo.get("parentSpanId").is_none());
+    assert_eq!(
+        attribute(echo, "http.request.method"),
+        Some(&serde_json::json!({"stringValue": "GET"}))
+    );
+    assert_eq!(
+        attribute(echo, "url.path"),
+        Some(&serde_json::json!({"stringValue": "/echo/hello"}))
+    );
+    assert_eq!(
+        attribute(echo, "http.route"),
+        Some(&serde_json::json!({"stringValue": "/echo/:msg"}))
+    );
+    assert_eq!(
+        attribute(echo, "http.response.status_code"),
+        Some(&serde_json::json!({"intValue": "200"}))
+    );
+    assert!(echo.get("status").is_none());
+
+    let start: u128 = echo["startTimeUnixNano"].as_str().unwrap().parse().unwrap();
+    let end: u128 = echo["endTimeUnixNano"].as_str().unwrap().parse().unwrap();
+    assert!(start > 0 && end >= start);
+
+    let error = &spans[1];
+    assert_ne!(error["traceId"], echo["traceId"]);
+    assert_eq!(error["status"]["code"], 2);
+}
+
+#[tokio::test]
+async fn continues_incoming_traces() {
+    let (collector, mut received, _) = start_collector().await;
+    exported_spans(
+        config(collector),
+        &["GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\ntraceparent: 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01\r\n\r\n"],
+    )
+    .await;
+
+    let spans = collect(&mut received);
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(spans[0]["parentSpanId"], "00f067aa0ba902b7");
+    assert_ne!(spans[0]["spanId"], "00f067aa0ba902b7");
+}
+
+#[tokio::test]
+async fn unsampled_and_invalid_parents() {
+    let (collector, mut received, _) = start_collector().await;
+    exported_spans(
+        config(collector),
+        &[
+            // Not sampled upstream: nothing recorded
+            "GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\ntraceparent: 00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00\r\n\r\n",
+            // Malformed: a fresh trace instead
+            "GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\ntraceparent: 00-00000000000000000000000000000000-00f067aa0ba902b7-01\r\n\r\n",
+        ],
+    )
+    .await;
+
+    let spans = collect(&mut received);
+    assert_eq!(spans.len(), 1);
+    assert_ne!(spans[0]["traceId"], "00000000000000000000000000000000");
+    assert!(spans[0].get("parentSpanId").is_none());
+}
+
+#[tokio::test]
+async fn traces_are_off_by_default() {
+    let (collector, mut received, _) = start_collector().await;
+    let config = Config {
+        otel_traces: false,
+        ..config(collector)
+    };
+    exported_spans(
+        config,
+        &["GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"],
+    )
+    .await;
+    assert!(collect(&mut received).is_empty());
+    assert!(!Config::default().otel_traces);
+}
+
+#[tokio::test]
+async fn exports_reuse_the_collector_connection() {
+    let (collector, mut received, connections) = start_collector().await;
+    let endpoint = format!("http://{}", collector).parse().unwrap();
+    let exporter = SpanExporter::new(&endpoint, "traces-test");
+
+    for path in ["/first", "/second"] {
+        let span = Span::start(None, Method::GET, path.to_string(), SystemTime::now());
+        exporter.record(span);
+        exporter.flush().await;
+    }
+    assert_eq!(collect(&mut received).len(), 2);
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn parses_traceparent() {
+    let parsed =
+        TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+    assert_eq!(parsed.trace_id[0], 0x4b);
+    assert_eq!(parsed.span_id[7], 0xb7);
+    assert!(parsed.sampled);
+
+    // Later versions may add fields
+    assert!(
+        TraceContext::parse("cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra")
+            .is_some()
+    );
+    for invalid in [
+        "",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "00-+bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e47-00f067aa0ba902b7-01",
+    ] {
+        assert_eq!(TraceContext::parse(invalid), None, "{:?}", invalid);
+    }
+}
@@ -0,0 +1,176 @@
+// Odd request targets are normalized or refused before routing, never
+// matched by accident
+
+mod common;
+
+use std::borrow::Cow;
+
+use std::net::SocketAddr;
+
+use common::{send_raw, start_server};
+use rust_http_server::normalize::{compose_path, normalize_path, PathError};
+use rust_http_server::Config;
+
+async fn get(target: &str) -> (String, String) {
+    let addr = start_server(Config::default()).await;
+    common::get(addr, target, "").await
+}
+
+fn status(head: &str) -> &str {
+    head.split(' ').nth(1).unwrap_or("")
+}
+
+#[test]
+fn normalizes_paths() {
+    for (path, expected) in [
+        ("/health#fragment", "/health"),
+        ("//health", "/health"),
+        ("/echo//hi", "/echo/hi"),
+        ("/./health", "/health"),
+        ("/echo/../health", "/health"),
+        ("/../../health", "/health"),
+        ("/kv/a/..", "/kv/"),
+        ("/kv/", "/kv/"),
+        ("/", "/"),
+        ("/..", "/"),
+        ("*", "*"),
+    ] {
+        assert_eq!(normalize_path(path).unwrap(), expected, "{}", path);
+    }
+}
+
+#[test]
+fn leaves_normal_paths_borrowed() {
+    assert!(matches!(
+        normalize_path("/echo/hello%20world"),
+        Ok(Cow::Borrowed(_))
+    ));
+}
+
+#[test]
+fn refuses_encoded_control_characters() {
+    for path in ["/health%00", "/echo/a%0Ab", "/echo/%1f", "/echo/%7F"] {
+        assert_eq!(
+            normalize_path(path),
+            Err(PathError::ControlCharacter),
+            "{}",
+            path
+        );
+    }
+    // Printable escapes, and stray `%`s, are fine
+    for path in [
+        "/echo/%20",
+        "/echo/%2F",
+        "/echo/100%",
+        "/echo/%zz",
+        "/echo/%+1",
+    ] {
+        assert!(normalize_path(path).is_ok(), "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn null_byte_is_a_bad_request() {
+    for target in ["/health%00", "/health%00/extra", "/echo/a%00b"] {
+        let (head, body) = get(target).await;
+        assert_eq!(status(&head), "400", "{}: {}", target, head);
+        assert!(body.contains("control characters"), "{}: {}", target, body);
+    }
+}
+
+#[tokio::test]
+async fn fragment_is_ignored() {
+    let (head, _) = get("/health#fragment").await;
+    assert_eq!(status(&head), "200", "{}", head);
+}
+
+#[tokio::test]
+async fn dot_segments_and_slashes_route_normally() {
+    for target in ["//health", "/echo/../health", "/stats/../health?x=1"] {
+        let (head, _) = get(target).await;
+        assert_eq!(status(&head), "200", "{}: {}", target, head);
+    }
+
+    let (head, body) = get("/echo//a/./b").await;
+    assert_eq!(status(&head), "200", "{}", head);
+    assert!(body.contains("Echo: a/b"), "{}", body);
+}
+
+#[tokio::test]
+async fn escapes_stay_encoded() {
+    // An encoded `#` or `/` is data, not structure
+    let (head, _) = get("/health%23x").await;
+    assert_eq!(status(&head), "404", "{}", head);
+    let (head, _) = get("/health%2F").await;
+    assert_eq!(status(&head), "404", "{}", head);
+}
+
+// "café" with a precomposed é, and with e followed by a combining acute
+const PRECOMPOSED: &str = "/kv/caf%C3%A9";
+const DECOMPOSED: &str = "/kv/cafe%CC%81";
+
+#[test]
+fn composes_unicode_to_nfc() {
+    assert_eq!(compose_path(Cow::Borrowed(DECOMPOSED)), PRECOMPOSED);
+    // Lowercase escapes too; the result uses uppercase
+    assert_eq!(compose_path(Cow::Borrowed("/kv/cafe%cc%81")), PRECOMPOSED);
+    // Raw UTF-8, should a client send it, comes out encoded
+    assert_eq!(compose_path(Cow::Borrowed("/kv/cafe\u{301}")), PRECOMPOSED);
+    // ASCII escapes aren't decoded, so the path keeps its structure
+    assert_eq!(
+        compose_path(Cow::Borrowed("/kv/a%2Fe%CC%81")),
+        "/kv/a%2F%C3%A9"
+    );
+
+    for path in [
+        PRECOMPOSED,
+        "/kv/caf%c3%a9",
+        "/health",
+        "/kv/%FF%FE",
+        "/kv/100%",
+    ] {
+        assert!(
+            matches!(compose_path(Cow::Borrowed(path)), Cow::Borrowed(_)),
+            "{}",
+            path
+        );
+    }
+}
+
+async fn request(addr: SocketAddr, method: &str, target: &str, body: &str) -> (String, String) {
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        target,
+        body.len(),
+        body
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+#[tokio::test]
+async fn normalization_forms_reach_the_same_key() {
+    let addr = start_server(Config {
+        normalize_path_unicode: true,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = request(addr, "PUT", DECOMPOSED, "latte").await;
+    assert_eq!(status(&head), "201", "{}", head);
+    let (head, body) = request(addr, "GET", PRECOMPOSED, "").await;
+    assert_eq!(status(&head), "200", "{}", head);
+    assert_eq!(body, "latte");
+    let (head, _) = request(addr, "PUT", PRECOMPOSED, "mocha").await;
+    assert_eq!(status(&head), "200", "{}", head);
+}
+
+#[tokio::test]
+async fn normalization_forms_differ_by_default() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = request(addr, "PUT", DECOMPOSED, "latte").await;
+    assert_eq!(status(&head), "201", "{}", head);
+    let (head, _) = request(addr, "GET", PRECOMPOSED, "").await;
+    assert_eq!(status(&head), "404", "{}", head);
+}
@@ -0,0 +1,118 @@
+// HTTP/1.1 pipelining: several requests written back-to-back on one
+// keep-alive connection must all be answered, in the order they were sent.
+
+use std::time::Duration;
+
+use rust_http_server::{serve, AppState, Config};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn start_server() -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(
+        listener,
+        AppState::new(Config::default()),
+        std::future::pending(),
+    ));
+    addr
+}
+
+// Reads one response (head + Content-Length body) off the stream,
+// keeping any bytes that belong to the next response in `buf`.
+async fn read_response(stream: &mut TcpStream, buf: &mut Vec<u8>) -> (String, String) {
+    loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            let head = String::from_utf8(buf[..end].to_vec()).unwrap();
+            let content_length = head
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.trim().parse::<usize>().unwrap())
+                })
+                .expect("response without Content-Length");
+            let body_start = end + 4;
+            if buf.len() >= body_start + content_length {
+                let body = String::from_utf8(buf[body_start..body_start + content_length].to_vec())
+                    .unwrap();
+                buf.drain(..body_start + content_length);
+                return (head, body);
+            }
+        }
+
+        let mut chunk = [0u8; 4096];
+        let n = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut chunk))
+            .await
+            .expect("timed out waiting for response")
+            .unwrap();
+        assert!(n > 0, "connection closed before the response was complete");
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+#[tokio::test]
+async fn pipelined_requests_are_answered_in_order() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(
+            b"GET /echo/first HTTP/1.1\r\nHost: localhost\r\n\r\n\
+              GET /echo/second HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+    let mut buf = Vec::new();
+    let (head, body) = read_response(&mut stream, &mut buf).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("Echo: first"), "unexpected body: {}", body);
+
+    let (head, body) = read_response(&mut stream, &mut buf).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("Echo: second"), "unexpected body: {}", body);
+}
+
+#[tokio::test]
+async fn pipelined_requests_with_different_routes() {
+    let addr = start_server().await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+
+    stream
+        .write_all(
+            b"GET /health HTTP/1.1\r\nHost: localhost\r\n\r\n\
+              GET /nonexistent HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+    let mut buf = Vec::new();
+    let (head, body) = read_response(&mut stream, &mut buf).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(
+        body.contains("Server is healthy"),
+        "unexpected body: {}",
+        body
+    );
+
+    let (head, body) = read_response(&mut stream, &mut buf).await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("Not Found"), "unexpected body: {}", body);
+}
@@ -0,0 +1,99 @@
+// Pre-serialized /health body matches what serde would produce
+
+mod common;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, SecondsFormat, TimeZone, Utc};
+use common::{send_raw, start_server};
+use rust_http_server::prebuilt::TimestampedJson;
+use rust_http_server::Config;
+
+fn serde_body<Tz: TimeZone>(message: &str, now: DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    serde_json::json!({
+        "message": message,
+        "timestamp": now.to_rfc3339(),
+        "server": "rust-http-server/1.0",
+    })
+    .to_string()
+}
+
+#[test]
+fn renders_the_same_bytes_as_serde() {
+    let messages = [
+        "Server is healthy",
+        "Le serveur est en bonne santé",
+        "quote \" and \\ slash",
+    ];
+    // Whole seconds and milli/micro/nanosecond precision all format differently
+    let nanos = [0, 120_000_000, 123_456_000, 123_456_789, 1];
+
+    let offsets = [0, -4 * 3600, 5 * 3600 + 1800];
+
+    for message in messages {
+        let template = TimestampedJson::new(message);
+        for nanos in nanos {
+            for offset in offsets {
+                let zone = FixedOffset::east_opt(offset).unwrap();
+                let now = zone.timestamp_opt(1_761_517_776, nanos).unwrap();
+                assert_eq!(
+                    std::str::from_utf8(&template.render(now)).unwrap(),
+                    serde_body(message, now)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn renders_utc_with_z() {
+    let template = TimestampedJson::new("Server is healthy");
+    for nanos in [0, 120_000_000, 123_456_789] {
+        let now = Utc.timestamp_opt(1_761_517_776, nanos).unwrap();
+        let expected = serde_json::json!({
+            "message": "Server is healthy",
+            "timestamp": now.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            "server": "rust-http-server/1.0",
+        })
+        .to_string();
+        assert_eq!(
+            std::str::from_utf8(&template.render_utc(now)).unwrap(),
+            expected
+        );
+    }
+}
+
+#[test]
+fn renders_leap_seconds_like_chrono() {
+    let template = TimestampedJson::new("Server is healthy");
+    let leap = NaiveDate::from_ymd_opt(2016, 12, 31)
+        .unwrap()
+        .and_hms_nano_opt(23, 59, 59, 1_500_000_000)
+        .unwrap()
+        .and_utc();
+    assert_eq!(
+        std::str::from_utf8(&template.render(leap)).unwrap(),
+        serde_body("Server is healthy", leap)
+    );
+}
+
+#[tokio::test]
+async fn health_body_is_valid_json() {
+    let addr = start_server(Config::default()).await;
+    let (head, body) = send_raw(
+        addr,
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["message"], "Server is healthy");
+    assert_eq!(json["server"], "rust-http-server/1.0");
+    assert!(DateTime::parse_from_rfc3339(json["timestamp"].as_str().unwrap()).is_ok());
+}
@@ -0,0 +1,72 @@
+// MAX_QUERY_PARAMS and MAX_QUERY_PARAM_BYTES cap what a query string may
+// hold before it is parsed
+
+mod common;
+
+use common::{get, start_server};
+use rust_http_server::Config;
+
+fn query(count: usize) -> String {
+    (0..count)
+        .map(|n| format!("p{}=1", n))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[tokio::test]
+async fn allows_up_to_the_limit() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = get(addr, &format!("/echo/hi?{}", query(63) + "&repeat=2"), "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
+
+#[tokio::test]
+async fn rejects_too_many_parameters() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = get(addr, &format!("/echo/hi?{}", query(65)), "").await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+    assert!(body.contains("at most 64"), "{}", body);
+
+    // Repeats of one key count too, and every route is covered
+    let repeated = vec!["a=1"; 65].join("&");
+    let (head, _) = get(addr, &format!("/health?{}", repeated), "").await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+}
+
+#[tokio::test]
+async fn rejects_long_keys_and_values() {
+    let addr = start_server(Config {
+        max_query_param_bytes: 16,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = get(addr, &format!("/stats?format={}", "j".repeat(16)), "").await;
+    // Within the limit, just not a format
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+    let (_, body) = get(addr, &format!("/stats?format={}", "j".repeat(17)), "").await;
+    assert!(body.contains("limited to 16 bytes"), "{}", body);
+    let (_, body) = get(addr, &format!("/stats?{}=json", "k".repeat(17)), "").await;
+    assert!(body.contains("limited to 16 bytes"), "{}", body);
+
+    // Measured before decoding: %41 is three bytes
+    let (_, body) = get(addr, &format!("/stats?format={}", "%41".repeat(6)), "").await;
+    assert!(body.contains("limited to 16 bytes"), "{}", body);
+}
+
+#[tokio::test]
+async fn limit_is_configurable() {
+    let addr = start_server(Config {
+        max_query_params: 2,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = get(addr, "/stream/ndjson?count=1&x=2", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    let (head, body) = get(addr, "/stream/ndjson?count=1&x=2&y=3", "").await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+    assert!(body.contains("at most 2"), "{}", body);
+}
@@ -0,0 +1,105 @@
+// Range requests against /echo/stream/:msg
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{decode_chunked, header, send_raw, start_server};
+use rust_http_server::compression::crc32;
+use rust_http_server::range::{resolve, ByteRange};
+use rust_http_server::Config;
+
+async fn get_range(addr: SocketAddr, path: &str, range: &str) -> (String, String) {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nTE: trailers\r\nRange: {}\r\nConnection: close\r\n\r\n",
+        path, range
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+#[test]
+fn resolves_single_ranges() {
+    let partial = |start, end| ByteRange::Partial { start, end };
+    assert_eq!(resolve(None, 10), ByteRange::Full);
+    assert_eq!(resolve(Some("bytes=2-4"), 10), partial(2, 5));
+    assert_eq!(resolve(Some("bytes=2-"), 10), partial(2, 10));
+    assert_eq!(resolve(Some("bytes=2-99"), 10), partial(2, 10));
+    assert_eq!(resolve(Some("bytes=-3"), 10), partial(7, 10));
+    assert_eq!(resolve(Some("bytes=-30"), 10), partial(0, 10));
+    assert_eq!(resolve(Some("bytes=10-"), 10), ByteRange::Unsatisfiable);
+    assert_eq!(resolve(Some("bytes=-0"), 10), ByteRange::Unsatisfiable);
+}
+
+#[test]
+fn ignores_unusable_ranges() {
+    for range in [
+        "items=0-1",
+        "bytes=4-2",
+        "bytes=0-1,4-5",
+        "bytes=x-",
+        "bytes=+1-2",
+    ] {
+        assert_eq!(resolve(Some(range), 10), ByteRange::Full, "{}", range);
+    }
+}
+
+#[tokio::test]
+async fn resumes_from_an_offset() {
+    let addr = start_server(Config::default()).await;
+
+    // "abc\n" x 4 = 16 bytes; resume mid-chunk
+    let (head, body) = get_range(addr, "/echo/stream/abc?count=4", "bytes=5-").await;
+    assert!(
+        head.starts_with("HTTP/1.1 206"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "content-range"), Some("bytes 5-15/16"));
+
+    let (data, trailers) = decode_chunked(&body);
+    assert_eq!(data, "bc\nabc\nabc\n");
+    assert_eq!(
+        trailers,
+        vec![format!("x-checksum: {:08x}", crc32(data.as_bytes()))]
+    );
+}
+
+#[tokio::test]
+async fn serves_a_bounded_range() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = get_range(addr, "/echo/stream/abc?count=4", "bytes=2-9").await;
+    assert_eq!(header(&head, "content-range"), Some("bytes 2-9/16"));
+    assert_eq!(decode_chunked(&body).0, "c\nabc\nab");
+
+    let (head, body) = get_range(addr, "/echo/stream/abc?count=4", "bytes=-3").await;
+    assert_eq!(header(&head, "content-range"), Some("bytes 13-15/16"));
+    assert_eq!(decode_chunked(&body).0, "bc\n");
+}
+
+#[tokio::test]
+async fn rejects_ranges_past_the_end() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = get_range(addr, "/echo/stream/abc?count=4", "bytes=16-").await;
+    assert!(
+        head.starts_with("HTTP/1.1 416"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "content-range"), Some("bytes */16"));
+}
+
+#[tokio::test]
+async fn advertises_range_support() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = get_range(addr, "/echo/stream/abc?count=2", "bytes=0-1,3-4").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    assert_eq!(header(&head, "accept-ranges"), Some("bytes"));
+    assert_eq!(decode_chunked(&body).0, "abc\nabc\n");
+}
@@ -0,0 +1,295 @@
+// Per-client rate limiting, keyed by IPv4 / IPv6 network prefix, by token
+// bucket or leaky bucket
+
+mod common;
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use common::{header, send_raw, start_server};
+use rust_http_server::rate_limit::{
+    normalize_ip, LeakyBucket, RateLimitAlgorithm, RateLimiter, TokenBucket,
+};
+use rust_http_server::Config;
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn ipv6_is_masked_to_prefix() {
+    assert_eq!(
+        normalize_ip(ip("2001:db8:1:2:aaaa:bbbb:cccc:dddd"), 32, 64),
+        ip("2001:db8:1:2::")
+    );
+    assert_eq!(
+        normalize_ip(ip("2001:db8:1:2:aaaa:bbbb:cccc:dddd"), 32, 48),
+        ip("2001:db8:1::")
+    );
+    assert_eq!(normalize_ip(ip("2001:db8::1"), 32, 128), ip("2001:db8::1"));
+    assert_eq!(normalize_ip(ip("2001:db8::1"), 32, 0), ip("::"));
+}
+
+#[test]
+fn ipv4_is_masked_to_prefix() {
+    assert_eq!(normalize_ip(ip("203.0.113.77"), 32, 64), ip("203.0.113.77"));
+    assert_eq!(normalize_ip(ip("203.0.113.77"), 24, 64), ip("203.0.113.0"));
+    assert_eq!(normalize_ip(ip("203.0.113.77"), 0, 64), ip("0.0.0.0"));
+}
+
+#[test]
+fn ipv4_mapped_ipv6_is_treated_as_ipv4() {
+    assert_eq!(
+        normalize_ip(ip("::ffff:203.0.113.77"), 32, 64),
+        ip("203.0.113.77")
+    );
+}
+
+#[test]
+fn ipv6_addresses_in_one_prefix_share_a_bucket() {
+    let limiter = TokenBucket::new(0.001, 2, 32, 64);
+
+    assert!(limiter.check(ip("2001:db8:0:1::1")).is_ok());
+    assert!(limiter.check(ip("2001:db8:0:1::2")).is_ok());
+    // Rotating within the same /64 doesn't help
+    assert!(limiter.check(ip("2001:db8:0:1:ffff::3")).is_err());
+
+    // A different /64 has its own bucket
+    assert!(limiter.check(ip("2001:db8:0:2::1")).is_ok());
+}
+
+#[test]
+fn ipv4_addresses_are_limited_individually() {
+    let limiter = TokenBucket::new(0.001, 1, 32, 64);
+
+    assert!(limiter.check(ip("198.51.100.1")).is_ok());
+    assert!(limiter.check(ip("198.51.100.1")).is_err());
+    assert!(limiter.check(ip("198.51.100.2")).is_ok());
+}
+
+#[test]
+fn ipv4_prefix_is_configurable() {
+    let limiter = TokenBucket::new(0.001, 1, 24, 64);
+
+    assert!(limiter.check(ip("198.51.100.1")).is_ok());
+    assert!(limiter.check(ip("198.51.100.2")).is_err());
+    assert!(limiter.check(ip("198.51.101.1")).is_ok());
+}
+
+#[test]
+fn retry_after_reflects_refill_rate() {
+    let limiter = TokenBucket::new(0.5, 1, 32, 64);
+
+    assert!(limiter.check(ip("198.51.100.1")).is_ok());
+    let retry_after = limiter.check(ip("198.51.100.1")).unwrap_err();
+    assert!(retry_after.as_secs_f64() > 1.5 && retry_after.as_secs_f64() <= 2.0);
+}
+
+#[test]
+fn sweep_drops_idle_buckets_once_refilled() {
+    let limiter = TokenBucket::new(1.0, 2, 32, 64);
+    let start = Instant::now();
+    assert!(limiter.check(ip("198.51.100.1")).is_ok());
+    assert!(limiter.check(ip("198.51.100.2")).is_ok());
+    assert!(limiter.check(ip("198.51.100.2")).is_ok());
+    assert_eq!(limiter.tracked(), 2);
+
+    // Neither has been idle long enough
+    assert_eq!(limiter.sweep(start, Duration::from_secs(1)), 0);
+
+    // The first is full again; the second, with no tokens left, isn't yet
+    // and would lose its debt if dropped
+    let later = start + Duration::from_millis(1500);
+    assert_eq!(limiter.sweep(later, Duration::from_secs(1)), 1);
+    assert_eq!(limiter.tracked(), 1);
+    assert!(limiter.check(ip("198.51.100.1")).is_ok());
+    assert_eq!(limiter.tracked(), 2);
+
+    let much_later = start + Duration::from_secs(10);
+    assert_eq!(limiter.sweep(much_later, Duration::from_secs(1)), 2);
+    assert_eq!(limiter.tracked(), 0);
+}
+
+#[tokio::test]
+async fn sweeper_runs_in_the_background() {
+    let limiter = TokenBucket::new(1000.0, 1, 32, 64);
+    for last in 1..=50 {
+        assert!(limiter.check(ip(&format!("198.51.100.{}", last))).is_ok());
+    }
+    assert_eq!(limiter.tracked(), 50);
+
+    let sweeper = limiter.spawn_sweeper(Duration::from_millis(20), Duration::from_millis(20));
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(limiter.tracked(), 0);
+    sweeper.abort();
+}
+
+#[tokio::test]
+async fn stats_report_tracked_clients() {
+    let request = b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+    let addr = start_server(Config {
+        rate_limit_per_sec: 100.0,
+        ..Config::default()
+    })
+    .await;
+    let (_, body) = send_raw(addr, request).await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    // The /stats request itself, from 127.0.0.1
+    assert_eq!(stats["rate_limiter_tracked_ips"], 1);
+
+    let addr = start_server(Config::default()).await;
+    let (_, body) = send_raw(addr, request).await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(stats["rate_limiter_tracked_ips"].is_null(), "{}", body);
+}
+
+#[tokio::test]
+async fn server_returns_429_once_burst_is_spent() {
+    let addr = start_server(Config {
+        rate_limit_per_sec: 0.1,
+        rate_limit_burst: 2,
+        ..Config::default()
+    })
+    .await;
+    let request = b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+    let (head, _) = send_raw(addr, request).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    let (head, _) = send_raw(addr, request).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+
+    let (head, body) = send_raw(addr, request).await;
+    assert!(
+        head.starts_with("HTTP/1.1 429"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("Too Many Requests"));
+    let retry_after: u64 = header(&head, "retry-after").unwrap().parse().unwrap();
+    assert!((1..=10).contains(&retry_after));
+}
+
+fn close_to(actual: Duration, expected_ms: u64) -> bool {
+    actual.abs_diff(Duration::from_millis(expected_ms)) < Duration::from_millis(20)
+}
+
+#[test]
+fn leaky_bucket_spaces_requests_out() {
+    let limiter = LeakyBucket::new(10.0, 3, 32, 64);
+
+    assert_eq!(limiter.check(ip("198.51.100.1")), Ok(Duration::ZERO));
+    // Queued behind the first, 100ms apart
+    assert!(close_to(limiter.check(ip("198.51.100.1")).unwrap(), 100));
+    assert!(close_to(limiter.check(ip("198.51.100.1")).unwrap(), 200));
+    // The queue is full until the first slot has drained
+    let retry_after = limiter.check(ip("198.51.100.1")).unwrap_err();
+    assert!(close_to(retry_after, 100), "{:?}", retry_after);
+
+    assert_eq!(limiter.check(ip("198.51.100.2")), Ok(Duration::ZERO));
+}
+
+#[test]
+fn leaky_bucket_without_burst_allows_no_queue() {
+    let limiter = LeakyBucket::new(2.0, 1, 32, 64);
+
+    assert_eq!(limiter.check(ip("198.51.100.1")), Ok(Duration::ZERO));
+    let retry_after = limiter.check(ip("198.51.100.1")).unwrap_err();
+    assert!(close_to(retry_after, 500), "{:?}", retry_after);
+}
+
+#[test]
+fn leaky_bucket_keys_by_prefix_and_sweeps_drained_queues() {
+    let limiter = LeakyBucket::new(1.0, 1, 32, 64);
+    let start = Instant::now();
+    assert!(limiter.check(ip("2001:db8:0:1::1")).is_ok());
+    assert!(limiter.check(ip("2001:db8:0:1::2")).is_err());
+    assert_eq!(limiter.tracked(), 1);
+
+    // Drains a second after the request, then idles for the TTL
+    assert_eq!(limiter.sweep(start, Duration::from_secs(1)), 0);
+    assert_eq!(
+        limiter.sweep(start + Duration::from_millis(2100), Duration::from_secs(1)),
+        1
+    );
+    assert_eq!(limiter.tracked(), 0);
+}
+
+#[test]
+fn algorithm_parses() {
+    assert_eq!(
+        "token_bucket".parse::<RateLimitAlgorithm>(),
+        Ok(RateLimitAlgorithm::TokenBucket)
+    );
+    assert_eq!(
+        " Leaky_Bucket ".parse::<RateLimitAlgorithm>(),
+        Ok(RateLimitAlgorithm::LeakyBucket)
+    );
+    assert!("fixed_window".parse::<RateLimitAlgorithm>().is_err());
+}
+
+#[tokio::test]
+async fn server_holds_back_leaky_bucket_bursts() {
+    let addr = start_server(Config {
+        rate_limit_algorithm: RateLimitAlgorithm::LeakyBucket,
+        rate_limit_per_sec: 5.0,
+        rate_limit_burst: 2,
+        ..Config::default()
+    })
+    .await;
+    let request = b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+    let started = Instant::now();
+    let (first, second) = tokio::join!(send_raw(addr, request), send_raw(addr, request));
+    assert!(first.0.starts_with("HTTP/1.1 200"), "{}", first.0);
+    assert!(second.0.starts_with("HTTP/1.1 200"), "{}", second.0);
+    // One of them waited its 200ms turn
+    assert!(started.elapsed() >= Duration::from_millis(180));
+
+    // With the queue still full, the overflow is turned away
+    let heads = tokio::join!(
+        send_raw(addr, request),
+        send_raw(addr, request),
+        send_raw(addr, request)
+    );
+    let refused: Vec<_> = [heads.0 .0, heads.1 .0, heads.2 .0]
+        .into_iter()
+        .filter(|head| head.starts_with("HTTP/1.1 429"))
+        .collect();
+    assert!(!refused.is_empty());
+    assert!(
+        header(&refused[0], "retry-after").is_some(),
+        "{}",
+        refused[0]
+    );
+}
+
+#[tokio::test]
+async fn leaky_bucket_waits_count_against_the_request_budget() {
+    let addr = start_server(Config {
+        rate_limit_algorithm: RateLimitAlgorithm::LeakyBucket,
+        rate_limit_per_sec: 1.0,
+        rate_limit_burst: 100,
+        request_timeout_ms: 300,
+        ..Config::default()
+    })
+    .await;
+    let request = b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+    let (head, _) = send_raw(addr, request).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    // Its turn is a second away, past the 300ms it may take in all
+    let started = Instant::now();
+    let (head, _) = send_raw(addr, request).await;
+    assert!(head.starts_with("HTTP/1.1 429"), "{}", head);
+    assert!(header(&head, "retry-after").is_some(), "{}", head);
+    assert!(started.elapsed() < Duration::from_millis(300));
+}
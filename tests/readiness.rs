@@ -0,0 +1,170 @@
+// /readyz answers 503 until every warm-up task has finished, STARTUP_DELAY_MS
+// being one, while /livez answers 200 throughout
+
+mod common;
+
+use std::net::SocketAddr;
+use std::process::{Command, Stdio};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use common::{header, send_raw};
+use rust_http_server::{serve, AppState, Config, Warmup};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+
+const READYZ: &[u8] = b"GET /readyz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+const LIVEZ: &[u8] = b"GET /livez HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+async fn start(state: AppState) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(listener, state, std::future::pending()));
+    addr
+}
+
+#[tokio::test]
+async fn ready_without_warmups() {
+    let addr = start(AppState::new(Config::default())).await;
+    let (head, body) = send_raw(addr, READYZ).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("\"Ready\""), "unexpected body: {}", body);
+}
+
+#[tokio::test]
+async fn not_ready_until_warmups_finish() {
+    let state = AppState::new(Config::default());
+    let (first_done, first) = oneshot::channel::<()>();
+    let (second_done, second) = oneshot::channel::<()>();
+    let warmups: Vec<Warmup> = vec![
+        Box::pin(async move {
+            let _ = first.await;
+        }),
+        Box::pin(async move {
+            let _ = second.await;
+        }),
+    ];
+    let warmed_up = state.ready_after(warmups);
+    let addr = start(state).await;
+
+    let (head, _) = send_raw(addr, READYZ).await;
+    assert!(
+        head.starts_with("HTTP/1.1 503"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(header(&head, "retry-after"), Some("1"));
+
+    // Other routes are served while warming up
+    let (head, _) = send_raw(
+        addr,
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+
+    first_done.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let (head, _) = send_raw(addr, READYZ).await;
+    assert!(
+        head.starts_with("HTTP/1.1 503"),
+        "ready with a task pending: {}",
+        head
+    );
+
+    second_done.send(()).unwrap();
+    warmed_up.await.unwrap();
+    let (head, _) = send_raw(addr, READYZ).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn failed_warmup_stays_not_ready() {
+    let state = AppState::new(Config::default());
+    let warmups: Vec<Warmup> = vec![Box::pin(async { panic!("cache load failed") })];
+    state.ready_after(warmups).await.unwrap();
+
+    assert!(!state.ready.load(Ordering::Relaxed));
+    let addr = start(state).await;
+    let (head, _) = send_raw(addr, READYZ).await;
+    assert!(
+        head.starts_with("HTTP/1.1 503"),
+        "unexpected status: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn alive_while_warming_up() {
+    let state = AppState::new(Config::default());
+    let warmups: Vec<Warmup> = vec![Box::pin(std::future::pending())];
+    state.ready_after(warmups);
+    let addr = start(state).await;
+
+    let (head, body) = send_raw(addr, LIVEZ).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert!(body.contains("\"Alive\""), "{}", body);
+    let (head, _) = send_raw(addr, READYZ).await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+}
+
+#[tokio::test]
+async fn startup_delay_holds_readiness() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+    let mut child = tokio::process::Command::from({
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rust-http-server"));
+        command
+            .env("LISTEN_ADDRS", addr.to_string())
+            .env("QUIET", "1")
+            .env("STARTUP_DELAY_MS", "1500")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        command
+    })
+    .kill_on_drop(true)
+    .spawn()
+    .unwrap();
+
+    let mut connected = false;
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect(addr).await.is_ok() {
+            connected = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(connected, "server never started on {}", addr);
+
+    let (head, _) = send_raw(addr, READYZ).await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+    let (head, _) = send_raw(addr, LIVEZ).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+    let mut ready = false;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if send_raw(addr, READYZ).await.0.starts_with("HTTP/1.1 200") {
+            ready = true;
+            break;
+        }
+    }
+    assert!(ready, "still not ready after the delay");
+    child.kill().await.unwrap();
+}
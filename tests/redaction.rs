@@ -0,0 +1,174 @@
+// Sensitive headers are redacted to "***" wherever headers are logged or
+// kept, and the list of them is configurable
+
+mod common;
+
+use std::net::{SocketAddr, TcpListener};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use common::{send_raw, start_server};
+use hyper::header::{HeaderName, HeaderValue};
+use hyper::HeaderMap;
+use rust_http_server::redact::{redact_headers, RedactList};
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::Config;
+use tokio::io::AsyncReadExt;
+
+const SECRET_REQUEST: &str = "GET /health HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer topsecret-1\r\nCookie: session=topsecret-2\r\nX-API-Key: topsecret-3\r\nReferer: https://example.com/?token=topsecret-4\r\nConnection: close\r\n\r\n";
+
+fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.append(
+            HeaderName::from_static(name),
+            HeaderValue::from_static(value),
+        );
+    }
+    headers
+}
+
+#[test]
+fn redacts_the_default_list() {
+    let redacted = redact_headers(
+        &headers(&[
+            ("authorization", "Bearer abc"),
+            ("cookie", "a=1"),
+            ("cookie", "b=2"),
+            ("proxy-authorization", "Basic xyz"),
+            ("x-api-key", "k"),
+            ("user-agent", "curl/8.5.0"),
+        ]),
+        &RedactList::default(),
+    );
+    assert_eq!(redacted["authorization"], "***");
+    assert_eq!(redacted["proxy-authorization"], "***");
+    assert_eq!(redacted["x-api-key"], "***");
+    let cookies: Vec<_> = redacted.get_all("cookie").iter().collect();
+    assert_eq!(cookies, ["***", "***"]);
+    assert_eq!(redacted["user-agent"], "curl/8.5.0");
+}
+
+#[test]
+fn the_list_is_configurable() {
+    let list: RedactList = " X-Session , referer ".parse().unwrap();
+    let redacted = redact_headers(
+        &headers(&[
+            ("x-session", "s"),
+            ("referer", "https://example.com/"),
+            ("authorization", "Bearer abc"),
+        ]),
+        &list,
+    );
+    assert_eq!(redacted["x-session"], "***");
+    assert_eq!(redacted["referer"], "***");
+    // The list replaces the defaults
+    assert_eq!(redacted["authorization"], "Bearer abc");
+
+    assert_eq!("".parse::<RedactList>().unwrap(), RedactList::none());
+    assert!("bad header".parse::<RedactList>().is_err());
+
+    let vars = [("REDACT_HEADERS", "x-session")];
+    let config = Config::from_lookup(|key| {
+        vars.iter()
+            .find(|(name, _)| *name == key)
+            .map(|(_, value)| value.to_string())
+    })
+    .unwrap();
+    assert_eq!(config.redacted_headers, "x-session".parse().unwrap());
+    assert_eq!(Config::default().redacted_headers, RedactList::default());
+}
+
+#[tokio::test]
+async fn recent_requests_keep_redacted_headers() {
+    let addr = start_server(Config {
+        recent_requests: 5,
+        route_toggles: RouteToggles::new().with_route("/debug/recent", true),
+        ..Config::default()
+    })
+    .await;
+    send_raw(addr, SECRET_REQUEST.as_bytes()).await;
+
+    let (_, body) = send_raw(
+        addr,
+        b"GET /debug/recent HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(!body.contains("topsecret-1"), "{}", body);
+    assert!(!body.contains("topsecret-2"), "{}", body);
+    assert!(!body.contains("topsecret-3"), "{}", body);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let headers = &body["requests"][0]["headers"];
+    assert_eq!(headers["authorization"], "***");
+    assert_eq!(headers["cookie"], "***");
+    assert_eq!(headers["x-api-key"], "***");
+    assert_eq!(headers["host"], "localhost");
+}
+
+// Runs the server binary with `env`, sends `SECRET_REQUEST` and returns
+// everything it logged
+async fn logged_by_server(env: &[(&str, &str)]) -> String {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+    let mut child = tokio::process::Command::from({
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rust-http-server"));
+        command
+            .env("LISTEN_ADDRS", addr.to_string())
+            .env("QUIET", "1")
+            .envs(env.iter().copied())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        command
+    })
+    .kill_on_drop(true)
+    .spawn()
+    .unwrap();
+
+    let mut connected = false;
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect(addr).await.is_ok() {
+            connected = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(connected, "server never started on {}", addr);
+    let (head, _) = send_raw(addr, SECRET_REQUEST.as_bytes()).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+    child.kill().await.unwrap();
+    let mut output = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut output)
+        .await
+        .unwrap();
+    output
+}
+
+#[tokio::test]
+async fn authorization_never_reaches_the_log() {
+    let output = logged_by_server(&[("LOG_HEADERS", "1")]).await;
+    assert!(output.contains("GET /health"), "{}", output);
+    assert!(output.contains(r#""authorization":"***""#), "{}", output);
+    for secret in ["topsecret-1", "topsecret-2", "topsecret-3"] {
+        assert!(!output.contains(secret), "{} logged: {}", secret, output);
+    }
+    // Not on the list, so logged as sent
+    assert!(output.contains("topsecret-4"), "{}", output);
+
+    let output = logged_by_server(&[
+        ("LOG_FORMAT", "combined"),
+        ("REDACT_HEADERS", "authorization,referer"),
+    ])
+    .await;
+    assert!(output.contains(r#" 200 "#), "{}", output);
+    assert!(output.contains(r#""***" "-""#), "{}", output);
+    assert!(!output.contains("topsecret"), "{}", output);
+}
@@ -0,0 +1,200 @@
+// gzipped request bodies are decompressed before the handler sees them,
+// with MAX_BODY_BYTES enforced on the decompressed size
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{header, start_server};
+use rust_http_server::compression::gzip;
+use rust_http_server::inflate::{gunzip, InflateError};
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+// `gzip -9` output for `lines(20)`; unlike our own encoder, it uses a
+// dynamic-Huffman block
+const DYNAMIC_GZIP: &str = "1f8b08000000000002039dd25d1642501885e17ba3f886604b3f9a8d384a0e270a65f49666e0bddeebbdda8faf3b67f1d53e0f67fd58178ddd8630775685af3dc7f6f5b630b9e13ffb7cf95919ee91df1a812601cd013429688ea03981e60c9a0b6832f229824024885010b120824144830807110f2220b453c40a94a705111a040000";
+
+// `gzip -0` output for "stored block!", a single stored block
+const STORED_GZIP: &str =
+    "1f8b0800000000000403010d00f2ff73746f72656420626c6f636b212d8c34690d000000";
+
+fn lines(count: usize) -> String {
+    (0..count)
+        .map(|i| format!("line {}: the quick brown fox jumps over the lazy dog\n", i))
+        .collect()
+}
+
+fn decode_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+async fn post(addr: SocketAddr, path: &str, encoding: &str, body: &[u8]) -> (String, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let head = format!(
+        "POST {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Encoding: {}\r\nContent-Length: {}\r\n\r\n",
+        path,
+        encoding,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(body).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .unwrap();
+    (
+        String::from_utf8(response[..split].to_vec()).unwrap(),
+        response[split + 4..].to_vec(),
+    )
+}
+
+#[test]
+fn decodes_every_block_type() {
+    let text = lines(20);
+    assert_eq!(
+        gunzip(&decode_hex(DYNAMIC_GZIP), 1 << 20).unwrap(),
+        text.as_bytes()
+    );
+    assert_eq!(
+        gunzip(&decode_hex(STORED_GZIP), 1 << 20).unwrap(),
+        b"stored block!"
+    );
+    // Fixed Huffman with back-references, from our own encoder
+    assert_eq!(
+        gunzip(&gzip(text.as_bytes()), 1 << 20).unwrap(),
+        text.as_bytes()
+    );
+    assert_eq!(gunzip(&gzip(b""), 1 << 20).unwrap(), b"");
+}
+
+#[test]
+fn decodes_concatenated_members() {
+    let mut data = gzip(b"first ");
+    data.extend(decode_hex(STORED_GZIP));
+    assert_eq!(gunzip(&data, 1 << 20).unwrap(), b"first stored block!");
+}
+
+#[test]
+fn stops_at_the_limit() {
+    let zeros = vec![0u8; 100_000];
+    let compressed = gzip(&zeros);
+    assert!(compressed.len() < 2_000, "{} bytes", compressed.len());
+    assert_eq!(gunzip(&compressed, 99_999), Err(InflateError::TooLarge));
+    assert_eq!(gunzip(&compressed, 100_000).unwrap().len(), 100_000);
+}
+
+#[test]
+fn rejects_corrupt_data() {
+    let good = gzip(b"hello, world");
+    assert_eq!(gunzip(b"hello, world", 1 << 20), Err(InflateError::Invalid));
+    assert_eq!(
+        gunzip(&good[..good.len() - 1], 1 << 20),
+        Err(InflateError::Invalid)
+    );
+
+    // A flipped CRC byte
+    let mut bad_crc = good.clone();
+    let index = bad_crc.len() - 8;
+    bad_crc[index] ^= 0xff;
+    assert_eq!(gunzip(&bad_crc, 1 << 20), Err(InflateError::Invalid));
+
+    // Reserved block type 3
+    let mut bad_block = good;
+    bad_block[10] |= 0b110;
+    assert_eq!(gunzip(&bad_block, 1 << 20), Err(InflateError::Invalid));
+}
+
+#[tokio::test]
+async fn echo_sees_the_decompressed_body() {
+    let addr = start_server(Config::default()).await;
+    let text = lines(20);
+
+    let (head, body) = post(addr, "/echo", "gzip", &decode_hex(DYNAMIC_GZIP)).await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    let body = String::from_utf8(body).unwrap();
+    assert!(
+        body.contains("line 19: the quick brown fox"),
+        "unexpected body: {}",
+        body
+    );
+
+    let (head, body) = post(addr, "/echo", "x-gzip", &gzip(text.as_bytes())).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert!(String::from_utf8(body).unwrap().contains("line 0:"));
+}
+
+#[tokio::test]
+async fn limit_applies_to_the_decompressed_size() {
+    let addr = start_server(Config {
+        max_body_bytes: 10_000,
+        ..Config::default()
+    })
+    .await;
+
+    let bomb = gzip(&vec![b'a'; 1_000_000]);
+    assert!(bomb.len() < 10_000, "{} bytes", bomb.len());
+    let (head, body) = post(addr, "/echo", "gzip", &bomb).await;
+    assert!(
+        head.starts_with("HTTP/1.1 413"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(String::from_utf8(body)
+        .unwrap()
+        .contains("exceeds maximum of 10000 bytes"));
+}
+
+#[tokio::test]
+async fn invalid_gzip_is_a_bad_request() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = post(addr, "/echo", "gzip", b"definitely not gzip").await;
+    assert!(
+        head.starts_with("HTTP/1.1 400"),
+        "unexpected head: {}",
+        head
+    );
+    assert!(String::from_utf8(body).unwrap().contains("not valid gzip"));
+}
+
+#[tokio::test]
+async fn unknown_encodings_get_415() {
+    let addr = start_server(Config::default()).await;
+
+    for encoding in ["br", "deflate", "gzip, gzip", "compress"] {
+        let (head, body) = post(addr, "/echo", encoding, b"data").await;
+        assert!(
+            head.starts_with("HTTP/1.1 415"),
+            "{}: unexpected head: {}",
+            encoding,
+            head
+        );
+        assert_eq!(
+            header(&head, "accept-encoding"),
+            Some("gzip"),
+            "{}",
+            encoding
+        );
+        assert!(
+            String::from_utf8(body).unwrap().contains("only gzip is"),
+            "{}",
+            encoding
+        );
+    }
+
+    // identity is no encoding at all
+    let (head, body) = post(addr, "/echo", "identity", b"plain").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert!(String::from_utf8(body).unwrap().contains("plain"));
+}
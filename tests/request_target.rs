@@ -0,0 +1,64 @@
+// Only origin-form targets are routed: absolute-form and authority-form are
+// how a client talks to a proxy, and this server isn't one
+
+mod common;
+
+use common::{send_raw, start_server};
+use rust_http_server::Config;
+
+#[tokio::test]
+async fn origin_form_is_routed() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = send_raw(
+        addr,
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert!(body.contains("healthy"), "{}", body);
+}
+
+#[tokio::test]
+async fn absolute_form_is_refused() {
+    let addr = start_server(Config::default()).await;
+
+    for target in [
+        "http://localhost/health",
+        "http://evil.example/health",
+        "https://localhost/echo/hi",
+    ] {
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            target
+        );
+        let (head, body) = send_raw(addr, request.as_bytes()).await;
+        assert!(head.starts_with("HTTP/1.1 400"), "{}: {}", target, head);
+        assert!(body.contains("Absolute-form"), "{}", body);
+    }
+}
+
+#[tokio::test]
+async fn authority_form_is_refused() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = send_raw(
+        addr,
+        b"GET localhost:8080 HTTP/1.1\r\nHost: localhost:8080\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+    assert!(body.contains("Authority-form"), "{}", body);
+}
+
+#[tokio::test]
+async fn asterisk_form_is_still_answered() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = send_raw(
+        addr,
+        b"OPTIONS * HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(!head.starts_with("HTTP/1.1 400"), "{}", head);
+}
@@ -0,0 +1,198 @@
+// RESPONSE_CACHE keeps GET responses per route, and identical requests that
+// miss at the same moment share a single computation
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::{get, header, start_server};
+use http_body_util::BodyExt;
+use hyper::Response;
+use rust_http_server::response_cache::{CacheRoutes, ResponseCache, CACHE_HEADER};
+use rust_http_server::{full, Config};
+
+fn config(routes: &str) -> Config {
+    Config {
+        response_cache: routes.parse::<CacheRoutes>().unwrap(),
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn concurrent_misses_compute_once() {
+    let cache = ResponseCache::new();
+    let computed = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = (0..20)
+        .map(|_| {
+            let cache = cache.clone();
+            let computed = computed.clone();
+            tokio::spawn(async move {
+                let response = cache
+                    .get_or_compute("key".to_string(), Duration::from_secs(5), async {
+                        computed.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        Response::new(full("expensive"))
+                    })
+                    .await;
+                let cache_status = response.headers()[CACHE_HEADER]
+                    .to_str()
+                    .unwrap()
+                    .to_string();
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                (cache_status, body)
+            })
+        })
+        .collect();
+
+    let mut misses = 0;
+    for task in tasks {
+        let (cache_status, body) = task.await.unwrap();
+        assert_eq!(body, "expensive");
+        if cache_status == "MISS" {
+            misses += 1;
+        }
+    }
+    assert_eq!(computed.load(Ordering::SeqCst), 1);
+    assert_eq!(misses, 1);
+}
+
+#[tokio::test]
+async fn a_cancelled_computation_is_taken_over() {
+    let cache = ResponseCache::new();
+    let first = tokio::spawn({
+        let cache = cache.clone();
+        async move {
+            cache
+                .get_or_compute("key".to_string(), Duration::from_secs(5), async {
+                    std::future::pending::<()>().await;
+                    unreachable!()
+                })
+                .await
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    let second = tokio::spawn({
+        let cache = cache.clone();
+        async move {
+            cache
+                .get_or_compute("key".to_string(), Duration::from_secs(5), async {
+                    Response::new(full("second"))
+                })
+                .await
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    first.abort();
+
+    let response = tokio::time::timeout(Duration::from_secs(1), second)
+        .await
+        .expect("waiter never took over")
+        .unwrap();
+    assert_eq!(response.headers()[CACHE_HEADER], "MISS");
+}
+
+#[tokio::test]
+async fn concurrent_requests_share_one_response() {
+    let addr = start_server(config("/delay/:ms=5000")).await;
+
+    let started = Instant::now();
+    let tasks: Vec<_> = (0..10)
+        .map(|_| tokio::spawn(async move { get(addr, "/delay/300", "").await }))
+        .collect();
+    let mut bodies = Vec::new();
+    let mut misses = 0;
+    for task in tasks {
+        let (head, body) = task.await.unwrap();
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+        if header(&head, CACHE_HEADER) == Some("MISS") {
+            misses += 1;
+        }
+        bodies.push(body);
+    }
+    assert_eq!(misses, 1);
+    // One computation, so one timestamp
+    assert!(bodies.iter().all(|body| body == &bodies[0]), "{:?}", bodies);
+    assert!(started.elapsed() < Duration::from_millis(2000));
+
+    // Still kept afterwards
+    let started = Instant::now();
+    let (head, body) = get(addr, "/delay/300", "").await;
+    assert_eq!(header(&head, CACHE_HEADER), Some("HIT"));
+    assert_eq!(body, bodies[0]);
+    assert!(started.elapsed() < Duration::from_millis(300));
+}
+
+#[tokio::test]
+async fn requests_differing_in_path_or_accept_are_kept_apart() {
+    let addr = start_server(config("/echo/:msg=5000")).await;
+
+    let (head, json) = get(addr, "/echo/a", "").await;
+    assert_eq!(header(&head, CACHE_HEADER), Some("MISS"));
+    let (head, _) = get(addr, "/echo/b", "").await;
+    assert_eq!(header(&head, CACHE_HEADER), Some("MISS"));
+    let (head, body) = get(addr, "/echo/a", "Accept: text/plain\r\n").await;
+    assert_eq!(header(&head, CACHE_HEADER), Some("MISS"));
+    assert_ne!(body, json);
+    let (head, _) = get(addr, "/echo/a", "").await;
+    assert_eq!(header(&head, CACHE_HEADER), Some("HIT"));
+}
+
+#[tokio::test]
+async fn entries_expire() {
+    let addr = start_server(config("/echo/:msg=100")).await;
+
+    let (head, _) = get(addr, "/echo/a", "").await;
+    assert_eq!(header(&head, CACHE_HEADER), Some("MISS"));
+    let (head, _) = get(addr, "/echo/a", "").await;
+    assert_eq!(header(&head, CACHE_HEADER), Some("HIT"));
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    let (head, _) = get(addr, "/echo/a", "").await;
+    assert_eq!(header(&head, CACHE_HEADER), Some("MISS"));
+}
+
+#[tokio::test]
+async fn errors_and_unlisted_routes_are_not_kept() {
+    let addr = start_server(config("/error/:code=5000")).await;
+
+    for _ in 0..2 {
+        let (head, _) = get(addr, "/error/503", "").await;
+        assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+        assert_eq!(header(&head, CACHE_HEADER), Some("MISS"));
+    }
+    let (head, _) = get(addr, "/echo/a", "").await;
+    assert_eq!(header(&head, CACHE_HEADER), None);
+}
+
+#[tokio::test]
+async fn requests_with_credentials_bypass_the_cache() {
+    let addr = start_server(config("/echo/:msg=5000")).await;
+
+    for credentials in ["Authorization: Bearer secret\r\n", "Cookie: session=1\r\n"] {
+        let (head, _) = get(addr, "/echo/a", credentials).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+        assert_eq!(header(&head, CACHE_HEADER), None, "{}", head);
+    }
+    // Nothing an authorized client got was kept for anyone else
+    let (head, _) = get(addr, "/echo/a", "").await;
+    assert_eq!(header(&head, CACHE_HEADER), Some("MISS"));
+    // Nor is what anyone else got handed to one
+    let (head, _) = get(addr, "/echo/a", "Authorization: Bearer secret\r\n").await;
+    assert_eq!(header(&head, CACHE_HEADER), None);
+    let (head, _) = get(addr, "/echo/a", "").await;
+    assert_eq!(header(&head, CACHE_HEADER), Some("HIT"));
+}
+
+#[test]
+fn parses_routes() {
+    let routes: CacheRoutes = "/delay/:ms=250, /echo/:msg=1000".parse().unwrap();
+    assert_eq!(
+        routes.ttl(Some("/delay/:ms")),
+        Some(Duration::from_millis(250))
+    );
+    assert_eq!(routes.ttl(Some("/stats")), None);
+    assert_eq!(routes.ttl(None), None);
+    assert!("".parse::<CacheRoutes>().unwrap().is_empty());
+    assert!("/delay/:ms".parse::<CacheRoutes>().is_err());
+}
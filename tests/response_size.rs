@@ -0,0 +1,84 @@
+// MAX_RESPONSE_SIZE turns a generated JSON body that would grow past it into
+// a 500, while streamed responses keep to their own caps
+
+mod common;
+
+use common::{get, send_raw, start_server};
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::Config;
+
+fn config() -> Config {
+    Config {
+        max_response_size: 4096,
+        ..Config::default()
+    }
+}
+
+#[tokio::test]
+async fn oversized_json_gets_500() {
+    let addr = start_server(config()).await;
+
+    let (head, body) = get(addr, "/echo/abcd?repeat=2000", "").await;
+    assert!(head.starts_with("HTTP/1.1 500"), "{}", head);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(
+        body["message"],
+        "Response would exceed the maximum of 4096 bytes"
+    );
+}
+
+#[tokio::test]
+async fn responses_under_the_limit_are_untouched() {
+    let addr = start_server(config()).await;
+
+    let (head, body) = get(addr, "/echo/abcd?repeat=100", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert!(body.contains(&"abcd".repeat(100)), "{}", body);
+    let (head, _) = get(addr, "/stats", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
+
+#[tokio::test]
+async fn streamed_responses_are_exempt() {
+    let addr = start_server(Config {
+        echo_stream_threshold_bytes: 1024,
+        ..config()
+    })
+    .await;
+
+    // Above the stream threshold, so generated as it is sent
+    let (head, body) = get(addr, "/echo/abcd?repeat=2000", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert!(body.contains(&"abcd".repeat(2000)));
+
+    // MAX_ECHO_BYTES still applies to them
+    let (head, _) = get(addr, "/echo/abcd?repeat=10000000", "").await;
+    assert!(head.starts_with("HTTP/1.1 400"), "{}", head);
+}
+
+#[tokio::test]
+async fn covers_reflected_request_bodies() {
+    let addr = start_server(Config {
+        route_toggles: RouteToggles::new().with_route("/debug/echo", true),
+        ..config()
+    })
+    .await;
+
+    let body = "x".repeat(8192);
+    let request = format!(
+        "POST /debug/echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let (head, body) = send_raw(addr, request.as_bytes()).await;
+    assert!(head.starts_with("HTTP/1.1 500"), "{}", head);
+    assert!(body.contains("maximum of 4096 bytes"), "{}", body);
+}
+
+#[tokio::test]
+async fn off_by_default() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, _) = get(addr, "/echo/abcd?repeat=2000", "").await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
@@ -0,0 +1,85 @@
+// ROUTE_HEADERS adds static headers per route, `/prefix/*` or `*`, on every
+// status the route answers with
+
+mod common;
+
+use common::{get, start_server};
+use rust_http_server::route_headers::RouteHeaders;
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::Config;
+
+fn config(headers: &str) -> Config {
+    Config {
+        route_headers: headers.parse().unwrap(),
+        route_toggles: RouteToggles::new().with_route("/debug/echo", true),
+        ..Config::default()
+    }
+}
+
+// Every value sent under `name`, in order
+fn values(head: &str, name: &str) -> Vec<String> {
+    head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .filter(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn prefix_covers_its_routes_only() {
+    let addr = start_server(config("/debug/*=X-Robots-Tag: noindex")).await;
+
+    let head = get(addr, "/debug/echo", "").await.0;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    assert_eq!(values(&head, "x-robots-tag"), ["noindex"]);
+
+    let head = get(addr, "/health", "").await.0;
+    assert!(values(&head, "x-robots-tag").is_empty(), "{}", head);
+}
+
+#[tokio::test]
+async fn errors_get_the_headers_too() {
+    let addr = start_server(config(
+        "/error/:code=X-Robots-Tag: noindex; *=X-Served-By: edge-1",
+    ))
+    .await;
+
+    let head = get(addr, "/error/503", "").await.0;
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+    assert_eq!(values(&head, "x-robots-tag"), ["noindex"]);
+
+    let head = get(addr, "/no/such/route", "").await.0;
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+    assert_eq!(values(&head, "x-served-by"), ["edge-1"]);
+    assert!(values(&head, "x-robots-tag").is_empty(), "{}", head);
+}
+
+#[tokio::test]
+async fn configured_header_replaces_the_handlers() {
+    let addr = start_server(config("/health=Content-Type: text/plain")).await;
+
+    let head = get(addr, "/health", "").await.0;
+    assert_eq!(values(&head, "content-type"), ["text/plain"]);
+}
+
+#[tokio::test]
+async fn repeated_entries_send_every_value() {
+    let addr = start_server(config("/health=Link: </a>, </b>; *=Link: </c>")).await;
+
+    let head = get(addr, "/health", "").await.0;
+    assert_eq!(values(&head, "link"), ["</a>, </b>", "</c>"]);
+}
+
+#[test]
+fn malformed_entries_are_rejected() {
+    for bad in [
+        "/health",
+        "/health=X-Robots-Tag",
+        "/health=Bad Name: x",
+        "/health=Content-Length: 5",
+        "/health=Transfer-Encoding: chunked",
+    ] {
+        assert!(bad.parse::<RouteHeaders>().is_err(), "{:?}", bad);
+    }
+    assert!("".parse::<RouteHeaders>().unwrap().is_empty());
+}
@@ -0,0 +1,109 @@
+// /stats counts requests per route pattern, so parameterized paths share one
+// entry instead of growing the map with every distinct value
+
+mod common;
+
+use std::net::SocketAddr;
+
+use common::{get, start_server};
+use rust_http_server::Config;
+use serde_json::json;
+
+async fn by_route(addr: SocketAddr, target: &str) -> serde_json::Map<String, serde_json::Value> {
+    let stats: serde_json::Value = serde_json::from_str(&get(addr, target, "").await.1).unwrap();
+    stats["by_route"].as_object().unwrap().clone()
+}
+
+// Only the routes something has asked for
+async fn requested(addr: SocketAddr, target: &str) -> serde_json::Value {
+    by_route(addr, target)
+        .await
+        .into_iter()
+        .filter(|(_, count)| count != 0)
+        .collect()
+}
+
+#[tokio::test]
+async fn distinct_echo_paths_share_one_entry() {
+    let addr = start_server(Config::default()).await;
+    let before: Vec<String> = by_route(addr, "/stats").await.keys().cloned().collect();
+    for i in 0..1000 {
+        get(addr, &format!("/echo/message-{}", i), "").await;
+    }
+
+    let after = by_route(addr, "/stats").await;
+    assert_eq!(after.keys().cloned().collect::<Vec<_>>(), before);
+    assert_eq!(after["/echo/:msg"], 1000);
+    assert_eq!(after["/stats"], 2);
+}
+
+#[tokio::test]
+async fn every_route_is_listed_from_the_start() {
+    let addr = start_server(Config::default()).await;
+    let counts = by_route(addr, "/stats").await;
+
+    assert_eq!(counts["/health"], 0);
+    assert_eq!(counts["/kv/:key"], 0);
+    assert_eq!(counts["/admin/shutdown"], 0);
+    // The /stats request counts itself, like total_requests
+    assert_eq!(counts["/stats"], 1);
+}
+
+#[tokio::test]
+async fn unmatched_paths_are_left_out() {
+    let addr = start_server(Config::default()).await;
+    get(addr, "/nowhere", "").await;
+    get(addr, "/kv", "").await;
+    get(addr, "/kv/a", "").await;
+
+    assert_eq!(
+        requested(addr, "/stats").await,
+        json!({"/stats": 1, "/kv/:key": 1})
+    );
+}
+
+#[tokio::test]
+async fn keys_are_patterns_under_base_path() {
+    let addr = start_server(Config {
+        base_path: "/api".to_string(),
+        ..Config::default()
+    })
+    .await;
+    get(addr, "/api/delay/0", "").await;
+    get(addr, "/api/delay/1", "").await;
+    // Outside BASE_PATH, so no route answers it
+    get(addr, "/delay/0", "").await;
+
+    assert_eq!(
+        requested(addr, "/api/stats").await,
+        json!({"/stats": 1, "/delay/:ms": 2})
+    );
+}
+
+#[tokio::test]
+async fn routes_in_prometheus_and_csv() {
+    let addr = start_server(Config::default()).await;
+    get(addr, "/echo/a", "").await;
+    get(addr, "/echo/b", "").await;
+
+    let text = get(addr, "/stats?format=prometheus", "").await.1;
+    assert!(
+        text.contains("# TYPE rust_http_server_by_route counter\n"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("\nrust_http_server_by_route{route=\"/echo/:msg\"} 2\n"),
+        "{}",
+        text
+    );
+
+    let csv = get(addr, "/stats?format=csv", "").await.1;
+    let mut rows = csv.lines().map(|row| row.split(',').collect::<Vec<_>>());
+    let (header_row, values) = (rows.next().unwrap(), rows.next().unwrap());
+    let column = header_row
+        .iter()
+        .position(|&name| name == "by_route./echo/:msg")
+        .expect("no /echo/:msg column");
+    assert_eq!(values[column], "2");
+}
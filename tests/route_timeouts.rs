@@ -0,0 +1,98 @@
+// ROUTE_TIMEOUTS overrides REQUEST_TIMEOUT_MS for individual routes
+
+mod common;
+
+use std::time::Duration;
+
+use common::{get, send_raw, start_server};
+use rust_http_server::route_timeout::RouteTimeouts;
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::Config;
+
+fn config(global_ms: u64, overrides: &str) -> Config {
+    Config {
+        request_timeout_ms: global_ms,
+        route_timeouts: overrides.parse().unwrap(),
+        ..Config::default()
+    }
+}
+
+#[test]
+fn parses_milliseconds_and_off() {
+    let timeouts: RouteTimeouts = " /stats=250, /delay/:ms = off ,/ws=0,".parse().unwrap();
+    let global = Some(Duration::from_secs(30));
+    assert_eq!(
+        timeouts.timeout(Some("/stats"), global),
+        Some(Duration::from_millis(250))
+    );
+    assert_eq!(timeouts.timeout(Some("/delay/:ms"), global), None);
+    assert_eq!(timeouts.timeout(Some("/ws"), global), None);
+    assert_eq!(timeouts.timeout(Some("/health"), global), global);
+    assert_eq!(timeouts.timeout(None, global), global);
+
+    assert!("/stats".parse::<RouteTimeouts>().is_err());
+    assert!("/stats=fast".parse::<RouteTimeouts>().is_err());
+    assert!("/stats=-1".parse::<RouteTimeouts>().is_err());
+}
+
+#[tokio::test]
+async fn route_override_is_shorter_than_the_global_timeout() {
+    let addr = start_server(config(5_000, "/delay/:ms=100")).await;
+
+    let head = get(addr, "/delay/500", "").await.0;
+    assert!(
+        head.starts_with("HTTP/1.1 504"),
+        "unexpected head: {}",
+        head
+    );
+    // Other routes keep the global timeout
+    let head = get(addr, "/health", "").await.0;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+}
+
+#[tokio::test]
+async fn route_override_is_longer_than_the_global_timeout() {
+    // Debug routes are off by default in release builds
+    let addr = start_server(Config {
+        route_toggles: RouteToggles::new().with_route("/debug/block/:ms", true),
+        ..config(100, "/delay/:ms=2000")
+    })
+    .await;
+
+    let head = get(addr, "/delay/300", "").await.0;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+    let head = get(addr, "/debug/block/300", "").await.0;
+    assert!(head.starts_with("HTTP/1.1 504"), "{}", head);
+}
+
+#[tokio::test]
+async fn routes_can_opt_out() {
+    let addr = start_server(config(100, "/delay/:ms=off")).await;
+
+    let head = get(addr, "/delay/300", "").await.0;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected head: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn caller_deadlines_still_apply_when_opted_out() {
+    let addr = start_server(config(0, "/delay/:ms=off")).await;
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /delay/500 HTTP/1.1\r\nHost: localhost\r\ngrpc-timeout: 100m\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 504"),
+        "unexpected head: {}",
+        head
+    );
+}
@@ -0,0 +1,150 @@
+// ROUTE_TOGGLES switches individual routes off (or on), and a disabled
+// route is indistinguishable from a missing one
+
+mod common;
+
+use std::collections::HashMap;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::route_toggle::{enabled_by_default, RouteToggles};
+use rust_http_server::{Config, ConfigError};
+
+fn toggles(spec: &str) -> Config {
+    Config {
+        route_toggles: spec.parse().unwrap(),
+        ..Config::default()
+    }
+}
+
+async fn request(config: Config, request: &str) -> (String, String) {
+    let addr = start_server(config).await;
+    send_raw(addr, request.as_bytes()).await
+}
+
+#[tokio::test]
+async fn disabled_route_is_not_found() {
+    let (head, body) = request(
+        toggles("/kv/:key=off"),
+        "GET /kv/a HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected status: {}",
+        head
+    );
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(
+        !body["suggestions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|route| route == "/kv/:key"),
+        "disabled route suggested: {}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn disabled_route_has_no_methods() {
+    // Neither 405 nor an Allow list gives the route away
+    let (head, _) = request(
+        toggles("/kv/:key=off"),
+        "POST /kv/a HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected status: {}",
+        head
+    );
+
+    let (head, _) = request(
+        toggles("/kv/:key=off"),
+        "OPTIONS /kv/a HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(header(&head, "allow"), None);
+}
+
+#[tokio::test]
+async fn other_routes_sharing_a_prefix_stay_up() {
+    // `/echo/:msg` off leaves `/echo/stream/:msg` and `POST /echo` alone
+    let config = toggles("/echo/:msg=off");
+    let (head, _) = request(
+        config.clone(),
+        "GET /echo/hi HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected status: {}",
+        head
+    );
+
+    let (head, _) = request(
+        config.clone(),
+        "GET /echo/stream/hi?count=1 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+
+    let (head, _) = request(
+        config,
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\nConnection: close\r\n\r\nhi",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn debug_routes_can_be_switched_on() {
+    let (head, _) = request(
+        toggles("/debug/echo=on"),
+        "GET /debug/echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+}
+
+#[test]
+fn debug_routes_default_to_the_build_profile() {
+    assert_eq!(enabled_by_default("/debug/echo"), cfg!(debug_assertions));
+    assert!(enabled_by_default("/health"));
+    assert!(RouteToggles::new().enabled("/kv/:key"));
+}
+
+#[test]
+fn parses_toggles() {
+    let parsed: RouteToggles = " /health=off, /debug/echo = yes ,".parse().unwrap();
+    assert!(!parsed.enabled("/health"));
+    assert!(parsed.enabled("/debug/echo"));
+
+    assert!("/health".parse::<RouteToggles>().is_err());
+    assert!("/health=maybe".parse::<RouteToggles>().is_err());
+}
+
+#[test]
+fn unknown_route_is_a_config_error() {
+    let vars = HashMap::from([("ROUTE_TOGGLES", "/helth=off")]);
+    let err = Config::from_lookup(|key| vars.get(key).map(|value| value.to_string())).unwrap_err();
+    assert!(matches!(err, ConfigError::Invalid { .. }), "{:?}", err);
+    assert_eq!(err.key(), "ROUTE_TOGGLES");
+}
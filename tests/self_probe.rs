@@ -0,0 +1,111 @@
+// The startup self-probe: a GET /health to the server's own listening
+// address, failing with a clear reason when no 200 comes back
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::start_server;
+use rust_http_server::route_toggle::RouteToggles;
+use rust_http_server::self_probe::{probe, target};
+use rust_http_server::Config;
+use tokio::net::TcpListener;
+
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+#[test]
+fn wildcard_addresses_are_probed_over_loopback() {
+    let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+    assert_eq!(target(addr("0.0.0.0:8080")), addr("127.0.0.1:8080"));
+    assert_eq!(target(addr("[::]:8080")), addr("[::1]:8080"));
+    assert_eq!(target(addr("192.0.2.7:8080")), addr("192.0.2.7:8080"));
+}
+
+#[tokio::test]
+async fn passes_against_a_running_server() {
+    let addr = start_server(Config::default()).await;
+    assert_eq!(probe(addr, "", None, TIMEOUT).await, Ok(()));
+}
+
+#[tokio::test]
+async fn respects_the_base_path() {
+    let addr = start_server(Config {
+        base_path: "/api".to_string(),
+        ..Config::default()
+    })
+    .await;
+    assert_eq!(probe(addr, "/api", None, TIMEOUT).await, Ok(()));
+    let err = probe(addr, "", None, TIMEOUT).await.unwrap_err();
+    assert!(err.contains("404"), "{}", err);
+}
+
+#[tokio::test]
+async fn fails_when_health_is_not_ok() {
+    let addr = start_server(Config {
+        route_toggles: RouteToggles::new().with_route("/health", false),
+        ..Config::default()
+    })
+    .await;
+    let err = probe(addr, "", None, TIMEOUT).await.unwrap_err();
+    assert!(err.contains("/health answered 404"), "{}", err);
+}
+
+#[tokio::test]
+async fn fails_when_nothing_is_listening() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    drop(listener);
+
+    let err = probe(addr, "", None, TIMEOUT).await.unwrap_err();
+    assert!(err.contains(&addr.to_string()), "{}", err);
+}
+
+#[tokio::test]
+async fn fails_when_the_answer_takes_too_long() {
+    // Accepts connections but never speaks HTTP
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut held = Vec::new();
+        while let Ok((stream, _)) = listener.accept().await {
+            held.push(stream);
+        }
+    });
+
+    let err = probe(addr, "", None, Duration::from_millis(100))
+        .await
+        .unwrap_err();
+    assert!(err.contains("didn't answer within 100ms"), "{}", err);
+}
+
+#[tokio::test]
+async fn names_a_virtual_host_that_serves_health() {
+    let config = Config {
+        virtual_hosts: "api.example.com=api, admin.example.com=admin"
+            .parse()
+            .unwrap(),
+        startup_probe: true,
+        ..Config::default()
+    };
+    config.validate().unwrap();
+    let host = config.probe_host();
+    assert_eq!(host.as_deref(), Some("admin.example.com"));
+
+    let addr = start_server(config).await;
+    assert_eq!(probe(addr, "", host.as_deref(), TIMEOUT).await, Ok(()));
+    // The listening address is on no site
+    let err = probe(addr, "", None, TIMEOUT).await.unwrap_err();
+    assert!(err.contains("404"), "{}", err);
+}
+
+#[test]
+fn default_host_is_preferred_when_it_serves_health() {
+    let config = Config {
+        virtual_hosts: "a.example.com=admin, b.example.com=all".parse().unwrap(),
+        default_host: Some("b.example.com".to_string()),
+        ..Config::default()
+    };
+    assert_eq!(config.probe_host().as_deref(), Some("b.example.com"));
+    assert_eq!(Config::default().probe_host(), None);
+}
@@ -0,0 +1,170 @@
+// Graceful shutdown: the listening socket is released straight away while
+// requests that are already in flight still run to completion, and requests
+// that only arrive afterwards get a 503.
+
+use std::time::Duration;
+
+use http_body_util::Full;
+use hyper::body::Bytes;
+use hyper::{Request, StatusCode};
+use rust_http_server::{bind, handle_request, serve, AppState, Config};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::oneshot;
+
+#[tokio::test]
+async fn shutdown_closes_listener_and_drains_in_flight_requests() {
+    let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server = tokio::spawn(serve(listener, AppState::new(Config::default()), async {
+        let _ = shutdown_rx.await;
+    }));
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /delay/300 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    shutdown_tx.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(
+        TcpStream::connect(addr).await.is_err(),
+        "listener still accepting after shutdown"
+    );
+
+    let mut response = String::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .expect("in-flight request was not drained")
+        .unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 200"),
+        "unexpected response: {}",
+        response
+    );
+    assert!(
+        response.contains("Delayed 300ms"),
+        "unexpected response: {}",
+        response
+    );
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not finish shutting down")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn requests_arriving_during_shutdown_get_503() {
+    let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server = tokio::spawn(serve(listener, AppState::new(Config::default()), async {
+        let _ = shutdown_rx.await;
+    }));
+
+    // Accepted before the signal, but the request isn't complete until after
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(b"GET /health HTTP/1.1\r\n").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    shutdown_tx.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream.write_all(b"Host: localhost\r\n\r\n").await.unwrap();
+
+    let mut response = String::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .expect("connection was not closed after the 503")
+        .unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 503"),
+        "unexpected response: {}",
+        response
+    );
+    let head = response.to_ascii_lowercase();
+    assert!(head.contains("\r\nconnection: close\r\n"), "{}", response);
+    assert!(head.contains("\r\nretry-after: 1\r\n"), "{}", response);
+    assert!(response.contains("Shutting down"), "{}", response);
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not finish shutting down")
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn draining_before_shutdown_still_serves() {
+    let state = AppState::new(Config::default());
+    state.begin_drain_then_shutdown(Duration::from_secs(60));
+
+    let response = handle_request(
+        Request::get("/health")
+            .header("Host", "localhost")
+            .body(Full::new(Bytes::new()))
+            .unwrap(),
+        state,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn bind_allows_immediate_rebind_after_close() {
+    let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Leave a connection in TIME_WAIT on the server side
+    let client = TcpStream::connect(addr).await.unwrap();
+    let (server_side, _) = listener.accept().await.unwrap();
+    drop(server_side);
+    drop(client);
+    drop(listener);
+
+    bind(addr).expect("rebinding the same address failed");
+}
+
+#[tokio::test]
+async fn request_cap_triggers_graceful_shutdown() {
+    let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = Config {
+        max_requests: 3,
+        ..Config::default()
+    };
+    let server = tokio::spawn(serve(
+        listener,
+        AppState::new(config),
+        std::future::pending(),
+    ));
+
+    for _ in 0..3 {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(
+            response.starts_with("HTTP/1.1 200"),
+            "unexpected response: {}",
+            response
+        );
+    }
+
+    // The request that hit the cap is answered, then the server stops by itself
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("server did not shut down after reaching the request cap")
+        .unwrap()
+        .unwrap();
+    assert!(TcpStream::connect(addr).await.is_err());
+}
@@ -0,0 +1,79 @@
+// SLOW_START_MS ramps the accept rate up after startup, answering
+// connections over it with 503 and closing them
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{header, send_raw, start_server};
+use rust_http_server::slow_start::SlowStart;
+use rust_http_server::Config;
+
+const HEALTH: &[u8] = b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+#[test]
+fn rate_climbs_to_full_then_stops_applying() {
+    let start = Instant::now();
+    let ramp = SlowStart::new(start, Duration::from_secs(10), 100.0);
+
+    assert_eq!(ramp.rate(start), Some(10.0));
+    let halfway = ramp.rate(start + Duration::from_secs(5)).unwrap();
+    assert!((halfway - 55.0).abs() < 1e-9, "{}", halfway);
+    assert_eq!(ramp.rate(start + Duration::from_secs(10)), None);
+}
+
+#[test]
+fn admits_at_the_current_rate() {
+    let start = Instant::now();
+    let mut ramp = SlowStart::new(start, Duration::from_secs(60), 10.0);
+
+    // One a second at first, and no burst saved up from before the first
+    assert!(ramp.admit(start));
+    assert!(!ramp.admit(start));
+    assert!(!ramp.admit(start + Duration::from_millis(500)));
+    assert!(ramp.admit(start + Duration::from_millis(1100)));
+
+    // Everything is admitted once the window is over
+    let after = start + Duration::from_secs(60);
+    assert!((0..100).all(|_| ramp.admit(after)));
+}
+
+#[tokio::test]
+async fn excess_connections_get_503_during_the_ramp() {
+    let addr = start_server(Config {
+        slow_start_ms: 60_000,
+        slow_start_accept_per_sec: 1.0,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = send_raw(addr, HEALTH).await;
+    assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+
+    let (head, body) = send_raw(addr, HEALTH).await;
+    assert!(head.starts_with("HTTP/1.1 503"), "{}", head);
+    assert_eq!(header(&head, "retry-after"), Some("1"));
+    assert_eq!(header(&head, "connection"), Some("close"));
+    assert!(body.contains("Starting up"), "{}", body);
+}
+
+#[tokio::test]
+async fn off_by_default_and_over_after_the_window() {
+    let addr = start_server(Config::default()).await;
+    for _ in 0..20 {
+        let (head, _) = send_raw(addr, HEALTH).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    }
+
+    let addr = start_server(Config {
+        slow_start_ms: 100,
+        slow_start_accept_per_sec: 1.0,
+        ..Config::default()
+    })
+    .await;
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    for _ in 0..20 {
+        let (head, _) = send_raw(addr, HEALTH).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+    }
+}
@@ -0,0 +1,248 @@
+// Request smuggling vectors, sent as raw bytes, must never let a second
+// request hide inside the first.
+//
+// Every vector carries the same smuggled request, a `PUT /kv/smuggled`.
+// Each test checks that exactly one response comes back, that the
+// connection is closed afterwards, and that the key was never written —
+// so the smuggled request wasn't run, whatever the server decided about
+// the outer one.
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::{send_raw, start_server};
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const SMUGGLED: &str =
+    "PUT /kv/smuggled HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\n\r\nyes";
+
+// Sends `request` and returns everything the server wrote before closing
+async fn attack(addr: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut response))
+        .await
+        .expect("connection left open after a smuggling attempt")
+        .unwrap();
+    String::from_utf8_lossy(&response).into_owned()
+}
+
+// Sends `request`, expecting a single response with `status`
+async fn assert_single_response(request: &str, status: u16) {
+    let addr = start_server(Config::default()).await;
+    let response = attack(addr, request).await;
+    assert!(
+        response.starts_with(&format!("HTTP/1.1 {}", status)),
+        "unexpected response: {}",
+        response
+    );
+    assert_eq!(
+        response.matches("HTTP/1.1 ").count(),
+        1,
+        "more than one response: {}",
+        response
+    );
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /kv/smuggled HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "smuggled request was executed: {}",
+        head
+    );
+}
+
+async fn assert_rejected(request: &str) {
+    assert_single_response(request, 400).await;
+}
+
+// CL.TE: a front end going by Content-Length forwards everything, while a
+// back end going by Transfer-Encoding would stop at the empty chunk and
+// read the smuggled request as the next one. Both headers together are
+// refused outright.
+#[tokio::test]
+async fn cl_te() {
+    let body = format!("0\r\n\r\n{}", SMUGGLED);
+    assert_rejected(&format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: keep-alive\r\nContent-Length: {}\r\nTransfer-Encoding: chunked\r\n\r\n{}",
+        body.len(),
+        body
+    ))
+    .await;
+}
+
+// CL.TE with the coding in capitals, so a server matching `chunked` case-
+// sensitively wouldn't notice the conflict
+#[tokio::test]
+async fn cl_te_uppercase_chunked() {
+    let body = format!("0\r\n\r\n{}", SMUGGLED);
+    assert_rejected(&format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nTransfer-Encoding: CHUNKED\r\n\r\n{}",
+        body.len(),
+        body
+    ))
+    .await;
+}
+
+// TE.CL: the reverse, with the smuggled request inside the chunk and a
+// Content-Length that ends the body early. hyper discards a
+// Content-Length that follows Transfer-Encoding before the server sees
+// it, so this one isn't refused, but the body is framed by chunked alone:
+// the smuggled request stays inside it and is echoed back, never run.
+#[tokio::test]
+async fn te_cl() {
+    let addr = start_server(Config::default()).await;
+    let response = attack(
+        addr,
+        &format!(
+            "POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nTransfer-Encoding: chunked\r\nContent-Length: 4\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n",
+            SMUGGLED.len(),
+            SMUGGLED
+        ),
+    )
+    .await;
+    assert!(
+        response.starts_with("HTTP/1.1 200"),
+        "unexpected response: {}",
+        response
+    );
+    assert!(response.contains("PUT /kv/smuggled"), "{}", response);
+    assert_eq!(response.matches("HTTP/1.1 ").count(), 1, "{}", response);
+
+    let (head, _) = send_raw(
+        addr,
+        b"GET /kv/smuggled HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+}
+
+// CL.CL: two different lengths, each a different place for the body to end
+#[tokio::test]
+async fn conflicting_content_lengths() {
+    assert_rejected(&format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nContent-Length: {}\r\n\r\n{}",
+        SMUGGLED.len(),
+        SMUGGLED
+    ))
+    .await;
+    assert_rejected(&format!(
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0, {}\r\n\r\n{}",
+        SMUGGLED.len(),
+        SMUGGLED
+    ))
+    .await;
+}
+
+// Lengths some parsers read as a number and others refuse: a sign, hex,
+// and trailing garbage
+#[tokio::test]
+async fn malformed_content_lengths() {
+    for length in ["+0", "0x0", "0 abc", "-1"] {
+        assert_rejected(&format!(
+            "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            length, SMUGGLED
+        ))
+        .await;
+    }
+}
+
+// Obfuscated Transfer-Encoding: values a lenient parser might take for
+// chunked, or a strict one ignore, leaving the two disagreeing on framing
+#[tokio::test]
+async fn obfuscated_transfer_encoding_values() {
+    for value in [
+        "xchunked",
+        "chunked-false",
+        "\"chunked\"",
+        "chunked, identity",
+        "chunked;q=1",
+        "identity",
+    ] {
+        assert_rejected(&format!(
+            "POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: {}\r\n\r\n0\r\n\r\n{}",
+            value, SMUGGLED
+        ))
+        .await;
+    }
+}
+
+// Obfuscated Transfer-Encoding headers: the name or value hidden behind
+// whitespace, line folding, a NUL or an underscore, so only some parsers
+// see chunked
+#[tokio::test]
+async fn obfuscated_transfer_encoding_headers() {
+    for header in [
+        "Transfer-Encoding : chunked",
+        " Transfer-Encoding: chunked",
+        "Transfer-Encoding:\r\n chunked",
+        "Transfer-Encoding: chunked\0",
+        "Transfer_Encoding: chunked",
+    ] {
+        assert_rejected(&format!(
+            "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n{}\r\n\r\n0\r\n\r\n{}",
+            header, SMUGGLED
+        ))
+        .await;
+    }
+}
+
+// Chunked on both header lines, or chunked then something else: the
+// combined list is what counts
+#[tokio::test]
+async fn transfer_encoding_split_across_lines() {
+    for second in ["chunked", "identity"] {
+        assert_rejected(&format!(
+            "POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: {}\r\n\r\n0\r\n\r\n{}",
+            second, SMUGGLED
+        ))
+        .await;
+    }
+}
+
+// A coding the server can't undo ahead of chunked is framed correctly but
+// not understood, so it gets 501 rather than 400; the connection is closed
+// all the same
+#[tokio::test]
+async fn unsupported_coding_before_chunked() {
+    assert_single_response(
+        &format!(
+            "POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n{}",
+            SMUGGLED
+        ),
+        501,
+    )
+    .await;
+}
+
+// HTTP/1.0 has no chunked coding, so an HTTP/1.0 hop would frame this body
+// by connection close
+#[tokio::test]
+async fn transfer_encoding_on_http_1_0() {
+    assert_rejected(&format!(
+        "POST /echo HTTP/1.0\r\nHost: localhost\r\nConnection: keep-alive\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n{}",
+        SMUGGLED
+    ))
+    .await;
+}
+
+// Chunk framing itself: a size that overflows, and a size line ended by a
+// bare LF, which parsers disagree on
+#[tokio::test]
+async fn malformed_chunks() {
+    for chunk in ["FFFFFFFFFFFFFFFFF1\r\nx\r\n", "5\nhello\r\n"] {
+        assert_rejected(&format!(
+            "POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n{}0\r\n\r\n{}",
+            chunk, SMUGGLED
+        ))
+        .await;
+    }
+}
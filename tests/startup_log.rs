@@ -0,0 +1,38 @@
+// The line the binary prints once it is listening names the bound address,
+// so `LISTEN_ADDRS=127.0.0.1:0` reports the port the kernel picked
+
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::TcpStream;
+
+#[tokio::test]
+async fn quiet_line_reports_the_bound_port() {
+    let mut child = tokio::process::Command::from({
+        let mut command = Command::new(env!("CARGO_BIN_EXE_rust-http-server"));
+        command
+            .env("LISTEN_ADDRS", "127.0.0.1:0")
+            .env("QUIET", "1")
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        command
+    })
+    .kill_on_drop(true)
+    .spawn()
+    .unwrap();
+
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let line = tokio::time::timeout(Duration::from_secs(5), lines.next_line())
+        .await
+        .expect("nothing logged")
+        .unwrap()
+        .expect("stdout closed");
+    let addr = line
+        .strip_prefix("level=info msg=\"listening\" addr=http://")
+        .unwrap_or_else(|| panic!("unexpected line: {}", line));
+    assert!(!addr.ends_with(":0"), "{}", line);
+    TcpStream::connect(addr).await.unwrap();
+
+    child.kill().await.unwrap();
+}
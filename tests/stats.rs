@@ -0,0 +1,82 @@
+// /stats values stay finite and serializable, and count the request that
+// asked for them
+
+mod common;
+
+use common::{send_raw, start_server};
+use rust_http_server::{format_duration, Config};
+
+#[tokio::test]
+async fn rates_are_finite_with_zero_uptime() {
+    let addr = start_server(Config::default()).await;
+    let (head, body) = send_raw(
+        addr,
+        b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["uptime_seconds"], 0);
+    assert_eq!(stats["uptime_human"], "0s");
+
+    let rps = stats["requests_per_second"]
+        .as_f64()
+        .expect("requests_per_second is not a number");
+    assert!(rps.is_finite());
+    assert_eq!(rps, 0.0);
+
+    let ratio = stats["compression_ratio"]
+        .as_f64()
+        .expect("compression_ratio is not a number");
+    assert!(ratio.is_finite());
+}
+
+async fn total_requests(addr: std::net::SocketAddr) -> u64 {
+    let (_, body) = send_raw(
+        addr,
+        b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    stats["total_requests"].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn counts_its_own_request() {
+    let addr = start_server(Config::default()).await;
+    assert_eq!(total_requests(addr).await, 1);
+    assert_eq!(total_requests(addr).await, 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_requests_each_see_their_own_count() {
+    let addr = start_server(Config::default()).await;
+    let tasks: Vec<_> = (0..32)
+        .map(|_| tokio::spawn(total_requests(addr)))
+        .collect();
+    let mut totals = Vec::new();
+    for task in tasks {
+        totals.push(task.await.unwrap());
+    }
+    // Each includes itself and every request counted before it, and no
+    // others, so no two requests report the same total
+    totals.sort_unstable();
+    assert_eq!(totals, (1..=32).collect::<Vec<u64>>());
+}
+
+#[test]
+fn uptime_is_formatted_from_the_largest_unit() {
+    assert_eq!(format_duration(0), "0s");
+    assert_eq!(format_duration(59), "59s");
+    assert_eq!(format_duration(365), "6m 5s");
+    assert_eq!(format_duration(3_600), "1h 0m 0s");
+    assert_eq!(
+        format_duration(2 * 86_400 + 3 * 3_600 + 14 * 60 + 5),
+        "2d 3h 14m 5s"
+    );
+}
@@ -0,0 +1,51 @@
+// /stats.csv mirrors the fields of /stats
+
+mod common;
+
+use common::{header, send_raw, start_server, stats_columns};
+use rust_http_server::Config;
+
+const STATS: &[u8] = b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+const STATS_CSV: &[u8] = b"GET /stats.csv HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+
+#[tokio::test]
+async fn csv_has_attachment_headers() {
+    let addr = start_server(Config::default()).await;
+    let (head, _) = send_raw(addr, STATS_CSV).await;
+
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(
+        header(&head, "content-type"),
+        Some("text/csv; charset=utf-8")
+    );
+    assert_eq!(
+        header(&head, "content-disposition"),
+        Some("attachment; filename=\"stats.csv\"")
+    );
+}
+
+#[tokio::test]
+async fn csv_columns_match_json_fields() {
+    let addr = start_server(Config::default()).await;
+
+    let (_, json) = send_raw(addr, STATS).await;
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let json_fields = stats_columns(&json);
+
+    let (_, csv) = send_raw(addr, STATS_CSV).await;
+    let mut lines = csv.lines();
+    let columns: Vec<&str> = lines.next().unwrap().split(',').collect();
+    let values: Vec<&str> = lines.next().unwrap().split(',').collect();
+
+    assert_eq!(columns, json_fields);
+    assert_eq!(values.len(), columns.len());
+    assert_eq!(lines.next(), None);
+
+    // Both requests so far were counted
+    let total = columns.iter().position(|&c| c == "total_requests").unwrap();
+    assert_eq!(values[total], "2");
+}
@@ -0,0 +1,108 @@
+// /stats picks JSON, Prometheus text or CSV from Accept or ?format=
+
+mod common;
+
+use common::{header, send_raw, start_server, stats_columns};
+use rust_http_server::Config;
+
+async fn get_stats(target: &str, accept: Option<&str>) -> (String, String) {
+    let addr = start_server(Config::default()).await;
+    let accept = accept
+        .map(|accept| format!("Accept: {}\r\n", accept))
+        .unwrap_or_default();
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\n{}Connection: close\r\n\r\n",
+        target, accept
+    );
+    send_raw(addr, request.as_bytes()).await
+}
+
+#[tokio::test]
+async fn defaults_to_json() {
+    let (head, body) = get_stats("/stats", None).await;
+    assert_eq!(header(&head, "content-type"), Some("application/json"));
+    assert_eq!(header(&head, "vary"), Some("Accept"));
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(stats["total_requests"], 1);
+}
+
+#[tokio::test]
+async fn accept_selects_prometheus() {
+    let (head, body) = get_stats("/stats", Some("text/plain")).await;
+    assert_eq!(
+        header(&head, "content-type"),
+        Some("text/plain; version=0.0.4; charset=utf-8")
+    );
+    assert!(body.contains("# TYPE rust_http_server_total_requests counter\n"));
+    assert!(body.contains("\nrust_http_server_total_requests 1\n"));
+    assert!(body.contains("# TYPE rust_http_server_uptime_seconds gauge\n"));
+}
+
+#[tokio::test]
+async fn accept_selects_csv_by_quality() {
+    let (head, body) = get_stats("/stats", Some("application/json;q=0.5, text/csv")).await;
+    assert_eq!(
+        header(&head, "content-type"),
+        Some("text/csv; charset=utf-8")
+    );
+    assert!(
+        body.starts_with("total_requests,"),
+        "unexpected body: {}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn format_query_overrides_accept() {
+    let (head, body) = get_stats("/stats?format=prometheus", Some("application/json")).await;
+    assert!(header(&head, "content-type")
+        .unwrap()
+        .starts_with("text/plain"));
+    assert!(body.contains("rust_http_server_total_requests 1"));
+}
+
+#[tokio::test]
+async fn unknown_format_is_rejected() {
+    let (head, _) = get_stats("/stats?format=xml", None).await;
+    assert!(
+        head.starts_with("HTTP/1.1 400"),
+        "unexpected status: {}",
+        head
+    );
+}
+
+#[tokio::test]
+async fn every_format_reports_the_same_fields() {
+    let (_, json) = get_stats("/stats", None).await;
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let fields = stats_columns(&json);
+
+    let (_, csv) = get_stats("/stats?format=csv", None).await;
+    let columns: Vec<&str> = csv.lines().next().unwrap().split(',').collect();
+    assert_eq!(columns, fields);
+
+    // Prometheus only has numbers to offer, with a labelled sample per
+    // breakdown key
+    let numeric: Vec<&str> = json
+        .as_object()
+        .unwrap()
+        .iter()
+        .flat_map(|(key, value)| match value.as_object() {
+            Some(breakdown) => vec![key.as_str(); breakdown.len()],
+            None if value.is_number() => vec![key.as_str()],
+            None => vec![],
+        })
+        .collect();
+    let (_, prometheus) = get_stats("/stats?format=prometheus", None).await;
+    let samples: Vec<&str> = prometheus
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| {
+            line.split([' ', '{'])
+                .next()
+                .unwrap()
+                .trim_start_matches("rust_http_server_")
+        })
+        .collect();
+    assert_eq!(samples, numeric);
+}
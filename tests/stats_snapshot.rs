@@ -0,0 +1,141 @@
+// STATS_SNAPSHOT_PATH carries lifetime counters across restarts
+
+mod common;
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use common::send_raw;
+use rust_http_server::snapshot::StatsSnapshot;
+use rust_http_server::{bind, serve, AppState, Config};
+use tokio::sync::oneshot;
+
+fn snapshot_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "rust-http-server-{}-{}.json",
+        name,
+        std::process::id()
+    ))
+}
+
+fn config(path: &Path) -> Config {
+    Config {
+        stats_snapshot_path: Some(path.to_path_buf()),
+        ..Config::default()
+    }
+}
+
+async fn stats(addr: SocketAddr) -> serde_json::Value {
+    let (_, body) = send_raw(
+        addr,
+        b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    serde_json::from_str(&body).unwrap()
+}
+
+// Runs one server lifetime: `requests` requests to /health, then a
+// graceful shutdown. Returns the stats from just before shutting down.
+async fn run_once(config: Config, requests: usize) -> serde_json::Value {
+    let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(serve(listener, AppState::new(config), async {
+        let _ = stopped.await;
+    }));
+
+    for _ in 0..requests {
+        send_raw(
+            addr,
+            b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+    }
+    let stats = stats(addr).await;
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+    stats
+}
+
+#[tokio::test]
+async fn counters_survive_a_restart() {
+    let path = snapshot_path("restart");
+    let _ = std::fs::remove_file(&path);
+
+    let first = run_once(config(&path), 2).await;
+    assert_eq!(first["total_requests"], 3);
+
+    let saved = StatsSnapshot::load(&path).unwrap();
+    assert_eq!(saved.total_requests, 3);
+    assert_eq!(saved.accepted_connections, 3);
+
+    let second = run_once(config(&path), 1).await;
+    assert_eq!(second["total_requests"], 5);
+    assert_eq!(second["accepted_connections"], 5);
+    assert_eq!(StatsSnapshot::load(&path).unwrap().total_requests, 5);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn request_cap_counts_only_this_process() {
+    let path = snapshot_path("cap");
+    StatsSnapshot {
+        total_requests: 100,
+        ..StatsSnapshot::default()
+    }
+    .save(&path)
+    .unwrap();
+
+    let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(serve(
+        listener,
+        AppState::new(Config {
+            max_requests: 2,
+            ..config(&path)
+        }),
+        std::future::pending(),
+    ));
+    assert_eq!(stats(addr).await["total_requests"], 101);
+    send_raw(
+        addr,
+        b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+
+    // The second request of this process hit the cap
+    tokio::time::timeout(std::time::Duration::from_secs(5), server)
+        .await
+        .expect("server didn't stop at max_requests")
+        .unwrap()
+        .unwrap();
+    assert_eq!(StatsSnapshot::load(&path).unwrap().total_requests, 102);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn missing_or_corrupt_snapshot_starts_from_zero() {
+    let missing = snapshot_path("missing");
+    let _ = std::fs::remove_file(&missing);
+    let state = AppState::new(config(&missing));
+    assert_eq!(state.stats.snapshot(), StatsSnapshot::default());
+
+    let corrupt = snapshot_path("corrupt");
+    std::fs::write(&corrupt, "{not json").unwrap();
+    let state = AppState::new(config(&corrupt));
+    assert_eq!(state.stats.snapshot(), StatsSnapshot::default());
+    std::fs::remove_file(&corrupt).unwrap();
+}
+
+#[test]
+fn older_snapshots_without_every_field_load() {
+    let path = snapshot_path("partial");
+    std::fs::write(&path, r#"{"total_requests": 7}"#).unwrap();
+    let snapshot = StatsSnapshot::load(&path).unwrap();
+    assert_eq!(snapshot.total_requests, 7);
+    assert_eq!(snapshot.accepted_connections, 0);
+    std::fs::remove_file(&path).unwrap();
+}
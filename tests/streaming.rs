@@ -0,0 +1,88 @@
+// Streaming responses from /echo/stream/:msg, including the X-Checksum trailer
+
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{decode_chunked, header, send_raw, start_server};
+use rust_http_server::compression::crc32;
+use rust_http_server::Config;
+
+fn get(path: &str, te_trailers: bool) -> Vec<u8> {
+    let te = if te_trailers { "TE: trailers\r\n" } else { "" };
+    format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}\r\n",
+        path, te
+    )
+    .into_bytes()
+}
+
+#[tokio::test]
+async fn streams_chunks_with_checksum_trailer() {
+    let addr = start_server(Config::default()).await;
+    let (head, body) = send_raw(addr, &get("/echo/stream/hi?count=3", true)).await;
+
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(header(&head, "transfer-encoding"), Some("chunked"));
+    assert_eq!(header(&head, "trailer"), Some("x-checksum"));
+
+    let (data, trailers) = decode_chunked(&body);
+    assert_eq!(data, "hi\nhi\nhi\n");
+    assert_eq!(
+        trailers,
+        vec![format!("x-checksum: {:08x}", crc32(b"hi\nhi\nhi\n"))]
+    );
+}
+
+#[tokio::test]
+async fn trailer_is_omitted_unless_client_accepts_trailers() {
+    let addr = start_server(Config::default()).await;
+    let (_, body) = send_raw(addr, &get("/echo/stream/hi?count=2", false)).await;
+
+    let (data, trailers) = decode_chunked(&body);
+    assert_eq!(data, "hi\nhi\n");
+    assert!(trailers.is_empty(), "unexpected trailers: {:?}", trailers);
+}
+
+#[tokio::test]
+async fn interval_spaces_out_chunks() {
+    let addr = start_server(Config::default()).await;
+    let started = Instant::now();
+    let (_, body) = send_raw(addr, &get("/echo/stream/x?count=4&interval_ms=50", true)).await;
+
+    assert!(started.elapsed() >= Duration::from_millis(150));
+    assert_eq!(decode_chunked(&body).0, "x\nx\nx\nx\n");
+}
+
+#[tokio::test]
+async fn count_and_interval_are_capped() {
+    let addr = start_server(Config {
+        max_stream_chunks: 5,
+        max_delay_ms: 100,
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = send_raw(addr, &get("/echo/stream/x?count=6", true)).await;
+    assert!(
+        head.starts_with("HTTP/1.1 400"),
+        "unexpected status: {}",
+        head
+    );
+    let (head, _) = send_raw(addr, &get("/echo/stream/x?interval_ms=101", true)).await;
+    assert!(
+        head.starts_with("HTTP/1.1 400"),
+        "unexpected status: {}",
+        head
+    );
+    let (head, _) = send_raw(addr, &get("/echo/stream/x?count=abc", true)).await;
+    assert!(
+        head.starts_with("HTTP/1.1 400"),
+        "unexpected status: {}",
+        head
+    );
+}
@@ -0,0 +1,83 @@
+// "Did you mean?" suggestions on 404 responses
+
+mod common;
+
+use common::{send_raw, start_server};
+use rust_http_server::suggest::{levenshtein, suggest};
+use rust_http_server::Config;
+
+const ROUTES: &[&str] = &[
+    "/",
+    "/health",
+    "/stats",
+    "/stats.csv",
+    "/delay/:ms",
+    "/echo",
+];
+
+#[test]
+fn levenshtein_distances() {
+    assert_eq!(levenshtein("", ""), 0);
+    assert_eq!(levenshtein("abc", ""), 3);
+    assert_eq!(levenshtein("", "abc"), 3);
+    assert_eq!(levenshtein("kitten", "sitting"), 3);
+    assert_eq!(levenshtein("/helth", "/health"), 1);
+    assert_eq!(levenshtein("/stats", "/stats"), 0);
+}
+
+#[test]
+fn suggests_closest_routes_first() {
+    assert_eq!(suggest("/helth", ROUTES), vec!["/health"]);
+    assert_eq!(suggest("/stat", ROUTES), vec!["/stats"]);
+    assert_eq!(suggest("/stats.cvs", ROUTES), vec!["/stats.csv"]);
+    assert_eq!(
+        suggest("/healh", &["/healthz", "/health"]),
+        vec!["/health", "/healthz"]
+    );
+}
+
+#[test]
+fn parameter_segments_take_the_requested_value() {
+    assert_eq!(suggest("/dealy/250", ROUTES), vec!["/delay/:ms"]);
+    assert_eq!(suggest("/delay", ROUTES), vec!["/delay/:ms"]);
+}
+
+#[test]
+fn distant_paths_get_no_suggestions() {
+    assert!(suggest("/completely/unrelated", ROUTES).is_empty());
+    assert!(suggest("/x", ROUTES).is_empty());
+}
+
+#[test]
+fn suggestions_are_capped() {
+    let routes = &["/aa", "/ab", "/ac", "/ad", "/ae"];
+    assert_eq!(suggest("/az", routes).len(), 3);
+}
+
+#[tokio::test]
+async fn not_found_includes_suggestions() {
+    let addr = start_server(Config::default()).await;
+
+    let (head, body) = send_raw(
+        addr,
+        b"GET /helth HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected status: {}",
+        head
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["message"], "Not Found");
+    assert_eq!(json["suggestions"], serde_json::json!(["/health"]));
+
+    let (_, body) = send_raw(
+        addr,
+        b"GET /nothing-like-it HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["suggestions"], serde_json::json!([]));
+}
@@ -0,0 +1,75 @@
+// Serving over a Unix domain socket instead of TCP
+#![cfg(unix)]
+
+use std::time::Duration;
+
+use rust_http_server::{serve, AppState, Config, UnixSocketListener};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::oneshot;
+
+fn socket_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "rust-http-server-{}-{}.sock",
+        name,
+        std::process::id()
+    ))
+}
+
+#[tokio::test]
+async fn serves_requests_and_removes_socket_on_shutdown() {
+    let path = socket_path("serve");
+    let listener = UnixSocketListener::bind(&path).unwrap();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let server = tokio::spawn(serve(listener, AppState::new(Config::default()), async {
+        let _ = shutdown_rx.await;
+    }));
+
+    let mut stream = UnixStream::connect(&path).await.unwrap();
+    stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    tokio::time::timeout(Duration::from_secs(5), stream.read_to_string(&mut response))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        response.starts_with("HTTP/1.1 200"),
+        "unexpected response: {}",
+        response
+    );
+    assert!(
+        response.contains("Server is healthy"),
+        "unexpected response: {}",
+        response
+    );
+
+    shutdown_tx.send(()).unwrap();
+    server.await.unwrap().unwrap();
+    assert!(!path.exists(), "socket file left behind after shutdown");
+}
+
+#[tokio::test]
+async fn stale_socket_file_is_replaced() {
+    let path = socket_path("stale");
+    let stale = UnixListener::bind(&path).unwrap();
+    drop(stale);
+    assert!(path.exists());
+
+    let listener = UnixSocketListener::bind(&path).unwrap();
+    assert_eq!(listener.path(), path);
+    drop(listener);
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn refuses_to_remove_regular_file() {
+    let path = socket_path("regular");
+    std::fs::write(&path, b"not a socket").unwrap();
+
+    assert!(UnixSocketListener::bind(&path).is_err());
+    assert!(path.exists());
+    std::fs::remove_file(&path).unwrap();
+}
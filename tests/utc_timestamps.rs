@@ -0,0 +1,77 @@
+// USE_UTC_TIMESTAMPS writes every timestamp in UTC, ending in Z. The setting
+// is process-wide, so every server in this file turns it on.
+
+mod common;
+
+use std::net::SocketAddr;
+
+use chrono::DateTime;
+use common::{send_raw, start_server};
+use rust_http_server::{timestamp, Config};
+
+fn config() -> Config {
+    Config {
+        use_utc_timestamps: true,
+        ..Config::default()
+    }
+}
+
+async fn timestamp_of(addr: SocketAddr, path: &str) -> String {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    );
+    let (head, body) = send_raw(addr, request.as_bytes()).await;
+    assert!(head.starts_with("HTTP/1.1 2"), "{}", head);
+    // Streamed bodies arrive chunked; the first JSON line is enough
+    let line = body.lines().find(|line| line.starts_with('{')).unwrap();
+    let body: serde_json::Value =
+        serde_json::from_str(line).unwrap_or_else(|_| panic!("not JSON: {}", body));
+    body["timestamp"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn responses_are_stamped_in_utc() {
+    let addr = start_server(config()).await;
+
+    for path in [
+        "/",
+        "/health",
+        "/echo/hi",
+        "/stream/ndjson?count=1",
+        "/readyz",
+    ] {
+        let stamp = timestamp_of(addr, path).await;
+        assert!(stamp.ends_with('Z'), "{}: {}", path, stamp);
+        DateTime::parse_from_rfc3339(&stamp).unwrap_or_else(|_| panic!("{}: {}", path, stamp));
+    }
+}
+
+#[tokio::test]
+async fn errors_are_stamped_in_utc() {
+    let addr = start_server(config()).await;
+
+    let (head, body) = send_raw(
+        addr,
+        b"GET /nowhere HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(head.starts_with("HTTP/1.1 404"), "{}", head);
+    let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert!(
+        body["timestamp"].as_str().unwrap().ends_with('Z'),
+        "{}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn logs_and_helpers_follow_the_setting() {
+    let _addr = start_server(config()).await;
+
+    assert!(timestamp::is_utc());
+    assert_eq!(timestamp::now().offset().local_minus_utc(), 0);
+    assert!(timestamp::timestamp_rfc3339().ends_with('Z'));
+    // `2026-10-14 09:05:03`
+    assert_eq!(timestamp::log_time().len(), 19);
+}
@@ -0,0 +1,151 @@
+// Host-based virtual hosting
+
+mod common;
+
+use common::{get, start_server};
+use rust_http_server::vhost::{HostTable, Site};
+use rust_http_server::Config;
+
+fn table() -> HostTable {
+    "api.example.com=api, admin.example.com=admin"
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn parses_host_table() {
+    let table = table();
+    assert_eq!(
+        table.resolve(Some("api.example.com"), None),
+        Some(Site::Api)
+    );
+    assert_eq!(
+        table.resolve(Some("admin.example.com"), None),
+        Some(Site::Admin)
+    );
+
+    assert!("".parse::<HostTable>().unwrap().is_empty());
+    assert!("api.example.com".parse::<HostTable>().is_err());
+    assert!("api.example.com=web".parse::<HostTable>().is_err());
+    assert!("=api".parse::<HostTable>().is_err());
+}
+
+#[test]
+fn hosts_match_regardless_of_case_port_and_trailing_dot() {
+    let table = table().with_host("[::1]", Site::All);
+    assert_eq!(
+        table.resolve(Some("API.Example.com:8080"), None),
+        Some(Site::Api)
+    );
+    assert_eq!(
+        table.resolve(Some("admin.example.com."), None),
+        Some(Site::Admin)
+    );
+    assert_eq!(table.resolve(Some("[::1]:8080"), None), Some(Site::All));
+}
+
+#[test]
+fn unknown_hosts_use_the_default_host() {
+    let table = table();
+    assert_eq!(table.resolve(Some("other.example.com"), None), None);
+    assert_eq!(table.resolve(None, None), None);
+    assert_eq!(
+        table.resolve(Some("other.example.com"), Some("admin.example.com")),
+        Some(Site::Admin)
+    );
+    assert_eq!(
+        table.resolve(None, Some("api.example.com")),
+        Some(Site::Api)
+    );
+}
+
+#[test]
+fn sites_serve_their_routes() {
+    assert!(Site::Api.serves("/echo/hi"));
+    assert!(Site::Api.serves("/delay/10"));
+    assert!(!Site::Api.serves("/stats"));
+    assert!(Site::Admin.serves("/stats.csv"));
+    assert!(!Site::Admin.serves("/echo/hi"));
+    assert!(Site::All.serves("/anything"));
+}
+
+#[tokio::test]
+async fn routes_by_host_header() {
+    let addr = start_server(Config {
+        virtual_hosts: table(),
+        ..Config::default()
+    })
+    .await;
+
+    let (head, body) = get(addr, "/echo/hi", "Host: api.example.com\r\n").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("hi"), "unexpected body: {}", body);
+
+    let (head, _) = get(addr, "/echo/hi", "Host: admin.example.com\r\n").await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected status: {}",
+        head
+    );
+
+    let (head, body) = get(addr, "/stats", "Host: admin.example.com\r\n").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("total_requests"), "unexpected body: {}", body);
+
+    let (head, body) = get(addr, "/health", "Host: unknown.example.com\r\n").await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected status: {}",
+        head
+    );
+    assert!(body.contains("Unknown host"), "unexpected body: {}", body);
+}
+
+#[tokio::test]
+async fn suggestions_stay_within_the_site() {
+    let addr = start_server(Config {
+        virtual_hosts: table(),
+        ..Config::default()
+    })
+    .await;
+
+    let (_, body) = get(addr, "/helth", "Host: api.example.com\r\n").await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["suggestions"], serde_json::json!([]));
+
+    let (_, body) = get(addr, "/helth", "Host: admin.example.com\r\n").await;
+    let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(json["suggestions"], serde_json::json!(["/health"]));
+}
+
+#[tokio::test]
+async fn unknown_hosts_fall_back_to_default_host() {
+    let addr = start_server(Config {
+        virtual_hosts: table(),
+        default_host: Some("admin.example.com".to_string()),
+        ..Config::default()
+    })
+    .await;
+
+    let (head, _) = get(addr, "/health", "Host: 10.0.0.1:8080\r\n").await;
+    assert!(
+        head.starts_with("HTTP/1.1 200"),
+        "unexpected status: {}",
+        head
+    );
+
+    let (head, _) = get(addr, "/echo/hi", "Host: 10.0.0.1:8080\r\n").await;
+    assert!(
+        head.starts_with("HTTP/1.1 404"),
+        "unexpected status: {}",
+        head
+    );
+}
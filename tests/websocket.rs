@@ -0,0 +1,235 @@
+// WebSocket echo on GET /ws
+
+mod common;
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use common::{header, send_raw, start_server};
+use rust_http_server::websocket::{
+    accept_key, sha1, OPCODE_BINARY, OPCODE_CLOSE, OPCODE_PING, OPCODE_PONG, OPCODE_TEXT,
+};
+use rust_http_server::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// A masked client frame
+fn client_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = [0x37, 0xfa, 0x21, 0x3d];
+    let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(0x80 | len as u8),
+        len => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+async fn read_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head).await.unwrap();
+    assert_eq!(head[1] & 0x80, 0, "server frames must not be masked");
+    let len = match head[1] & 0x7f {
+        126 => stream.read_u16().await.unwrap() as usize,
+        127 => stream.read_u64().await.unwrap() as usize,
+        len => len as usize,
+    };
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).await.unwrap();
+    (head[0] & 0x0f, payload)
+}
+
+// Performs the opening handshake and returns the upgraded stream
+async fn connect(addr: SocketAddr) -> TcpStream {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(
+            b"GET /ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+              Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(head).unwrap();
+    assert!(
+        head.starts_with("HTTP/1.1 101"),
+        "unexpected response: {}",
+        head
+    );
+    assert_eq!(
+        header(&head, "sec-websocket-accept"),
+        Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+    );
+    stream
+}
+
+async fn active_websockets(addr: SocketAddr) -> u64 {
+    let (_, body) = send_raw(
+        addr,
+        b"GET /stats HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+    stats["active_websockets"].as_u64().unwrap()
+}
+
+#[test]
+fn sha1_matches_known_digests() {
+    assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(
+        hex(&sha1(b"abc")),
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+    assert_eq!(
+        hex(&sha1(
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+        )),
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+    );
+}
+
+#[test]
+fn accept_key_matches_rfc_example() {
+    assert_eq!(
+        accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+}
+
+#[tokio::test]
+async fn echoes_text_and_binary_messages() {
+    let addr = start_server(Config::default()).await;
+    let mut ws = connect(addr).await;
+
+    ws.write_all(&client_frame(true, OPCODE_TEXT, b"hello"))
+        .await
+        .unwrap();
+    assert_eq!(read_frame(&mut ws).await, (OPCODE_TEXT, b"hello".to_vec()));
+
+    let large = vec![b'x'; 1000];
+    ws.write_all(&client_frame(true, OPCODE_BINARY, &large))
+        .await
+        .unwrap();
+    assert_eq!(read_frame(&mut ws).await, (OPCODE_BINARY, large));
+}
+
+#[tokio::test]
+async fn reassembles_fragments_and_answers_pings() {
+    let addr = start_server(Config::default()).await;
+    let mut ws = connect(addr).await;
+
+    ws.write_all(&client_frame(false, OPCODE_TEXT, b"hel"))
+        .await
+        .unwrap();
+    ws.write_all(&client_frame(true, OPCODE_PING, b"p"))
+        .await
+        .unwrap();
+    ws.write_all(&client_frame(true, 0x0, b"lo")).await.unwrap();
+
+    assert_eq!(read_frame(&mut ws).await, (OPCODE_PONG, b"p".to_vec()));
+    assert_eq!(read_frame(&mut ws).await, (OPCODE_TEXT, b"hello".to_vec()));
+}
+
+#[tokio::test]
+async fn closing_handshake_echoes_status() {
+    let addr = start_server(Config::default()).await;
+    let mut ws = connect(addr).await;
+
+    ws.write_all(&client_frame(true, OPCODE_CLOSE, &1000u16.to_be_bytes()))
+        .await
+        .unwrap();
+    assert_eq!(
+        read_frame(&mut ws).await,
+        (OPCODE_CLOSE, 1000u16.to_be_bytes().to_vec())
+    );
+}
+
+#[tokio::test]
+async fn protocol_violations_close_the_socket() {
+    let addr = start_server(Config {
+        max_body_bytes: 16,
+        ..Config::default()
+    })
+    .await;
+
+    // Unmasked client frame
+    let mut ws = connect(addr).await;
+    ws.write_all(&[0x81, 0x02, b'h', b'i']).await.unwrap();
+    assert_eq!(
+        read_frame(&mut ws).await,
+        (OPCODE_CLOSE, 1002u16.to_be_bytes().to_vec())
+    );
+
+    // Invalid UTF-8 in a text message
+    let mut ws = connect(addr).await;
+    ws.write_all(&client_frame(true, OPCODE_TEXT, &[0xff, 0xfe]))
+        .await
+        .unwrap();
+    assert_eq!(
+        read_frame(&mut ws).await,
+        (OPCODE_CLOSE, 1007u16.to_be_bytes().to_vec())
+    );
+
+    // Message over MAX_BODY_BYTES
+    let mut ws = connect(addr).await;
+    ws.write_all(&client_frame(true, OPCODE_TEXT, &[b'a'; 17]))
+        .await
+        .unwrap();
+    assert_eq!(
+        read_frame(&mut ws).await,
+        (OPCODE_CLOSE, 1009u16.to_be_bytes().to_vec())
+    );
+}
+
+#[tokio::test]
+async fn active_connections_are_counted_in_stats() {
+    let addr = start_server(Config::default()).await;
+    assert_eq!(active_websockets(addr).await, 0);
+
+    let mut ws = connect(addr).await;
+    assert_eq!(active_websockets(addr).await, 1);
+
+    ws.write_all(&client_frame(true, OPCODE_CLOSE, &[]))
+        .await
+        .unwrap();
+    read_frame(&mut ws).await;
+    drop(ws);
+
+    let mut active = 1;
+    for _ in 0..50 {
+        active = active_websockets(addr).await;
+        if active == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(active, 0);
+}
+
+#[tokio::test]
+async fn plain_get_requires_upgrade() {
+    let addr = start_server(Config::default()).await;
+    let (head, _) = send_raw(
+        addr,
+        b"GET /ws HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+    )
+    .await;
+    assert!(
+        head.starts_with("HTTP/1.1 426"),
+        "unexpected status: {}",
+        head
+    );
+    assert_eq!(header(&head, "upgrade"), Some("websocket"));
+}
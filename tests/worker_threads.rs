@@ -0,0 +1,37 @@
+// The server's runtime names its threads after WORKER_THREAD_NAME, so they
+// can be told apart in profiles
+
+use rust_http_server::{runtime, Config};
+
+fn thread_name() -> String {
+    std::thread::current()
+        .name()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[test]
+fn threads_are_named_and_numbered() {
+    let runtime = runtime(&Config::default()).unwrap();
+
+    let worker = runtime.block_on(async { tokio::spawn(async { thread_name() }).await.unwrap() });
+    let number = worker
+        .strip_prefix("http-worker-")
+        .unwrap_or_else(|| panic!("unexpected worker name {:?}", worker));
+    assert!(number.parse::<u64>().is_ok(), "{}", worker);
+
+    let blocking =
+        runtime.block_on(async { tokio::task::spawn_blocking(thread_name).await.unwrap() });
+    assert!(blocking.starts_with("http-worker-"), "{}", blocking);
+}
+
+#[test]
+fn prefix_comes_from_the_config() {
+    let config =
+        Config::from_lookup(|key| (key == "WORKER_THREAD_NAME").then(|| "api".to_string()))
+            .unwrap();
+    let runtime = runtime(&config).unwrap();
+
+    let worker = runtime.block_on(async { tokio::spawn(async { thread_name() }).await.unwrap() });
+    assert!(worker.starts_with("api-"), "{}", worker);
+}